@@ -12,7 +12,10 @@ use std::time::Instant;
 use flood_rs::{WriteOctetStream, ReadOctetStream};
 
 use conclave_room_serialize::{ClientReceiveCommand, RoomInfoCommand, ServerReceiveCommand};
-use conclave_room_session::{ConnectionIndex, Room};
+use conclave_room_session::{ConnectionIndex, PingPayload, Room};
+
+/// Sent as the leader index when the room has no leader
+pub const NO_LEADER_INDEX: u8 = 0xff;
 
 pub struct NetworkConnection {
     pub id: ConnectionIndex,
@@ -24,14 +27,20 @@ pub trait SendDatagram {
 }
 
 impl SendDatagram for Room {
+    /// Fails if the leader index does not fit in the octet of the room info, [NO_LEADER_INDEX] is reserved
     fn send(&self, stream: &mut dyn WriteOctetStream) -> io::Result<()> {
+        let leader_index = match self.leader() {
+            Some(index) => u8::try_from(index.value())
+                .ok()
+                .filter(|value| *value != NO_LEADER_INDEX)
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("leader {} does not fit in an octet", index))
+                })?,
+            None => NO_LEADER_INDEX,
+        };
         let room_info_command = RoomInfoCommand {
-            term: self.term,
-            leader_index: if let Some(index) = self.leader_index {
-                index.0
-            } else {
-                0xff
-            } as u8,
+            term: self.term(),
+            leader_index,
             client_infos: vec![],
         };
         let client_receive_command = ClientReceiveCommand::RoomInfoType(room_info_command);
@@ -56,20 +65,18 @@ impl ReceiveDatagram for Room {
         now: Instant,
         in_stream: &mut dyn ReadOctetStream,
     ) -> io::Result<()> {
-        if !self.connections.contains_key(&connection_id) {
+        if !self.contains(connection_id) {
             return Err(io::Error::new(io::ErrorKind::Other, format!("there is no connection {}", connection_id)));
         }
 
         let command = ServerReceiveCommand::from_stream(in_stream)?;
         match command {
             ServerReceiveCommand::PingCommandType(ping_command) => {
-                self.on_ping(
-                    connection_id,
-                    ping_command.term,
-                    &ping_command.has_connection_to_leader,
-                    ping_command.knowledge,
-                    now,
-                );
+                let ping = PingPayload::new()
+                    .with_term(ping_command.term)
+                    .with_connection_to_leader(ping_command.has_connection_to_leader)
+                    .with_knowledge(ping_command.knowledge);
+                self.on_ping(connection_id, &ping, now);
             }
         }
         Ok(())
@@ -78,11 +85,11 @@ impl ReceiveDatagram for Room {
 
 #[cfg(test)]
 mod tests {
-    use std::io::Cursor;
+    use std::io::{self, Cursor};
     use std::time::Instant;
 
     use conclave_room_serialize::PING_COMMAND_TYPE_ID;
-    use conclave_room_session::Room;
+    use conclave_room_session::{ConnectionIndex, Room};
     use flood_rs::{InOctetStream, OutOctetStream};
 
     use crate::{ReceiveDatagram, SendDatagram};
@@ -96,6 +103,14 @@ mod tests {
         assert_eq!(vec![0x2a, 0x00, 0x00, 0x00, 0xff], out_stream.data);
     }
 
+    #[test]
+    fn reject_leader_index_that_does_not_fit() {
+        let mut room = Room::new();
+        room.create_connection_with_id(ConnectionIndex::new(255), Instant::now()).unwrap();
+        let mut out_stream = OutOctetStream::default();
+        assert_eq!(room.send(&mut out_stream).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
     #[test]
     fn on_ping() {
         const EXPECTED_KNOWLEDGE_VALUE: u64 = 17718865395771014920;
@@ -118,11 +133,11 @@ mod tests {
 
         let mut room = Room::new();
         let now = Instant::now();
-        let first_connection_id = room.create_connection(now);
+        let first_connection_id = room.create_connection(now).unwrap();
         let receive_result = room.receive(first_connection_id, now, &mut in_stream);
         assert!(receive_result.is_ok());
 
-        let connection_after_receive = room.connections.get(&first_connection_id).unwrap();
+        let connection_after_receive = room.get(first_connection_id);
         assert_eq!(connection_after_receive.knowledge.0, EXPECTED_KNOWLEDGE_VALUE);
     }
 }
@@ -0,0 +1,88 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! Throughput of the ping and update hot path, for rooms of different sizes and ping rates.
+//!
+//! Run with `cargo bench -p conclave-room-session`.
+use std::time::{Duration, Instant};
+
+use conclave_room_session::{ConnectionIndex, PingPayload, Room};
+use conclave_types::{ConnectionToLeader, Knowledge};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const ROOM_SIZES: [usize; 4] = [1, 10, 100, 1000];
+const PINGS_PER_SECOND: [u32; 2] = [10, 30];
+
+/// A room where every connection pings at `pings_per_second`, in turn, like a room in a running game
+struct PingingRoom {
+    room: Room,
+    connections: Vec<ConnectionIndex>,
+    now: Instant,
+    interval: Duration,
+    next: usize,
+    knowledge: u64,
+}
+
+impl PingingRoom {
+    fn new(size: usize, pings_per_second: u32) -> Self {
+        let now = Instant::now();
+        let mut room = Room::new();
+        let connections = (0..size).map(|_| room.create_connection(now).unwrap()).collect();
+        let mut pinging = Self {
+            room,
+            connections,
+            now,
+            interval: Duration::from_secs(1) / pings_per_second / size as u32,
+            next: 0,
+            knowledge: 0,
+        };
+        // Long enough for every connection to get a quality assessment
+        for _ in 0..size * pings_per_second as usize * 3 {
+            pinging.ping();
+        }
+        pinging
+    }
+
+    fn ping(&mut self) {
+        self.now += self.interval;
+        self.knowledge += 1;
+        let ping = PingPayload::new()
+            .with_term(self.room.term())
+            .with_connection_to_leader(ConnectionToLeader::Connected)
+            .with_knowledge(Knowledge(self.knowledge));
+        self.room.on_ping(self.connections[self.next], &ping, self.now);
+        self.next = (self.next + 1) % self.connections.len();
+    }
+}
+
+fn on_ping(c: &mut Criterion) {
+    let mut group = c.benchmark_group("on_ping");
+    group.throughput(Throughput::Elements(1));
+    for pings_per_second in PINGS_PER_SECOND {
+        for size in ROOM_SIZES {
+            let mut pinging = PingingRoom::new(size, pings_per_second);
+            let id = BenchmarkId::new(format!("{pings_per_second}_per_second"), size);
+            group.bench_function(id, |b| b.iter(|| pinging.ping()));
+        }
+    }
+    group.finish();
+}
+
+/// Updates between pings, as a host that ticks the room more often than pings arrive
+fn update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update");
+    for size in ROOM_SIZES {
+        let mut pinging = PingingRoom::new(size, 10);
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                pinging.ping();
+                pinging.room.update(pinging.now + pinging.interval / 2);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, on_ping, update);
+criterion_main!(benches);
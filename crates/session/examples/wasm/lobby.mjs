@@ -0,0 +1,94 @@
+// Drives a room compiled to wasm32-unknown-unknown from JavaScript timers, like a lobby server would.
+//
+//   cargo rustc -p conclave-room-session --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+//   node crates/session/examples/wasm/lobby.mjs target/wasm32-unknown-unknown/release/conclave_room_session.wasm
+//
+// Struct layouts follow include/conclave_room.h.
+
+import { readFile } from "node:fs/promises";
+
+const CONNECTION_SIZE = 8;
+const PING_SIZE = 24;
+const EVENT_SIZE = 24;
+const EVENT_CAPACITY = 16;
+const PROTOCOL_VERSION = 1;
+
+const path = process.argv[2] ?? "target/wasm32-unknown-unknown/release/conclave_room_session.wasm";
+const { instance } = await WebAssembly.instantiate(await readFile(path));
+const room = instance.exports;
+const view = () => new DataView(room.memory.buffer);
+
+const start = performance.now();
+const now = () => BigInt(Math.round(performance.now() - start));
+
+function readConnection(pointer) {
+    return { value: view().getUint32(pointer, true), generation: view().getUint32(pointer + 4, true) };
+}
+
+function createConnection(handle) {
+    const pointer = room.conclave_alloc(CONNECTION_SIZE);
+    if (room.room_create_connection(handle, now(), pointer) !== 0) {
+        throw new Error("could not create connection");
+    }
+    return pointer;
+}
+
+function ping(handle, connection, knowledge, sequence) {
+    const pointer = room.conclave_alloc(PING_SIZE);
+    const memory = view();
+    memory.setUint16(pointer, room.room_term(handle), true);
+    memory.setBigUint64(pointer + 8, BigInt(knowledge), true);
+    memory.setUint8(pointer + 16, 1);
+    memory.setUint16(pointer + 18, PROTOCOL_VERSION, true);
+    memory.setUint8(pointer + 20, 1);
+    memory.setUint16(pointer + 22, sequence & 0xffff, true);
+    const result = room.room_on_ping(handle, connection, pointer, now());
+    room.conclave_free(pointer, PING_SIZE);
+    return result;
+}
+
+function drainEvents(handle, events) {
+    const count = room.room_drain_events(handle, events, EVENT_CAPACITY);
+    for (let index = 0; index < count; index++) {
+        const pointer = events + index * EVENT_SIZE;
+        const memory = view();
+        const kind = memory.getUint32(pointer, true);
+        const term = memory.getUint16(pointer + 4, true);
+        const connection = memory.getUint8(pointer + 6) ? readConnection(pointer + 8) : null;
+        console.log(`event kind:${kind} term:${term} connection:${JSON.stringify(connection)}`);
+    }
+}
+
+const handle = room.room_create();
+const events = room.conclave_alloc(EVENT_SIZE * EVENT_CAPACITY);
+const leaderOut = room.conclave_alloc(CONNECTION_SIZE);
+const first = createConnection(handle);
+const second = createConnection(handle);
+let sequence = 0;
+
+// The first connection stops pinging after a second, so the room elects the second one
+const pings = setInterval(() => {
+    sequence++;
+    if (performance.now() - start < 1000) {
+        ping(handle, first, sequence, sequence);
+    }
+    ping(handle, second, sequence, sequence);
+}, 50);
+
+const updates = setInterval(() => {
+    room.room_update(handle, now());
+    drainEvents(handle, events);
+}, 100);
+
+setTimeout(() => {
+    clearInterval(pings);
+    clearInterval(updates);
+    if (room.room_leader(handle, leaderOut) === 1) {
+        console.log(`leader ${JSON.stringify(readConnection(leaderOut))} in term ${room.room_term(handle)}`);
+    }
+    room.room_destroy(handle);
+    for (const pointer of [first, second, leaderOut]) {
+        room.conclave_free(pointer, CONNECTION_SIZE);
+    }
+    room.conclave_free(events, EVENT_SIZE * EVENT_CAPACITY);
+}, 4000);
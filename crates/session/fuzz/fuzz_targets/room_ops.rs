@@ -0,0 +1,19 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+#![no_main]
+
+use std::time::Instant;
+
+use conclave_room_session::{Room, RoomOp};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|ops: Vec<RoomOp>| {
+    let mut room = Room::new();
+    let mut now = Instant::now();
+    for op in &ops {
+        room.apply(op, &mut now);
+        room.check_invariants().unwrap();
+    }
+});
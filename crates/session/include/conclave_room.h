@@ -0,0 +1,131 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+/*
+ * C interface to conclave-room-session, built with the `ffi` feature:
+ *
+ *   cargo rustc -p conclave-room-session --features ffi --crate-type cdylib
+ *
+ * Time is given in milliseconds on any monotonic clock, the first timestamp a room sees is its epoch.
+ * A panic never unwinds into the caller, functions that return a status return CONCLAVE_ERROR_PANIC instead
+ * and the others their NULL or zero value.
+ */
+#ifndef CONCLAVE_ROOM_H
+#define CONCLAVE_ROOM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CONCLAVE_OK 0
+#define CONCLAVE_PING_REJECTED 1
+#define CONCLAVE_ERROR_NULL (-1)
+#define CONCLAVE_ERROR_STALE_HANDLE (-2)
+#define CONCLAVE_ERROR_UNKNOWN_CONNECTION (-3)
+#define CONCLAVE_ERROR_CONNECTION_INDEX_IN_USE (-4)
+#define CONCLAVE_ERROR_IDENTITY_IN_USE (-5)
+#define CONCLAVE_ERROR_NO_CONNECTION_INDEX_AVAILABLE (-6)
+#define CONCLAVE_ERROR_ROOM_CLOSING (-7)
+#define CONCLAVE_ERROR_ROOM_FULL (-8)
+#define CONCLAVE_ERROR_NOT_KICKABLE (-9)
+#define CONCLAVE_ERROR_KICK_VOTE_IN_PROGRESS (-10)
+/* The room panicked, it may be half updated and should only be passed to room_destroy */
+#define CONCLAVE_ERROR_PANIC (-11)
+
+#define CONCLAVE_EVENT_LEADER_CHANGED 1
+#define CONCLAVE_EVENT_LEADER_CONFIRMED 2
+#define CONCLAVE_EVENT_LEASE_EXPIRED 3
+#define CONCLAVE_EVENT_TERM_CONVERGED 4
+#define CONCLAVE_EVENT_LEADER_ACTIVATED 5
+#define CONCLAVE_EVENT_DISCONNECTED 6 /* value: 0 poor quality, 1 protocol mismatch */
+#define CONCLAVE_EVENT_WARMED_UP 7
+#define CONCLAVE_EVENT_PROTOCOL_MISMATCH 8 /* value: protocol version of the connection */
+#define CONCLAVE_EVENT_AUTH_FAILURE 9 /* value: failures so far */
+#define CONCLAVE_EVENT_SUSPICIOUS_KNOWLEDGE 10 /* value: reported knowledge */
+#define CONCLAVE_EVENT_KNOWLEDGE_LAGGING 11 /* value: knowledge behind the leader */
+#define CONCLAVE_EVENT_KNOWLEDGE_CAUGHT_UP 12
+#define CONCLAVE_EVENT_WENT_IDLE 13
+#define CONCLAVE_EVENT_RETURNED_FROM_IDLE 14
+#define CONCLAVE_EVENT_QUARANTINED 15
+#define CONCLAVE_EVENT_REHABILITATED 16
+#define CONCLAVE_EVENT_REPRESENTATIVE_CHANGED 17 /* value: group id */
+#define CONCLAVE_EVENT_UNREACHABLE_BY_LEADER 18
+#define CONCLAVE_EVENT_REACHABLE_BY_LEADER 19
+#define CONCLAVE_EVENT_TRANSFERRED_OUT 20
+#define CONCLAVE_EVENT_TRANSFERRED_IN 21 /* value: connection index value in the previous room */
+#define CONCLAVE_EVENT_PENDING_ACTIVATED 22
+#define CONCLAVE_EVENT_PENDING_EXPIRED 23
+#define CONCLAVE_EVENT_STATE_SYNC_ASSIGNED 24 /* value: index value of the donor, 0 if none */
+#define CONCLAVE_EVENT_KICKED 25 /* value: 0 authentication failed, 1 evicted, 2 voted out */
+#define CONCLAVE_EVENT_TIME_WENT_BACKWARDS 26 /* value: milliseconds */
+#define CONCLAVE_EVENT_ROOM_STUCK 27 /* value: election attempts */
+#define CONCLAVE_EVENT_LEADER_RESTORED 28
+#define CONCLAVE_EVENT_HEALTH_DEGRADED 29 /* value: threshold << 8 | score */
+#define CONCLAVE_EVENT_HEALTH_RECOVERED 30 /* value: threshold << 8 | score */
+#define CONCLAVE_EVENT_RETIRED_LEADER_RELEASED 31
+#define CONCLAVE_EVENT_PARTITION_HEALED 32 /* value: the winning term */
+#define CONCLAVE_EVENT_CONNECTION_REPLACED 33 /* value: connection index value of the replaced connection */
+#define CONCLAVE_EVENT_ROOM_CLOSING 34 /* value: close reason, 0 match ended, 1 server shutdown, 2 abandoned */
+#define CONCLAVE_EVENT_ROOM_CLOSED 35 /* value: close reason */
+#define CONCLAVE_EVENT_ROOM_QUIET 36 /* value: milliseconds without pings */
+#define CONCLAVE_EVENT_ROOM_ABANDONED 37 /* value: milliseconds without pings */
+#define CONCLAVE_EVENT_LOAD_REBALANCE 38 /* value: burden of the leader in hundredths */
+#define CONCLAVE_EVENT_ELECTION_RANKED 39 /* connection: runner-up, value: number of ranked candidates */
+#define CONCLAVE_EVENT_ADMITTED_BY_EVICTION 40 /* value: index value of the evicted connection */
+#define CONCLAVE_EVENT_KICK_PROPOSED 41 /* connection: target, value: proposal id */
+#define CONCLAVE_EVENT_KICK_VOTE_RESOLVED 42 /* connection: target, value: 0 approved, 1 rejected, 2 expired, 3 withdrawn */
+#define CONCLAVE_EVENT_ELECTION_RECORDED 43 /* connection: winner, value: convergence in milliseconds, UINT64_MAX if it did not converge */
+#define CONCLAVE_EVENT_BACKPRESSURE 44 /* value: connections in the room */
+#define CONCLAVE_EVENT_CRITICAL_SECTION_TIMED_OUT 45 /* value: milliseconds the section was held */
+
+typedef struct ConclaveRoom ConclaveRoom;
+
+typedef struct ConclaveConnection {
+    uint32_t value;
+    uint32_t generation;
+} ConclaveConnection;
+
+typedef struct ConclavePing {
+    uint16_t term;
+    uint64_t knowledge;
+    uint8_t connection_to_leader; /* 0 unknown, 1 connected, 2 disconnected */
+    uint16_t protocol_version;
+    uint8_t has_sequence;
+    uint16_t sequence;
+} ConclavePing;
+
+typedef struct ConclaveEvent {
+    uint32_t kind;
+    uint16_t term;
+    uint8_t has_connection;
+    ConclaveConnection connection;
+    uint64_t value;
+} ConclaveEvent;
+
+ConclaveRoom *room_create(void);
+/* Returns NULL if the threshold is not a finite number above zero */
+ConclaveRoom *room_create_with_threshold(float pings_per_second_threshold);
+void room_destroy(ConclaveRoom *room);
+
+int32_t room_create_connection(ConclaveRoom *room, uint64_t now_ms, ConclaveConnection *out);
+int32_t room_destroy_connection(ConclaveRoom *room, ConclaveConnection connection);
+
+int32_t room_on_ping(ConclaveRoom *room, ConclaveConnection connection, const ConclavePing *ping, uint64_t now_ms);
+int32_t room_update(ConclaveRoom *room, uint64_t now_ms);
+
+/* Returns 1 and writes the leader to out, or 0 if there is no leader or room or out is NULL */
+int32_t room_leader(const ConclaveRoom *room, ConclaveConnection *out);
+uint16_t room_term(const ConclaveRoom *room);
+
+/* Moves up to capacity events into out, returns how many were written. The rest are kept for the next call */
+size_t room_drain_events(ConclaveRoom *room, ConclaveEvent *out, size_t capacity);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,120 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{CloseReason, Instant, Room, RoomEvent};
+
+/// How far a room that stopped receiving pings has escalated, see [Room::abandonment_stage]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AbandonmentStage {
+    /// Received a ping within [crate::RoomConfig::quiet_after], or has never received one
+    Active,
+    /// No pings within [crate::RoomConfig::quiet_after], see [RoomEvent::RoomQuiet]
+    Quiet,
+    /// No pings within [crate::RoomConfig::abandoned_after], see [RoomEvent::RoomAbandoned]
+    Abandoned,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Every ping puts the room back to [AbandonmentStage::Active]
+    pub fn abandonment_stage(&self) -> AbandonmentStage {
+        self.abandonment_stage
+    }
+
+    /// Called after every update. Each stage is reported once, in order, and the room is closed with
+    /// [CloseReason::Abandoned] after [crate::RoomConfig::close_abandoned_after]
+    pub(crate) fn escalate_abandonment(&mut self, time: Instant) {
+        let Some(latest_ping) = self.latest_ping_timestamp else {
+            return;
+        };
+        let silent_for = time.saturating_duration_since(latest_ping);
+        let is_beyond = |limit: Duration| silent_for > limit;
+
+        if self.abandonment_stage < AbandonmentStage::Quiet && self.config.quiet_after.is_some_and(is_beyond) {
+            self.abandonment_stage = AbandonmentStage::Quiet;
+            self.push_event(RoomEvent::RoomQuiet { silent_for });
+        }
+        if self.abandonment_stage < AbandonmentStage::Abandoned && is_beyond(self.config.abandoned_after) {
+            info!("room {} has not received a ping for {:?}, it is abandoned", self.id, silent_for);
+            self.abandonment_stage = AbandonmentStage::Abandoned;
+            self.push_event(RoomEvent::RoomAbandoned { silent_for });
+        }
+        if self.config.close_abandoned_after.is_some_and(is_beyond) && !self.is_closing() {
+            self.start_closing(CloseReason::Abandoned, time);
+        }
+    }
+
+    /// The latest deadline of [Room::escalate_abandonment] that has not been reached yet
+    pub(crate) fn next_escalation_at(&self) -> Option<Instant> {
+        let latest_ping = self.latest_ping_timestamp?;
+        let quiet = self
+            .config
+            .quiet_after
+            .filter(|_| self.abandonment_stage < AbandonmentStage::Quiet);
+        let abandoned = Some(self.config.abandoned_after).filter(|_| self.abandonment_stage < AbandonmentStage::Abandoned);
+        let close = self.config.close_abandoned_after.filter(|_| !self.is_closing());
+        [quiet, abandoned, close]
+            .into_iter()
+            .flatten()
+            .map(|limit| latest_ping + limit + Duration::from_nanos(1))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{AbandonmentStage, CloseReason, PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn escalate_until_closed() {
+        let mut room = RoomConfig::new()
+            .with_quiet_after(Some(Duration::from_secs(10)))
+            .with_abandoned_after(Duration::from_secs(60))
+            .with_close_abandoned_after(Some(Duration::from_secs(120)))
+            .with_disconnect_bad_connections(false)
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let connection = room.create_connection(now).unwrap();
+        room.on_ping(connection, &PingPayload::new(), now);
+        room.drain_events();
+
+        room.update(now + Duration::from_secs(11));
+        assert_eq!(room.abandonment_stage(), AbandonmentStage::Quiet);
+        assert_eq!(
+            room.drain_events(),
+            vec![RoomEvent::RoomQuiet {
+                silent_for: Duration::from_secs(11)
+            }]
+        );
+
+        // A ping starts over, and every stage is reported again
+        room.on_ping(connection, &PingPayload::new(), now + Duration::from_secs(20));
+        assert_eq!(room.abandonment_stage(), AbandonmentStage::Active);
+        room.update(now + Duration::from_secs(90));
+        assert_eq!(room.abandonment_stage(), AbandonmentStage::Abandoned);
+        assert_eq!(
+            room.drain_events(),
+            vec![
+                RoomEvent::RoomQuiet {
+                    silent_for: Duration::from_secs(70)
+                },
+                RoomEvent::RoomAbandoned {
+                    silent_for: Duration::from_secs(70)
+                },
+            ]
+        );
+
+        room.update(now + Duration::from_secs(141));
+        assert_eq!(room.close_reason(), Some(CloseReason::Abandoned));
+        room.update(now + Duration::from_secs(150));
+        assert!(room.is_closed());
+    }
+}
@@ -0,0 +1,102 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::{KnowledgeOrd, Term};
+use log::info;
+
+use crate::{ConnectionIndex, ConnectionState, Room, RoomEvent};
+
+/// Which online connections have reported the current term, see [Room::term_acknowledgement]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermAcknowledgement {
+    pub term: Term,
+    /// Sorted by connection index
+    pub acknowledged: Vec<ConnectionIndex>,
+    /// Sorted by connection index
+    pub waiting_for: Vec<ConnectionIndex>,
+}
+
+impl TermAcknowledgement {
+    /// True if every online connection has reported the current term
+    pub fn is_converged(&self) -> bool {
+        self.waiting_for.is_empty()
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// How far the current term has spread, based on the [crate::Connection::last_reported_term] of every
+    /// online connection
+    pub fn term_acknowledgement(&self) -> TermAcknowledgement {
+        let mut online: Vec<ConnectionIndex> = self
+            .connections
+            .values()
+            .filter(|connection| connection.state == ConnectionState::Online)
+            .map(|connection| connection.id)
+            .collect();
+        online.sort_by_key(|index| index.value());
+
+        let (acknowledged, waiting_for) = online
+            .into_iter()
+            .partition(|index| self.connections[index].last_reported_term == Some(self.term));
+        TermAcknowledgement {
+            term: self.term,
+            acknowledged,
+            waiting_for,
+        }
+    }
+
+    /// Emits [RoomEvent::TermConverged] the first time every online connection reports the current term
+    pub(crate) fn update_term_convergence(&mut self) {
+        if self.converged_term == Some(self.term) {
+            return;
+        }
+        // Runs on every update until converged, so the connections are counted rather than collected
+        let mut acknowledged = 0;
+        for connection in self.connections.values() {
+            if connection.state != ConnectionState::Online {
+                continue;
+            }
+            if connection.last_reported_term != Some(self.term) {
+                return;
+            }
+            acknowledged += 1;
+        }
+        if acknowledged == 0 {
+            return;
+        }
+
+        info!("all {} online connections know about term {}", acknowledged, self.term);
+        self.converged_term = Some(self.term);
+        self.push_event(RoomEvent::TermConverged { term: self.term });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::{PingPayload, Room, RoomEvent};
+
+    #[test]
+    fn converge_when_everyone_reports_term() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        let ping = PingPayload::new().with_term(room.term);
+
+        room.on_ping(leader, &ping, now);
+        let acknowledgement = room.term_acknowledgement();
+        assert_eq!(acknowledgement.acknowledged, vec![leader]);
+        assert_eq!(acknowledgement.waiting_for, vec![follower]);
+        assert!(!room.drain_events().contains(&RoomEvent::TermConverged { term: room.term }));
+
+        room.on_ping(follower, &ping, now);
+        assert!(room.term_acknowledgement().is_converged());
+        assert_eq!(room.drain_events(), vec![RoomEvent::TermConverged { term: room.term }]);
+
+        room.on_ping(follower, &ping, now);
+        assert!(room.drain_events().is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{ConnectionIndex, Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// True if the leader has been elected, but is not activated yet, see [crate::RoomConfig::quorum_term_activation]
+    pub fn is_leader_provisional(&self) -> bool {
+        self.leader_index.is_some() && self.provisional_previous_leader.is_some()
+    }
+
+    /// The connection that traffic should be routed through: the previous leader while the leader is provisional
+    /// and the previous leader is still in the room, otherwise the leader
+    pub fn routing_leader(&self) -> Option<ConnectionIndex> {
+        self.provisional_previous_leader
+            .filter(|previous| self.connections.contains_key(previous))
+            .or(self.leader_index)
+    }
+
+    /// Called when the leader has changed from `previous`. Without a previous leader to route through there is
+    /// nothing to wait for, so the new leader is active right away
+    pub(crate) fn begin_term_activation(&mut self, previous: Option<ConnectionIndex>) {
+        self.provisional_previous_leader = if self.config.quorum_term_activation && self.leader_index.is_some() {
+            previous.filter(|previous| Some(*previous) != self.leader_index)
+        } else {
+            None
+        };
+    }
+
+    /// Activates the provisional leader once a majority of the voters have pinged with the current term
+    pub(crate) fn update_term_activation(&mut self) {
+        if !self.is_leader_provisional() {
+            return;
+        }
+        let voters = self
+            .connections
+            .values()
+            .filter(|connection| connection.takes_part_in_election());
+        let voter_count = voters.clone().count();
+        let acknowledged = voters
+            .filter(|connection| connection.last_reported_term == Some(self.term))
+            .count();
+        if acknowledged <= voter_count / 2 {
+            return;
+        }
+
+        let leader = self.leader_index.unwrap();
+        info!("{} of {} voters know about term {}, activating leader {}", acknowledged, voter_count, self.term, leader);
+        self.provisional_previous_leader = None;
+        self.push_event(RoomEvent::LeaderActivated { term: self.term, leader });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::{ElectionTrigger, PingPayload, RoomConfig, RoomEvent, RoomState};
+
+    #[test]
+    fn route_through_old_leader_until_majority_knows_new_term() {
+        let mut room = RoomConfig::new().with_quorum_term_activation(true).build().unwrap();
+        let now = Instant::now();
+        let old_leader = room.create_connection(now).unwrap();
+        let connections = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+        assert!(!room.is_leader_provisional());
+
+        room.switch_leader_to_best_knowledge_and_quality(ElectionTrigger::LeaderLeft);
+        let new_leader = room.leader_index.unwrap();
+        assert_ne!(new_leader, old_leader);
+        assert!(room.is_leader_provisional());
+        assert_eq!(room.routing_leader(), Some(old_leader));
+
+        let ping = PingPayload::new().with_term(room.term);
+        room.on_ping(connections[0], &ping, now);
+        assert!(room.is_leader_provisional());
+        assert_eq!(room.state(now), RoomState::ProvisionalLeader);
+
+        room.drain_events();
+        room.on_ping(connections[1], &ping, now);
+        assert!(!room.is_leader_provisional());
+        assert_eq!(room.routing_leader(), Some(new_leader));
+        assert!(room.drain_events().contains(&RoomEvent::LeaderActivated {
+            term: room.term,
+            leader: new_leader,
+        }));
+    }
+}
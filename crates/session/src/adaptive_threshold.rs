@@ -0,0 +1,92 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+
+use crate::{ConnectionState, Room};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Median of the latest measured ping rates of the connections that are not pending or disconnected,
+    /// `None` if fewer than two connections have been measured
+    pub fn median_pings_per_second(&self) -> Option<f32> {
+        let mut rates: Vec<f32> = self
+            .connections
+            .values()
+            .filter(|connection| !matches!(connection.state, ConnectionState::Pending | ConnectionState::Disconnected))
+            .filter_map(|connection| connection.quality.measured_pings_per_second())
+            .collect();
+        if rates.len() < 2 {
+            return None;
+        }
+
+        rates.sort_by(f32::total_cmp);
+        let middle = rates.len() / 2;
+        Some(if rates.len().is_multiple_of(2) {
+            (rates[middle - 1] + rates[middle]) / 2.0
+        } else {
+            rates[middle]
+        })
+    }
+
+    /// The threshold can not be higher than this, `None` if the threshold is not adaptive
+    pub(crate) fn adaptive_threshold_ceiling(&self) -> Option<f32> {
+        let fraction = self.config.adaptive_threshold_fraction?;
+        Some(self.median_pings_per_second()? * fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{ConnectionIndex, ConnectionState, PingPayload, Room, RoomConfig};
+
+    /// The room is updated every 100 milliseconds in `(from, to]`, and every connection in `pinging` pings
+    /// every `interval` milliseconds
+    fn run(room: &mut Room, pinging: &[ConnectionIndex], now: Instant, from: u64, to: u64, interval: u64) {
+        for millis in (from + 100..=to).step_by(100) {
+            let time = now + Duration::from_millis(millis);
+            if millis % interval == 0 {
+                for connection in pinging {
+                    room.on_ping(*connection, &PingPayload::new(), time);
+                }
+            }
+            room.update(time);
+        }
+    }
+
+    fn room_after_hiccup(config: RoomConfig) -> (Room, Vec<ConnectionIndex>) {
+        let mut room = config.build().unwrap();
+        let now = Instant::now();
+        let connections: Vec<ConnectionIndex> = (0..3).map(|_| room.create_connection(now).unwrap()).collect();
+        run(&mut room, &connections, now, 0, 1200, 100);
+        // Every connection slows down to a third of the threshold
+        run(&mut room, &connections, now, 1200, 3000, 600);
+        (room, connections)
+    }
+
+    #[test]
+    fn survive_room_wide_hiccup() {
+        let (room, connections) = room_after_hiccup(RoomConfig::new());
+        assert!(connections.iter().all(|connection| room.get(*connection).state == ConnectionState::Disconnected));
+
+        let (room, connections) = room_after_hiccup(RoomConfig::new().with_adaptive_threshold_fraction(0.5));
+        assert!(room.median_pings_per_second().is_some_and(|median| median < room.config.pings_per_second_threshold));
+        assert!(connections.iter().all(|connection| room.get(*connection).state == ConnectionState::Online));
+    }
+
+    #[test]
+    fn disconnect_single_slow_connection() {
+        let mut room = RoomConfig::new().with_adaptive_threshold_fraction(0.5).build().unwrap();
+        let now = Instant::now();
+        let first = room.create_connection(now).unwrap();
+        let second = room.create_connection(now).unwrap();
+        let slow = room.create_connection(now).unwrap();
+        run(&mut room, &[first, second, slow], now, 0, 600, 100);
+        run(&mut room, &[first, second], now, 600, 1200, 100);
+
+        assert_eq!(room.get(first).state, ConnectionState::Online);
+        assert_eq!(room.get(slow).state, ConnectionState::Disconnected);
+    }
+}
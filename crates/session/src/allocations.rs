@@ -0,0 +1,88 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! Counts heap allocations in the crate's own tests, so tests can assert that the hot path does not allocate
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Number of allocations made by the current thread while running `f`
+pub(crate) fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{ConnectionToLeader, Knowledge};
+
+    use crate::allocations::count_allocations;
+    use crate::{ConnectionState, PingPayload, Room};
+
+    #[test]
+    fn settled_room_does_not_allocate() {
+        for size in [1, 2, 10, 100] {
+            let mut room = Room::new();
+            let mut now = Instant::now();
+            let connections: Vec<_> = (0..size).map(|_| room.create_connection(now).unwrap()).collect();
+            // Disconnected for not pinging, and assessed again on every measurement
+            let silent = room.create_connection(now).unwrap();
+            let interval = Duration::from_secs(1) / 20 / size as u32;
+            let mut knowledge = 0;
+            let mut tick = |room: &mut Room| {
+                for connection in &connections {
+                    now += interval;
+                    knowledge += 1;
+                    let ping = PingPayload::new()
+                        .with_term(room.term())
+                        .with_connection_to_leader(ConnectionToLeader::Connected)
+                        .with_knowledge(Knowledge(knowledge));
+                    room.on_ping(*connection, &ping, now);
+                    room.update(now);
+                }
+            };
+            for _ in 0..60 {
+                tick(&mut room);
+            }
+            assert_eq!(room.get(silent).state, ConnectionState::Disconnected);
+            room.drain_events();
+
+            let allocations = count_allocations(|| {
+                for _ in 0..60 {
+                    tick(&mut room);
+                }
+            });
+
+            assert_eq!(allocations, 0, "{} allocations in a room of {}", allocations, size);
+            assert!(room.drain_events().is_empty());
+        }
+    }
+}
@@ -0,0 +1,106 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+
+use crate::knowledge::SuspicionReason;
+use crate::{ConnectionIndex, Instant, PingPayload, Room};
+
+/// A [PingPayload::knowledge_checksum] the leader sent for a knowledge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Checkpoint {
+    /// The [KnowledgeOrd::progress] of the knowledge
+    progress: u64,
+    checksum: u64,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Keeps the checksums the leader sends, and compares the ones the other connections echo against them.
+    ///
+    /// A connection that echoes another checksum for a knowledge the leader has attested has claimed a state it
+    /// never received, which counts as [SuspicionReason::ChecksumMismatch]. Knowledge without a kept checkpoint
+    /// is not checked, the leader does not have to send one for every knowledge.
+    pub(crate) fn attest_knowledge(&mut self, connection_index: ConnectionIndex, ping: &PingPayload<K>, time: Instant) {
+        let Some(checksum) = ping.knowledge_checksum else {
+            return;
+        };
+        let progress = ping.knowledge.progress();
+        if self.leader_index == Some(connection_index) {
+            self.keep_checkpoint(Checkpoint { progress, checksum });
+            return;
+        }
+        let Some(expected) = self
+            .checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.progress == progress)
+            .map(|checkpoint| checkpoint.checksum)
+        else {
+            return;
+        };
+        if expected != checksum {
+            let reason = SuspicionReason::ChecksumMismatch { expected };
+            self.report_suspicious_knowledge(connection_index, ping.knowledge, reason, time);
+        }
+    }
+
+    /// Keeps at most [crate::RoomConfig::attestation_history] checkpoints, the latest one for every knowledge
+    fn keep_checkpoint(&mut self, checkpoint: Checkpoint) {
+        let capacity = self.config.attestation_history;
+        if capacity == 0 {
+            return;
+        }
+        self.checkpoints.retain(|kept| kept.progress != checkpoint.progress);
+        while self.checkpoints.len() >= capacity {
+            self.checkpoints.pop_front();
+        }
+        self.checkpoints.push_back(checkpoint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::Knowledge;
+
+    use crate::knowledge::SuspicionReason;
+    use crate::{PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn catch_knowledge_that_was_never_received() {
+        let mut room = RoomConfig::new()
+            .with_exclude_suspicious_from_election(true)
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let honest = room.create_connection(now).unwrap();
+        let cheater = room.create_connection(now).unwrap();
+        let state = |knowledge, checksum| PingPayload::new().with_knowledge(Knowledge(knowledge)).with_knowledge_checksum(checksum);
+
+        room.on_ping(leader, &state(10, 0xA1), now);
+        room.on_ping(leader, &state(20, 0xB2), now);
+        room.on_ping(honest, &state(20, 0xB2), now);
+        room.on_ping(cheater, &state(10, 0xA1), now);
+        // Not attested by the leader, so it can not be checked
+        room.on_ping(cheater, &state(15, 0xFF), now);
+        assert!(!room.get(cheater).is_suspicious());
+        room.drain_events();
+
+        room.on_ping(cheater, &state(20, 0xC3), now);
+
+        assert!(!room.get(honest).is_suspicious());
+        assert_eq!(room.get(cheater).suspicion_score(), 1);
+        assert_eq!(
+            room.drain_events(),
+            vec![RoomEvent::SuspiciousKnowledge {
+                connection: cheater,
+                reported: Knowledge(20),
+                reason: SuspicionReason::ChecksumMismatch { expected: 0xB2 },
+            }]
+        );
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader(), Some(honest));
+    }
+}
@@ -0,0 +1,17 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::fmt;
+
+use conclave_types::{Knowledge, KnowledgeOrd};
+
+use crate::{ConnectionIndex, PingPayload};
+
+/// Verifies that a ping really was sent by the connection it claims to come from.
+///
+/// Typically checks [PingPayload::signature] against a HMAC or signature of the payload, using a key
+/// that was exchanged with the connection when it joined. Pings that fail verification are never applied.
+pub trait PingAuthenticator<K: KnowledgeOrd = Knowledge>: fmt::Debug + Send + Sync {
+    fn verify(&self, connection_index: ConnectionIndex, ping: &PingPayload<K>) -> bool;
+}
@@ -0,0 +1,80 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::debug;
+
+use crate::{Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// True if an update after a ping was deferred by [crate::RoomConfig::update_budget] and has not run yet
+    pub fn has_deferred_update(&self) -> bool {
+        self.update_deferred
+    }
+
+    /// A gate on the size of the room: true if it has more connections than [crate::RoomConfig::update_budget],
+    /// so the update after a ping is skipped as a whole and has to wait for the next [Room::update]. The update is
+    /// never split, a deferred one runs in full.
+    ///
+    /// The budget is not a duration, as the room never reads a clock and has to behave the same when a
+    /// recording is replayed. The connections are what every pass of an update goes through, so they stand in for
+    /// the time it takes.
+    ///
+    /// Emits [RoomEvent::Backpressure] for the first deferred update since the last one that ran, and
+    /// [Room::time_until_next_action] is zero until the deferred update has run.
+    pub(crate) fn is_too_large_to_update_after_ping(&mut self) -> bool {
+        let connections = self.connections.len();
+        if self.config.update_budget.is_none_or(|budget| connections <= budget) {
+            return false;
+        }
+        self.metrics.deferred_updates = self.metrics.deferred_updates.saturating_add(1);
+        if let Some(sink) = &self.metrics_sink {
+            sink.update_deferred(connections);
+        }
+        if !self.update_deferred {
+            debug!("update of {} connections is over budget, deferring it to the next update", connections);
+            self.update_deferred = true;
+            self.push_event(RoomEvent::Backpressure { connections });
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::ConnectionToLeader;
+
+    use crate::{PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn defer_update_over_budget_to_next_update() {
+        let mut room = RoomConfig::new().with_update_budget(Some(2)).build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        room.on_ping(leader, &PingPayload::new(), now);
+        assert!(!room.has_deferred_update());
+
+        let other = room.create_connection(now).unwrap();
+        room.drain_events();
+        let down_vote =
+            PingPayload::new().with_term(room.term()).with_connection_to_leader(ConnectionToLeader::Disconnected);
+        let later = now + Duration::from_millis(100);
+        room.on_ping(follower, &down_vote, later);
+        room.on_ping(other, &down_vote, later);
+
+        // The leader is down-voted, but replacing it waits for the explicit update
+        assert_eq!(room.leader(), Some(leader));
+        assert!(room.has_deferred_update());
+        assert_eq!(room.time_until_next_action(later), Some(Duration::ZERO));
+        assert_eq!(room.metrics().deferred_updates, 2);
+        assert_eq!(room.drain_events(), vec![RoomEvent::Backpressure { connections: 3 }]);
+
+        room.update(later);
+        assert!(!room.has_deferred_update());
+        assert_ne!(room.leader(), Some(leader));
+    }
+}
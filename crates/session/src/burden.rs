@@ -0,0 +1,200 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{Connection, ConnectionIndex, ConnectionState, ElectionTrigger, Instant, RecordedInput, Room, RoomError, RoomEvent};
+
+/// Weight of the latest report in [RoundTripTrend::smoothed], the same as for the smoothed RTT of TCP
+pub const ROUND_TRIP_SMOOTHING: f32 = 0.125;
+
+/// Round trip times the transport reported for a connection, see [Room::record_round_trip]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundTripTrend {
+    /// The lowest round trip time reported
+    pub baseline: Duration,
+    /// Moving average of the reported round trip times, see [ROUND_TRIP_SMOOTHING]
+    pub smoothed: Duration,
+}
+
+impl RoundTripTrend {
+    fn new(round_trip: Duration) -> Self {
+        Self {
+            baseline: round_trip,
+            smoothed: round_trip,
+        }
+    }
+
+    fn sample(&mut self, round_trip: Duration) {
+        self.baseline = self.baseline.min(round_trip);
+        self.smoothed = self.smoothed.mul_f32(1.0 - ROUND_TRIP_SMOOTHING) + round_trip.mul_f32(ROUND_TRIP_SMOOTHING);
+    }
+
+    /// How many times slower the connection is than at its best, 1.0 or more
+    pub fn trend(&self) -> f32 {
+        if self.baseline.is_zero() {
+            return 1.0;
+        }
+        (self.smoothed.as_secs_f32() / self.baseline.as_secs_f32()).max(1.0)
+    }
+}
+
+impl<K: KnowledgeOrd> Connection<K> {
+    /// The [crate::PingPayload::capability] as a fraction of 100, 1.0 if the connection has not reported it
+    pub(crate) fn capability_factor(&self) -> f32 {
+        self.capability.map_or(1.0, |capability| capability.max(1) as f32 / 100.0)
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Called by the transport with a round trip time it measured to the connection, see
+    /// [crate::Connection::round_trip]
+    pub fn record_round_trip(
+        &mut self,
+        connection_index: ConnectionIndex,
+        round_trip: Duration,
+        time: Instant,
+    ) -> Result<(), RoomError> {
+        self.record(
+            time,
+            RecordedInput::RoundTrip {
+                connection: connection_index.value(),
+                generation: connection_index.generation(),
+                round_trip,
+            },
+        );
+        self.observe_time(time);
+        self.validate_connection(connection_index)?;
+        let connection = self.connections.get_mut(&connection_index).unwrap();
+        match &mut connection.round_trip {
+            Some(trend) => trend.sample(round_trip),
+            None => connection.round_trip = Some(RoundTripTrend::new(round_trip)),
+        }
+        self.publish_view();
+        Ok(())
+    }
+
+    /// How hard the current leader has to work, `None` without a leader. See [Room::burden_as_leader]
+    pub fn leader_burden(&self) -> Option<f32> {
+        let leader = self.connections.get(&self.leader_index?)?;
+        Some(self.burden_as_leader(leader))
+    }
+
+    /// How hard `connection` would have to work as leader: the connections it hosts, in multiples of
+    /// [crate::RoomConfig::leader_burden_room_size], times its [RoundTripTrend::trend], divided by its
+    /// [crate::PingPayload::capability] (as a fraction of 100). 1.0 is a typical device hosting a room of the
+    /// configured size as fast as it ever has
+    pub fn burden_as_leader(&self, connection: &Connection<K>) -> f32 {
+        let hosted = self
+            .connections
+            .values()
+            .filter(|other| other.id != connection.id && other.state != ConnectionState::Pending)
+            .count();
+        let size = hosted as f32 / self.config.leader_burden_room_size as f32;
+        let trend = connection.round_trip.map_or(1.0, |round_trip| round_trip.trend());
+        size * trend / connection.capability_factor()
+    }
+
+    /// Hands the leadership over to the best ranked candidate that can carry the room when the leader is over
+    /// [crate::RoomConfig::max_leader_burden]. Nothing changes while the leader is on
+    /// [crate::RoomConfig::leader_probation], so the handed-over leader has time to settle, or if no candidate
+    /// would be under the limit either.
+    pub(crate) fn rebalance_leader_load(&mut self, time: Instant) -> bool {
+        let (Some(max_burden), Some(burden)) = (self.config.max_leader_burden, self.leader_burden()) else {
+            return false;
+        };
+        if burden <= max_burden || self.leader_on_probation(time).is_some() || self.is_backing_off_reelection(time) {
+            return false;
+        }
+        let mut report = self.evaluate_election(self.leader_index);
+        let mut ranked: Vec<_> = report.candidates.iter().filter(|candidate| candidate.rank.is_some()).collect();
+        ranked.sort_by_key(|candidate| candidate.rank);
+        let Some(stronger) = ranked
+            .into_iter()
+            .map(|candidate| candidate.connection)
+            .find(|candidate| self.burden_as_leader(&self.connections[candidate]) <= max_burden)
+        else {
+            return false;
+        };
+        if !self.is_leader_change_allowed(Some(stronger), time) {
+            return false;
+        }
+
+        let leader = self.leader_index.unwrap();
+        info!("leader {} carries a burden of {:.2}, handing over to {}", leader, burden, stronger);
+        self.push_event(RoomEvent::LoadRebalance {
+            term: self.term,
+            leader,
+            burden,
+        });
+        report.winner = Some(stronger);
+        self.switch_leader(report.winner);
+        self.remember_election(report, ElectionTrigger::LoadRebalance);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{ConnectionToLeader, Term};
+
+    use crate::{PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn hand_over_from_weak_leader() {
+        let mut room = RoomConfig::new()
+            .with_leader_burden_room_size(4)
+            .with_max_leader_burden(Some(1.0))
+            .with_leader_probation(Duration::from_secs(1))
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let phone = room.create_connection(now).unwrap();
+        let desktop = room.create_connection(now).unwrap();
+        let followers = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+        room.record_round_trip(phone, Duration::from_millis(40), now).unwrap();
+        room.record_round_trip(phone, Duration::from_millis(200), now).unwrap();
+        assert_eq!(room.get(phone).round_trip().unwrap().trend(), 1.5);
+
+        let tick = |room: &mut crate::Room, millis: u64| {
+            let time = now + Duration::from_millis(millis);
+            let ping = PingPayload::new()
+                .with_term(room.term())
+                .with_connection_to_leader(ConnectionToLeader::Connected);
+            room.on_ping(phone, &ping.clone().with_capability(50), time);
+            room.on_ping(desktop, &ping.clone().with_capability(200), time);
+            for follower in followers {
+                room.on_ping(follower, &ping, time);
+            }
+            room.update(time);
+        };
+        // Three connections in a room size of four, a round trip trend of 1.5 and half the capability of a
+        // typical device
+        tick(&mut room, 100);
+        assert_eq!(room.leader_burden(), Some(2.25));
+
+        // Kept during the probation
+        for millis in (200..=900).step_by(100) {
+            tick(&mut room, millis);
+        }
+        assert_eq!(room.leader(), Some(phone));
+        room.drain_events();
+
+        tick(&mut room, 1000);
+
+        assert_eq!(room.leader(), Some(desktop));
+        assert_eq!(room.leader_burden(), Some(0.375));
+        let events = room.drain_events();
+        assert!(matches!(events[0], RoomEvent::LoadRebalance { term: Term(1), leader, .. } if leader == phone));
+        assert!(events.contains(&RoomEvent::LeaderChanged {
+            term: Term(2),
+            leader: Some(desktop)
+        }));
+    }
+}
@@ -0,0 +1,214 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::collections::HashMap;
+use std::time::Duration;
+
+use conclave_types::{Knowledge, KnowledgeOrd};
+use log::debug;
+
+use crate::{ConnectionIndex, Instant, PingOutcome, PingPayload, QualityAssessment, Room};
+
+/// Synthetic faults for integration tests, available behind the `testing` feature.
+///
+/// Set on a room with [Room::set_chaos], it drops and delays pings handed to [Room::on_ping], freezes connections
+/// and forces quality assessments. Everything random is drawn from a seeded generator, so a test given the same
+/// inputs sees the same faults.
+#[derive(Debug, Clone)]
+pub struct Chaos<K: KnowledgeOrd = Knowledge> {
+    rng: u64,
+    /// Probability, between 0 and 1, that a ping is dropped
+    pub drop_rate: f32,
+    /// Probability, between 0 and 1, that a ping that was not dropped is delayed by `delay`
+    pub delay_rate: f32,
+    pub delay: Duration,
+    frozen: Vec<ConnectionIndex>,
+    forced_assessments: HashMap<ConnectionIndex, QualityAssessment>,
+    /// Delayed pings in the order they are due
+    delayed: Vec<(Instant, ConnectionIndex, PingPayload<K>)>,
+}
+
+impl<K: KnowledgeOrd> Chaos<K> {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: seed,
+            drop_rate: 0.0,
+            delay_rate: 0.0,
+            delay: Duration::ZERO,
+            frozen: Vec::new(),
+            forced_assessments: HashMap::new(),
+            delayed: Vec::new(),
+        }
+    }
+
+    pub fn with_drop_rate(mut self, rate: f32) -> Self {
+        self.drop_rate = rate;
+        self
+    }
+
+    pub fn with_delay(mut self, rate: f32, delay: Duration) -> Self {
+        self.delay_rate = rate;
+        self.delay = delay;
+        self
+    }
+
+    /// Drops every ping from the connection until it is thawed
+    pub fn freeze(&mut self, connection: ConnectionIndex) {
+        if !self.frozen.contains(&connection) {
+            self.frozen.push(connection);
+        }
+    }
+
+    pub fn thaw(&mut self, connection: ConnectionIndex) {
+        self.frozen.retain(|frozen| *frozen != connection);
+    }
+
+    pub fn is_frozen(&self, connection: ConnectionIndex) -> bool {
+        self.frozen.contains(&connection)
+    }
+
+    /// Replaces every assessment of the connection with `assessment`, until cleared
+    pub fn force_assessment(&mut self, connection: ConnectionIndex, assessment: QualityAssessment) {
+        self.forced_assessments.insert(connection, assessment);
+    }
+
+    pub fn clear_assessment(&mut self, connection: ConnectionIndex) {
+        self.forced_assessments.remove(&connection);
+    }
+
+    /// Number of delayed pings that have not been delivered yet
+    pub fn delayed_count(&self) -> usize {
+        self.delayed.len()
+    }
+
+    /// Delays the delivery of held pings by the time the room was paused
+    pub(crate) fn shift(&mut self, paused_duration: Duration) {
+        for (due, _, _) in &mut self.delayed {
+            *due += paused_duration;
+        }
+    }
+
+    /// Uniformly distributed in `[0, 1)`, from a splitmix64 sequence
+    fn next_fraction(&mut self) -> f32 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        ((z >> 11) as f64 / (1u64 << 53) as f64) as f32
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Starts injecting faults, replacing any previous controller and the pings it had delayed
+    pub fn set_chaos(&mut self, chaos: Chaos<K>) {
+        self.chaos = Some(chaos);
+    }
+
+    pub fn chaos_mut(&mut self) -> Option<&mut Chaos<K>> {
+        self.chaos.as_mut()
+    }
+
+    /// Stops injecting faults. Delayed pings that are still held are never delivered
+    pub fn clear_chaos(&mut self) -> Option<Chaos<K>> {
+        self.chaos.take()
+    }
+
+    /// Returns the outcome if the ping was dropped or delayed, `None` if it should be received now
+    pub(crate) fn inject_ping_fault(
+        &mut self,
+        connection_index: ConnectionIndex,
+        ping: &PingPayload<K>,
+        time: Instant,
+    ) -> Option<PingOutcome> {
+        let chaos = self.chaos.as_mut()?;
+        if chaos.is_frozen(connection_index) || chaos.next_fraction() < chaos.drop_rate {
+            debug!("chaos dropped {} from {}", ping, connection_index);
+            return Some(PingOutcome::Injected);
+        }
+        if chaos.next_fraction() < chaos.delay_rate {
+            let due = time + chaos.delay;
+            let position = chaos.delayed.partition_point(|(at, _, _)| *at <= due);
+            chaos.delayed.insert(position, (due, connection_index, ping.clone()));
+            return Some(PingOutcome::Injected);
+        }
+        None
+    }
+
+    /// Receives the delayed pings that are due at `time`, at the time they were due
+    pub(crate) fn deliver_delayed_pings(&mut self, time: Instant) {
+        let Some(chaos) = self.chaos.as_mut() else {
+            return;
+        };
+        let due_count = chaos.delayed.partition_point(|(at, _, _)| *at <= time);
+        let due: Vec<_> = chaos.delayed.drain(..due_count).collect();
+        for (at, connection_index, ping) in due {
+            self.receive_ping(connection_index, &ping, at);
+        }
+    }
+
+    pub(crate) fn apply_forced_assessments(&mut self) {
+        let Some(chaos) = &self.chaos else {
+            return;
+        };
+        let mut forced = Vec::new();
+        for (connection_index, assessment) in &chaos.forced_assessments {
+            if let Some(connection) = self.connections.get_mut(connection_index) {
+                connection.quality.assessment = *assessment;
+                forced.push(*connection_index);
+            }
+        }
+        for connection_index in forced {
+            self.observe_assessment(connection_index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{Chaos, PingOutcome, PingPayload, QualityAssessment, Room};
+
+    #[test]
+    fn drop_and_delay_pings_deterministically() {
+        let run = |seed| {
+            let mut room = Room::new();
+            let now = Instant::now();
+            let connection = room.create_connection(now).unwrap();
+            room.set_chaos(Chaos::new(seed).with_drop_rate(0.3).with_delay(0.5, Duration::from_millis(250)));
+            let outcomes: Vec<PingOutcome> = (0..20)
+                .map(|millis| room.on_ping(connection, &PingPayload::new(), now + Duration::from_millis(millis * 10)))
+                .collect();
+            let delayed = room.chaos_mut().unwrap().delayed_count();
+            room.update(now + Duration::from_millis(300));
+            (outcomes, delayed, room.chaos_mut().unwrap().delayed_count())
+        };
+
+        let (outcomes, delayed, remaining) = run(7);
+        assert_eq!(run(7).0, outcomes);
+        let injected = outcomes.iter().filter(|outcome| **outcome == PingOutcome::Injected).count();
+        assert!(injected > delayed && delayed > 0);
+        assert!(remaining < delayed);
+    }
+
+    #[test]
+    fn freeze_and_force_assessment() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        let mut chaos = Chaos::new(1);
+        chaos.freeze(leader);
+        chaos.force_assessment(follower, QualityAssessment::Good);
+        room.set_chaos(chaos);
+
+        assert_eq!(room.on_ping(leader, &PingPayload::new(), now), PingOutcome::Injected);
+        room.update(now);
+        assert_eq!(room.get(follower).assessment(), QualityAssessment::Good);
+
+        room.chaos_mut().unwrap().thaw(leader);
+        assert!(room.on_ping(leader, &PingPayload::new(), now).is_accepted());
+    }
+}
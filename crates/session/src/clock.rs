@@ -0,0 +1,98 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use crate::Instant;
+
+/// Relates the clock of a connection to the time given to the room, from the [crate::PingPayload::sent_at] of its
+/// pings, see [crate::Connection::clock_skew].
+///
+/// The offset is the receive time minus the send time, relative to the first ping, smoothed with
+/// [crate::RoomConfig::clock_skew_smoothing]. It includes the latency to the room, so a room time is when an event
+/// stamped by the client would have reached the room.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockSkew {
+    room_anchor: Instant,
+    client_anchor: Duration,
+    offset: f64,
+    samples: u32,
+}
+
+impl ClockSkew {
+    pub(crate) fn new(received_at: Instant, sent_at: Duration) -> Self {
+        Self {
+            room_anchor: received_at,
+            client_anchor: sent_at,
+            offset: 0.0,
+            samples: 1,
+        }
+    }
+
+    pub(crate) fn sample(&mut self, received_at: Instant, sent_at: Duration, smoothing: f32) {
+        let room_elapsed = seconds_between(self.room_anchor, received_at);
+        let client_elapsed = sent_at.as_secs_f64() - self.client_anchor.as_secs_f64();
+        let alpha = smoothing as f64;
+        self.offset = alpha * (room_elapsed - client_elapsed) + (1.0 - alpha) * self.offset;
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// How much later, in seconds, the pings arrive than at the first ping. It grows if the client clock runs
+    /// slower than the time given to the room, or if the latency goes up
+    pub fn drift(&self) -> f64 {
+        self.offset
+    }
+
+    /// Number of pings the estimate is based on
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// The room time for `client_time` on the clock of the connection
+    pub fn to_room_time(&self, client_time: Duration) -> Instant {
+        let seconds = client_time.as_secs_f64() - self.client_anchor.as_secs_f64() + self.offset;
+        if seconds >= 0.0 {
+            self.room_anchor + Duration::from_secs_f64(seconds)
+        } else {
+            self.room_anchor
+                .checked_sub(Duration::from_secs_f64(-seconds))
+                .unwrap_or(self.room_anchor)
+        }
+    }
+}
+
+/// Signed number of seconds from `from` to `to`
+fn seconds_between(from: Instant, to: Instant) -> f64 {
+    match to.checked_duration_since(from) {
+        Some(elapsed) => elapsed.as_secs_f64(),
+        None => -from.saturating_duration_since(to).as_secs_f64(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{PingPayload, RoomConfig};
+
+    #[test]
+    fn estimate_clock_of_slow_client() {
+        let mut room = RoomConfig::new().with_clock_skew_smoothing(0.5).build().unwrap();
+        let now = Instant::now();
+        let connection = room.create_connection(now).unwrap();
+        assert_eq!(room.get(connection).clock_skew(), None);
+
+        let client_clock = Duration::from_secs(1000);
+        for (room_millis, client_millis) in [(0, 0), (100, 90), (200, 180)] {
+            let ping = PingPayload::new().with_sent_at(client_clock + Duration::from_millis(client_millis));
+            room.on_ping(connection, &ping, now + Duration::from_millis(room_millis));
+        }
+
+        let skew = room.get(connection).clock_skew().unwrap();
+        assert_eq!(skew.samples(), 3);
+        assert!((skew.drift() - 0.0125).abs() < 1e-9);
+        let room_time = skew.to_room_time(client_clock + Duration::from_millis(180));
+        assert!(((room_time - now).as_secs_f64() - 0.1925).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,261 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::outgoing::OutgoingIntent;
+use crate::recorder::RecordedInput;
+use crate::{ConnectionIndex, ConnectionState, Instant, Room, RoomError, RoomEvent};
+
+/// Why the room is closed, see [Room::begin_close]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CloseReason {
+    MatchEnded,
+    ServerShutdown,
+    Abandoned,
+}
+
+impl CloseReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloseReason::MatchEnded => "match_ended",
+            CloseReason::ServerShutdown => "server_shutdown",
+            CloseReason::Abandoned => "abandoned",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Closing {
+    reason: CloseReason,
+    /// None if the drain period is too long to end at a time that can be represented
+    drain_until: Option<Instant>,
+    /// Connections that were told and have not acknowledged yet, sorted by connection index
+    waiting_for: Vec<ConnectionIndex>,
+    is_closed: bool,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Starts closing the room: new connections are refused with [RoomError::RoomClosing], every connection that
+    /// has pinged gets an [OutgoingIntent::RoomClosing] and [RoomEvent::RoomClosing] is emitted.
+    ///
+    /// The room is closed once every told connection has called [Room::acknowledge_close], or by the first
+    /// [Room::update] after [crate::RoomConfig::close_drain_period]. Closing removes all connections and emits
+    /// [RoomEvent::RoomClosed]. Calling it again while closing keeps the first reason and deadline.
+    pub fn begin_close(&mut self, reason: CloseReason, now: Instant) {
+        self.record(now, RecordedInput::BeginClose { reason });
+        let time = self.observe_time(now);
+        self.start_closing(reason, time);
+        self.publish_view();
+    }
+
+    pub(crate) fn start_closing(&mut self, reason: CloseReason, time: Instant) {
+        if self.closing.is_some() {
+            return;
+        }
+        info!("closing room {} because of {}", self.id, reason.as_str());
+        let mut waiting_for: Vec<ConnectionIndex> = self
+            .connections
+            .values()
+            .filter(|connection| connection.state != ConnectionState::Pending)
+            .map(|connection| connection.id)
+            .collect();
+        waiting_for.sort_by_key(|index| index.value());
+        for connection in &waiting_for {
+            self.push_outgoing(*connection, OutgoingIntent::RoomClosing { reason });
+        }
+        self.closing = Some(Closing {
+            reason,
+            drain_until: time.checked_add(self.config.close_drain_period),
+            waiting_for,
+            is_closed: false,
+        });
+        self.push_event(RoomEvent::RoomClosing { reason });
+        self.close_if_drained(time);
+    }
+
+    /// The connection has received [OutgoingIntent::RoomClosing], the room closes when the last one does
+    pub fn acknowledge_close(&mut self, connection_index: ConnectionIndex) -> Result<(), RoomError> {
+        self.record_untimed(RecordedInput::AcknowledgeClose {
+            connection: connection_index.value(),
+            generation: connection_index.generation(),
+        });
+        self.validate_connection(connection_index)?;
+        if let Some(closing) = &mut self.closing {
+            closing.waiting_for.retain(|index| *index != connection_index);
+            if let Some(time) = self.latest_time {
+                self.close_if_drained(time);
+            }
+        }
+        self.publish_view();
+        Ok(())
+    }
+
+    /// True from [Room::begin_close] on
+    pub fn is_closing(&self) -> bool {
+        self.closing.is_some()
+    }
+
+    /// True once the room has closed and released its connections
+    pub fn is_closed(&self) -> bool {
+        self.closing.as_ref().is_some_and(|closing| closing.is_closed)
+    }
+
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.closing.as_ref().map(|closing| closing.reason)
+    }
+
+    /// Returns [RoomError::RoomClosing] if the room is closing or closed
+    pub(crate) fn ensure_open(&self) -> Result<(), RoomError> {
+        if self.closing.is_some() {
+            return Err(RoomError::RoomClosing);
+        }
+        Ok(())
+    }
+
+    /// When the drain period of a closing room is over
+    pub(crate) fn close_deadline(&self) -> Option<Instant> {
+        self.closing
+            .as_ref()
+            .filter(|closing| !closing.is_closed)
+            .and_then(|closing| closing.drain_until)
+    }
+
+    /// Closes the room if every told connection has acknowledged, left the room, or the drain period is over
+    pub(crate) fn close_if_drained(&mut self, time: Instant) {
+        let Some(closing) = &mut self.closing else {
+            return;
+        };
+        if closing.is_closed {
+            return;
+        }
+        let connections = &self.connections;
+        closing.waiting_for.retain(|index| connections.contains_key(index));
+        if !closing.waiting_for.is_empty() && closing.drain_until.is_none_or(|drain_until| time < drain_until) {
+            return;
+        }
+        closing.is_closed = true;
+        let reason = closing.reason;
+        let unacknowledged = std::mem::take(&mut closing.waiting_for);
+        info!("room {} is closed, {} connections did not acknowledge", self.id, unacknowledged.len());
+        self.release_connections();
+        self.push_event(RoomEvent::RoomClosed { reason, unacknowledged });
+    }
+
+    /// Removes every connection at once, without electing anyone
+    fn release_connections(&mut self) {
+        self.connections.clear();
+        self.identities.clear();
+        self.state_syncs.clear();
+        self.representatives.clear();
+        self.unreachable_by_leader.clear();
+        self.scan = Default::default();
+        self.leader_index = None;
+        self.retiring_leader = None;
+        self.kick_vote = None;
+        self.provisional_previous_leader = None;
+    }
+
+    /// Moves the drain deadline forward, so `duration` does not count towards it
+    pub(crate) fn shift_close_deadline(&mut self, duration: Duration) {
+        if let Some(closing) = &mut self.closing {
+            closing.drain_until = closing.drain_until.and_then(|drain_until| drain_until.checked_add(duration));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::outgoing::{Outgoing, OutgoingIntent};
+    use crate::{CloseReason, PingPayload, Room, RoomError, RoomEvent, RoomState};
+
+    #[test]
+    fn close_when_everyone_acknowledged() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        let _placeholder = room.preregister_connection("late", now).unwrap();
+        room.drain_outgoing();
+        room.drain_events();
+
+        room.begin_close(CloseReason::MatchEnded, now);
+
+        assert_eq!(room.state(now), RoomState::Closing);
+        assert_eq!(room.create_connection(now), Err(RoomError::RoomClosing));
+        assert_eq!(
+            room.drain_outgoing(),
+            vec![
+                Outgoing {
+                    connection: leader,
+                    intent: OutgoingIntent::RoomClosing {
+                        reason: CloseReason::MatchEnded
+                    },
+                },
+                Outgoing {
+                    connection: follower,
+                    intent: OutgoingIntent::RoomClosing {
+                        reason: CloseReason::MatchEnded
+                    },
+                },
+            ]
+        );
+
+        room.acknowledge_close(leader).unwrap();
+        assert!(!room.is_closed());
+        room.acknowledge_close(follower).unwrap();
+
+        assert!(room.is_closed());
+        assert_eq!(room.state(now), RoomState::Closed);
+        assert!(room.connections().is_empty());
+        assert_eq!(room.leader(), None);
+        assert_eq!(room.find_by_identity("late"), None);
+        assert_eq!(
+            room.drain_events(),
+            vec![
+                RoomEvent::RoomClosing {
+                    reason: CloseReason::MatchEnded
+                },
+                RoomEvent::RoomClosed {
+                    reason: CloseReason::MatchEnded,
+                    unacknowledged: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn close_after_drain_period() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let silent = room.create_connection(now).unwrap();
+
+        room.begin_close(CloseReason::ServerShutdown, now);
+        room.acknowledge_close(leader).unwrap();
+        let drain_period = room.config.close_drain_period;
+        room.on_ping(leader, &PingPayload::new(), now + drain_period / 2);
+        room.update(now + drain_period / 2);
+        assert!(!room.is_closed());
+
+        room.update(now + drain_period);
+
+        assert!(room.is_closed());
+        assert!(room.drain_events().contains(&RoomEvent::RoomClosed {
+            reason: CloseReason::ServerShutdown,
+            unacknowledged: vec![silent],
+        }));
+        assert!(!room.on_ping(leader, &PingPayload::new(), now + drain_period).is_accepted());
+    }
+}
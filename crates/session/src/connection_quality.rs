@@ -1,10 +1,15 @@
 use core::fmt;
-use std::time::Instant;
+use std::time::Duration;
 
 use crate::metrics::RateMetrics;
+use crate::{Instant, RoomConfig};
+
+/// Time it takes for a flip between healthy and unhealthy to count half as much towards [ConnectionQuality::stability]
+pub const FLAPPING_HALF_LIFE: Duration = Duration::from_secs(30);
 
 /// Resulting Assessment made by [ConnectionQuality]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "snake_case"))]
 pub enum QualityAssessment {
     NeedMoreInformation,
     RecommendDisconnect,
@@ -20,6 +25,14 @@ pub struct ConnectionQuality {
     pub last_pings_per_second: f32,
     pub assessment: QualityAssessment,
     threshold: f32,
+    /// Flips between healthy and unhealthy assessments, decayed by [FLAPPING_HALF_LIFE]
+    flapping: f32,
+    flapping_decayed_at: Instant,
+    was_healthy: Option<bool>,
+    has_measured: bool,
+    samples: u32,
+    min_samples: u32,
+    smoothing: Option<f32>,
 }
 
 
@@ -33,31 +46,199 @@ impl ConnectionQuality {
     pub fn new(threshold: f32, time: Instant) -> Self {
         Self {
             assessment: QualityAssessment::NeedMoreInformation,
-            last_ping_at: Instant::now(),
+            last_ping_at: time,
             pings_per_second: RateMetrics::new(time),
             last_pings_per_second: 0.0,
             threshold,
+            flapping: 0.0,
+            flapping_decayed_at: time,
+            was_healthy: None,
+            has_measured: false,
+            samples: 0,
+            min_samples: 1,
+            smoothing: None,
         }
     }
 
+    /// Quality evaluated with the threshold, window and smoothing from the room config
+    pub fn from_config(config: &RoomConfig, time: Instant) -> Self {
+        Self::new(config.pings_per_second_threshold, time)
+            .with_window(config.assessment_window)
+            .with_min_samples(config.min_assessment_samples)
+            .with_smoothing(config.rate_smoothing)
+    }
+
+    /// Applies the threshold, window and smoothing from a changed room config, keeping the pings counted
+    /// so far and the flapping history
+    pub(crate) fn reconfigure(&mut self, config: &RoomConfig) {
+        self.threshold = config.pings_per_second_threshold;
+        self.pings_per_second.set_period(config.assessment_window);
+        self.min_samples = config.min_assessment_samples;
+        self.smoothing = config.rate_smoothing;
+    }
+
+    /// How long the pings are counted for every rate calculation
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.pings_per_second = self.pings_per_second.with_period(window);
+        self
+    }
+
+    /// Number of rate calculations needed before the first assessment is made
+    pub fn with_min_samples(mut self, min_samples: u32) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    /// Smooths the rate with an exponential moving average, `alpha` is the weight of the latest window
+    pub fn with_smoothing(mut self, alpha: Option<f32>) -> Self {
+        self.smoothing = alpha;
+        self
+    }
+
     pub fn on_ping(&mut self, time: Instant) {
         self.last_ping_at = time;
         self.pings_per_second.increment();
     }
 
-    pub fn update(&mut self, time: Instant) {
+    /// True if the time since the last ping is short enough to keep up with the threshold rate
+    pub fn is_within_rate(&self, time: Instant) -> bool {
+        self.is_within_intervals(1.0, time)
+    }
+
+    /// True if the time since the last ping is at most `intervals` of the time between pings at the threshold rate
+    pub fn is_within_intervals(&self, intervals: f32, time: Instant) -> bool {
+        time.saturating_duration_since(self.last_ping_at).as_secs_f32() <= intervals / self.threshold
+    }
+
+    /// From 1.0 for a connection that has kept the same health, towards 0.0 for one that recently flipped
+    /// between healthy and unhealthy assessments many times
+    pub fn stability(&self) -> f32 {
+        1.0 / (1.0 + self.flapping)
+    }
+
+    /// When the next assessment, based on a new rate calculation, can be made
+    pub fn next_assessment_at(&self) -> Instant {
+        self.pings_per_second.next_calculation_at()
+    }
+
+    /// Moves all timestamps forward, as if `duration` never passed
+    pub(crate) fn shift(&mut self, duration: Duration) {
+        self.last_ping_at += duration;
+        self.pings_per_second.shift(duration);
+        self.flapping_decayed_at += duration;
+    }
+
+    /// Forgets the rates, assessments and flapping so far, the next assessment is made from the pings after `time`
+    pub(crate) fn reset(&mut self, time: Instant) {
+        self.pings_per_second.restart(time);
+        self.last_pings_per_second = 0.0;
+        self.assessment = QualityAssessment::NeedMoreInformation;
+        self.flapping = 0.0;
+        self.flapping_decayed_at = time;
+        self.was_healthy = None;
+        self.has_measured = false;
+        self.samples = 0;
+    }
+
+    fn decay_flapping(&mut self, time: Instant) {
+        let elapsed = time.saturating_duration_since(self.flapping_decayed_at);
+        self.flapping *= 0.5_f32.powf(elapsed.as_secs_f32() / FLAPPING_HALF_LIFE.as_secs_f32());
+        self.flapping_decayed_at = time;
+    }
+
+    /// Calculates a new rate if enough time has passed since the last one, otherwise there is not enough information
+    /// for an assessment. Returns true if a new rate was calculated and enough rates have been calculated to assess it.
+    pub(crate) fn measure(&mut self, time: Instant) -> bool {
         if !self.pings_per_second.has_enough_time_passed(time) {
             self.assessment = QualityAssessment::NeedMoreInformation;
+            return false;
+        }
+        let rate = self.pings_per_second.calculate_rate(time);
+        self.last_pings_per_second = match self.smoothing {
+            Some(alpha) if self.has_measured => alpha * rate + (1.0 - alpha) * self.last_pings_per_second,
+            _ => rate,
+        };
+        self.has_measured = true;
+        self.samples = self.samples.saturating_add(1);
+        if self.samples < self.min_samples {
+            self.assessment = QualityAssessment::NeedMoreInformation;
+            return false;
+        }
+        true
+    }
+
+    /// The latest calculated rate, `None` until the first one has been calculated
+    pub fn measured_pings_per_second(&self) -> Option<f32> {
+        self.has_measured.then_some(self.last_pings_per_second)
+    }
+
+    /// The threshold, lowered to `ceiling` if that is lower
+    pub(crate) fn effective_threshold(&self, ceiling: Option<f32>) -> f32 {
+        ceiling.map_or(self.threshold, |ceiling| self.threshold.min(ceiling))
+    }
+
+    /// Assesses the latest rate against the threshold, lowered to `ceiling` if that is lower
+    pub(crate) fn assess(&mut self, ceiling: Option<f32>, time: Instant) {
+        let threshold = self.effective_threshold(ceiling);
+        self.assessment = if self.last_pings_per_second < threshold {
+            QualityAssessment::RecommendDisconnect
+        } else if self.last_pings_per_second > threshold * 2.0 {
+            QualityAssessment::Good
         } else {
-            self.last_pings_per_second = self.pings_per_second.calculate_rate(time);
-            self.assessment = if self.last_pings_per_second < self.threshold {
-                QualityAssessment::RecommendDisconnect
-            } else if self.last_pings_per_second > self.threshold * 2.0 {
-                QualityAssessment::Good
-            } else {
-                QualityAssessment::Acceptable
-            };
+            QualityAssessment::Acceptable
+        };
+
+        self.decay_flapping(time);
+        let is_healthy = self.assessment != QualityAssessment::RecommendDisconnect;
+        if self.was_healthy.is_some_and(|was_healthy| was_healthy != is_healthy) {
+            self.flapping += 1.0;
+        }
+        self.was_healthy = Some(is_healthy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::connection_quality::{ConnectionQuality, QualityAssessment};
 
+    /// Pings `count` times and measures the rate after `window`
+    fn ping_window(quality: &mut ConnectionQuality, time: &mut Instant, count: u32, window: Duration) {
+        for _ in 0..count {
+            quality.on_ping(*time);
         }
+        *time += window;
+        if quality.measure(*time) {
+            quality.assess(None, *time);
+        }
+    }
+
+    #[test]
+    fn wait_for_min_samples() {
+        let mut time = Instant::now();
+        let window = Duration::from_secs(2);
+        let mut quality = ConnectionQuality::new(5.0, time).with_window(window).with_min_samples(2);
+
+        ping_window(&mut quality, &mut time, 0, window + Duration::from_millis(1));
+        assert_eq!(quality.assessment, QualityAssessment::NeedMoreInformation);
+
+        ping_window(&mut quality, &mut time, 0, window + Duration::from_millis(1));
+        assert_eq!(quality.assessment, QualityAssessment::RecommendDisconnect);
+    }
+
+    #[test]
+    fn smooth_sudden_drop() {
+        let mut time = Instant::now();
+        let window = Duration::from_secs(1);
+        let mut quality = ConnectionQuality::new(5.0, time).with_smoothing(Some(0.25));
+
+        ping_window(&mut quality, &mut time, 20, window);
+        assert_eq!(quality.assessment, QualityAssessment::Good);
+
+        // A single silent window only lowers the smoothed rate by a quarter
+        ping_window(&mut quality, &mut time, 0, window);
+        assert_eq!(quality.last_pings_per_second, 15.0);
+        assert_eq!(quality.assessment, QualityAssessment::Good);
     }
 }
@@ -0,0 +1,169 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+use log::{info, warn};
+
+use crate::recorder::RecordedInput;
+use crate::{Instant, Room, RoomEvent};
+
+/// A time during which the leader is kept, see [Room::enter_critical_section]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CriticalSection {
+    /// Given by the host, e.g. `"scoring"`, to tell the sections apart in [RoomEvent::CriticalSectionTimedOut]
+    pub tag: String,
+    pub entered_at: Instant,
+    /// None if `max_duration` reaches beyond any time that can be represented, the section then never expires
+    pub expires_at: Option<Instant>,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Keeps the leader until [Room::exit_critical_section] is called, for moments when a new leader would be
+    /// disruptive, like scoring a match or taking a checkpoint of the game state.
+    ///
+    /// Down-votes, bad quality, lease expiry, load rebalancing and partition reconciliation do not replace the
+    /// leader in the meantime. A leader that leaves the room is still replaced, and a room without a leader still
+    /// elects one. If the section is not exited within `max_duration`, it ends on its own with
+    /// [RoomEvent::CriticalSectionTimedOut]. Returns the section that was open before, which this one replaces.
+    pub fn enter_critical_section(
+        &mut self,
+        tag: &str,
+        max_duration: Duration,
+        time: Instant,
+    ) -> Option<CriticalSection> {
+        self.record(
+            time,
+            RecordedInput::EnterCriticalSection {
+                tag: tag.to_string(),
+                max_duration,
+            },
+        );
+        let time = self.observe_time(time);
+        info!("entering critical section '{}' for at most {:?}", tag, max_duration);
+        let previous = self.critical_section.replace(CriticalSection {
+            tag: tag.to_string(),
+            entered_at: time,
+            expires_at: time.checked_add(max_duration),
+        });
+        self.publish_view();
+        previous
+    }
+
+    /// Lets the leader be replaced again from the next update. Returns the section that was open, if any
+    pub fn exit_critical_section(&mut self) -> Option<CriticalSection> {
+        self.record_untimed(RecordedInput::ExitCriticalSection);
+        let section = self.critical_section.take();
+        if let Some(section) = &section {
+            info!("exiting critical section '{}'", section.tag);
+        }
+        self.publish_view();
+        section
+    }
+
+    pub fn critical_section(&self) -> Option<&CriticalSection> {
+        self.critical_section.as_ref()
+    }
+
+    /// True if a critical section keeps the leader at `time`. A section that has expired ends here.
+    pub(crate) fn is_in_critical_section(&mut self, time: Instant) -> bool {
+        let Some(section) = &self.critical_section else {
+            return false;
+        };
+        if section.expires_at.is_none_or(|expires_at| time < expires_at) {
+            return true;
+        }
+        let section = self.critical_section.take().unwrap();
+        let held_for = time.saturating_duration_since(section.entered_at);
+        warn!("critical section '{}' was not exited within {:?}", section.tag, held_for);
+        self.push_event(RoomEvent::CriticalSectionTimedOut {
+            tag: section.tag,
+            held_for,
+        });
+        false
+    }
+
+    /// Moves the expiry of the open section forward, so `duration` does not count towards it
+    pub(crate) fn shift_critical_section(&mut self, duration: Duration) {
+        if let Some(section) = &mut self.critical_section {
+            section.entered_at += duration;
+            section.expires_at = section.expires_at.and_then(|expires_at| expires_at.checked_add(duration));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::ConnectionToLeader;
+
+    use crate::{CloseReason, PingPayload, Room, RoomConfig, RoomEvent};
+
+    #[test]
+    fn keep_leader_until_section_exits_or_expires() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let followers = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+        let down_vote = PingPayload::new()
+            .with_term(room.term())
+            .with_connection_to_leader(ConnectionToLeader::Disconnected);
+
+        assert!(room.enter_critical_section("scoring", Duration::from_millis(300), now).is_none());
+        for millis in (100..=200).step_by(50) {
+            let time = now + Duration::from_millis(millis);
+            room.on_ping(leader, &PingPayload::new().with_term(room.term()), time);
+            for follower in followers {
+                room.on_ping(follower, &down_vote, time);
+            }
+        }
+        assert_eq!(room.leader(), Some(leader));
+        assert_eq!(room.critical_section().unwrap().tag, "scoring");
+
+        room.update(now + Duration::from_millis(300));
+        assert!(room.critical_section().is_none());
+        assert_ne!(room.leader(), Some(leader));
+        assert!(room.drain_events().contains(&RoomEvent::CriticalSectionTimedOut {
+            tag: "scoring".to_string(),
+            held_for: Duration::from_millis(300),
+        }));
+
+        let later = now + Duration::from_millis(400);
+        room.enter_critical_section("checkpoint", Duration::from_secs(5), later);
+        assert_eq!(room.exit_critical_section().unwrap().tag, "checkpoint");
+        assert!(room.exit_critical_section().is_none());
+    }
+
+    #[test]
+    fn never_expire_when_duration_overflows() {
+        let mut room = RoomConfig::new()
+            .with_kick_vote_duration(Duration::MAX)
+            .with_close_drain_period(Duration::MAX)
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let proposer = room.create_connection(now).unwrap();
+        let target = room.create_connection(now).unwrap();
+
+        room.enter_critical_section("forever", Duration::MAX, now);
+        room.propose_kick(target, proposer, now).unwrap();
+        assert_eq!(room.critical_section().unwrap().expires_at, None);
+        assert_eq!(room.kick_proposal().unwrap().ends_at, None);
+
+        room.pause(now);
+        room.resume(now + Duration::from_secs(1));
+        let later = now + Duration::from_secs(3600);
+        room.update(later);
+        assert!(room.critical_section().is_some());
+        assert!(room.kick_proposal().is_some());
+
+        room.begin_close(CloseReason::MatchEnded, later);
+        room.acknowledge_close(leader).unwrap();
+        room.update(later + Duration::from_secs(3600));
+        assert!(!room.is_closed());
+    }
+}
@@ -0,0 +1,103 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::fmt::Write;
+
+use conclave_types::{ConnectionToLeader, KnowledgeOrd};
+
+use crate::{Connection, ConnectionState, QualityAssessment, Room};
+
+fn fill_color<K: KnowledgeOrd>(connection: &Connection<K>) -> &'static str {
+    match connection.state {
+        ConnectionState::Disconnected => return "gray",
+        ConnectionState::Pending => return "whitesmoke",
+        ConnectionState::Joining => return "white",
+        ConnectionState::Quarantined => return "orange",
+        ConnectionState::Online => {}
+    }
+    match connection.assessment() {
+        QualityAssessment::NeedMoreInformation => "lightyellow",
+        QualityAssessment::RecommendDisconnect => "salmon",
+        QualityAssessment::Acceptable => "lightblue",
+        QualityAssessment::Good => "palegreen",
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Renders the room in the graphviz DOT format.
+    ///
+    /// Connections are nodes filled by state and quality assessment, the leader is drawn with a double border.
+    /// Each connection that reported its connection to the leader gets an edge to the leader, solid if
+    /// it can reach the leader and dashed red if it can not.
+    pub fn to_dot(&self) -> String {
+        let mut connections: Vec<&Connection<K>> = self.connections.values().collect();
+        connections.sort_by_key(|connection| connection.id.value());
+
+        let mut dot = String::new();
+        writeln!(dot, "digraph room {{").unwrap();
+        writeln!(dot, "    label=\"room {} {}\";", self.id.value(), self.term).unwrap();
+        writeln!(dot, "    node [shape=circle, style=filled];").unwrap();
+
+        for connection in &connections {
+            let is_leader = self.leader_index == Some(connection.id);
+            let name = connection.debug_name.as_deref().unwrap_or("");
+            writeln!(
+                dot,
+                "    c{} [label=\"{} {}\\nknowledge {}\", fillcolor={}{}];",
+                connection.id.value(),
+                connection.id.value(),
+                name,
+                connection.knowledge,
+                fill_color(connection),
+                if is_leader { ", shape=doublecircle, penwidth=2" } else { "" },
+            )
+            .unwrap();
+        }
+
+        if let Some(leader_index) = self.leader_index {
+            for connection in connections.iter().filter(|connection| connection.id != leader_index) {
+                let style = match connection.has_connection_host {
+                    ConnectionToLeader::Unknown => continue,
+                    ConnectionToLeader::Connected => "solid",
+                    ConnectionToLeader::Disconnected => "dashed, color=red",
+                };
+                writeln!(dot, "    c{} -> c{} [style={}];", connection.id.value(), leader_index.value(), style)
+                    .unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::{ConnectionToLeader, Term};
+
+    use crate::{PingPayload, Room};
+
+    #[test]
+    fn render_topology() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        room.create_connection(now).unwrap();
+        let connected = room.create_connection(now).unwrap();
+        let disconnected = room.create_connection(now).unwrap();
+        room.create_connection(now).unwrap();
+        let ping = PingPayload::new().with_term(Term(1));
+        room.on_ping(connected, &ping.clone().with_connection_to_leader(ConnectionToLeader::Connected), now);
+        room.on_ping(disconnected, &ping.with_connection_to_leader(ConnectionToLeader::Disconnected), now);
+
+        let dot = room.to_dot();
+
+        assert!(dot.starts_with("digraph room {\n"));
+        assert!(dot.contains("c1 [label=\"1 \\nknowledge Knowledge: 0\", fillcolor=lightyellow, shape=doublecircle"));
+        assert!(dot.contains("c2 -> c1 [style=solid];"));
+        assert!(dot.contains("c3 -> c1 [style=dashed, color=red];"));
+        assert!(!dot.contains("c4 ->"));
+    }
+}
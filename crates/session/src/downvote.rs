@@ -0,0 +1,207 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::{ConnectionToLeader, KnowledgeOrd, Term};
+
+use crate::{Connection, ConnectionIndex, Instant, Room};
+
+/// How the voters see the leader in the current term, see [Room::downvote_status]. Every list is sorted by
+/// connection index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownvoteStatus {
+    pub term: Term,
+    /// Voters whose latest ping in this term reports that they lost the connection to the leader
+    pub disconnected: Vec<ConnectionIndex>,
+    /// Voters whose latest ping in this term reports that they can reach the leader
+    pub connected: Vec<ConnectionIndex>,
+    /// Voters that have not pinged in this term, or did not know if they could reach the leader
+    pub not_reported: Vec<ConnectionIndex>,
+    /// The part of `disconnected` that counts towards replacing the leader, after
+    /// [crate::RoomConfig::down_vote_freshness], [crate::RoomConfig::sustained_leader_loss] and
+    /// [crate::RoomConfig::max_down_vote_changes]
+    pub counted: Vec<ConnectionIndex>,
+    /// Voters that changed their down-vote too often, their down-votes do not count for now
+    pub rate_limited: Vec<ConnectionIndex>,
+    /// `counted` out of all voters, 0.0 without voters
+    pub quorum_fraction: f32,
+    /// True if a majority of the voters has down-voted the leader, which replaces it unless
+    /// [crate::RoomConfig::leader_stability] holds the change back
+    pub has_quorum: bool,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Explains how close the leader is to being down-voted at `now`. Pending, joining and quarantined
+    /// connections do not vote.
+    pub fn downvote_status(&self, now: Instant) -> DownvoteStatus {
+        let mut voters: Vec<&Connection<K>> = self
+            .connections
+            .values()
+            .filter(|connection| connection.takes_part_in_election())
+            .collect();
+        voters.sort_by_key(|connection| connection.id.value());
+
+        let mut status = DownvoteStatus {
+            term: self.term,
+            disconnected: Vec::new(),
+            connected: Vec::new(),
+            not_reported: Vec::new(),
+            counted: Vec::new(),
+            rate_limited: Vec::new(),
+            quorum_fraction: 0.0,
+            has_quorum: false,
+        };
+        for connection in &voters {
+            let list = match connection.has_connection_host {
+                _ if connection.last_reported_term != Some(self.term) => &mut status.not_reported,
+                ConnectionToLeader::Disconnected => &mut status.disconnected,
+                ConnectionToLeader::Connected => &mut status.connected,
+                ConnectionToLeader::Unknown => &mut status.not_reported,
+            };
+            list.push(connection.id);
+            if connection.is_down_vote_rate_limited(now) {
+                status.rate_limited.push(connection.id);
+            }
+            if connection.is_down_voting(self.term, &self.config, now) {
+                status.counted.push(connection.id);
+            }
+        }
+        if !voters.is_empty() {
+            status.quorum_fraction = status.counted.len() as f32 / voters.len() as f32;
+        }
+        status.has_quorum = status.counted.len() > voters.len() / 2;
+        status
+    }
+}
+
+/// Counts how often a connection starts or stops down-voting, see [crate::RoomConfig::max_down_vote_changes]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DownvoteLimiter {
+    window_started_at: Option<Instant>,
+    changes: u32,
+    limited_until: Option<Instant>,
+    violations: u32,
+}
+
+impl DownvoteLimiter {
+    /// Counts a change at `time`, true if it is more than `max_changes` within `window`. Every change over the
+    /// limit keeps the down-votes from counting for another `window`
+    pub(crate) fn on_change(&mut self, max_changes: u32, window: Duration, time: Instant) -> bool {
+        let is_in_window = self
+            .window_started_at
+            .is_some_and(|started_at| time.saturating_duration_since(started_at) < window);
+        if !is_in_window {
+            self.window_started_at = Some(time);
+            self.changes = 0;
+        }
+        self.changes += 1;
+        if self.changes <= max_changes {
+            return false;
+        }
+        self.limited_until = Some(time + window);
+        self.violations = self.violations.saturating_add(1);
+        true
+    }
+
+    pub(crate) fn is_limited(&self, time: Instant) -> bool {
+        self.limited_until.is_some_and(|until| time < until)
+    }
+
+    pub(crate) fn violations(&self) -> u32 {
+        self.violations
+    }
+
+    /// Moves the times forward, so `duration` does not count towards the window
+    pub(crate) fn shift(&mut self, duration: Duration) {
+        if let Some(started_at) = &mut self.window_started_at {
+            *started_at += duration;
+        }
+        if let Some(until) = &mut self.limited_until {
+            *until += duration;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::ConnectionToLeader;
+
+    use crate::{PingPayload, Room, RoomConfig};
+
+    #[test]
+    fn explain_down_votes() {
+        let mut room = RoomConfig::new()
+            .with_sustained_leader_loss(Some(Duration::from_secs(1)))
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let reporter = room.create_connection(now).unwrap();
+        let veteran = room.create_connection(now).unwrap();
+        let silent = room.create_connection(now).unwrap();
+        let term = room.term();
+
+        let ping = |connection_to_leader| PingPayload::new().with_term(term).with_connection_to_leader(connection_to_leader);
+        room.on_ping(leader, &ping(ConnectionToLeader::Connected), now);
+        room.on_ping(reporter, &ping(ConnectionToLeader::Disconnected), now);
+        let sustained = ping(ConnectionToLeader::Disconnected).with_leader_unreachable_since(Duration::from_secs(5));
+        room.on_ping(veteran, &sustained, now);
+
+        let status = room.downvote_status(now);
+        assert_eq!(status.term, term);
+        assert_eq!(status.connected, vec![leader]);
+        assert_eq!(status.disconnected, vec![reporter, veteran]);
+        assert_eq!(status.not_reported, vec![silent]);
+        assert_eq!(status.counted, vec![veteran]);
+        assert_eq!(status.quorum_fraction, 0.25);
+        assert!(!status.has_quorum);
+    }
+
+    #[test]
+    fn stop_counting_flapping_down_votes() {
+        let mut room = RoomConfig::new()
+            .with_max_down_vote_changes(Some(3))
+            .with_down_vote_change_window(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        let mut now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let flapper = room.create_connection(now).unwrap();
+        let term = room.term();
+        let report = |room: &mut Room, connection_to_leader, now| {
+            let ping = |connection_to_leader| PingPayload::new().with_term(term).with_connection_to_leader(connection_to_leader);
+            room.on_ping(leader, &ping(ConnectionToLeader::Connected), now);
+            room.on_ping(flapper, &ping(connection_to_leader), now);
+        };
+
+        for connection_to_leader in [ConnectionToLeader::Disconnected, ConnectionToLeader::Connected, ConnectionToLeader::Disconnected] {
+            now += Duration::from_millis(100);
+            report(&mut room, connection_to_leader, now);
+        }
+        assert_eq!(room.downvote_status(now).counted, vec![flapper]);
+
+        for connection_to_leader in [ConnectionToLeader::Connected, ConnectionToLeader::Disconnected] {
+            now += Duration::from_millis(100);
+            report(&mut room, connection_to_leader, now);
+        }
+        let status = room.downvote_status(now);
+        assert_eq!(status.disconnected, vec![flapper]);
+        assert!(status.counted.is_empty());
+        assert_eq!(status.rate_limited, vec![flapper]);
+        assert_eq!(room.get(flapper).down_vote_violations(), 2);
+        assert_eq!(room.metrics().down_vote_violations, 2);
+
+        // Keeping the same report does not change the down-vote, so it counts again after a window
+        for _ in 0..50 {
+            now += Duration::from_millis(100);
+            report(&mut room, ConnectionToLeader::Disconnected, now);
+        }
+        assert_eq!(room.term(), term);
+        assert!(!room.get(flapper).is_down_vote_rate_limited(now));
+        assert_eq!(room.downvote_status(now).counted, vec![flapper]);
+    }
+}
@@ -0,0 +1,276 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::{ConnectionToLeader, KnowledgeOrd};
+
+use crate::{ConnectionState, Instant, QualityAssessment, Room, RoomConfig, RoomState};
+
+/// Snapshot of a whole [Room], see [Room::debug_dump].
+///
+/// Instants are converted to ages relative to the time of the dump. With the `serde` feature enabled it
+/// implements `Serialize`, so it can be written as JSON.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RoomDump {
+    pub id: u32,
+    pub state: RoomState,
+    pub term: u16,
+    pub leader: Option<u32>,
+    pub latest_ping_age: Option<Duration>,
+    pub connections: Vec<ConnectionDump>,
+    pub config: RoomConfig,
+}
+
+/// Snapshot of a single connection in a [RoomDump]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ConnectionDump {
+    pub id: u32,
+    pub debug_name: Option<String>,
+    pub state: ConnectionState,
+    pub assessment: QualityAssessment,
+    pub pings_per_second: f32,
+    pub stability: f32,
+    pub last_ping_age: Duration,
+    pub knowledge: u64,
+    pub last_reported_term: Option<u16>,
+    /// `None` if the connection has not reported if it can reach the leader
+    pub has_connection_to_leader: Option<bool>,
+    pub protocol_version: Option<u16>,
+    pub suspicion_score: u32,
+    pub needs_state_sync: bool,
+    pub is_idle: bool,
+    /// The leader reported that it can not reach the connection
+    pub unreachable_by_leader: bool,
+    /// See [crate::Connection::traffic]
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+/// One difference between two [RoomDump]s, see [RoomDump::diff]. Ids are connection index values.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "snake_case"))]
+pub enum StateChange {
+    RoomStateChanged { from: RoomState, to: RoomState },
+    TermChanged { from: u16, to: u16 },
+    LeaderChanged { from: Option<u32>, to: Option<u32> },
+    ConnectionAdded { id: u32 },
+    ConnectionRemoved { id: u32 },
+    ConnectionStateChanged {
+        id: u32,
+        from: ConnectionState,
+        to: ConnectionState,
+    },
+    /// The [KnowledgeOrd::progress] of the connection changed, `delta` is `to - from`
+    KnowledgeChanged { id: u32, from: u64, to: u64, delta: i64 },
+}
+
+impl RoomDump {
+    /// What changed from this dump to `other`: the room itself first, then every connection by id.
+    /// Ages, quality measurements and the config are not compared.
+    pub fn diff(&self, other: &RoomDump) -> Vec<StateChange> {
+        let mut changes = Vec::new();
+        if self.state != other.state {
+            changes.push(StateChange::RoomStateChanged {
+                from: self.state,
+                to: other.state,
+            });
+        }
+        if self.term != other.term {
+            changes.push(StateChange::TermChanged {
+                from: self.term,
+                to: other.term,
+            });
+        }
+        if self.leader != other.leader {
+            changes.push(StateChange::LeaderChanged {
+                from: self.leader,
+                to: other.leader,
+            });
+        }
+
+        let mut before = self.connections.iter().peekable();
+        let mut after = other.connections.iter().peekable();
+        loop {
+            match (before.peek(), after.peek()) {
+                (Some(old), Some(new)) if old.id == new.id => {
+                    old.diff(new, &mut changes);
+                    before.next();
+                    after.next();
+                }
+                (Some(old), Some(new)) if old.id > new.id => {
+                    changes.push(StateChange::ConnectionAdded { id: new.id });
+                    after.next();
+                }
+                (Some(old), _) => {
+                    changes.push(StateChange::ConnectionRemoved { id: old.id });
+                    before.next();
+                }
+                (None, Some(new)) => {
+                    changes.push(StateChange::ConnectionAdded { id: new.id });
+                    after.next();
+                }
+                (None, None) => break,
+            }
+        }
+        changes
+    }
+}
+
+impl ConnectionDump {
+    fn diff(&self, other: &ConnectionDump, changes: &mut Vec<StateChange>) {
+        if self.state != other.state {
+            changes.push(StateChange::ConnectionStateChanged {
+                id: self.id,
+                from: self.state,
+                to: other.state,
+            });
+        }
+        if self.knowledge != other.knowledge {
+            changes.push(StateChange::KnowledgeChanged {
+                id: self.id,
+                from: self.knowledge,
+                to: other.knowledge,
+                delta: other.knowledge.wrapping_sub(self.knowledge) as i64,
+            });
+        }
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Captures the state of the room and all its connections, sorted by connection id
+    pub fn debug_dump(&self, now: Instant) -> RoomDump {
+        let mut connections: Vec<ConnectionDump> = self
+            .connections
+            .values()
+            .map(|connection| ConnectionDump {
+                id: connection.id.value(),
+                debug_name: connection.debug_name.clone(),
+                state: connection.state,
+                assessment: connection.assessment(),
+                pings_per_second: connection.quality.last_pings_per_second,
+                stability: connection.stability(),
+                last_ping_age: now.saturating_duration_since(connection.quality.last_ping_at),
+                knowledge: connection.knowledge.progress(),
+                last_reported_term: connection.last_reported_term.map(|term| term.value()),
+                has_connection_to_leader: match connection.has_connection_host {
+                    ConnectionToLeader::Unknown => None,
+                    ConnectionToLeader::Connected => Some(true),
+                    ConnectionToLeader::Disconnected => Some(false),
+                },
+                protocol_version: connection.protocol_version,
+                suspicion_score: connection.suspicion_score(),
+                needs_state_sync: connection.needs_state_sync(),
+                is_idle: connection.is_idle(),
+                unreachable_by_leader: self.is_unreachable_by_leader(connection.id),
+                bytes_in: connection.traffic().bytes_in,
+                bytes_out: connection.traffic().bytes_out,
+            })
+            .collect();
+        connections.sort_by_key(|connection| connection.id);
+
+        RoomDump {
+            id: self.id.value(),
+            state: self.state(now),
+            term: self.term.value(),
+            leader: self.leader_index.map(|index| index.value()),
+            latest_ping_age: self.latest_ping_timestamp.map(|time| now.saturating_duration_since(time)),
+            connections,
+            config: self.config.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{ConnectionToLeader, Knowledge, Term};
+
+    use crate::{ConnectionState, PingPayload, Room, RoomState, StateChange};
+
+    #[test]
+    fn dump_room() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        room.on_ping(
+            follower,
+            &PingPayload::new()
+                .with_term(Term(1))
+                .with_connection_to_leader(ConnectionToLeader::Connected)
+                .with_knowledge(Knowledge(7)),
+            now,
+        );
+
+        let dump = room.debug_dump(now + Duration::from_millis(250));
+
+        assert_eq!(dump.state, RoomState::Active);
+        assert_eq!(dump.term, 1);
+        assert_eq!(dump.leader, Some(leader.value()));
+        assert_eq!(dump.latest_ping_age, Some(Duration::from_millis(250)));
+        assert_eq!(dump.connections.len(), 2);
+        let follower_dump = &dump.connections[1];
+        assert_eq!(follower_dump.id, follower.value());
+        assert_eq!(follower_dump.state, ConnectionState::Online);
+        assert_eq!(follower_dump.knowledge, 7);
+        assert_eq!(follower_dump.last_reported_term, Some(1));
+        assert_eq!(follower_dump.has_connection_to_leader, Some(true));
+        assert_eq!(follower_dump.last_ping_age, Duration::from_millis(250));
+    }
+
+    #[test]
+    fn diff_dumps() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        let ping = |knowledge| PingPayload::new().with_term(Term(1)).with_knowledge(Knowledge(knowledge));
+        room.on_ping(follower, &ping(10), now);
+        let before = room.debug_dump(now);
+        assert!(before.diff(&before).is_empty());
+
+        room.destroy_connection(leader).unwrap();
+        let joined = room.create_connection(now).unwrap();
+        room.on_ping(follower, &ping(4), now);
+        let after = room.debug_dump(now);
+
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                StateChange::TermChanged { from: 1, to: 2 },
+                StateChange::LeaderChanged {
+                    from: Some(leader.value()),
+                    to: Some(follower.value()),
+                },
+                StateChange::ConnectionRemoved { id: leader.value() },
+                StateChange::KnowledgeChanged {
+                    id: follower.value(),
+                    from: 10,
+                    to: 4,
+                    delta: -6,
+                },
+                StateChange::ConnectionAdded { id: joined.value() },
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn dump_as_json() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let connection_id = room.create_connection(now).unwrap();
+        room.on_ping(connection_id, &PingPayload::new(), now);
+
+        let json = serde_json::to_value(room.debug_dump(now)).unwrap();
+
+        assert_eq!(json["state"], "active");
+        assert_eq!(json["connections"][0]["state"], "online");
+        assert_eq!(json["config"]["pings_per_second_threshold"], 5.0);
+    }
+}
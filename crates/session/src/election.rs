@@ -0,0 +1,436 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use conclave_types::{ConnectionToLeader, KnowledgeOrd, Term};
+
+use crate::{Connection, ConnectionIndex, ConnectionState, ElectionTrigger, Instant, QualityAssessment, Room, RoomEvent};
+
+/// Why a connection could not be elected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ineligibility {
+    /// The connection is the leader that is being replaced
+    BeingReplaced,
+    Pending,
+    Joining,
+    Quarantined,
+    /// See [crate::RoomConfig::exclude_suspicious_from_election]
+    Suspicious,
+    /// Not allowed to lead by the [crate::LeaderEligibility] set on the room
+    Vetoed,
+    /// The connection is [ConnectionState::Disconnected], or assessed as [QualityAssessment::RecommendDisconnect]
+    Disconnected,
+}
+
+/// The factors the election considered for a single connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandidateReport {
+    pub connection: ConnectionIndex,
+    /// The [KnowledgeOrd::progress] of the knowledge
+    pub knowledge: u64,
+    /// Only decides eligibility, a connection recommended to disconnect is [Ineligibility::Disconnected]
+    pub assessment: QualityAssessment,
+    pub stability: f32,
+    /// The [crate::PingPayload::capability] the connection reported
+    pub capability: Option<u8>,
+    /// The stability times the capability as a fraction of 100, or 1.0 if none was reported. Compares
+    /// candidates that are equal in everything before it
+    pub score: f32,
+    pub is_idle: bool,
+    /// False if the connection sends more than [crate::RoomConfig::traffic_budget]
+    pub has_traffic_headroom: bool,
+    /// False if the leader reports that it can not reach the connection, see [Room::is_unreachable_by_leader].
+    /// Not ranked on, as the leader being replaced is often the one that is cut off from the others
+    pub is_reachable_by_leader: bool,
+    /// What the connection last reported about its connection to the leader
+    pub has_connection_to_leader: ConnectionToLeader,
+    /// True if the knowledge is within [crate::RoomConfig::stability_knowledge_tolerance] of the most knowledgeable candidate
+    pub within_knowledge_tolerance: bool,
+    /// Estimated latency from the other connections, see [crate::RoomConfig::latency_preference]
+    pub follower_latency: Option<Duration>,
+    /// Place in the election, 1 is the winner. `None` if the connection could not be elected
+    pub rank: Option<u32>,
+    pub ineligible: Option<Ineligibility>,
+}
+
+/// Every connection in the room and how it placed in an election, sorted by connection index
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElectionReport {
+    /// The term the winner was elected for
+    pub term: Term,
+    pub winner: Option<ConnectionIndex>,
+    pub candidates: Vec<CandidateReport>,
+}
+
+impl ElectionReport {
+    /// The connections that could be elected, best first. The first ones are the likely leaders after the
+    /// winner, so a transport can connect to them ahead of time
+    pub fn ranking(&self) -> Vec<ConnectionIndex> {
+        let mut ranked: Vec<&CandidateReport> = self.candidates.iter().filter(|candidate| candidate.rank.is_some()).collect();
+        ranked.sort_by_key(|candidate| candidate.rank);
+        ranked.into_iter().map(|candidate| candidate.connection).collect()
+    }
+}
+
+/// Returned by [Room::election_report]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElectionExplanation {
+    /// The report from when the current leader was elected, `None` if there has not been an election
+    /// since the first connection was appointed leader
+    pub last: Option<ElectionReport>,
+    /// Time since the last election
+    pub last_age: Option<Duration>,
+    /// The outcome if the leader was replaced now
+    pub dry_run: ElectionReport,
+}
+
+/// The leader of the current term, see [Room::current_leader]
+#[derive(Debug, Clone, Copy)]
+pub struct LeaderInfo<'a, K: KnowledgeOrd> {
+    pub index: ConnectionIndex,
+    pub connection: &'a Connection<K>,
+    pub term: Term,
+    /// Time from the leader change to the latest time given to the room, `None` if the room was not given a
+    /// time before the change, e.g. when restored from a snapshot
+    pub led_for: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub(crate) struct LastElection {
+    at: Option<Instant>,
+    report: ElectionReport,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    pub(crate) fn ineligibility(&self, connection: &Connection<K>, exclude_index: Option<ConnectionIndex>) -> Option<Ineligibility> {
+        if exclude_index == Some(connection.id) {
+            Some(Ineligibility::BeingReplaced)
+        } else if connection.state == ConnectionState::Pending {
+            Some(Ineligibility::Pending)
+        } else if connection.state == ConnectionState::Joining {
+            Some(Ineligibility::Joining)
+        } else if connection.state == ConnectionState::Quarantined {
+            Some(Ineligibility::Quarantined)
+        } else if connection.state == ConnectionState::Disconnected
+            || connection.assessment() == QualityAssessment::RecommendDisconnect
+        {
+            Some(Ineligibility::Disconnected)
+        } else if self.config.exclude_suspicious_from_election && connection.is_suspicious() {
+            Some(Ineligibility::Suspicious)
+        } else if self.is_vetoed(connection) {
+            Some(Ineligibility::Vetoed)
+        } else {
+            None
+        }
+    }
+
+    /// Ranks every eligible connection, the winner is the one with the highest rank.
+    ///
+    /// Idle connections come last if [crate::RoomConfig::deprioritize_idle_in_election] is set, followed by the
+    /// connections without [crate::RoomConfig::traffic_budget] headroom. After that, connections within the
+    /// knowledge tolerance of the most knowledgeable one come first, ordered by latency, [CandidateReport::score]
+    /// and then knowledge, so a flapping connection does not win over a stable one just by being slightly ahead.
+    pub(crate) fn evaluate_election(&self, exclude_index: Option<ConnectionIndex>) -> ElectionReport {
+        let mut connections: Vec<&Connection<K>> = self.connections.values().collect();
+        connections.sort_by_key(|connection| connection.id.value());
+        let (eligible, is_within_tolerance) = self.rank_candidates(&connections, exclude_index);
+
+        let candidates = connections
+            .iter()
+            .map(|connection| CandidateReport {
+                connection: connection.id,
+                knowledge: connection.knowledge.progress(),
+                assessment: connection.assessment(),
+                stability: connection.stability(),
+                capability: connection.capability,
+                score: candidate_score(connection),
+                is_idle: connection.is_idle,
+                has_traffic_headroom: self.has_traffic_headroom(connection),
+                is_reachable_by_leader: !self.is_unreachable_by_leader(connection.id),
+                has_connection_to_leader: connection.has_connection_host,
+                within_knowledge_tolerance: is_within_tolerance(connection),
+                follower_latency: self.follower_latency(connection),
+                rank: eligible
+                    .iter()
+                    .position(|candidate| candidate.id == connection.id)
+                    .map(|position| position as u32 + 1),
+                ineligible: self.ineligibility(connection, exclude_index),
+            })
+            .collect();
+
+        ElectionReport {
+            term: self.term,
+            winner: eligible.first().map(|connection| connection.id),
+            candidates,
+        }
+    }
+
+    /// The eligible connections among `connections`, best candidate first, see [Room::evaluate_election].
+    /// Also returns if a connection is within the knowledge tolerance of the most knowledgeable candidate.
+    pub(crate) fn rank_candidates<'a>(
+        &'a self,
+        connections: &[&'a Connection<K>],
+        exclude_index: Option<ConnectionIndex>,
+    ) -> (Vec<&'a Connection<K>>, impl Fn(&Connection<K>) -> bool + 'a) {
+        let deprioritize_idle = self.config.deprioritize_idle_in_election;
+        let prefer_active = move |a: &Connection<K>, b: &Connection<K>| {
+            if deprioritize_idle {
+                b.is_idle.cmp(&a.is_idle)
+            } else {
+                Ordering::Equal
+            }
+        };
+        let mut eligible: Vec<&Connection<K>> = connections
+            .iter()
+            .copied()
+            .filter(|connection| self.ineligibility(connection, exclude_index).is_none())
+            .collect();
+        let most_knowledge = eligible
+            .iter()
+            .copied()
+            .max_by(|a, b| prefer_active(a, b).then_with(|| a.knowledge.cmp_knowledge(&b.knowledge)));
+        let tolerance = self.config.stability_knowledge_tolerance;
+        let is_within_tolerance = move |connection: &Connection<K>| {
+            most_knowledge.is_some_and(|most| {
+                prefer_active(connection, most) == Ordering::Equal
+                    && most.knowledge.progress().saturating_sub(connection.knowledge.progress()) <= tolerance
+            })
+        };
+
+        // Estimated once per candidate, as every estimate goes through all the other connections
+        let follower_latencies: HashMap<ConnectionIndex, Duration> = eligible
+            .iter()
+            .filter_map(|connection| Some((connection.id, self.follower_latency(connection)?)))
+            .collect();
+        let prefer_closer = |a: &Connection<K>, b: &Connection<K>| {
+            match (follower_latencies.get(&a.id), follower_latencies.get(&b.id)) {
+                (Some(a), Some(b)) => a.cmp(b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        };
+
+        // Stable sort, so connections that are equal in every way are ranked by index
+        eligible.sort_by(|a, b| {
+            prefer_active(b, a)
+                .then_with(|| self.has_traffic_headroom(b).cmp(&self.has_traffic_headroom(a)))
+                .then_with(|| is_within_tolerance(b).cmp(&is_within_tolerance(a)))
+                .then_with(|| prefer_closer(a, b))
+                .then_with(|| candidate_score(b).total_cmp(&candidate_score(a)))
+                .then_with(|| b.knowledge.cmp_knowledge(&a.knowledge))
+        });
+
+        (eligible, is_within_tolerance)
+    }
+
+    fn has_traffic_headroom(&self, connection: &Connection<K>) -> bool {
+        self.config
+            .traffic_budget
+            .is_none_or(|budget| connection.traffic().bytes_out_per_second <= budget as f32)
+    }
+
+    /// Keeps the report of an election that has just been held
+    pub(crate) fn remember_election(&mut self, mut report: ElectionReport, trigger: ElectionTrigger) {
+        report.term = self.term;
+        let previous_at = self.last_election.as_ref().and_then(|last| last.at);
+        self.open_election_record(&report, trigger, previous_at);
+        self.push_event(RoomEvent::ElectionRanked {
+            term: self.term,
+            ranking: report.ranking(),
+        });
+        self.last_election = Some(LastElection {
+            at: self.latest_time,
+            report,
+        });
+    }
+
+    /// The leader together with its connection and term, `None` if the room has no leader
+    pub fn current_leader(&self) -> Option<LeaderInfo<'_, K>> {
+        let index = self.leader_index?;
+        let connection = self.connections.get(&index)?;
+        let led_for = match (self.leader_changes.back(), self.latest_time) {
+            (Some(changed_at), Some(latest)) => Some(latest.saturating_duration_since(*changed_at)),
+            _ => None,
+        };
+        Some(LeaderInfo {
+            index,
+            connection,
+            term: self.term,
+            led_for,
+        })
+    }
+
+    /// The connection that would be elected if `exclude`, e.g. the current leader, left the room.
+    ///
+    /// Nothing in the room is changed, the term stays the same. `now` is part of the signature so candidate
+    /// selection can take time into account without breaking callers.
+    pub fn would_elect(&self, exclude: Option<ConnectionIndex>, _now: Instant) -> Option<ConnectionIndex> {
+        self.evaluate_election(exclude).winner
+    }
+
+    /// Explains why the current leader was elected, and who would be elected if the leader was replaced now
+    pub fn election_report(&self, now: Instant) -> ElectionExplanation {
+        let mut dry_run = self.evaluate_election(self.leader_index);
+        dry_run.term = Term(self.term.value().wrapping_add(1));
+        ElectionExplanation {
+            last: self.last_election.as_ref().map(|last| last.report.clone()),
+            last_age: self
+                .last_election
+                .as_ref()
+                .and_then(|last| last.at)
+                .map(|at| now.saturating_duration_since(at)),
+            dry_run,
+        }
+    }
+}
+
+/// See [CandidateReport::score]
+fn candidate_score<K: KnowledgeOrd>(connection: &Connection<K>) -> f32 {
+    connection.stability() * connection.capability_factor()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{Knowledge, Term};
+
+    use crate::election::Ineligibility;
+    use crate::{ConnectionState, PingPayload, QualityAssessment, Room, RoomConfig, RoomEvent};
+
+    #[test]
+    fn explain_election() {
+        let mut room = RoomConfig::new()
+            .with_exclude_suspicious_from_election(true)
+            .with_knowledge_lead_tolerance(50)
+            .build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        let cheater = room.create_connection(now).unwrap();
+        room.on_ping(leader, &PingPayload::new().with_knowledge(Knowledge(100)), now);
+        room.on_ping(follower, &PingPayload::new().with_knowledge(Knowledge(90)), now);
+        room.on_ping(cheater, &PingPayload::new().with_knowledge(Knowledge(1000)), now);
+        assert!(room.election_report(now).last.is_none());
+
+        let dry_run = room.election_report(now).dry_run;
+        assert_eq!(dry_run.winner, Some(follower));
+        assert_eq!(dry_run.candidates[0].ineligible, Some(Ineligibility::BeingReplaced));
+        assert_eq!(dry_run.candidates[1].rank, Some(1));
+        assert_eq!(dry_run.candidates[2].ineligible, Some(Ineligibility::Suspicious));
+        assert_eq!(dry_run.candidates[2].rank, None);
+
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader_index, Some(follower));
+
+        let explanation = room.election_report(now + Duration::from_secs(1));
+        let last = explanation.last.unwrap();
+        assert_eq!(last.winner, Some(follower));
+        assert_eq!(last.term, room.term);
+        assert_eq!(last.candidates.len(), 2);
+        assert_eq!(explanation.last_age, Some(Duration::from_secs(1)));
+        assert_eq!(explanation.dry_run.term, Term(room.term.value() + 1));
+        assert_eq!(explanation.dry_run.winner, None);
+    }
+
+    #[test]
+    fn next_leader_if_current_leaves() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        let term = room.term;
+
+        assert_eq!(room.would_elect(Some(leader), now), Some(follower));
+        assert_eq!(room.would_elect(None, now), Some(leader));
+        assert_eq!(room.leader_index, Some(leader));
+        assert_eq!(room.term, term);
+    }
+
+    #[test]
+    fn current_leader_with_term_and_time_led() {
+        let mut room = Room::new();
+        assert!(room.current_leader().is_none());
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        room.on_ping(leader, &PingPayload::new().with_knowledge(Knowledge(7)), now + Duration::from_secs(3));
+
+        let current = room.current_leader().unwrap();
+        assert_eq!(current.index, leader);
+        assert_eq!(current.connection.knowledge, Knowledge(7));
+        assert_eq!(current.term, Term(1));
+        assert_eq!(current.led_for, Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn rank_every_candidate() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let behind = room.create_connection(now).unwrap();
+        let ahead = room.create_connection(now).unwrap();
+        let pending = room.preregister_connection("late", now).unwrap();
+        room.on_ping(behind, &PingPayload::new().with_knowledge(Knowledge(10)), now);
+        room.on_ping(ahead, &PingPayload::new().with_knowledge(Knowledge(20)), now);
+        room.drain_events();
+
+        room.destroy_connection(leader).unwrap();
+
+        let ranking = room.election_report(now).last.unwrap().ranking();
+        assert_eq!(ranking, vec![ahead, behind]);
+        assert!(!ranking.contains(&pending));
+        assert!(room.drain_events().contains(&RoomEvent::ElectionRanked {
+            term: room.term,
+            ranking,
+        }));
+    }
+
+    #[test]
+    fn never_elect_disconnected_connections() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let disconnected = room.create_connection(now).unwrap();
+        let recommended = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        room.on_ping(disconnected, &PingPayload::new().with_knowledge(Knowledge(30)), now);
+        room.on_ping(recommended, &PingPayload::new().with_knowledge(Knowledge(20)), now);
+        room.get_mut(disconnected).state = ConnectionState::Disconnected;
+        room.get_mut(recommended).quality.assessment = QualityAssessment::RecommendDisconnect;
+
+        let dry_run = room.election_report(now).dry_run;
+        assert_eq!(dry_run.winner, Some(follower));
+        assert_eq!(dry_run.candidates[0].ineligible, Some(Ineligibility::BeingReplaced));
+        assert_eq!(dry_run.candidates[1].ineligible, Some(Ineligibility::Disconnected));
+        assert_eq!(dry_run.candidates[2].ineligible, Some(Ineligibility::Disconnected));
+        assert_eq!(room.leader(), Some(leader));
+    }
+
+    #[test]
+    fn prefer_capable_candidate_when_equally_stable() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let phone = room.create_connection(now).unwrap();
+        let desktop = room.create_connection(now).unwrap();
+        room.on_ping(phone, &PingPayload::new().with_knowledge(Knowledge(5)), now);
+        room.on_ping(desktop, &PingPayload::new().with_knowledge(Knowledge(5)).with_capability(200), now);
+
+        let dry_run = room.election_report(now).dry_run;
+        assert_eq!(dry_run.winner, Some(desktop));
+        let report = |connection| {
+            dry_run.candidates.iter().find(|candidate| candidate.connection == connection).unwrap()
+        };
+        assert_eq!(report(phone).capability, None);
+        assert_eq!(report(phone).score, 1.0);
+        assert_eq!(report(desktop).capability, Some(200));
+        assert_eq!(report(desktop).score, 2.0);
+        assert!(report(desktop).is_reachable_by_leader);
+        assert_eq!(report(leader).ineligible, Some(Ineligibility::BeingReplaced));
+    }
+}
@@ -0,0 +1,159 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::{KnowledgeOrd, Term};
+use log::debug;
+
+use crate::{CandidateReport, ConnectionIndex, ElectionReport, Instant, Room, RoomEvent};
+
+/// Why an election was held, see [ElectionRecord]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElectionTrigger {
+    /// [crate::RoomConfig::min_connections_for_election] connections have joined
+    FirstLeader,
+    /// The leader was destroyed, kicked or transferred
+    LeaderLeft,
+    /// A majority of the voters lost the connection to the leader
+    DownVoted,
+    /// The connection quality of the leader was too bad
+    BadQuality,
+    /// See [crate::RoomConfig::lease_duration]
+    LeaseExpired,
+    /// Partitions with conflicting terms were reconciled and none of them had a leader to follow
+    PartitionHealed,
+    /// See [crate::RoomConfig::max_leader_burden]
+    LoadRebalance,
+    /// See [crate::RoomConfig::restore_leader_when_leaderless]
+    Restored,
+    /// See [crate::RoomConfig::election_watchdog]
+    Watchdog,
+}
+
+/// Telemetry about a single election, for tuning the stability settings. Emitted with
+/// [RoomEvent::ElectionRecorded] once the term has converged, or when the term changes before it did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElectionRecord {
+    pub term: Term,
+    pub trigger: ElectionTrigger,
+    /// The latest time the room had been given when the election was held
+    pub at: Option<Instant>,
+    pub winner: Option<ConnectionIndex>,
+    /// Every connection and how it placed, see [ElectionReport::candidates]
+    pub candidates: Vec<CandidateReport>,
+    /// Number of connections in the room, pending ones included
+    pub room_size: usize,
+    /// `None` for the first election
+    pub since_previous: Option<Duration>,
+    /// Time until every online connection reported the term, see [RoomEvent::TermConverged]. `None` if the term
+    /// changed before it converged
+    pub convergence: Option<Duration>,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// The last [crate::RoomConfig::election_record_history] records, oldest first
+    pub fn election_records(&self) -> impl DoubleEndedIterator<Item = &ElectionRecord> {
+        self.election_records.iter()
+    }
+
+    /// Starts the record of an election that has just been held, it is finished when the term converges
+    pub(crate) fn open_election_record(
+        &mut self,
+        report: &ElectionReport,
+        trigger: ElectionTrigger,
+        previous_at: Option<Instant>,
+    ) {
+        self.finish_election_record(None);
+        let since_previous = match (previous_at, self.latest_time) {
+            (Some(previous), Some(latest)) => Some(latest.saturating_duration_since(previous)),
+            _ => None,
+        };
+        self.pending_election_record = Some(ElectionRecord {
+            term: report.term,
+            trigger,
+            at: self.latest_time,
+            winner: report.winner,
+            candidates: report.candidates.clone(),
+            room_size: self.connections.len(),
+            since_previous,
+            convergence: None,
+        });
+    }
+
+    /// Finishes the open record when the term it was for has converged, or has been replaced
+    pub(crate) fn update_election_record(&mut self) {
+        let Some(record) = &self.pending_election_record else {
+            return;
+        };
+        if record.term != self.term {
+            self.finish_election_record(None);
+        } else if self.converged_term == Some(record.term) {
+            let convergence = match (record.at, self.latest_time) {
+                (Some(at), Some(latest)) => Some(latest.saturating_duration_since(at)),
+                _ => None,
+            };
+            self.finish_election_record(convergence);
+        }
+    }
+
+    fn finish_election_record(&mut self, convergence: Option<Duration>) {
+        let Some(mut record) = self.pending_election_record.take() else {
+            return;
+        };
+        record.convergence = convergence;
+        debug!("election of term {} converged in {:?}", record.term, convergence);
+        if self.config.election_record_history > 0 {
+            self.election_records.push_back(record.clone());
+            self.trim_election_records();
+        }
+        self.push_event(RoomEvent::ElectionRecorded { record: Box::new(record) });
+    }
+
+    pub(crate) fn trim_election_records(&mut self) {
+        while self.election_records.len() > self.config.election_record_history {
+            self.election_records.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{ElectionTrigger, PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn record_election_once_term_converges() {
+        let mut room = RoomConfig::new().with_election_record_history(4).build().unwrap();
+        let now = Instant::now();
+        let first = room.create_connection(now).unwrap();
+        let second = room.create_connection(now).unwrap();
+        let third = room.create_connection(now).unwrap();
+
+        let later = now + Duration::from_millis(100);
+        room.update(later);
+        room.destroy_connection(first).unwrap();
+        let term = room.term();
+        let winner = room.leader();
+        assert_eq!(room.election_records().count(), 0);
+
+        room.on_ping(second, &PingPayload::new().with_term(term), later + Duration::from_millis(50));
+        room.on_ping(third, &PingPayload::new().with_term(term), later + Duration::from_millis(150));
+
+        let record = room.election_records().next().unwrap();
+        assert_eq!(record.term, term);
+        assert_eq!(record.trigger, ElectionTrigger::LeaderLeft);
+        assert_eq!(record.at, Some(later));
+        assert_eq!(record.winner, winner);
+        assert_eq!(record.room_size, 2);
+        assert_eq!(record.candidates.len(), 2);
+        assert_eq!(record.since_previous, None);
+        assert_eq!(record.convergence, Some(Duration::from_millis(150)));
+        assert!(room
+            .drain_events()
+            .iter()
+            .any(|event| matches!(event, RoomEvent::ElectionRecorded { record } if record.term == term)));
+    }
+}
@@ -0,0 +1,75 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::fmt;
+
+use conclave_types::{Knowledge, KnowledgeOrd};
+
+use crate::{Connection, Room};
+
+/// Lets the host veto leader candidates for reasons the room knows nothing about, e.g. platform restrictions,
+/// parental controls or anti-cheat flags.
+///
+/// Consulted every time the room picks a leader, including the first connection and forced re-elections. A
+/// vetoed connection is reported as [crate::Ineligibility::Vetoed]. [Room::appoint_leader] is not checked, and a
+/// leader that is vetoed after it was elected keeps leading until it is replaced for another reason.
+pub trait LeaderEligibility<K: KnowledgeOrd = Knowledge>: fmt::Debug + Send + Sync {
+    fn is_eligible(&self, connection: &Connection<K>) -> bool;
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Every candidate is checked with `eligibility` before it can be elected
+    pub fn set_leader_eligibility(&mut self, eligibility: Box<dyn LeaderEligibility<K>>) {
+        self.leader_eligibility = Some(eligibility);
+    }
+
+    /// True if the [LeaderEligibility] set on the room does not allow `connection` to lead
+    pub(crate) fn is_vetoed(&self, connection: &Connection<K>) -> bool {
+        self.leader_eligibility
+            .as_ref()
+            .is_some_and(|eligibility| !eligibility.is_eligible(connection))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::Knowledge;
+
+    use crate::{Connection, Ineligibility, LeaderEligibility, PingPayload, Room};
+
+    /// Vetoes the players on the list
+    #[derive(Debug)]
+    struct Banned(Vec<&'static str>);
+
+    impl LeaderEligibility for Banned {
+        fn is_eligible(&self, connection: &Connection) -> bool {
+            !connection.identity.as_deref().is_some_and(|identity| self.0.contains(&identity))
+        }
+    }
+
+    #[test]
+    fn vetoed_connection_is_not_elected() {
+        let mut room = Room::new();
+        room.set_leader_eligibility(Box::new(Banned(vec!["flagged"])));
+        let now = Instant::now();
+        let flagged = room.create_connection_with_identity("flagged", now).unwrap();
+        assert_eq!(room.leader(), None);
+
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        assert_eq!(room.leader(), Some(leader));
+        room.on_ping(flagged, &PingPayload::new().with_term(room.term()).with_knowledge(Knowledge(100)), now);
+        room.on_ping(follower, &PingPayload::new().with_term(room.term()).with_knowledge(Knowledge(10)), now);
+
+        let dry_run = room.election_report(now).dry_run;
+        assert_eq!(dry_run.winner, Some(follower));
+        assert_eq!(dry_run.candidates[0].ineligible, Some(Ineligibility::Vetoed));
+        assert_eq!(dry_run.candidates[0].rank, None);
+
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader(), Some(follower));
+    }
+}
@@ -0,0 +1,85 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::fmt;
+
+use crate::ConnectionIndex;
+
+/// Errors returned by the fallible [crate::Room] operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomError {
+    /// The handle refers to a connection that has been destroyed, and its index value now belongs to `current`
+    StaleHandle {
+        handle: ConnectionIndex,
+        current: ConnectionIndex,
+    },
+    /// There is no connection with that index value in the room
+    UnknownConnection(ConnectionIndex),
+    /// A connection with the requested index value is already in the room
+    ConnectionIndexInUse(ConnectionIndex),
+    /// A connection was already preregistered or created with the same identity
+    IdentityInUse(ConnectionIndex),
+    /// All index values up to [crate::RoomConfig::max_connection_index] are in use
+    NoConnectionIndexAvailable,
+    /// The room does not take new connections after [crate::Room::begin_close]
+    RoomClosing,
+    /// The room has [crate::RoomConfig::max_connections], and nobody could be evicted
+    RoomFull,
+    /// The leader, and the proposer itself, can not be the target of [crate::Room::propose_kick]
+    NotKickable(ConnectionIndex),
+    /// Another kick vote is open, against the connection
+    KickVoteInProgress(ConnectionIndex),
+}
+
+impl fmt::Display for RoomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoomError::StaleHandle { handle, current } => {
+                write!(f, "stale connection handle {}, the index now belongs to {}", handle, current)
+            }
+            RoomError::UnknownConnection(index) => write!(f, "unknown connection {}", index),
+            RoomError::ConnectionIndexInUse(index) => write!(f, "connection index is used by {}", index),
+            RoomError::IdentityInUse(index) => write!(f, "identity is used by {}", index),
+            RoomError::NoConnectionIndexAvailable => write!(f, "no connection index available"),
+            RoomError::RoomClosing => write!(f, "room is closing"),
+            RoomError::RoomFull => write!(f, "room is full"),
+            RoomError::NotKickable(index) => write!(f, "{} can not be voted out", index),
+            RoomError::KickVoteInProgress(index) => write!(f, "a kick vote against {} is open", index),
+        }
+    }
+}
+
+impl std::error::Error for RoomError {}
+
+/// Why a [crate::RoomConfig] was refused by [crate::RoomConfig::build], `field` is the name of the config field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigError {
+    /// The value must be a finite number above zero
+    NotPositive { field: &'static str, value: f32 },
+    /// The value must be a finite number within `min..=max`
+    OutOfRange {
+        field: &'static str,
+        value: f32,
+        min: f32,
+        max: f32,
+    },
+    /// The count or duration must be above zero
+    Zero { field: &'static str },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::NotPositive { field, value } => {
+                write!(f, "{} must be a finite number above zero, got {}", field, value)
+            }
+            ConfigError::OutOfRange { field, value, min, max } => {
+                write!(f, "{} must be between {} and {}, got {}", field, min, max, value)
+            }
+            ConfigError::Zero { field } => write!(f, "{} must be above zero", field),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
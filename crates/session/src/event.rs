@@ -0,0 +1,431 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::{Knowledge, Term};
+
+use crate::group::GroupId;
+use crate::knowledge::SuspicionReason;
+use crate::{CloseReason, ConnectionIndex, ElectionRecord, KickVoteOutcome};
+
+/// Why the room removed a connection on its own initiative
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KickReason {
+    AuthenticationFailed,
+    /// Made room for a connection with a higher priority, see [crate::Room::admit_with_eviction]
+    Evicted,
+    /// Voted out by the other connections, see [crate::Room::propose_kick]
+    Voted,
+}
+
+/// Why the room set a connection to [crate::ConnectionState::Disconnected]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    PoorQuality,
+    ProtocolMismatch,
+}
+
+impl DisconnectReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DisconnectReason::PoorQuality => "poor_quality",
+            DisconnectReason::ProtocolMismatch => "protocol_mismatch",
+        }
+    }
+}
+
+/// Notable things that happened in the room, collected until drained with [crate::Room::drain_events]
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoomEvent {
+    /// A new term started, `leader` is `None` if no connection could be elected
+    LeaderChanged {
+        term: Term,
+        leader: Option<ConnectionIndex>,
+    },
+    /// The leader made it through [crate::RoomConfig::leader_probation] without being replaced
+    LeaderConfirmed { term: Term, leader: ConnectionIndex },
+    /// The leader did not ping within [crate::RoomConfig::lease_duration] and was replaced
+    LeaseExpired { term: Term, leader: ConnectionIndex },
+    /// Every online connection has reported the current term, see [crate::Room::term_acknowledgement]
+    TermConverged { term: Term },
+    /// A majority has pinged with the term of the provisional leader, see [crate::RoomConfig::quorum_term_activation]
+    LeaderActivated { term: Term, leader: ConnectionIndex },
+    Disconnected {
+        connection: ConnectionIndex,
+        reason: DisconnectReason,
+    },
+    /// A joining connection has pinged at the expected rate long enough to be online
+    WarmedUp { connection: ConnectionIndex },
+    /// A connection pinged with a protocol version older than [crate::RoomConfig::min_supported_version]
+    ProtocolMismatch {
+        connection: ConnectionIndex,
+        version: u16,
+        min_supported_version: u16,
+    },
+    /// A ping could not be verified by the [crate::PingAuthenticator]
+    AuthFailure {
+        connection: ConnectionIndex,
+        failures: u32,
+    },
+    /// A connection reported knowledge that it can not plausibly have, `reported` is the [crate::KnowledgeOrd::progress]
+    SuspiciousKnowledge {
+        connection: ConnectionIndex,
+        reported: Knowledge,
+        reason: SuspicionReason,
+    },
+    /// The connection fell more than [crate::RoomConfig::knowledge_lag_threshold] behind the leader
+    KnowledgeLagging { connection: ConnectionIndex, delta: u64 },
+    /// A connection that was lagging is now within the threshold again
+    KnowledgeCaughtUp { connection: ConnectionIndex },
+    /// The connection reported a [crate::PingPayload::last_input_age] longer than [crate::RoomConfig::idle_after]
+    WentIdle { connection: ConnectionIndex },
+    /// An idle connection reported recent input again
+    ReturnedFromIdle { connection: ConnectionIndex },
+    /// The connection was assessed as bad and has [crate::RoomConfig::quarantine_period] to recover
+    Quarantined { connection: ConnectionIndex },
+    /// A quarantined connection recovered before its quarantine period was over
+    Rehabilitated { connection: ConnectionIndex },
+    /// The representative of the group was changed, `None` if no member can represent it
+    RepresentativeChanged {
+        group: GroupId,
+        representative: Option<ConnectionIndex>,
+    },
+    /// The leader reported that it can not reach the connection
+    UnreachableByLeader { connection: ConnectionIndex },
+    /// The leader no longer reports the connection as unreachable
+    ReachableByLeader { connection: ConnectionIndex },
+    /// The connection was moved to another room with [crate::Room::transfer_connection]
+    TransferredOut { connection: ConnectionIndex },
+    /// A connection was moved here from another room, `previous` is the index it had there
+    TransferredIn {
+        connection: ConnectionIndex,
+        previous: ConnectionIndex,
+    },
+    /// A preregistered connection pinged for the first time
+    PendingActivated { connection: ConnectionIndex },
+    /// A preregistered connection did not ping within [crate::RoomConfig::pending_timeout] and was removed
+    PendingExpired { connection: ConnectionIndex },
+    /// A connection that joined mid-session should receive the full state from `donor`
+    StateSyncAssigned {
+        receiver: ConnectionIndex,
+        donor: Option<ConnectionIndex>,
+    },
+    /// The connection was destroyed by the room
+    Kicked {
+        connection: ConnectionIndex,
+        reason: KickReason,
+    },
+    /// The room was given a time earlier than a previous one, by `by`. The latest time was used instead
+    TimeWentBackwards { by: Duration },
+    /// A majority has down-voted the leader for [crate::RoomConfig::election_watchdog] without it being
+    /// replaced, over `attempts` updates. Followed by [RoomEvent::LeaderChanged] if
+    /// [crate::RoomConfig::force_reelection_when_stuck] is set
+    RoomStuck {
+        term: Term,
+        leader: Option<ConnectionIndex>,
+        attempts: u32,
+    },
+    /// The room had no leader and elected `leader` once an online connection had acceptable quality again, see
+    /// [crate::RoomConfig::restore_leader_when_leaderless]. Follows the [RoomEvent::LeaderChanged] for `term`
+    LeaderRestored { term: Term, leader: ConnectionIndex },
+    /// [crate::Room::health] dropped below `threshold`, one of [crate::RoomConfig::health_thresholds]
+    HealthDegraded { score: u8, threshold: u8 },
+    /// [crate::Room::health] is back at or above `threshold` after having been below it
+    HealthRecovered { score: u8, threshold: u8 },
+    /// The [crate::Room::retiring_leader] is released, because [crate::RoomConfig::leader_overlap] is over, it is
+    /// no longer online or the leader changed again
+    RetiredLeaderReleased { term: Term, connection: ConnectionIndex },
+    /// Connections reported terms ahead of the room, and the room now follows `winning_term` and its leader in
+    /// `term`, see [crate::RoomConfig::reconcile_partitions]. `stragglers` did not report the winning term
+    PartitionHealed {
+        term: Term,
+        leader: Option<ConnectionIndex>,
+        winning_term: Term,
+        conflicting_terms: Vec<Term>,
+        stragglers: Vec<ConnectionIndex>,
+    },
+    /// `connection` was created with the identity of `previous`, which was destroyed, see
+    /// [crate::DuplicateIdentity::Replace]
+    ConnectionReplaced {
+        connection: ConnectionIndex,
+        previous: ConnectionIndex,
+    },
+    /// [crate::Room::begin_close] was called, the connections are told with [crate::OutgoingIntent::RoomClosing]
+    RoomClosing { reason: CloseReason },
+    /// The room has closed and removed every connection, `unacknowledged` did not acknowledge before
+    /// [crate::RoomConfig::close_drain_period]
+    RoomClosed {
+        reason: CloseReason,
+        unacknowledged: Vec<ConnectionIndex>,
+    },
+    /// No pings have been received for [crate::RoomConfig::quiet_after], see [crate::AbandonmentStage]
+    RoomQuiet { silent_for: Duration },
+    /// No pings have been received for [crate::RoomConfig::abandoned_after]
+    RoomAbandoned { silent_for: Duration },
+    /// The leader carried more than [crate::RoomConfig::max_leader_burden] and is handed over to a stronger
+    /// candidate. Followed by [RoomEvent::LeaderChanged]
+    LoadRebalance {
+        term: Term,
+        leader: ConnectionIndex,
+        burden: f32,
+    },
+    /// An election was held for `term`, follows its [RoomEvent::LeaderChanged]. `ranking` is every connection
+    /// that could be elected, best first, see [crate::ElectionReport::ranking]
+    ElectionRanked { term: Term, ranking: Vec<ConnectionIndex> },
+    /// The connection was admitted to a full room after `evicted` was kicked, see [crate::Room::admit_with_eviction]
+    AdmittedByEviction {
+        connection: ConnectionIndex,
+        evicted: ConnectionIndex,
+    },
+    /// `proposer` opened a vote to kick `target`, see [crate::Room::propose_kick]
+    KickProposed {
+        proposal: u32,
+        target: ConnectionIndex,
+        proposer: ConnectionIndex,
+    },
+    /// The kick vote has ended, an approved one follows [RoomEvent::Kicked]
+    KickVoteResolved {
+        proposal: u32,
+        target: ConnectionIndex,
+        outcome: KickVoteOutcome,
+    },
+    /// Telemetry about the election of a term, see [crate::RoomConfig::election_record_history]
+    ElectionRecorded { record: Box<ElectionRecord> },
+    /// The update after a ping would go through `connections`, more than [crate::RoomConfig::update_budget], and
+    /// waits for the next [crate::Room::update]. Emitted once until an update has run
+    Backpressure { connections: usize },
+    /// The critical section `tag` was not exited within its max duration and has ended, so the leader can be
+    /// replaced again, see [crate::Room::enter_critical_section]
+    CriticalSectionTimedOut { tag: String, held_for: Duration },
+}
+
+/// How urgent a [RoomEvent] is, ordered from least to most urgent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum EventSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// What part of the room a [RoomEvent] is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum EventCategory {
+    /// Connections joining, leaving or changing state
+    Membership,
+    /// Leaders, terms and representatives
+    Election,
+    /// Connection quality, reachability, knowledge and the health of the room
+    Quality,
+}
+
+impl RoomEvent {
+    /// The connection the event is mainly about: the leader for elections, the receiver of a state sync, the
+    /// target of a kick vote and the winner of a recorded election. `None` for events about the whole room
+    pub fn connection(&self) -> Option<ConnectionIndex> {
+        match *self {
+            RoomEvent::LeaderChanged { leader, .. }
+            | RoomEvent::RoomStuck { leader, .. }
+            | RoomEvent::PartitionHealed { leader, .. } => leader,
+            RoomEvent::LeaderConfirmed { leader, .. }
+            | RoomEvent::LeaseExpired { leader, .. }
+            | RoomEvent::LeaderActivated { leader, .. }
+            | RoomEvent::LeaderRestored { leader, .. }
+            | RoomEvent::LoadRebalance { leader, .. } => Some(leader),
+            RoomEvent::Disconnected { connection, .. }
+            | RoomEvent::WarmedUp { connection }
+            | RoomEvent::ProtocolMismatch { connection, .. }
+            | RoomEvent::AuthFailure { connection, .. }
+            | RoomEvent::SuspiciousKnowledge { connection, .. }
+            | RoomEvent::KnowledgeLagging { connection, .. }
+            | RoomEvent::KnowledgeCaughtUp { connection }
+            | RoomEvent::WentIdle { connection }
+            | RoomEvent::ReturnedFromIdle { connection }
+            | RoomEvent::Quarantined { connection }
+            | RoomEvent::Rehabilitated { connection }
+            | RoomEvent::UnreachableByLeader { connection }
+            | RoomEvent::ReachableByLeader { connection }
+            | RoomEvent::TransferredOut { connection }
+            | RoomEvent::TransferredIn { connection, .. }
+            | RoomEvent::PendingActivated { connection }
+            | RoomEvent::PendingExpired { connection }
+            | RoomEvent::StateSyncAssigned { receiver: connection, .. }
+            | RoomEvent::Kicked { connection, .. }
+            | RoomEvent::RetiredLeaderReleased { connection, .. }
+            | RoomEvent::ConnectionReplaced { connection, .. }
+            | RoomEvent::AdmittedByEviction { connection, .. }
+            | RoomEvent::KickProposed { target: connection, .. }
+            | RoomEvent::KickVoteResolved { target: connection, .. } => Some(connection),
+            RoomEvent::RepresentativeChanged { representative, .. } => representative,
+            RoomEvent::ElectionRecorded { ref record } => record.winner,
+            _ => None,
+        }
+    }
+
+    pub fn severity(&self) -> EventSeverity {
+        match self {
+            RoomEvent::LeaderChanged { leader: None, .. } | RoomEvent::RoomStuck { .. } => EventSeverity::Critical,
+            RoomEvent::LeaseExpired { .. }
+            | RoomEvent::Disconnected { .. }
+            | RoomEvent::ProtocolMismatch { .. }
+            | RoomEvent::AuthFailure { .. }
+            | RoomEvent::SuspiciousKnowledge { .. }
+            | RoomEvent::KnowledgeLagging { .. }
+            | RoomEvent::Quarantined { .. }
+            | RoomEvent::UnreachableByLeader { .. }
+            | RoomEvent::PendingExpired { .. }
+            | RoomEvent::Kicked { .. }
+            | RoomEvent::TimeWentBackwards { .. }
+            | RoomEvent::HealthDegraded { .. }
+            | RoomEvent::PartitionHealed { .. }
+            | RoomEvent::Backpressure { .. }
+            | RoomEvent::CriticalSectionTimedOut { .. }
+            | RoomEvent::RoomAbandoned { .. } => EventSeverity::Warning,
+            _ => EventSeverity::Info,
+        }
+    }
+
+    pub fn category(&self) -> EventCategory {
+        match self {
+            RoomEvent::LeaderChanged { .. }
+            | RoomEvent::LeaderConfirmed { .. }
+            | RoomEvent::LoadRebalance { .. }
+            | RoomEvent::ElectionRanked { .. }
+            | RoomEvent::ElectionRecorded { .. }
+            | RoomEvent::LeaseExpired { .. }
+            | RoomEvent::TermConverged { .. }
+            | RoomEvent::LeaderActivated { .. }
+            | RoomEvent::RepresentativeChanged { .. }
+            | RoomEvent::RoomStuck { .. }
+            | RoomEvent::LeaderRestored { .. }
+            | RoomEvent::RetiredLeaderReleased { .. }
+            | RoomEvent::PartitionHealed { .. }
+            | RoomEvent::CriticalSectionTimedOut { .. } => EventCategory::Election,
+            RoomEvent::Disconnected { .. }
+            | RoomEvent::WarmedUp { .. }
+            | RoomEvent::ProtocolMismatch { .. }
+            | RoomEvent::AuthFailure { .. }
+            | RoomEvent::WentIdle { .. }
+            | RoomEvent::ReturnedFromIdle { .. }
+            | RoomEvent::TransferredOut { .. }
+            | RoomEvent::TransferredIn { .. }
+            | RoomEvent::PendingActivated { .. }
+            | RoomEvent::PendingExpired { .. }
+            | RoomEvent::StateSyncAssigned { .. }
+            | RoomEvent::Kicked { .. }
+            | RoomEvent::ConnectionReplaced { .. }
+            | RoomEvent::AdmittedByEviction { .. }
+            | RoomEvent::KickProposed { .. }
+            | RoomEvent::KickVoteResolved { .. }
+            | RoomEvent::RoomClosing { .. }
+            | RoomEvent::RoomClosed { .. } => EventCategory::Membership,
+            RoomEvent::SuspiciousKnowledge { .. }
+            | RoomEvent::KnowledgeLagging { .. }
+            | RoomEvent::KnowledgeCaughtUp { .. }
+            | RoomEvent::Quarantined { .. }
+            | RoomEvent::Rehabilitated { .. }
+            | RoomEvent::UnreachableByLeader { .. }
+            | RoomEvent::ReachableByLeader { .. }
+            | RoomEvent::TimeWentBackwards { .. }
+            | RoomEvent::HealthDegraded { .. }
+            | RoomEvent::HealthRecovered { .. }
+            | RoomEvent::RoomQuiet { .. }
+            | RoomEvent::Backpressure { .. }
+            | RoomEvent::RoomAbandoned { .. } => EventCategory::Quality,
+        }
+    }
+}
+
+/// Selects the events returned by [crate::Room::drain_events], see [crate::RoomConfig::event_filter].
+/// The default keeps every event.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventFilter {
+    /// Events less urgent than this are dropped
+    pub min_severity: EventSeverity,
+    /// Events outside of these categories are dropped
+    pub categories: Vec<EventCategory>,
+}
+
+impl Default for EventFilter {
+    fn default() -> Self {
+        Self {
+            min_severity: EventSeverity::Info,
+            categories: vec![EventCategory::Membership, EventCategory::Election, EventCategory::Quality],
+        }
+    }
+}
+
+impl EventFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_min_severity(mut self, severity: EventSeverity) -> Self {
+        self.min_severity = severity;
+        self
+    }
+
+    pub fn with_categories(mut self, categories: Vec<EventCategory>) -> Self {
+        self.categories = categories;
+        self
+    }
+
+    pub fn allows(&self, event: &RoomEvent) -> bool {
+        event.severity() >= self.min_severity && self.categories.contains(&event.category())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{DisconnectReason, EventCategory, EventFilter, EventSeverity, PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn drain_only_filtered_events() {
+        let filter = EventFilter::new()
+            .with_min_severity(EventSeverity::Warning)
+            .with_categories(vec![EventCategory::Membership]);
+        let mut room = RoomConfig::new()
+            .with_min_supported_version(1)
+            .with_event_filter(filter)
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let later = now + Duration::from_secs(1);
+        let connection = room.create_connection(now).unwrap();
+        room.on_ping(connection, &PingPayload::new(), later);
+        room.on_ping(connection, &PingPayload::new(), now);
+        room.on_ping(connection, &PingPayload::new().with_protocol_version(0), later);
+
+        assert_eq!(
+            room.drain_events(),
+            vec![
+                RoomEvent::ProtocolMismatch {
+                    connection,
+                    version: 0,
+                    min_supported_version: 1,
+                },
+                RoomEvent::Disconnected {
+                    connection,
+                    reason: DisconnectReason::ProtocolMismatch,
+                },
+            ]
+        );
+        let history: Vec<_> = room.recent_events().map(|timed| timed.event.category()).collect();
+        assert!(history.contains(&EventCategory::Election));
+        assert!(history.contains(&EventCategory::Quality));
+    }
+}
@@ -0,0 +1,567 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! C interface to the room logic, declared in `include/conclave_room.h`
+//!
+//! Build it as a shared library with `cargo rustc -p conclave-room-session --features ffi --crate-type cdylib`.
+//! Rooms are handed out as opaque pointers, connections as value and generation pairs. Time is given in
+//! milliseconds on any monotonic clock, the first timestamp a room sees is its epoch.
+//!
+//! A panic never unwinds into the caller: entry points that return a status return [CONCLAVE_ERROR_PANIC], the
+//! others their null or zero value. The room may be left half updated, so the only safe call on it after that is
+//! [room_destroy].
+
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Duration;
+
+use conclave_types::{ConnectionToLeader, Knowledge, Term};
+
+use crate::time::origin;
+use crate::{
+    ConnectionIndex, DisconnectReason, Instant, KickReason, KickVoteOutcome, PingOutcome, PingPayload, PingRejection, Room, RoomConfig,
+    RoomError, RoomEvent,
+};
+
+pub const CONCLAVE_OK: i32 = 0;
+pub const CONCLAVE_PING_REJECTED: i32 = 1;
+pub const CONCLAVE_ERROR_NULL: i32 = -1;
+pub const CONCLAVE_ERROR_STALE_HANDLE: i32 = -2;
+pub const CONCLAVE_ERROR_UNKNOWN_CONNECTION: i32 = -3;
+pub const CONCLAVE_ERROR_CONNECTION_INDEX_IN_USE: i32 = -4;
+pub const CONCLAVE_ERROR_IDENTITY_IN_USE: i32 = -5;
+pub const CONCLAVE_ERROR_NO_CONNECTION_INDEX_AVAILABLE: i32 = -6;
+pub const CONCLAVE_ERROR_ROOM_CLOSING: i32 = -7;
+pub const CONCLAVE_ERROR_ROOM_FULL: i32 = -8;
+pub const CONCLAVE_ERROR_NOT_KICKABLE: i32 = -9;
+pub const CONCLAVE_ERROR_KICK_VOTE_IN_PROGRESS: i32 = -10;
+pub const CONCLAVE_ERROR_PANIC: i32 = -11;
+
+pub const CONCLAVE_EVENT_LEADER_CHANGED: u32 = 1;
+pub const CONCLAVE_EVENT_LEADER_CONFIRMED: u32 = 2;
+pub const CONCLAVE_EVENT_LEASE_EXPIRED: u32 = 3;
+pub const CONCLAVE_EVENT_TERM_CONVERGED: u32 = 4;
+pub const CONCLAVE_EVENT_LEADER_ACTIVATED: u32 = 5;
+pub const CONCLAVE_EVENT_DISCONNECTED: u32 = 6;
+pub const CONCLAVE_EVENT_WARMED_UP: u32 = 7;
+pub const CONCLAVE_EVENT_PROTOCOL_MISMATCH: u32 = 8;
+pub const CONCLAVE_EVENT_AUTH_FAILURE: u32 = 9;
+pub const CONCLAVE_EVENT_SUSPICIOUS_KNOWLEDGE: u32 = 10;
+pub const CONCLAVE_EVENT_KNOWLEDGE_LAGGING: u32 = 11;
+pub const CONCLAVE_EVENT_KNOWLEDGE_CAUGHT_UP: u32 = 12;
+pub const CONCLAVE_EVENT_WENT_IDLE: u32 = 13;
+pub const CONCLAVE_EVENT_RETURNED_FROM_IDLE: u32 = 14;
+pub const CONCLAVE_EVENT_QUARANTINED: u32 = 15;
+pub const CONCLAVE_EVENT_REHABILITATED: u32 = 16;
+pub const CONCLAVE_EVENT_REPRESENTATIVE_CHANGED: u32 = 17;
+pub const CONCLAVE_EVENT_UNREACHABLE_BY_LEADER: u32 = 18;
+pub const CONCLAVE_EVENT_REACHABLE_BY_LEADER: u32 = 19;
+pub const CONCLAVE_EVENT_TRANSFERRED_OUT: u32 = 20;
+pub const CONCLAVE_EVENT_TRANSFERRED_IN: u32 = 21;
+pub const CONCLAVE_EVENT_PENDING_ACTIVATED: u32 = 22;
+pub const CONCLAVE_EVENT_PENDING_EXPIRED: u32 = 23;
+pub const CONCLAVE_EVENT_STATE_SYNC_ASSIGNED: u32 = 24;
+pub const CONCLAVE_EVENT_KICKED: u32 = 25;
+pub const CONCLAVE_EVENT_TIME_WENT_BACKWARDS: u32 = 26;
+pub const CONCLAVE_EVENT_ROOM_STUCK: u32 = 27;
+pub const CONCLAVE_EVENT_LEADER_RESTORED: u32 = 28;
+pub const CONCLAVE_EVENT_HEALTH_DEGRADED: u32 = 29;
+pub const CONCLAVE_EVENT_HEALTH_RECOVERED: u32 = 30;
+pub const CONCLAVE_EVENT_RETIRED_LEADER_RELEASED: u32 = 31;
+pub const CONCLAVE_EVENT_PARTITION_HEALED: u32 = 32;
+pub const CONCLAVE_EVENT_CONNECTION_REPLACED: u32 = 33;
+pub const CONCLAVE_EVENT_ROOM_CLOSING: u32 = 34;
+pub const CONCLAVE_EVENT_ROOM_CLOSED: u32 = 35;
+pub const CONCLAVE_EVENT_ROOM_QUIET: u32 = 36;
+pub const CONCLAVE_EVENT_ROOM_ABANDONED: u32 = 37;
+pub const CONCLAVE_EVENT_LOAD_REBALANCE: u32 = 38;
+pub const CONCLAVE_EVENT_ELECTION_RANKED: u32 = 39;
+pub const CONCLAVE_EVENT_ADMITTED_BY_EVICTION: u32 = 40;
+pub const CONCLAVE_EVENT_KICK_PROPOSED: u32 = 41;
+pub const CONCLAVE_EVENT_KICK_VOTE_RESOLVED: u32 = 42;
+pub const CONCLAVE_EVENT_ELECTION_RECORDED: u32 = 43;
+pub const CONCLAVE_EVENT_BACKPRESSURE: u32 = 44;
+pub const CONCLAVE_EVENT_CRITICAL_SECTION_TIMED_OUT: u32 = 45;
+
+/// A room owned by the C side, free it with [room_destroy]
+pub struct ConclaveRoom {
+    room: Room,
+    epoch: Instant,
+    epoch_ms: Option<u64>,
+    /// Events taken from the room that did not fit in the buffer given to [room_drain_events]
+    events: VecDeque<RoomEvent>,
+}
+
+impl ConclaveRoom {
+    fn new(config: RoomConfig) -> Self {
+        Self {
+            room: Room::new_with_config(config),
+            epoch: origin(),
+            epoch_ms: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn time(&mut self, now_ms: u64) -> Instant {
+        let epoch_ms = *self.epoch_ms.get_or_insert(now_ms);
+        self.epoch + Duration::from_millis(now_ms.saturating_sub(epoch_ms))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConclaveConnection {
+    pub value: u32,
+    pub generation: u32,
+}
+
+impl From<ConnectionIndex> for ConclaveConnection {
+    fn from(index: ConnectionIndex) -> Self {
+        Self {
+            value: index.value(),
+            generation: index.generation(),
+        }
+    }
+}
+
+impl From<ConclaveConnection> for ConnectionIndex {
+    fn from(connection: ConclaveConnection) -> Self {
+        ConnectionIndex::with_generation(connection.value, connection.generation)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConclavePing {
+    pub term: u16,
+    pub knowledge: u64,
+    /// 0 unknown, 1 connected, 2 disconnected
+    pub connection_to_leader: u8,
+    pub protocol_version: u16,
+    /// 1 if `sequence` is set
+    pub has_sequence: u8,
+    pub sequence: u16,
+}
+
+/// A [RoomEvent], `kind` is one of the `CONCLAVE_EVENT_` constants. The meaning of `value` depends on the kind
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConclaveEvent {
+    pub kind: u32,
+    pub term: u16,
+    /// 1 if `connection` is set
+    pub has_connection: u8,
+    pub connection: ConclaveConnection,
+    pub value: u64,
+}
+
+impl ConclaveEvent {
+    fn new(kind: u32, term: Term, connection: Option<ConnectionIndex>, value: u64) -> Self {
+        Self {
+            kind,
+            term: term.value(),
+            has_connection: connection.is_some() as u8,
+            connection: connection.map_or(ConclaveConnection { value: 0, generation: 0 }, Into::into),
+            value,
+        }
+    }
+}
+
+impl From<&RoomEvent> for ConclaveEvent {
+    fn from(event: &RoomEvent) -> Self {
+        let none = Term(0);
+        match *event {
+            RoomEvent::LeaderChanged { term, leader } => Self::new(CONCLAVE_EVENT_LEADER_CHANGED, term, leader, 0),
+            RoomEvent::LeaderConfirmed { term, leader } => {
+                Self::new(CONCLAVE_EVENT_LEADER_CONFIRMED, term, Some(leader), 0)
+            }
+            RoomEvent::LeaseExpired { term, leader } => Self::new(CONCLAVE_EVENT_LEASE_EXPIRED, term, Some(leader), 0),
+            RoomEvent::TermConverged { term } => Self::new(CONCLAVE_EVENT_TERM_CONVERGED, term, None, 0),
+            RoomEvent::LeaderActivated { term, leader } => {
+                Self::new(CONCLAVE_EVENT_LEADER_ACTIVATED, term, Some(leader), 0)
+            }
+            RoomEvent::Disconnected { connection, reason } => {
+                let reason = match reason {
+                    DisconnectReason::PoorQuality => 0,
+                    DisconnectReason::ProtocolMismatch => 1,
+                };
+                Self::new(CONCLAVE_EVENT_DISCONNECTED, none, Some(connection), reason)
+            }
+            RoomEvent::WarmedUp { connection } => Self::new(CONCLAVE_EVENT_WARMED_UP, none, Some(connection), 0),
+            RoomEvent::ProtocolMismatch { connection, version, .. } => {
+                Self::new(CONCLAVE_EVENT_PROTOCOL_MISMATCH, none, Some(connection), version as u64)
+            }
+            RoomEvent::AuthFailure { connection, failures } => {
+                Self::new(CONCLAVE_EVENT_AUTH_FAILURE, none, Some(connection), failures as u64)
+            }
+            RoomEvent::SuspiciousKnowledge { connection, reported, .. } => {
+                Self::new(CONCLAVE_EVENT_SUSPICIOUS_KNOWLEDGE, none, Some(connection), reported.value())
+            }
+            RoomEvent::KnowledgeLagging { connection, delta } => {
+                Self::new(CONCLAVE_EVENT_KNOWLEDGE_LAGGING, none, Some(connection), delta)
+            }
+            RoomEvent::KnowledgeCaughtUp { connection } => {
+                Self::new(CONCLAVE_EVENT_KNOWLEDGE_CAUGHT_UP, none, Some(connection), 0)
+            }
+            RoomEvent::WentIdle { connection } => Self::new(CONCLAVE_EVENT_WENT_IDLE, none, Some(connection), 0),
+            RoomEvent::ReturnedFromIdle { connection } => {
+                Self::new(CONCLAVE_EVENT_RETURNED_FROM_IDLE, none, Some(connection), 0)
+            }
+            RoomEvent::Quarantined { connection } => Self::new(CONCLAVE_EVENT_QUARANTINED, none, Some(connection), 0),
+            RoomEvent::Rehabilitated { connection } => {
+                Self::new(CONCLAVE_EVENT_REHABILITATED, none, Some(connection), 0)
+            }
+            RoomEvent::RepresentativeChanged { group, representative } => {
+                Self::new(CONCLAVE_EVENT_REPRESENTATIVE_CHANGED, none, representative, group.0 as u64)
+            }
+            RoomEvent::UnreachableByLeader { connection } => {
+                Self::new(CONCLAVE_EVENT_UNREACHABLE_BY_LEADER, none, Some(connection), 0)
+            }
+            RoomEvent::ReachableByLeader { connection } => {
+                Self::new(CONCLAVE_EVENT_REACHABLE_BY_LEADER, none, Some(connection), 0)
+            }
+            RoomEvent::TransferredOut { connection } => {
+                Self::new(CONCLAVE_EVENT_TRANSFERRED_OUT, none, Some(connection), 0)
+            }
+            RoomEvent::TransferredIn { connection, previous } => {
+                Self::new(CONCLAVE_EVENT_TRANSFERRED_IN, none, Some(connection), previous.value() as u64)
+            }
+            RoomEvent::PendingActivated { connection } => {
+                Self::new(CONCLAVE_EVENT_PENDING_ACTIVATED, none, Some(connection), 0)
+            }
+            RoomEvent::PendingExpired { connection } => {
+                Self::new(CONCLAVE_EVENT_PENDING_EXPIRED, none, Some(connection), 0)
+            }
+            RoomEvent::StateSyncAssigned { receiver, donor } => Self::new(
+                CONCLAVE_EVENT_STATE_SYNC_ASSIGNED,
+                none,
+                Some(receiver),
+                donor.map_or(0, |donor| donor.value() as u64),
+            ),
+            RoomEvent::Kicked { connection, reason } => {
+                let reason = match reason {
+                    KickReason::AuthenticationFailed => 0,
+                    KickReason::Evicted => 1,
+                    KickReason::Voted => 2,
+                };
+                Self::new(CONCLAVE_EVENT_KICKED, none, Some(connection), reason)
+            }
+            RoomEvent::TimeWentBackwards { by } => {
+                Self::new(CONCLAVE_EVENT_TIME_WENT_BACKWARDS, none, None, by.as_millis() as u64)
+            }
+            RoomEvent::RoomStuck { term, leader, attempts } => {
+                Self::new(CONCLAVE_EVENT_ROOM_STUCK, term, leader, attempts as u64)
+            }
+            RoomEvent::LeaderRestored { term, leader } => {
+                Self::new(CONCLAVE_EVENT_LEADER_RESTORED, term, Some(leader), 0)
+            }
+            RoomEvent::HealthDegraded { score, threshold } => Self::new(
+                CONCLAVE_EVENT_HEALTH_DEGRADED,
+                none,
+                None,
+                (threshold as u64) << 8 | score as u64,
+            ),
+            RoomEvent::HealthRecovered { score, threshold } => Self::new(
+                CONCLAVE_EVENT_HEALTH_RECOVERED,
+                none,
+                None,
+                (threshold as u64) << 8 | score as u64,
+            ),
+            RoomEvent::RetiredLeaderReleased { term, connection } => {
+                Self::new(CONCLAVE_EVENT_RETIRED_LEADER_RELEASED, term, Some(connection), 0)
+            }
+            RoomEvent::PartitionHealed {
+                term,
+                leader,
+                winning_term,
+                ..
+            } => Self::new(CONCLAVE_EVENT_PARTITION_HEALED, term, leader, winning_term.value() as u64),
+            RoomEvent::ConnectionReplaced { connection, previous } => {
+                Self::new(CONCLAVE_EVENT_CONNECTION_REPLACED, none, Some(connection), previous.value() as u64)
+            }
+            RoomEvent::RoomClosing { reason } => Self::new(CONCLAVE_EVENT_ROOM_CLOSING, none, None, reason as u64),
+            RoomEvent::RoomClosed { reason, .. } => Self::new(CONCLAVE_EVENT_ROOM_CLOSED, none, None, reason as u64),
+            RoomEvent::RoomQuiet { silent_for } => {
+                Self::new(CONCLAVE_EVENT_ROOM_QUIET, none, None, silent_for.as_millis() as u64)
+            }
+            RoomEvent::RoomAbandoned { silent_for } => {
+                Self::new(CONCLAVE_EVENT_ROOM_ABANDONED, none, None, silent_for.as_millis() as u64)
+            }
+            RoomEvent::LoadRebalance { term, leader, burden } => {
+                Self::new(CONCLAVE_EVENT_LOAD_REBALANCE, term, Some(leader), (burden * 100.0).round() as u64)
+            }
+            RoomEvent::ElectionRanked { term, ref ranking } => {
+                Self::new(CONCLAVE_EVENT_ELECTION_RANKED, term, ranking.get(1).copied(), ranking.len() as u64)
+            }
+            RoomEvent::AdmittedByEviction { connection, evicted } => {
+                Self::new(CONCLAVE_EVENT_ADMITTED_BY_EVICTION, none, Some(connection), evicted.value() as u64)
+            }
+            RoomEvent::KickProposed { proposal, target, .. } => {
+                Self::new(CONCLAVE_EVENT_KICK_PROPOSED, none, Some(target), proposal as u64)
+            }
+            RoomEvent::KickVoteResolved { target, outcome, .. } => {
+                let outcome = match outcome {
+                    KickVoteOutcome::Approved => 0,
+                    KickVoteOutcome::Rejected => 1,
+                    KickVoteOutcome::Expired => 2,
+                    KickVoteOutcome::Withdrawn => 3,
+                };
+                Self::new(CONCLAVE_EVENT_KICK_VOTE_RESOLVED, none, Some(target), outcome)
+            }
+            RoomEvent::ElectionRecorded { ref record } => {
+                let convergence = record.convergence.map_or(u64::MAX, |convergence| convergence.as_millis() as u64);
+                Self::new(CONCLAVE_EVENT_ELECTION_RECORDED, record.term, record.winner, convergence)
+            }
+            RoomEvent::Backpressure { connections } => {
+                Self::new(CONCLAVE_EVENT_BACKPRESSURE, none, None, connections as u64)
+            }
+            RoomEvent::CriticalSectionTimedOut { held_for, .. } => Self::new(
+                CONCLAVE_EVENT_CRITICAL_SECTION_TIMED_OUT,
+                none,
+                None,
+                held_for.as_millis() as u64,
+            ),
+        }
+    }
+}
+
+fn error_code(error: RoomError) -> i32 {
+    match error {
+        RoomError::StaleHandle { .. } => CONCLAVE_ERROR_STALE_HANDLE,
+        RoomError::UnknownConnection(_) => CONCLAVE_ERROR_UNKNOWN_CONNECTION,
+        RoomError::ConnectionIndexInUse(_) => CONCLAVE_ERROR_CONNECTION_INDEX_IN_USE,
+        RoomError::IdentityInUse(_) => CONCLAVE_ERROR_IDENTITY_IN_USE,
+        RoomError::NoConnectionIndexAvailable => CONCLAVE_ERROR_NO_CONNECTION_INDEX_AVAILABLE,
+        RoomError::RoomClosing => CONCLAVE_ERROR_ROOM_CLOSING,
+        RoomError::RoomFull => CONCLAVE_ERROR_ROOM_FULL,
+        RoomError::NotKickable(_) => CONCLAVE_ERROR_NOT_KICKABLE,
+        RoomError::KickVoteInProgress(_) => CONCLAVE_ERROR_KICK_VOTE_IN_PROGRESS,
+    }
+}
+
+/// Runs `body`, returns `on_panic` instead of unwinding across the C boundary if it panics
+fn catch_panic<T>(on_panic: T, body: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or(on_panic)
+}
+
+/// Creates a room with the default config
+#[no_mangle]
+pub extern "C" fn room_create() -> *mut ConclaveRoom {
+    catch_panic(std::ptr::null_mut(), || Box::into_raw(Box::new(ConclaveRoom::new(RoomConfig::default()))))
+}
+
+/// Creates a room with the default config and the given quality threshold, null if the threshold is not valid
+#[no_mangle]
+pub extern "C" fn room_create_with_threshold(pings_per_second_threshold: f32) -> *mut ConclaveRoom {
+    catch_panic(std::ptr::null_mut(), || {
+        let config = RoomConfig::default().pings_per_second_threshold(pings_per_second_threshold);
+        if config.validate().is_err() {
+            return std::ptr::null_mut();
+        }
+        Box::into_raw(Box::new(ConclaveRoom::new(config)))
+    })
+}
+
+/// # Safety
+///
+/// `room` must be null or a pointer returned by `room_create`, that has not been destroyed before
+#[no_mangle]
+pub unsafe extern "C" fn room_destroy(room: *mut ConclaveRoom) {
+    if !room.is_null() {
+        catch_panic((), || drop(Box::from_raw(room)));
+    }
+}
+
+/// # Safety
+///
+/// `room` must be null or a live room, `out` must be null or point to writable memory
+#[no_mangle]
+pub unsafe extern "C" fn room_create_connection(
+    room: *mut ConclaveRoom,
+    now_ms: u64,
+    out: *mut ConclaveConnection,
+) -> i32 {
+    let (Some(room), Some(out)) = (room.as_mut(), out.as_mut()) else {
+        return CONCLAVE_ERROR_NULL;
+    };
+    catch_panic(CONCLAVE_ERROR_PANIC, || {
+        let time = room.time(now_ms);
+        match room.room.create_connection(time) {
+            Ok(index) => {
+                *out = index.into();
+                CONCLAVE_OK
+            }
+            Err(error) => error_code(error),
+        }
+    })
+}
+
+/// # Safety
+///
+/// `room` must be null or a live room
+#[no_mangle]
+pub unsafe extern "C" fn room_destroy_connection(room: *mut ConclaveRoom, connection: ConclaveConnection) -> i32 {
+    let Some(room) = room.as_mut() else {
+        return CONCLAVE_ERROR_NULL;
+    };
+    catch_panic(CONCLAVE_ERROR_PANIC, || match room.room.destroy_connection(connection.into()) {
+        Ok(()) => CONCLAVE_OK,
+        Err(error) => error_code(error),
+    })
+}
+
+/// Returns [CONCLAVE_OK] if the ping was applied and [CONCLAVE_PING_REJECTED] if it was refused, e.g. as a duplicate
+///
+/// # Safety
+///
+/// `room` must be null or a live room, `ping` must be null or point to a ping
+#[no_mangle]
+pub unsafe extern "C" fn room_on_ping(
+    room: *mut ConclaveRoom,
+    connection: ConclaveConnection,
+    ping: *const ConclavePing,
+    now_ms: u64,
+) -> i32 {
+    let (Some(room), Some(ping)) = (room.as_mut(), ping.as_ref()) else {
+        return CONCLAVE_ERROR_NULL;
+    };
+    catch_panic(CONCLAVE_ERROR_PANIC, || {
+        let mut payload = PingPayload::new()
+            .with_term(Term(ping.term))
+            .with_knowledge(Knowledge(ping.knowledge))
+            .with_connection_to_leader(
+                ConnectionToLeader::from_u8(ping.connection_to_leader).unwrap_or(ConnectionToLeader::Unknown),
+            )
+            .with_protocol_version(ping.protocol_version);
+        if ping.has_sequence != 0 {
+            payload = payload.with_sequence(ping.sequence);
+        }
+        let time = room.time(now_ms);
+        match room.room.on_ping(connection.into(), &payload, time) {
+            PingOutcome::Accepted => CONCLAVE_OK,
+            PingOutcome::Rejected(PingRejection::InvalidConnection(error)) => error_code(error),
+            PingOutcome::Rejected(_) => CONCLAVE_PING_REJECTED,
+            PingOutcome::Injected => CONCLAVE_OK,
+        }
+    })
+}
+
+/// Returns [CONCLAVE_OK], or [CONCLAVE_ERROR_NULL] if `room` is null
+///
+/// # Safety
+///
+/// `room` must be null or a live room
+#[no_mangle]
+pub unsafe extern "C" fn room_update(room: *mut ConclaveRoom, now_ms: u64) -> i32 {
+    let Some(room) = room.as_mut() else {
+        return CONCLAVE_ERROR_NULL;
+    };
+    catch_panic(CONCLAVE_ERROR_PANIC, || {
+        let time = room.time(now_ms);
+        room.room.update(time);
+        CONCLAVE_OK
+    })
+}
+
+/// Writes the leader to `out` and returns 1, or returns 0 if the room has no leader or `room` or `out` is null
+///
+/// # Safety
+///
+/// `room` must be null or a live room, `out` must be null or point to writable memory
+#[no_mangle]
+pub unsafe extern "C" fn room_leader(room: *const ConclaveRoom, out: *mut ConclaveConnection) -> i32 {
+    let (Some(room), Some(out)) = (room.as_ref(), out.as_mut()) else {
+        return 0;
+    };
+    catch_panic(0, || match room.room.leader_index {
+        Some(leader) => {
+            *out = leader.into();
+            1
+        }
+        None => 0,
+    })
+}
+
+/// # Safety
+///
+/// `room` must be null or a live room
+#[no_mangle]
+pub unsafe extern "C" fn room_term(room: *const ConclaveRoom) -> u16 {
+    catch_panic(0, || room.as_ref().map_or(0, |room| room.room.term.value()))
+}
+
+/// Moves up to `capacity` events, oldest first, into `out` and returns how many were written. Events that do
+/// not fit are kept for the next call.
+///
+/// # Safety
+///
+/// `room` must be null or a live room, `out` must be null or point to room for `capacity` events
+#[no_mangle]
+pub unsafe extern "C" fn room_drain_events(room: *mut ConclaveRoom, out: *mut ConclaveEvent, capacity: usize) -> usize {
+    let Some(room) = room.as_mut() else {
+        return 0;
+    };
+    if out.is_null() {
+        return 0;
+    }
+    catch_panic(0, || {
+        room.events.extend(room.room.drain_events());
+        let count = capacity.min(room.events.len());
+        for (position, event) in room.events.drain(..count).enumerate() {
+            out.add(position).write(ConclaveEvent::from(&event));
+        }
+        count
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elect_through_c_interface() {
+        unsafe {
+            let room = room_create();
+            let mut first = ConclaveConnection { value: 0, generation: 0 };
+            let mut second = first;
+            assert_eq!(room_create_connection(room, 1000, &mut first), CONCLAVE_OK);
+            assert_eq!(room_create_connection(room, 1000, &mut second), CONCLAVE_OK);
+
+            let mut leader = ConclaveConnection { value: 0, generation: 0 };
+            assert_eq!(room_leader(room, &mut leader), 1);
+            assert_eq!(leader, first);
+            assert_eq!(room_leader(std::ptr::null(), &mut leader), 0);
+            assert_eq!(room_leader(room, std::ptr::null_mut()), 0);
+
+            let ping = ConclavePing {
+                term: room_term(room),
+                knowledge: 10,
+                connection_to_leader: 1,
+                protocol_version: crate::PROTOCOL_VERSION,
+                has_sequence: 1,
+                sequence: 1,
+            };
+            assert_eq!(room_on_ping(room, second, &ping, 1100), CONCLAVE_OK);
+            assert_eq!(room_on_ping(room, second, &ping, 1100), CONCLAVE_PING_REJECTED);
+            assert_eq!(room_update(room, 1200), CONCLAVE_OK);
+            assert_eq!(room_update(std::ptr::null_mut(), 1200), CONCLAVE_ERROR_NULL);
+
+            let mut events = [ConclaveEvent::new(0, Term(0), None, 0); 1];
+            assert_eq!(room_drain_events(room, events.as_mut_ptr(), 1), 1);
+            assert_eq!(events[0].kind, CONCLAVE_EVENT_LEADER_CHANGED);
+            assert_eq!(events[0].connection, first);
+
+            assert_eq!(room_destroy_connection(room, second), CONCLAVE_OK);
+            assert_eq!(room_destroy_connection(room, second), CONCLAVE_ERROR_UNKNOWN_CONNECTION);
+            assert!(room_create_with_threshold(0.0).is_null());
+            room_destroy(room);
+        }
+    }
+
+    #[test]
+    fn return_error_instead_of_unwinding() {
+        assert_eq!(catch_panic(CONCLAVE_ERROR_PANIC, || panic!("broken invariant")), CONCLAVE_ERROR_PANIC);
+        assert_eq!(catch_panic(CONCLAVE_ERROR_PANIC, || CONCLAVE_OK), CONCLAVE_OK);
+    }
+}
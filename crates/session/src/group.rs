@@ -0,0 +1,166 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::fmt;
+
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::recorder::RecordedInput;
+use crate::{Connection, ConnectionIndex, ConnectionState, Room, RoomError, RoomEvent};
+
+/// A sub-group of the connections in a room, e.g. a team
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupId(pub u32);
+
+impl fmt::Display for GroupId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[group {}]", self.0)
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Moves the connection to `group`, or out of its group with `None`. Every group has a representative,
+    /// chosen among its members the same way the leader is elected, see [Room::representative].
+    pub fn set_group(&mut self, connection_index: ConnectionIndex, group: Option<GroupId>) -> Result<(), RoomError> {
+        self.record_untimed(RecordedInput::SetGroup {
+            connection: connection_index.value(),
+            generation: connection_index.generation(),
+            group: group.map(|group| group.0),
+        });
+        self.validate_connection(connection_index)?;
+        self.connections.get_mut(&connection_index).unwrap().group = group;
+        self.scan.may_have_groups |= group.is_some();
+        self.update_representatives();
+        self.publish_view();
+        Ok(())
+    }
+
+    /// Members of the group, ordered by connection index
+    pub fn group_members(&self, group: GroupId) -> Vec<ConnectionIndex> {
+        let mut members: Vec<ConnectionIndex> = self
+            .connections
+            .values()
+            .filter(|connection| connection.group == Some(group))
+            .map(|connection| connection.id)
+            .collect();
+        members.sort_by_key(|index| index.value());
+        members
+    }
+
+    /// The member that represents the group, `None` if no member can be elected or is connected
+    pub fn representative(&self, group: GroupId) -> Option<ConnectionIndex> {
+        self.representatives.get(&group).copied()
+    }
+
+    fn can_represent(&self, connection: &Connection<K>) -> bool {
+        connection.state != ConnectionState::Disconnected && self.ineligibility(connection, None).is_none()
+    }
+
+    /// Keeps every representative that can still represent its group, and elects a new one among the members
+    /// for the groups that lost theirs
+    pub(crate) fn update_representatives(&mut self) {
+        if !self.scan.may_have_groups && self.representatives.is_empty() {
+            return;
+        }
+        let mut groups: Vec<GroupId> = self.connections.values().filter_map(|connection| connection.group).collect();
+        self.scan.may_have_groups = !groups.is_empty();
+        groups.extend(self.representatives.keys().copied());
+        groups.sort();
+        groups.dedup();
+
+        let mut changed = Vec::<(GroupId, Option<ConnectionIndex>)>::new();
+        for group in groups {
+            let mut members: Vec<&Connection<K>> = self
+                .connections
+                .values()
+                .filter(|connection| connection.group == Some(group))
+                .collect();
+            members.sort_by_key(|connection| connection.id.value());
+
+            let current = self.representative(group);
+            let keeps_current = current.is_some_and(|representative| {
+                members
+                    .iter()
+                    .any(|member| member.id == representative && self.can_represent(member))
+            });
+            if keeps_current {
+                continue;
+            }
+
+            let (ranked, _) = self.rank_candidates(&members, None);
+            let next = ranked
+                .into_iter()
+                .find(|candidate| candidate.state != ConnectionState::Disconnected)
+                .map(|candidate| candidate.id);
+            if next != current {
+                changed.push((group, next));
+            }
+        }
+
+        for (group, representative) in changed {
+            info!("{} is now represented by {:?}", group, representative);
+            match representative {
+                Some(representative) => self.representatives.insert(group, representative),
+                None => self.representatives.remove(&group),
+            };
+            self.push_event(RoomEvent::RepresentativeChanged { group, representative });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::Knowledge;
+
+    use crate::{GroupId, PingPayload, Room, RoomEvent};
+
+    #[test]
+    fn elect_representative_per_group() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let red = GroupId(1);
+        let blue = GroupId(2);
+        let red_first = room.create_connection(now).unwrap();
+        let red_second = room.create_connection(now).unwrap();
+        let blue_only = room.create_connection(now).unwrap();
+        room.on_ping(red_second, &PingPayload::new().with_knowledge(Knowledge(100)), now);
+
+        room.set_group(red_first, Some(red)).unwrap();
+        assert_eq!(room.representative(red), Some(red_first));
+        room.set_group(red_second, Some(red)).unwrap();
+        room.set_group(blue_only, Some(blue)).unwrap();
+        assert_eq!(room.group_members(red), vec![red_first, red_second]);
+        // The representative is kept when a more knowledgeable member joins the group
+        assert_eq!(room.representative(red), Some(red_first));
+        assert_eq!(room.representative(blue), Some(blue_only));
+
+        room.drain_events();
+        room.destroy_connection(red_first).unwrap();
+        assert_eq!(room.representative(red), Some(red_second));
+        room.set_group(blue_only, None).unwrap();
+        assert_eq!(room.representative(blue), None);
+        let changes: Vec<RoomEvent> = room
+            .drain_events()
+            .into_iter()
+            .filter(|event| matches!(event, RoomEvent::RepresentativeChanged { .. }))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                RoomEvent::RepresentativeChanged {
+                    group: red,
+                    representative: Some(red_second),
+                },
+                RoomEvent::RepresentativeChanged {
+                    group: blue,
+                    representative: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use conclave_types::KnowledgeOrd;
+
+use crate::{Connection, ConnectionIndex, Room, RoomError};
+
+static NEXT_ROOM_INSTANCE: AtomicU64 = AtomicU64::new(1);
+
+/// Unique for every room created in the process, so a handle is never resolved in another room
+pub(crate) fn next_room_instance() -> u64 {
+    NEXT_ROOM_INSTANCE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A [ConnectionIndex] bound to the room it was taken from, see [Room::handle].
+///
+/// The handle does not borrow the room or keep the connection alive, so it can be moved into an async task that
+/// runs after the connection was destroyed. The task checks it with [ConnectionHandle::is_valid] or
+/// [ConnectionHandle::upgrade] instead of risking a panic in [Room::get]. Indices are generational, so a handle
+/// never refers to a newer connection that reused the index value, and it is never valid in another room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionHandle {
+    room: u64,
+    index: ConnectionIndex,
+}
+
+impl ConnectionHandle {
+    pub fn index(&self) -> ConnectionIndex {
+        self.index
+    }
+
+    /// True if `room` is the room the handle was taken from and the connection is still in it
+    pub fn is_valid<K: KnowledgeOrd>(&self, room: &Room<K>) -> bool {
+        self.room == room.instance && room.contains(self.index)
+    }
+
+    /// The connection, `None` if the handle is no longer valid in `room`
+    pub fn upgrade<'a, K: KnowledgeOrd>(&self, room: &'a Room<K>) -> Option<&'a Connection<K>> {
+        if self.room != room.instance {
+            return None;
+        }
+        room.connections.get(&self.index)
+    }
+
+    /// The connection, `None` if the handle is no longer valid in `room`
+    pub fn upgrade_mut<'a, K: KnowledgeOrd>(&self, room: &'a mut Room<K>) -> Option<&'a mut Connection<K>> {
+        if self.room != room.instance {
+            return None;
+        }
+        room.invalidate_view();
+        room.connections.get_mut(&self.index)
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// A handle for the connection that can be kept after the connection is gone, see [ConnectionHandle].
+    /// Fails like [Room::try_get] if the connection is not in the room
+    pub fn handle(&self, connection_index: ConnectionIndex) -> Result<ConnectionHandle, RoomError> {
+        self.validate_connection(connection_index)?;
+        Ok(ConnectionHandle {
+            room: self.instance,
+            index: connection_index,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::Knowledge;
+
+    use crate::{ConnectionIndex, Room, RoomError};
+
+    #[test]
+    fn detect_stale_handles() {
+        let mut room = Room::new();
+        let mut other = Room::new();
+        let now = Instant::now();
+        let connection = room.create_connection(now).unwrap();
+        let twin = other.create_connection(now).unwrap();
+        assert_eq!(connection, twin);
+
+        let handle = room.handle(connection).unwrap();
+        assert_eq!(handle.index(), connection);
+        assert!(handle.is_valid(&room));
+        assert_eq!(handle.upgrade(&room).unwrap().id, connection);
+        let view = room.view();
+        handle.upgrade_mut(&mut room).unwrap().knowledge = Knowledge(5);
+        assert_eq!(room.view().member(connection).unwrap().knowledge, 5);
+        assert_eq!(view.member(connection).unwrap().knowledge, 0);
+        assert!(!handle.is_valid(&other));
+        assert!(handle.upgrade(&other).is_none());
+
+        room.destroy_connection(connection).unwrap();
+        let reused = room.create_connection_with_id(ConnectionIndex::new(connection.value()), now).unwrap();
+        assert!(!handle.is_valid(&room));
+        assert!(handle.upgrade(&room).is_none());
+        assert_eq!(
+            room.handle(connection),
+            Err(RoomError::StaleHandle {
+                handle: connection,
+                current: reused
+            })
+        );
+    }
+}
@@ -0,0 +1,44 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+use crate::ConnectionIndex;
+
+/// Map keyed by connection index, looked up several times for every ping. The iteration order depends on the
+/// table layout, so anything that is decided, emitted or sent while iterating is sorted by index value (or ties
+/// broken by it) first, which keeps identical inputs producing identical events.
+pub(crate) type ConnectionMap<V> = HashMap<ConnectionIndex, V, BuildHasherDefault<ConnectionIndexHasher>>;
+
+/// Multiplicative hash for the two `u32`s of a [ConnectionIndex]. The indices are chosen by the room, or by the
+/// host, so there is no need for the flooding protection of the default hasher, which is many times slower.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ConnectionIndexHasher {
+    hash: u64,
+}
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+impl ConnectionIndexHasher {
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for ConnectionIndexHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.add(*byte as u64);
+        }
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.add(value as u64);
+    }
+}
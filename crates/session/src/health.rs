@@ -0,0 +1,146 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{ConnectionState, QualityAssessment, Room, RoomEvent};
+
+/// How well the room is doing, see [Room::health]. The parts range from 0.0 (sick) to 1.0 (healthy).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoomHealth {
+    /// The average of the parts, from 0 to 100
+    pub score: u8,
+    /// Share of the assessed connections with acceptable or good quality
+    pub quality: f32,
+    /// Zero without a leader, otherwise the share of voters that can reach the leader, divided by the number of
+    /// times the leader has changed in the last minute
+    pub leader_stability: f32,
+    /// Share of the connections within [crate::RoomConfig::knowledge_lag_threshold] of the leader, or within
+    /// [crate::RoomConfig::stability_knowledge_tolerance] if no lag threshold is set
+    pub knowledge_convergence: f32,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Scores the room from its connection assessments, how stable the leader is and how far the knowledge of the
+    /// connections has converged. Pending connections are not counted.
+    pub fn health(&self) -> RoomHealth {
+        let connections: Vec<_> = self
+            .connections
+            .values()
+            .filter(|connection| connection.state != ConnectionState::Pending)
+            .collect();
+
+        let assessed: Vec<QualityAssessment> = connections
+            .iter()
+            .map(|connection| connection.assessment())
+            .filter(|assessment| *assessment != QualityAssessment::NeedMoreInformation)
+            .collect();
+        let quality = share(assessed.len(), |index| assessed[index] != QualityAssessment::RecommendDisconnect);
+
+        let leader_stability = if self.leader_index.is_some() {
+            let changes = self.latest_time.map_or(0, |time| self.recent_leader_changes(time));
+            self.leader_reachability() / changes.max(1) as f32
+        } else {
+            0.0
+        };
+
+        let tolerance = self
+            .config
+            .knowledge_lag_threshold
+            .unwrap_or(self.config.stability_knowledge_tolerance);
+        let knowledge_convergence = share(connections.len(), |index| {
+            self.knowledge_lag(connections[index].id)
+                .is_none_or(|lag| lag <= tolerance)
+        });
+
+        let average = (quality + leader_stability + knowledge_convergence) / 3.0;
+        RoomHealth {
+            score: (average * 100.0).round() as u8,
+            quality,
+            leader_stability,
+            knowledge_convergence,
+        }
+    }
+
+    /// Emits an event for every [crate::RoomConfig::health_thresholds] the score has crossed since the last update
+    pub(crate) fn update_health(&mut self) {
+        if self.config.health_thresholds.is_empty() {
+            return;
+        }
+
+        let score = self.health().score;
+        let previous = self.health_score;
+        self.health_score = score;
+        let mut thresholds = self.config.health_thresholds.clone();
+        thresholds.sort_unstable();
+        thresholds.dedup();
+        for threshold in thresholds {
+            let was_healthy = previous >= threshold;
+            let is_healthy = score >= threshold;
+            if was_healthy == is_healthy {
+                continue;
+            }
+            info!("health of room {} went from {} to {}, crossing {}", self.id, previous, score, threshold);
+            self.push_event(if is_healthy {
+                RoomEvent::HealthRecovered { score, threshold }
+            } else {
+                RoomEvent::HealthDegraded { score, threshold }
+            });
+        }
+    }
+}
+
+/// Share of `count` items that `is_healthy`, 1.0 if there are no items
+fn share(count: usize, is_healthy: impl Fn(usize) -> bool) -> f32 {
+    if count == 0 {
+        return 1.0;
+    }
+    (0..count).filter(|index| is_healthy(*index)).count() as f32 / count as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::Knowledge;
+
+    use crate::{PingPayload, Room, RoomConfig, RoomEvent};
+
+    fn health_events(room: &mut Room) -> Vec<RoomEvent> {
+        room.drain_events()
+            .into_iter()
+            .filter(|event| matches!(event, RoomEvent::HealthDegraded { .. } | RoomEvent::HealthRecovered { .. }))
+            .collect()
+    }
+
+    #[test]
+    fn degrade_and_recover_health() {
+        let mut room = RoomConfig::new()
+            .with_knowledge_lag_threshold(100)
+            .with_health_thresholds(vec![90, 50])
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        assert_eq!(room.health().score, 100);
+
+        let term = room.term;
+        let ping = |knowledge| PingPayload::new().with_term(term).with_knowledge(Knowledge(knowledge));
+        room.on_ping(leader, &ping(1000), now);
+        room.on_ping(follower, &ping(0), now);
+        room.update(now);
+        let health = room.health();
+        assert_eq!(health.knowledge_convergence, 0.5);
+        assert_eq!(health.score, 83);
+        assert_eq!(health_events(&mut room), vec![RoomEvent::HealthDegraded { score: 83, threshold: 90 }]);
+
+        let later = now + Duration::from_millis(100);
+        room.on_ping(follower, &ping(950), later);
+        room.update(later);
+        assert_eq!(room.health().score, 100);
+        assert_eq!(health_events(&mut room), vec![RoomEvent::HealthRecovered { score: 100, threshold: 90 }]);
+    }
+}
@@ -0,0 +1,90 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+
+use crate::{Instant, Room, RoomEvent};
+
+/// A [RoomEvent] kept in the history of the room, see [Room::recent_events]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedEvent {
+    /// The latest time the room had been given when the event happened, `None` if it had not been given any
+    pub at: Option<Instant>,
+    pub event: RoomEvent,
+    /// Tags of the [RoomEvent::connection] when the event happened, empty if it had left the room
+    pub tags: Vec<String>,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// The last [crate::RoomConfig::event_history] events, oldest first. They are kept even after
+    /// [Room::drain_events] has taken them.
+    pub fn recent_events(&self) -> impl DoubleEndedIterator<Item = &TimedEvent> {
+        self.event_history.iter()
+    }
+
+    /// The events in the history that happened at or after `since`, e.g. `now - Duration::from_secs(30)`
+    pub fn recent_events_since(&self, since: Instant) -> impl DoubleEndedIterator<Item = &TimedEvent> {
+        self.event_history
+            .iter()
+            .filter(move |timed| timed.at.is_some_and(|at| at >= since))
+    }
+
+    /// Queues the event for [Room::drain_events] and adds it to the history
+    pub(crate) fn push_event(&mut self, event: RoomEvent) {
+        if self.config.event_history > 0 {
+            self.event_history.push_back(TimedEvent {
+                at: self.latest_time,
+                event: event.clone(),
+                tags: self.tags_of(event.connection()),
+            });
+            self.trim_event_history();
+        }
+        self.notify_observer(&event);
+        if self.config.event_filter.allows(&event) {
+            self.events.push(event);
+        }
+    }
+
+    pub(crate) fn trim_event_history(&mut self) {
+        while self.event_history.len() > self.config.event_history {
+            self.event_history.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn keep_last_events_after_drain() {
+        let mut room = RoomConfig::new().with_event_history(4).build().unwrap();
+        let now = Instant::now();
+        let first = room.create_connection(now).unwrap();
+        let second = room.create_connection(now).unwrap();
+        room.drain_events();
+
+        let later = now + Duration::from_millis(500);
+        room.on_ping(second, &PingPayload::new(), later);
+        room.destroy_connection(first).unwrap();
+        assert_eq!(room.recent_events().count(), 4);
+        assert_eq!(room.recent_events().next().unwrap().at, Some(now));
+
+        let recent: Vec<_> = room.recent_events_since(later).collect();
+        assert_eq!(recent.len(), 3);
+        assert_eq!(recent[0].at, Some(later));
+        assert_eq!(
+            recent[0].event,
+            RoomEvent::LeaderChanged {
+                term: room.term,
+                leader: Some(second),
+            }
+        );
+
+        room.update_config(RoomConfig::new().with_event_history(0)).unwrap();
+        assert_eq!(room.recent_events().count(), 0);
+    }
+}
@@ -0,0 +1,170 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::recorder::RecordedInput;
+use crate::{ConnectionIndex, ConnectionPriority, Instant, Room, RoomError, RoomEvent};
+
+/// What [Room::create_connection_with_identity] does when the identity already has a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DuplicateIdentity {
+    /// Returns [RoomError::IdentityInUse]
+    #[default]
+    Reject,
+    /// Replaces the previous connection, e.g. when a player reconnects before the room has noticed that the old
+    /// connection is gone. The new connection inherits the knowledge, group and tags of the previous one, which is
+    /// destroyed within the same call so the two never count as separate voters. Followed by
+    /// [RoomEvent::ConnectionReplaced]
+    Replace,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Adds a connection for a participant the host knows as `identity`, e.g. a player id or an account, so it can
+    /// be found with [Room::find_by_identity] until it is destroyed. Identities that are not strings, like a `u64`
+    /// or opaque bytes, can be formatted or hex encoded.
+    ///
+    /// If the identity already has a connection, preregistered or not, [crate::RoomConfig::duplicate_identity]
+    /// decides if [RoomError::IdentityInUse] is returned or the previous connection is replaced.
+    pub fn create_connection_with_identity(
+        &mut self,
+        identity: &str,
+        time: Instant,
+    ) -> Result<ConnectionIndex, RoomError> {
+        self.record(
+            time,
+            RecordedInput::CreateConnectionWithIdentity {
+                identity: identity.to_string(),
+            },
+        );
+        let time = self.observe_time(time);
+        self.ensure_open()?;
+        let created = self.create_identified(identity, ConnectionPriority::default(), time);
+        self.publish_view();
+        created
+    }
+
+    /// Creates the connection for [Room::create_connection_with_identity] and [Room::admit_with_eviction]
+    pub(crate) fn create_identified(
+        &mut self,
+        identity: &str,
+        priority: ConnectionPriority,
+        time: Instant,
+    ) -> Result<ConnectionIndex, RoomError> {
+        let previous = self.find_by_identity(identity);
+        match previous {
+            Some(previous) if self.config.duplicate_identity == DuplicateIdentity::Reject => {
+                return Err(RoomError::IdentityInUse(previous));
+            }
+            Some(_) => {}
+            None => self.ensure_capacity()?,
+        }
+        let value = self.find_unique_connection_value()?;
+        self.id = ConnectionIndex::new(value);
+        let mut connection = self.new_connection(value, time);
+        connection.identity = Some(identity.to_string());
+        connection.priority = priority;
+        if let Some(previous) = previous.and_then(|previous| self.connections.get(&previous)) {
+            connection.knowledge = previous.knowledge;
+            connection.group = previous.group;
+            connection.tags = previous.tags.clone();
+        }
+        let connection_id = connection.id;
+        info!("create connection {} for '{}'", connection, identity);
+        self.insert_connection(connection);
+        self.admit_connection(connection_id);
+
+        if let Some(previous) = previous {
+            // Removed before returning, so the room never counts both. If it led, the new connection is a candidate
+            info!("{} replaces {} for '{}'", connection_id, previous, identity);
+            self.remove_connection(previous);
+            self.push_event(RoomEvent::ConnectionReplaced {
+                connection: connection_id,
+                previous,
+            });
+        }
+        self.assert_invariants();
+        Ok(connection_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::Knowledge;
+
+    use crate::{DuplicateIdentity, GroupId, PingPayload, Room, RoomConfig, RoomError, RoomEvent};
+
+    #[test]
+    fn reject_duplicate_identity() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let first = room.create_connection_with_identity("1001", now).unwrap();
+        assert_eq!(room.find_by_identity("1001"), Some(first));
+        assert_eq!(room.create_connection_with_identity("1001", now), Err(RoomError::IdentityInUse(first)));
+        assert_eq!(room.preregister_connection("1001", now), Err(RoomError::IdentityInUse(first)));
+        assert_eq!(room.connections().len(), 1);
+
+        room.destroy_connection(first).unwrap();
+        assert_eq!(room.find_by_identity("1001"), None);
+        let second = room.create_connection_with_identity("1001", now).unwrap();
+        assert_eq!(room.find_by_identity("1001"), Some(second));
+    }
+
+    #[test]
+    fn replace_connection_with_same_identity() {
+        let mut room = Room::new_with_config(RoomConfig::new().with_duplicate_identity(DuplicateIdentity::Replace));
+        let now = Instant::now();
+        let leader = room.create_connection_with_identity("1001", now).unwrap();
+        let follower = room.create_connection_with_identity("1002", now).unwrap();
+        room.drain_events();
+
+        let reconnected = room.create_connection_with_identity("1001", now).unwrap();
+
+        assert_eq!(room.find_by_identity("1001"), Some(reconnected));
+        assert_eq!(room.find_by_identity("1002"), Some(follower));
+        assert_eq!(room.connections().len(), 2);
+        assert_eq!(room.leader(), Some(follower));
+        assert!(room.drain_events().contains(&RoomEvent::ConnectionReplaced {
+            connection: reconnected,
+            previous: leader,
+        }));
+
+        room.destroy_connection(follower).unwrap();
+        let alone = room.create_connection_with_identity("1001", now).unwrap();
+        assert_eq!(room.connections().len(), 1);
+        assert_eq!(room.leader(), Some(alone));
+    }
+
+    #[test]
+    fn rejoining_player_inherits_knowledge_of_zombie() {
+        let mut room = Room::new_with_config(RoomConfig::new().with_duplicate_identity(DuplicateIdentity::Replace));
+        let mut now = Instant::now();
+        let leader = room.create_connection_with_identity("1001", now).unwrap();
+        let zombie = room.create_connection_with_identity("1002", now).unwrap();
+        room.set_group(zombie, Some(GroupId(3))).unwrap();
+        for _ in 0..10 {
+            now += Duration::from_millis(100);
+            room.on_ping(leader, &PingPayload::new().with_term(room.term()).with_knowledge(Knowledge(10)), now);
+            room.on_ping(zombie, &PingPayload::new().with_term(room.term()).with_knowledge(Knowledge(42)), now);
+        }
+        let term = room.term();
+
+        let rejoined = room.create_connection_with_identity("1002", now).unwrap();
+
+        assert!(room.try_get(zombie).is_err());
+        assert_eq!(room.connections().len(), 2);
+        assert_eq!(room.get(rejoined).knowledge, Knowledge(42));
+        assert_eq!(room.get(rejoined).group(), Some(GroupId(3)));
+        assert_eq!(room.leader(), Some(leader));
+        assert_eq!(room.term(), term);
+    }
+}
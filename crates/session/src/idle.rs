@@ -0,0 +1,104 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{ConnectionIndex, Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Connections whose players have not given any input for [crate::RoomConfig::idle_after]
+    pub fn idle_connections(&self) -> Vec<ConnectionIndex> {
+        let mut idle: Vec<ConnectionIndex> = self
+            .connections
+            .values()
+            .filter(|connection| connection.is_idle)
+            .map(|connection| connection.id)
+            .collect();
+        idle.sort_by_key(|index| index.value());
+        idle
+    }
+
+    /// Emits an event if the reported input age made the connection go idle, or return from being idle.
+    /// Pings that do not report an input age leave the connection as it is.
+    pub(crate) fn update_idle(&mut self, connection_index: ConnectionIndex, last_input_age: Option<Duration>) {
+        let (Some(idle_after), Some(last_input_age)) = (self.config.idle_after, last_input_age) else {
+            return;
+        };
+
+        let connection = self.connections.get_mut(&connection_index).unwrap();
+        let is_idle = last_input_age > idle_after;
+        if is_idle == connection.is_idle {
+            return;
+        }
+        connection.is_idle = is_idle;
+        info!("{} is {}", connection, if is_idle { "idle" } else { "active again" });
+        self.push_event(if is_idle {
+            RoomEvent::WentIdle {
+                connection: connection_index,
+            }
+        } else {
+            RoomEvent::ReturnedFromIdle {
+                connection: connection_index,
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{ConnectionToLeader, Knowledge};
+
+    use crate::{PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn going_idle_and_returning() {
+        let mut room = RoomConfig::new().with_idle_after(Duration::from_secs(30)).build().unwrap();
+        let now = Instant::now();
+        let connection = room.create_connection(now).unwrap();
+
+        room.on_ping(connection, &PingPayload::new().with_last_input_age(Duration::from_secs(10)), now);
+        assert!(!room.get(connection).is_idle());
+
+        room.drain_events();
+        room.on_ping(connection, &PingPayload::new().with_last_input_age(Duration::from_secs(31)), now);
+        assert!(room.get(connection).is_idle());
+        assert_eq!(room.idle_connections(), vec![connection]);
+        assert_eq!(room.drain_events(), vec![RoomEvent::WentIdle { connection }]);
+
+        // Not reporting activity does not change anything
+        room.on_ping(connection, &PingPayload::new(), now);
+        assert!(room.get(connection).is_idle());
+
+        room.on_ping(connection, &PingPayload::new().with_last_input_age(Duration::ZERO), now);
+        assert!(!room.get(connection).is_idle());
+        assert_eq!(room.drain_events(), vec![RoomEvent::ReturnedFromIdle { connection }]);
+    }
+
+    #[test]
+    fn idle_connection_is_not_preferred_as_leader() {
+        let mut room = RoomConfig::new()
+            .with_idle_after(Duration::from_secs(30))
+            .with_deprioritize_idle_in_election(true)
+            .build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let active = room.create_connection(now).unwrap();
+        let idle = room.create_connection(now).unwrap();
+
+        let ping = PingPayload::new().with_connection_to_leader(ConnectionToLeader::Connected);
+        room.on_ping(active, &ping.clone().with_knowledge(Knowledge(10)).with_last_input_age(Duration::ZERO), now);
+        room.on_ping(idle, &ping.with_knowledge(Knowledge(100)).with_last_input_age(Duration::from_secs(60)), now);
+
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader_index, Some(active));
+
+        room.destroy_connection(active).unwrap();
+        assert_eq!(room.leader_index, Some(idle));
+    }
+}
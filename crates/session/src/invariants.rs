@@ -0,0 +1,145 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::fmt;
+
+use conclave_types::{KnowledgeOrd, Term};
+
+use crate::{ConnectionIndex, ConnectionState, Room};
+
+/// A property of [Room] that should always hold, but didn't. See [Room::check_invariants]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvariantViolation {
+    /// `leader_index` refers to a connection that is not in the room
+    LeaderMissing { leader: ConnectionIndex },
+    /// The term is lower than it has been after an earlier change to the room
+    TermDecreased { previous: Term, current: Term },
+    /// A connection is stored under a different index than its own id
+    MismatchedConnectionId { key: ConnectionIndex, id: ConnectionIndex },
+    /// A state sync is pending for a connection that is not in the room
+    OrphanedStateSync { receiver: ConnectionIndex },
+    /// The leader is [crate::ConnectionState::Disconnected]
+    DisconnectedLeader { leader: ConnectionIndex },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvariantViolation::LeaderMissing { leader } => write!(f, "leader {} is not in the room", leader),
+            InvariantViolation::TermDecreased { previous, current } => {
+                write!(f, "term decreased from {} to {}", previous, current)
+            }
+            InvariantViolation::MismatchedConnectionId { key, id } => {
+                write!(f, "connection {} is stored as {}", id, key)
+            }
+            InvariantViolation::OrphanedStateSync { receiver } => {
+                write!(f, "state sync pending for {} which is not in the room", receiver)
+            }
+            InvariantViolation::DisconnectedLeader { leader } => write!(f, "leader {} is disconnected", leader),
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Verifies the internal consistency of the room, returning the first violation found.
+    ///
+    /// Intended for fuzzers and property tests. With the `invariants` feature enabled, the room also
+    /// checks itself after every mutation and panics on a violation.
+    ///
+    /// A leader is never disconnected for bad quality, it is replaced first or, if there is no one to replace it
+    /// with, stays connected (see [crate::RoomConfig::allowed_to_remove_single_leader]).
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        if self.term.value() < self.highest_term.value() {
+            return Err(InvariantViolation::TermDecreased {
+                previous: self.highest_term,
+                current: self.term,
+            });
+        }
+
+        if let Some(leader) = self.leader_index {
+            match self.connections.get(&leader) {
+                None => return Err(InvariantViolation::LeaderMissing { leader }),
+                Some(connection) if connection.state == ConnectionState::Disconnected => {
+                    return Err(InvariantViolation::DisconnectedLeader { leader });
+                }
+                Some(_) => {}
+            }
+        }
+
+        for (key, connection) in &self.connections {
+            if *key != connection.id {
+                return Err(InvariantViolation::MismatchedConnectionId {
+                    key: *key,
+                    id: connection.id,
+                });
+            }
+        }
+
+        for sync in &self.state_syncs {
+            if !self.connections.contains_key(&sync.receiver) {
+                return Err(InvariantViolation::OrphanedStateSync { receiver: sync.receiver });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Panics on an invariant violation, but only with the `invariants` feature enabled. Called after every
+    /// mutation, so it also keeps track of the highest term for [InvariantViolation::TermDecreased]
+    pub(crate) fn assert_invariants(&mut self) {
+        #[cfg(feature = "invariants")]
+        if let Err(violation) = self.check_invariants() {
+            panic!("room invariant violated: {}", violation);
+        }
+        if self.term.value() > self.highest_term.value() {
+            self.highest_term = self.term;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::Term;
+
+    use crate::invariants::InvariantViolation;
+    use crate::{ConnectionIndex, ConnectionState, Room};
+
+    #[test]
+    fn detect_violations() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        assert_eq!(room.check_invariants(), Ok(()));
+
+        room.leader_index = Some(ConnectionIndex::new(99));
+        assert_eq!(
+            room.check_invariants(),
+            Err(InvariantViolation::LeaderMissing {
+                leader: ConnectionIndex::new(99)
+            })
+        );
+
+        room.leader_index = Some(leader);
+        room.get_mut(leader).state = ConnectionState::Disconnected;
+        assert_eq!(room.check_invariants(), Err(InvariantViolation::DisconnectedLeader { leader }));
+
+        room.get_mut(leader).state = ConnectionState::Online;
+        room.term = Term(0);
+        assert_eq!(
+            room.check_invariants(),
+            Err(InvariantViolation::TermDecreased {
+                previous: Term(1),
+                current: Term(0)
+            })
+        );
+        assert!(room.check_invariants().is_err());
+
+        room.term = Term(1);
+        assert_eq!(room.check_invariants(), Ok(()));
+    }
+}
@@ -0,0 +1,274 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::recorder::RecordedInput;
+use crate::{ConnectionIndex, Instant, KickReason, Room, RoomError, RoomEvent};
+
+/// A vote on the open kick proposal, sent in [crate::PingPayload::kick_vote]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KickBallot {
+    /// [KickProposal::id] of the proposal, ballots for any other proposal are ignored
+    pub proposal: u32,
+    pub approve: bool,
+}
+
+/// How a kick vote ended, see [RoomEvent::KickVoteResolved]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KickVoteOutcome {
+    /// The quorum approved, and the target was kicked with [KickReason::Voted]
+    Approved,
+    /// Enough voters rejected that the quorum can not be reached
+    Rejected,
+    /// The quorum was not reached within [crate::RoomConfig::kick_vote_duration]
+    Expired,
+    /// The target left the room before the vote ended
+    Withdrawn,
+}
+
+/// A vote to kick a connection, see [Room::propose_kick]. The votes are sorted by connection index
+#[derive(Debug, Clone, PartialEq)]
+pub struct KickProposal {
+    pub id: u32,
+    pub target: ConnectionIndex,
+    pub proposer: ConnectionIndex,
+    /// None if [crate::RoomConfig::kick_vote_duration] is too long to end at a time that can be represented
+    pub ends_at: Option<Instant>,
+    pub approvals: Vec<ConnectionIndex>,
+    pub rejections: Vec<ConnectionIndex>,
+}
+
+impl KickProposal {
+    /// Replaces any earlier vote of `voter`
+    fn cast(&mut self, voter: ConnectionIndex, approve: bool) {
+        self.approvals.retain(|index| *index != voter);
+        self.rejections.retain(|index| *index != voter);
+        let votes = if approve { &mut self.approvals } else { &mut self.rejections };
+        let position = votes.partition_point(|index| index.value() < voter.value());
+        votes.insert(position, voter);
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Opens a vote to kick `target`, approved by `proposer`, and returns the [KickProposal::id]. The other
+    /// connections vote by sending a [KickBallot] in [crate::PingPayload::kick_vote].
+    ///
+    /// The voters are the connections that vote in elections, apart from the target. When more than
+    /// [crate::RoomConfig::kick_vote_quorum] of them approve within [crate::RoomConfig::kick_vote_duration], the
+    /// target is kicked with [KickReason::Voted]. Emits [RoomEvent::KickProposed], and [RoomEvent::KickVoteResolved]
+    /// when the vote ends. Only one vote is open at a time, and the leader is down-voted instead, so it returns
+    /// [RoomError::NotKickable] for the leader and for the proposer itself.
+    pub fn propose_kick(
+        &mut self,
+        target: ConnectionIndex,
+        proposer: ConnectionIndex,
+        time: Instant,
+    ) -> Result<u32, RoomError> {
+        self.record(
+            time,
+            RecordedInput::ProposeKick {
+                target: target.value(),
+                target_generation: target.generation(),
+                proposer: proposer.value(),
+                proposer_generation: proposer.generation(),
+            },
+        );
+        let time = self.observe_time(time);
+        self.validate_connection(target)?;
+        self.validate_connection(proposer)?;
+        if self.leader_index == Some(target) || target == proposer {
+            return Err(RoomError::NotKickable(target));
+        }
+        if let Some(open) = &self.kick_vote {
+            return Err(RoomError::KickVoteInProgress(open.target));
+        }
+        self.kick_proposals = self.kick_proposals.wrapping_add(1);
+        let id = self.kick_proposals;
+        info!("{} proposes to kick {} (proposal {})", proposer, target, id);
+        self.kick_vote = Some(KickProposal {
+            id,
+            target,
+            proposer,
+            ends_at: time.checked_add(self.config.kick_vote_duration),
+            approvals: vec![proposer],
+            rejections: Vec::new(),
+        });
+        self.push_event(RoomEvent::KickProposed {
+            proposal: id,
+            target,
+            proposer,
+        });
+        self.resolve_kick_vote(time);
+        self.publish_view();
+        Ok(id)
+    }
+
+    /// The open kick vote, if any
+    pub fn kick_proposal(&self) -> Option<&KickProposal> {
+        self.kick_vote.as_ref()
+    }
+
+    pub(crate) fn cast_kick_ballot(&mut self, voter: ConnectionIndex, ballot: Option<KickBallot>) {
+        let (Some(ballot), Some(proposal)) = (ballot, &mut self.kick_vote) else {
+            return;
+        };
+        if ballot.proposal != proposal.id || voter == proposal.target {
+            return;
+        }
+        proposal.cast(voter, ballot.approve);
+    }
+
+    /// Ends the open vote if the quorum has approved, can no longer be reached, or the vote has expired
+    pub(crate) fn resolve_kick_vote(&mut self, time: Instant) {
+        let Some(proposal) = &self.kick_vote else {
+            return;
+        };
+        let voters: Vec<ConnectionIndex> = self
+            .connections
+            .values()
+            .filter(|connection| connection.takes_part_in_election() && connection.id != proposal.target)
+            .map(|connection| connection.id)
+            .collect();
+        let counted = |votes: &[ConnectionIndex]| votes.iter().filter(|index| voters.contains(index)).count();
+        let needed = (voters.len() as f32 * self.config.kick_vote_quorum).floor() as usize + 1;
+        let outcome = if counted(&proposal.approvals) >= needed {
+            KickVoteOutcome::Approved
+        } else if voters.len() - counted(&proposal.rejections) < needed {
+            KickVoteOutcome::Rejected
+        } else if proposal.ends_at.is_some_and(|ends_at| time >= ends_at) {
+            KickVoteOutcome::Expired
+        } else {
+            return;
+        };
+        let proposal = self.kick_vote.take().unwrap();
+        info!("kick vote {} against {} ended: {:?}", proposal.id, proposal.target, outcome);
+        if outcome == KickVoteOutcome::Approved {
+            self.kick(proposal.target, KickReason::Voted);
+        }
+        self.push_event(RoomEvent::KickVoteResolved {
+            proposal: proposal.id,
+            target: proposal.target,
+            outcome,
+        });
+    }
+
+    /// Ends the open vote with [KickVoteOutcome::Withdrawn] if `connection_index` is its target
+    pub(crate) fn withdraw_kick_vote(&mut self, connection_index: ConnectionIndex) {
+        if self.kick_vote.as_ref().is_some_and(|proposal| proposal.target == connection_index) {
+            let proposal = self.kick_vote.take().unwrap();
+            self.push_event(RoomEvent::KickVoteResolved {
+                proposal: proposal.id,
+                target: proposal.target,
+                outcome: KickVoteOutcome::Withdrawn,
+            });
+        }
+    }
+
+    /// Moves the deadline of the open vote forward, so `duration` does not count towards it
+    pub(crate) fn shift_kick_vote(&mut self, duration: Duration) {
+        if let Some(proposal) = &mut self.kick_vote {
+            proposal.ends_at = proposal.ends_at.and_then(|ends_at| ends_at.checked_add(duration));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::outgoing::{Outgoing, OutgoingIntent};
+    use crate::{KickBallot, KickReason, KickVoteOutcome, PingPayload, Room, RoomError, RoomEvent};
+
+    #[test]
+    fn kick_when_quorum_approves() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let proposer = room.create_connection(now).unwrap();
+        let undecided = room.create_connection(now).unwrap();
+        let target = room.create_connection(now).unwrap();
+        for connection in [leader, proposer, undecided, target] {
+            room.on_ping(connection, &PingPayload::new(), now);
+        }
+        assert_eq!(room.propose_kick(leader, proposer, now), Err(RoomError::NotKickable(leader)));
+        room.drain_outgoing();
+        room.drain_events();
+
+        let proposal = room.propose_kick(target, proposer, now).unwrap();
+        assert_eq!(room.propose_kick(undecided, leader, now), Err(RoomError::KickVoteInProgress(target)));
+        let vote = |approve| {
+            PingPayload::new().with_kick_vote(KickBallot {
+                proposal,
+                approve,
+            })
+        };
+        // The target can not vote for itself, and one approval out of three voters is not enough
+        room.on_ping(target, &vote(false), now);
+        room.on_ping(undecided, &vote(false), now);
+        assert_eq!(room.kick_proposal().unwrap().rejections, vec![undecided]);
+
+        // Changing the vote replaces the earlier one
+        room.on_ping(undecided, &vote(true), now + Duration::from_millis(100));
+
+        assert!(room.kick_proposal().is_none());
+        assert!(room.try_get(target).is_err());
+        assert!(room.drain_outgoing().contains(&Outgoing {
+            connection: target,
+            intent: OutgoingIntent::YouWereKicked {
+                reason: KickReason::Voted
+            },
+        }));
+        let events = room.drain_events();
+        assert_eq!(
+            events[0],
+            RoomEvent::KickProposed {
+                proposal,
+                target,
+                proposer
+            }
+        );
+        assert!(events.ends_with(&[
+            RoomEvent::Kicked {
+                connection: target,
+                reason: KickReason::Voted
+            },
+            RoomEvent::KickVoteResolved {
+                proposal,
+                target,
+                outcome: KickVoteOutcome::Approved
+            },
+        ]));
+    }
+
+    #[test]
+    fn expire_or_withdraw_vote() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let proposer = room.create_connection(now).unwrap();
+        let target = room.create_connection(now).unwrap();
+        let _silent = room.create_connection(now).unwrap();
+
+        let first = room.propose_kick(target, proposer, now).unwrap();
+        room.update(now + room.config.kick_vote_duration);
+        assert!(room.drain_events().contains(&RoomEvent::KickVoteResolved {
+            proposal: first,
+            target,
+            outcome: KickVoteOutcome::Expired
+        }));
+
+        let second = room.propose_kick(target, leader, now + room.config.kick_vote_duration).unwrap();
+        room.destroy_connection(target).unwrap();
+        assert!(room.kick_proposal().is_none());
+        assert!(room.drain_events().contains(&RoomEvent::KickVoteResolved {
+            proposal: second,
+            target,
+            outcome: KickVoteOutcome::Withdrawn
+        }));
+    }
+}
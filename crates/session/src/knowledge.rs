@@ -0,0 +1,335 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::cmp::Ordering;
+
+use conclave_types::{Knowledge, KnowledgeOrd};
+use log::info;
+
+use crate::{ConnectionIndex, ConnectionState, Instant, Room, RoomEvent};
+
+/// Why a reported knowledge was considered suspicious.
+///
+/// Knowledge is represented by its [KnowledgeOrd::progress], so the reason is the same for every knowledge type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuspicionReason {
+    /// The connection reported less knowledge than it did before
+    Regression { previous: Knowledge },
+    /// The connection claims to be further ahead of the leader than [crate::RoomConfig::knowledge_lead_tolerance] allows
+    AheadOfLeader { leader: Knowledge },
+    /// The connection echoed another [crate::PingPayload::knowledge_checksum] than the leader sent for the same
+    /// knowledge
+    ChecksumMismatch { expected: u64 },
+}
+
+/// The knowledge of the online connections, see [Room::knowledge_spread]. Knowledge is represented by its
+/// [KnowledgeOrd::progress].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnowledgeSpread {
+    pub min: u64,
+    pub max: u64,
+    /// The lower of the two middle values if the number of connections is even
+    pub median: u64,
+    /// Online connections more than [crate::RoomConfig::convergence_tolerance] behind the leader, or behind the
+    /// most knowledgeable connection if there is no leader. Sorted by connection index.
+    pub behind: Vec<ConnectionIndex>,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// How spread out the knowledge of the online connections is, `None` if no connection is online
+    pub fn knowledge_spread(&self) -> Option<KnowledgeSpread> {
+        let mut online: Vec<(ConnectionIndex, u64)> = self
+            .connections
+            .values()
+            .filter(|connection| connection.state == ConnectionState::Online)
+            .map(|connection| (connection.id, connection.knowledge.progress()))
+            .collect();
+        online.sort_by_key(|(index, _)| index.value());
+
+        let mut progress: Vec<u64> = online.iter().map(|(_, progress)| *progress).collect();
+        progress.sort_unstable();
+        let min = *progress.first()?;
+        let max = *progress.last()?;
+        let median = progress[(progress.len() - 1) / 2];
+
+        let reference = self
+            .leader_index
+            .and_then(|leader| self.connections.get(&leader))
+            .map_or(max, |leader| leader.knowledge.progress());
+        let tolerance = self.config.convergence_tolerance;
+        let behind = online
+            .into_iter()
+            .filter(|(_, progress)| reference.saturating_sub(*progress) > tolerance)
+            .map(|(index, _)| index)
+            .collect();
+
+        Some(KnowledgeSpread {
+            min,
+            max,
+            median,
+            behind,
+        })
+    }
+
+    /// How far behind the leader's knowledge the connection is, `None` if there is no leader
+    pub fn knowledge_lag(&self, connection_index: ConnectionIndex) -> Option<u64> {
+        let leader_knowledge = self.connections.get(&self.leader_index?)?.knowledge;
+        let connection = self.connections.get(&connection_index)?;
+        Some(leader_knowledge.progress().saturating_sub(connection.knowledge.progress()))
+    }
+
+    /// Connections lagging more than [crate::RoomConfig::knowledge_lag_threshold] behind the leader, furthest behind first
+    pub fn laggards(&self) -> Vec<(ConnectionIndex, u64)> {
+        let Some(threshold) = self.config.knowledge_lag_threshold else {
+            return Vec::new();
+        };
+
+        let mut laggards: Vec<(ConnectionIndex, u64)> = self
+            .connections
+            .keys()
+            .filter_map(|index| self.knowledge_lag(*index).map(|delta| (*index, delta)))
+            .filter(|(_, delta)| *delta > threshold)
+            .collect();
+        laggards.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.value().cmp(&b.0.value())));
+        laggards
+    }
+
+    /// Emits events for connections that started lagging behind, or caught up with, the leader
+    pub(crate) fn update_knowledge_lag(&mut self) {
+        let Some(threshold) = self.config.knowledge_lag_threshold else {
+            return;
+        };
+
+        let lags: Vec<(ConnectionIndex, u64)> = self
+            .connections
+            .keys()
+            .map(|index| (*index, self.knowledge_lag(*index).unwrap_or(0)))
+            .collect();
+
+        for (connection_index, delta) in lags {
+            let connection = self.connections.get_mut(&connection_index).unwrap();
+            let is_lagging = delta > threshold;
+            if is_lagging == connection.is_lagging {
+                continue;
+            }
+            connection.is_lagging = is_lagging;
+            self.push_event(if is_lagging {
+                RoomEvent::KnowledgeLagging {
+                    connection: connection_index,
+                    delta,
+                }
+            } else {
+                RoomEvent::KnowledgeCaughtUp {
+                    connection: connection_index,
+                }
+            });
+        }
+    }
+
+    /// Compares the knowledge reported by a connection with what it has reported before and what the leader knows.
+    /// Must be called before the knowledge is applied to the connection.
+    pub(crate) fn check_reported_knowledge(&mut self, connection_index: ConnectionIndex, reported: K, time: Instant) {
+        let connection = &self.connections[&connection_index];
+
+        let reason = if connection.last_reported_term.is_some()
+            && reported.cmp_knowledge(&connection.knowledge) == Ordering::Less
+        {
+            Some(SuspicionReason::Regression {
+                previous: Knowledge(connection.knowledge.progress()),
+            })
+        } else {
+            self.knowledge_ahead_of_leader(connection_index, reported)
+        };
+
+        if let Some(reason) = reason {
+            self.report_suspicious_knowledge(connection_index, reported, reason, time);
+        }
+    }
+
+    /// Adds to the suspicion score of the connection and emits [RoomEvent::SuspiciousKnowledge]
+    pub(crate) fn report_suspicious_knowledge(
+        &mut self,
+        connection_index: ConnectionIndex,
+        reported: K,
+        reason: SuspicionReason,
+        time: Instant,
+    ) {
+        let connection = self.connections.get_mut(&connection_index).unwrap();
+        connection.suspicion_score += 1;
+        connection.suspected_at = Some(time);
+        info!("suspicious knowledge {} reported by {}: {:?}", reported, connection, reason);
+        self.push_event(RoomEvent::SuspiciousKnowledge {
+            connection: connection_index,
+            reported: Knowledge(reported.progress()),
+            reason,
+        });
+    }
+
+    /// Forgives one point of suspicion for every [crate::RoomConfig::suspicion_decay] since the score was last
+    /// raised or decayed
+    pub(crate) fn decay_suspicion(&mut self, connection_index: ConnectionIndex, time: Instant) {
+        let Some(decay) = self.config.suspicion_decay else {
+            return;
+        };
+        let connection = self.connections.get_mut(&connection_index).unwrap();
+        if connection.suspicion_score == 0 {
+            return;
+        }
+        // A score restored from a snapshot starts to decay from the first ping
+        let suspected_at = *connection.suspected_at.get_or_insert(time);
+        let periods = (time.saturating_duration_since(suspected_at).as_nanos() / decay.as_nanos()) as u32;
+        if periods == 0 {
+            return;
+        }
+        connection.suspicion_score = connection.suspicion_score.saturating_sub(periods);
+        connection.suspected_at = Some(suspected_at + decay * periods);
+        info!("suspicion of {} decayed to {}", connection_index, connection.suspicion_score);
+    }
+
+    /// Not checked until the leader has pinged, the knowledge it was created with says nothing
+    fn knowledge_ahead_of_leader(&self, connection_index: ConnectionIndex, reported: K) -> Option<SuspicionReason> {
+        let tolerance = self.config.knowledge_lead_tolerance?;
+        let leader_index = self.leader_index.filter(|leader_index| *leader_index != connection_index)?;
+        let leader = self.connections.get(&leader_index)?;
+        leader.last_reported_term?;
+        let leader_knowledge = leader.knowledge;
+
+        if reported.progress() > leader_knowledge.progress().saturating_add(tolerance) {
+            Some(SuspicionReason::AheadOfLeader {
+                leader: Knowledge(leader_knowledge.progress()),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::Knowledge;
+
+    use crate::knowledge::SuspicionReason;
+    use crate::{PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn lagging_and_catching_up() {
+        let mut room = RoomConfig::new().with_knowledge_lag_threshold(10).build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        room.drain_events();
+
+        room.on_ping(follower, &PingPayload::new().with_knowledge(Knowledge(5)), now);
+        room.on_ping(leader, &PingPayload::new().with_knowledge(Knowledge(30)), now);
+        assert_eq!(room.laggards(), vec![(follower, 25)]);
+
+        room.on_ping(follower, &PingPayload::new().with_knowledge(Knowledge(25)), now);
+        assert!(room.laggards().is_empty());
+        assert_eq!(room.knowledge_lag(follower), Some(5));
+
+        assert_eq!(
+            room.drain_events(),
+            vec![
+                RoomEvent::KnowledgeLagging {
+                    connection: follower,
+                    delta: 25
+                },
+                RoomEvent::KnowledgeCaughtUp { connection: follower },
+            ]
+        );
+    }
+
+    #[test]
+    fn flag_regression_and_spoofed_knowledge() {
+        let mut room = RoomConfig::new()
+            .with_knowledge_lead_tolerance(100)
+            .with_exclude_suspicious_from_election(true)
+            .build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        let spoofer = room.create_connection(now).unwrap();
+        room.drain_events();
+
+        room.on_ping(leader, &PingPayload::new().with_knowledge(Knowledge(1000)), now);
+        room.on_ping(follower, &PingPayload::new().with_knowledge(Knowledge(990)), now);
+        room.on_ping(spoofer, &PingPayload::new().with_knowledge(Knowledge(u64::MAX)), now);
+        room.on_ping(spoofer, &PingPayload::new().with_knowledge(Knowledge(980)), now);
+
+        assert_eq!(
+            room.drain_events(),
+            vec![
+                RoomEvent::SuspiciousKnowledge {
+                    connection: spoofer,
+                    reported: Knowledge(u64::MAX),
+                    reason: SuspicionReason::AheadOfLeader {
+                        leader: Knowledge(1000)
+                    },
+                },
+                RoomEvent::SuspiciousKnowledge {
+                    connection: spoofer,
+                    reported: Knowledge(980),
+                    reason: SuspicionReason::Regression {
+                        previous: Knowledge(u64::MAX)
+                    },
+                },
+            ]
+        );
+        assert_eq!(room.get(spoofer).suspicion_score(), 2);
+        assert!(!room.get(follower).is_suspicious());
+
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader_index, Some(follower));
+    }
+
+    #[test]
+    fn trust_followers_before_leader_pings_and_forgive_over_time() {
+        let mut room = RoomConfig::new()
+            .with_knowledge_lead_tolerance(100)
+            .with_suspicion_decay(Some(Duration::from_secs(10)))
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+
+        room.on_ping(follower, &PingPayload::new().with_knowledge(Knowledge(5000)), now);
+        assert!(!room.get(follower).is_suspicious());
+
+        room.on_ping(leader, &PingPayload::new().with_knowledge(Knowledge(1000)), now);
+        room.on_ping(follower, &PingPayload::new().with_knowledge(Knowledge(9000)), now);
+        room.on_ping(follower, &PingPayload::new().with_knowledge(Knowledge(8000)), now);
+        assert_eq!(room.get(follower).suspicion_score(), 2);
+
+        let ping = PingPayload::new().with_knowledge(Knowledge(8000));
+        room.on_ping(leader, &ping, now + Duration::from_secs(9));
+        room.on_ping(follower, &ping, now + Duration::from_secs(9));
+        assert_eq!(room.get(follower).suspicion_score(), 2);
+        room.on_ping(follower, &ping, now + Duration::from_secs(15));
+        assert_eq!(room.get(follower).suspicion_score(), 1);
+        room.on_ping(follower, &ping, now + Duration::from_secs(20));
+        assert!(!room.get(follower).is_suspicious());
+    }
+
+    #[test]
+    fn spread_of_online_knowledge() {
+        let mut room = RoomConfig::new().with_convergence_tolerance(5).build().unwrap();
+        assert_eq!(room.knowledge_spread(), None);
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let close = room.create_connection(now).unwrap();
+        let behind = room.create_connection(now).unwrap();
+        let ahead = room.create_connection(now).unwrap();
+        room.preregister_connection("pending", now).unwrap();
+        for (connection, knowledge) in [(leader, 100), (close, 96), (behind, 40), (ahead, 120)] {
+            room.on_ping(connection, &PingPayload::new().with_knowledge(Knowledge(knowledge)), now);
+        }
+
+        let spread = room.knowledge_spread().unwrap();
+        assert_eq!((spread.min, spread.max, spread.median), (40, 120, 96));
+        assert_eq!(spread.behind, vec![behind]);
+    }
+}
@@ -0,0 +1,109 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+
+use crate::{Connection, Room};
+
+/// A tag starting with this gives the region of the connection, e.g. `"region:eu"`, see
+/// [Room::create_connection_with_tags]
+pub const REGION_TAG_PREFIX: &str = "region:";
+
+/// Which latency from the other connections to a candidate the election keeps low, see
+/// [crate::RoomConfig::latency_preference]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum LatencyPreference {
+    /// Only the link quality of the candidate itself counts
+    #[default]
+    Ignore,
+    /// The latency of the connection furthest away from the candidate
+    WorstCase,
+    Median,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Estimated latency from the other connections that take part in elections to `candidate`, from the
+    /// regions in their tags and [crate::RoomConfig::region_latencies]. Connections in the same region count
+    /// as zero unless the table says otherwise, and connections without a region, or with regions that are not
+    /// in the table, are left out. `None` if [crate::RoomConfig::latency_preference] is off or nothing is known.
+    pub(crate) fn follower_latency(&self, candidate: &Connection<K>) -> Option<Duration> {
+        if self.config.latency_preference == LatencyPreference::Ignore {
+            return None;
+        }
+        let region = region_of(candidate)?;
+        let mut latencies: Vec<Duration> = self
+            .connections
+            .values()
+            .filter(|follower| follower.id != candidate.id && follower.takes_part_in_election())
+            .filter_map(|follower| self.region_latency(region, region_of(follower)?))
+            .collect();
+        latencies.sort();
+        match self.config.latency_preference {
+            LatencyPreference::Ignore => None,
+            LatencyPreference::WorstCase => latencies.last().copied(),
+            LatencyPreference::Median => latencies.get(latencies.len() / 2).copied(),
+        }
+    }
+
+    fn region_latency(&self, a: &str, b: &str) -> Option<Duration> {
+        self.config
+            .region_latencies
+            .iter()
+            .find(|(from, to, _)| (from == a && to == b) || (from == b && to == a))
+            .map(|(_, _, latency)| *latency)
+            .or((a == b).then_some(Duration::ZERO))
+    }
+}
+
+/// The name after [REGION_TAG_PREFIX] in the first region tag of the connection
+fn region_of<K: KnowledgeOrd>(connection: &Connection<K>) -> Option<&str> {
+    connection.tags().iter().find_map(|tag| tag.strip_prefix(REGION_TAG_PREFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{LatencyPreference, PingPayload, RoomConfig};
+
+    #[test]
+    fn elect_candidate_closest_to_followers() {
+        let mut room = RoomConfig::new()
+            .with_latency_preference(LatencyPreference::WorstCase)
+            .with_region_latency("eu", "us", Duration::from_millis(90))
+            .with_region_latency("eu", "asia", Duration::from_millis(150))
+            .with_region_latency("us", "asia", Duration::from_millis(120))
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection_with_tags(&["region:asia"], now).unwrap();
+        let far = room.create_connection_with_tags(&["region:eu"], now).unwrap();
+        let central = room.create_connection_with_tags(&["region:us"], now).unwrap();
+        let _follower = room.create_connection_with_tags(&["region:asia"], now).unwrap();
+        let _untagged = room.create_connection(now).unwrap();
+        for millis in (0..=400).step_by(50) {
+            let time = now + Duration::from_millis(millis);
+            for connection in room.connections().iter().map(|connection| connection.id).collect::<Vec<_>>() {
+                room.on_ping(connection, &PingPayload::new().with_term(room.term()), time);
+            }
+        }
+
+        let dry_run = room.election_report(now + Duration::from_millis(400)).dry_run;
+        assert_eq!(dry_run.winner, Some(central));
+        assert_eq!(dry_run.candidates[1].connection, far);
+        assert_eq!(dry_run.candidates[1].follower_latency, Some(Duration::from_millis(150)));
+        assert_eq!(dry_run.candidates[2].follower_latency, Some(Duration::from_millis(120)));
+        assert_eq!(dry_run.candidates[4].follower_latency, None);
+
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader(), Some(central));
+    }
+}
@@ -0,0 +1,294 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::{ConnectionToLeader, KnowledgeOrd};
+use log::debug;
+
+use crate::{Connection, ConnectionIndex, Instant, QualityAssessment, Room};
+
+const CHANGE_RATE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Limits how often [Room::update] replaces the leader, to reduce churn on jittery networks.
+/// The default never holds back a leader change.
+///
+/// Leaders that leave the room are always replaced.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LeaderStability {
+    /// A leader is not replaced before it has led for this long
+    pub min_term_duration: Duration,
+    /// How much higher the score of the challenger must be than the score of the leader. The score is the
+    /// [Connection::stability], zero if the connection is assessed as bad, and for the leader it is scaled by the
+    /// share of voters that have not lost their connection to it.
+    pub challenger_margin: f32,
+    /// Leader changes allowed within a minute, `None` for no limit
+    pub max_changes_per_minute: Option<u32>,
+    /// After a leader change, a leader with bad quality is not replaced for a random time between half of this
+    /// and all of it. A majority down-voting the leader can still replace it. `None` disables the backoff
+    pub reelection_backoff: Option<Duration>,
+    /// Seeds the random backoff, so a room given the same inputs backs off the same way
+    pub backoff_seed: u64,
+}
+
+impl Default for LeaderStability {
+    fn default() -> Self {
+        Self {
+            min_term_duration: Duration::ZERO,
+            challenger_margin: 0.0,
+            max_changes_per_minute: None,
+            reelection_backoff: None,
+            backoff_seed: 0,
+        }
+    }
+}
+
+impl LeaderStability {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_min_term_duration(mut self, duration: Duration) -> Self {
+        self.min_term_duration = duration;
+        self
+    }
+
+    pub fn with_challenger_margin(mut self, margin: f32) -> Self {
+        self.challenger_margin = margin;
+        self
+    }
+
+    pub fn with_max_changes_per_minute(mut self, changes: u32) -> Self {
+        self.max_changes_per_minute = Some(changes);
+        self
+    }
+
+    pub fn with_reelection_backoff(mut self, backoff: Duration) -> Self {
+        self.reelection_backoff = Some(backoff);
+        self
+    }
+
+    pub fn with_backoff_seed(mut self, seed: u64) -> Self {
+        self.backoff_seed = seed;
+        self
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Uniformly distributed in `[0, 1)`, from a splitmix64 sequence seeded by [LeaderStability::backoff_seed]
+    fn next_backoff_fraction(&mut self) -> f64 {
+        self.backoff_rng = self.backoff_rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.backoff_rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Remembers when the leader was changed, for [LeaderStability::max_changes_per_minute], and starts the
+    /// [LeaderStability::reelection_backoff]
+    pub(crate) fn record_leader_change(&mut self) {
+        self.metrics.leader_changes = self.metrics.leader_changes.saturating_add(1);
+        let Some(time) = self.latest_time else {
+            return;
+        };
+        if let Some(backoff) = self.config.leader_stability.reelection_backoff {
+            let half = backoff / 2;
+            let backoff = half + (backoff - half).mul_f64(self.next_backoff_fraction());
+            debug!("leader can not be replaced for bad quality for {:?}", backoff);
+            self.quality_reelection_blocked_until = Some(time + backoff);
+        }
+        while self
+            .leader_changes
+            .front()
+            .is_some_and(|changed_at| time.saturating_duration_since(*changed_at) >= CHANGE_RATE_PERIOD)
+        {
+            self.leader_changes.pop_front();
+        }
+        self.leader_changes.push_back(time);
+    }
+
+    /// Number of times the leader has been changed in the minute before `time`
+    pub(crate) fn recent_leader_changes(&self, time: Instant) -> usize {
+        self.leader_changes
+            .iter()
+            .filter(|changed_at| time.saturating_duration_since(**changed_at) < CHANGE_RATE_PERIOD)
+            .count()
+    }
+
+    /// Share of the voters that have not reported that they lost the connection to the leader in this term
+    pub(crate) fn leader_reachability(&self) -> f32 {
+        let voters: Vec<&Connection<K>> = self
+            .connections
+            .values()
+            .filter(|connection| connection.takes_part_in_election())
+            .collect();
+        if voters.is_empty() {
+            return 1.0;
+        }
+        let lost = voters
+            .iter()
+            .filter(|connection| {
+                connection.has_connection_host == ConnectionToLeader::Disconnected
+                    && connection.last_reported_term == Some(self.term)
+            })
+            .count();
+        1.0 - lost as f32 / voters.len() as f32
+    }
+
+    /// True while a leader with bad quality is kept, see [LeaderStability::reelection_backoff]
+    pub(crate) fn is_backing_off_reelection(&self, time: Instant) -> bool {
+        self.quality_reelection_blocked_until.is_some_and(|until| time < until)
+    }
+
+    /// See [LeaderStability::challenger_margin]
+    fn leader_score(&self, connection: &Connection<K>) -> f32 {
+        let quality = if connection.assessment() == QualityAssessment::RecommendDisconnect {
+            0.0
+        } else {
+            connection.stability()
+        };
+        if self.leader_index == Some(connection.id) {
+            quality * self.leader_reachability()
+        } else {
+            quality
+        }
+    }
+
+    /// True if [crate::RoomConfig::leader_stability] allows the leader to be replaced by `challenger` at `time`
+    pub(crate) fn is_leader_change_allowed(&self, challenger: Option<ConnectionIndex>, time: Instant) -> bool {
+        let stability = &self.config.leader_stability;
+        if let Some(changed_at) = self.leader_changes.back() {
+            if time.saturating_duration_since(*changed_at) < stability.min_term_duration {
+                debug!("leader has not led for {:?} yet, keeping it", stability.min_term_duration);
+                return false;
+            }
+        }
+
+        if let Some(max_changes) = stability.max_changes_per_minute {
+            let recent_changes = self.recent_leader_changes(time);
+            if recent_changes >= max_changes as usize {
+                debug!("leader has changed {} times in the last minute, keeping it", recent_changes);
+                return false;
+            }
+        }
+
+        let leader = self.leader_index.and_then(|leader| self.connections.get(&leader));
+        let challenger = challenger.and_then(|challenger| self.connections.get(&challenger));
+        if let (Some(leader), Some(challenger)) = (leader, challenger) {
+            let leader_score = self.leader_score(leader);
+            let challenger_score = self.leader_score(challenger);
+            if challenger_score < leader_score + stability.challenger_margin {
+                debug!("challenger score {} is not enough to replace leader score {}", challenger_score, leader_score);
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::ConnectionToLeader;
+
+    use crate::{ConnectionIndex, LeaderStability, PingPayload, Room, RoomConfig};
+
+    /// Every connection pings every 100 milliseconds in `(from, to]`, the followers report that they
+    /// lost the leader
+    fn down_vote(room: &mut Room, connections: &[ConnectionIndex], now: Instant, from: u64, to: u64) {
+        for millis in (from + 100..=to).step_by(100) {
+            let time = now + Duration::from_millis(millis);
+            for connection in connections {
+                let to_leader = if room.leader_index == Some(*connection) {
+                    ConnectionToLeader::Connected
+                } else {
+                    ConnectionToLeader::Disconnected
+                };
+                let ping = PingPayload::new().with_term(room.term).with_connection_to_leader(to_leader);
+                room.on_ping(*connection, &ping, time);
+            }
+            room.update(time);
+        }
+    }
+
+    fn room_with(stability: LeaderStability) -> (Room, Vec<ConnectionIndex>, Instant) {
+        let mut room = RoomConfig::new().with_leader_stability(stability).build().unwrap();
+        let now = Instant::now();
+        let connections = (0..3).map(|_| room.create_connection(now).unwrap()).collect();
+        (room, connections, now)
+    }
+
+    #[test]
+    fn keep_leader_for_min_term_duration() {
+        let (mut room, connections, now) =
+            room_with(LeaderStability::new().with_min_term_duration(Duration::from_secs(2)));
+        let term = room.term;
+
+        down_vote(&mut room, &connections, now, 0, 1800);
+        assert_eq!(room.term, term);
+
+        down_vote(&mut room, &connections, now, 1800, 2200);
+        assert_eq!(room.term.value(), term.value() + 1);
+    }
+
+    #[test]
+    fn limit_leader_changes_per_minute() {
+        let (mut room, connections, now) = room_with(LeaderStability::new().with_max_changes_per_minute(2));
+        let term = room.term;
+
+        // Creating the room appointed the first leader, so only one more change is allowed
+        down_vote(&mut room, &connections, now, 0, 3000);
+        assert_eq!(room.term.value(), term.value() + 1);
+    }
+
+    #[test]
+    fn back_off_from_quality_reelection() {
+        let backoff = Duration::from_secs(4);
+        let (mut room, connections, now) = room_with(LeaderStability::new().with_reelection_backoff(backoff));
+        let leader = room.leader_index.unwrap();
+        let blocked_until = room.quality_reelection_blocked_until.unwrap();
+        assert!(blocked_until >= now + backoff / 2 && blocked_until <= now + backoff);
+
+        // The leader stops pinging, but is kept as long as the backoff lasts
+        let followers: Vec<ConnectionIndex> = connections.into_iter().filter(|connection| *connection != leader).collect();
+        let ping = PingPayload::new().with_connection_to_leader(ConnectionToLeader::Connected);
+        let mut millis = 0;
+        while now + Duration::from_millis(millis) < blocked_until {
+            for follower in &followers {
+                room.on_ping(*follower, &ping, now + Duration::from_millis(millis));
+            }
+            room.update(now + Duration::from_millis(millis));
+            assert_eq!(room.leader_index, Some(leader));
+            millis += 100;
+        }
+
+        for step in 1..=10 {
+            room.update(blocked_until + Duration::from_millis(step * 100));
+        }
+        assert_ne!(room.leader_index, Some(leader));
+    }
+
+    #[test]
+    fn down_vote_during_backoff() {
+        let (mut room, connections, now) =
+            room_with(LeaderStability::new().with_reelection_backoff(Duration::from_secs(10)));
+        let term = room.term;
+
+        down_vote(&mut room, &connections, now, 0, 100);
+        assert_eq!(room.term.value(), term.value() + 1);
+    }
+
+    #[test]
+    fn challenger_must_beat_leader_by_margin() {
+        let (mut room, connections, now) = room_with(LeaderStability::new().with_challenger_margin(0.9));
+        let term = room.term;
+
+        down_vote(&mut room, &connections, now, 0, 1000);
+        assert_eq!(room.term, term);
+    }
+}
@@ -0,0 +1,69 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+use crate::{ElectionTrigger, Instant, Room, RoomEvent};
+impl<K: KnowledgeOrd> Room<K> {
+    /// When the leader loses its position unless it pings before then, `None` without a
+    /// [crate::RoomConfig::lease_duration] or a leader.
+    ///
+    /// Can be sent to the leader client, so it can be warned before the lease runs out.
+    pub fn lease_deadline(&self) -> Option<Instant> {
+        self.lease_expires_at
+    }
+
+    /// Gives the leader a full lease, done when it is appointed and on every ping from it
+    pub(crate) fn renew_lease(&mut self, time: Option<Instant>) {
+        self.lease_expires_at = match (self.config.lease_duration, self.leader_index, time) {
+            (Some(duration), Some(_), Some(time)) => Some(time + duration),
+            _ => None,
+        };
+    }
+
+    /// Elects a new leader right away if the lease has run out
+    pub(crate) fn expire_lease(&mut self, time: Instant) -> bool {
+        let (Some(expires_at), Some(leader)) = (self.lease_expires_at, self.leader_index) else {
+            return false;
+        };
+        if time < expires_at || !self.is_possible_to_switch_leader() {
+            return false;
+        }
+        info!("lease of leader {} expired, electing a new leader", leader);
+        self.push_event(RoomEvent::LeaseExpired { term: self.term, leader });
+        self.switch_leader_to_best_knowledge_and_quality(ElectionTrigger::LeaseExpired);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn reelect_when_lease_expires() {
+        let lease = Duration::from_millis(300);
+        let mut room = RoomConfig::new().with_lease_duration(lease).build().unwrap();
+        let now = Instant::now();
+        let at = |millis| now + Duration::from_millis(millis);
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        assert_eq!(room.lease_deadline(), Some(now + lease));
+
+        room.on_ping(leader, &PingPayload::new(), at(200));
+        assert_eq!(room.lease_deadline(), Some(at(200) + lease));
+        // Pings from others do not renew the lease
+        room.on_ping(follower, &PingPayload::new(), at(400));
+        assert_eq!(room.leader_index, Some(leader));
+
+        let term = room.term;
+        room.drain_events();
+        room.update(at(500));
+        assert_eq!(room.leader_index, Some(follower));
+        assert_eq!(room.lease_deadline(), Some(at(500) + lease));
+        assert_eq!(room.drain_events()[0], RoomEvent::LeaseExpired { term, leader });
+    }
+}
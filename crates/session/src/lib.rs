@@ -6,118 +6,684 @@
 //!
 //! Evaluating connection quality for all connections attached to the room. Using "votes" from the connections, together with
 //! [Knowledge] and [ConnectionQuality] it determines which connection should be appointed leader.
+//!
+//! The knowledge type is pluggable through [KnowledgeOrd], [Room] defaults to the plain [Knowledge] counter.
 
 extern crate core;
 
 use core::fmt;
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use log::{debug, info, trace};
 
+pub use conclave_types::KnowledgeOrd;
 use conclave_types::{ConnectionToLeader, Knowledge, Term};
-use connection_quality::QualityAssessment;
 
+pub use crate::abandonment::AbandonmentStage;
+pub use crate::acknowledgement::TermAcknowledgement;
+use crate::attestation::Checkpoint;
+pub use crate::auth::PingAuthenticator;
+pub use crate::burden::{RoundTripTrend, ROUND_TRIP_SMOOTHING};
+#[cfg(feature = "testing")]
+pub use crate::chaos::Chaos;
+pub use crate::clock::ClockSkew;
+pub use crate::close::CloseReason;
+use crate::close::Closing;
 use crate::connection_quality::ConnectionQuality;
-
+pub use crate::connection_quality::QualityAssessment;
+pub use crate::critical_section::CriticalSection;
+pub use crate::downvote::DownvoteStatus;
+use crate::downvote::DownvoteLimiter;
+pub use crate::dump::{ConnectionDump, RoomDump, StateChange};
+pub use crate::eligibility::LeaderEligibility;
+pub use crate::election::{CandidateReport, ElectionExplanation, ElectionReport, Ineligibility, LeaderInfo};
+pub use crate::election_record::{ElectionRecord, ElectionTrigger};
+use crate::election::LastElection;
+use crate::hash::ConnectionMap;
+pub use crate::error::{ConfigError, RoomError};
+pub use crate::event::{DisconnectReason, EventCategory, EventFilter, EventSeverity, KickReason, RoomEvent};
+pub use crate::group::GroupId;
+pub use crate::handle::ConnectionHandle;
+pub use crate::health::RoomHealth;
+pub use crate::history::TimedEvent;
+pub use crate::identity::DuplicateIdentity;
+pub use crate::invariants::InvariantViolation;
+pub use crate::kick_vote::{KickBallot, KickProposal, KickVoteOutcome};
+pub use crate::knowledge::{KnowledgeSpread, SuspicionReason};
+pub use crate::latency::{LatencyPreference, REGION_TAG_PREFIX};
+pub use crate::leader_stability::LeaderStability;
+pub use crate::metrics::{
+    DroppedPingCounts, IntervalHistogram, MetricsSink, RejectedPingCounts, RoomMetrics, TrafficStats,
+    DEFAULT_RATE_PERIOD,
+};
+use crate::metrics::TrafficMeter;
+pub use crate::notification::{Notification, NotificationReason};
+pub use crate::observer::RoomObserver;
+pub use crate::ops::RoomOp;
+pub use crate::outgoing::{Outgoing, OutgoingIntent};
+#[cfg(feature = "snapshot")]
+pub use crate::persistence::{Checkpoints, InMemoryRoomStore, RoomStore};
+#[cfg(feature = "snapshot")]
+use crate::persistence::Persistence;
+use crate::ping::is_sequence_newer;
+pub use crate::ping::{PingOutcome, PingPayload, PingRejection, PROTOCOL_VERSION};
+pub use crate::priority::ConnectionPriority;
+use crate::recorder::Recorder;
+use crate::scan::ScanSummary;
+pub use crate::recorder::{RecordedEntry, RecordedInput, RoomLog};
+pub use crate::state_sync::StateSync;
+pub use crate::stats::RoomStats;
+pub use crate::template::RoomTemplate;
+pub use crate::time::Instant;
+pub use crate::view::{MemberView, RoomView, ViewReader};
+
+mod abandonment;
+mod acknowledgement;
+mod activation;
+mod adaptive_threshold;
+#[cfg(test)]
+mod allocations;
+mod attestation;
+mod auth;
+mod backpressure;
+mod burden;
+#[cfg(feature = "testing")]
+mod chaos;
+mod clock;
+mod close;
 mod connection_quality;
+mod critical_section;
+mod dot;
+mod downvote;
+mod dump;
+mod election;
+mod election_record;
+mod eligibility;
+mod error;
+mod event;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod group;
+mod handle;
+mod hash;
+mod health;
+mod history;
+mod identity;
+mod idle;
+mod invariants;
+mod kick_vote;
+mod knowledge;
+mod latency;
+mod leader_stability;
+mod lease;
 mod metrics;
-
-/// ID or index for a room connection
+mod notification;
+mod observer;
+#[cfg(any(feature = "wire", feature = "snapshot"))]
+mod octets;
+mod ops;
+mod outgoing;
+mod pacing;
+mod partition;
+mod path_change;
+mod pause;
+mod pending;
+#[cfg(feature = "snapshot")]
+mod persistence;
+mod ping;
+mod priority;
+mod probation;
+#[cfg(feature = "prometheus")]
+pub mod prometheus_exporter;
+mod quarantine;
+mod query;
+mod reconfigure;
+mod reachability;
+mod recorder;
+mod recovery;
+#[cfg(any(test, feature = "testing"))]
+pub mod reference;
+mod retirement;
+mod scan;
+mod schedule;
+mod silence;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+mod state_sync;
+mod stats;
+mod tags;
+mod template;
+mod time;
+mod traffic;
+mod validation;
+mod transfer;
+mod view;
+mod warm_up;
+mod watchdog;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "wire")]
+pub mod wire;
+
+/// ID or index for a room connection.
+///
+/// The `value` can be reused by the room after the connection is destroyed, the `generation` is unique for every
+/// created connection, so a handle kept around after its connection was destroyed never refers to a newer connection.
 #[derive(Default, Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd)]
-pub struct ConnectionIndex(pub u16);
+pub struct ConnectionIndex {
+    value: u32,
+    generation: u32,
+}
 
 impl fmt::Display for ConnectionIndex {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[conn_id: {}]", self.0)
+        write!(f, "[conn_id: {} gen:{}]", self.value, self.generation)
     }
 }
 
 impl ConnectionIndex {
-    pub fn new(value: u16) -> Self {
-        Self(value)
+    pub fn new(value: u32) -> Self {
+        Self { value, generation: 0 }
     }
 
-    pub fn value(&self) -> u16 {
-        self.0
+    pub fn with_generation(value: u32, generation: u32) -> Self {
+        Self { value, generation }
+    }
+
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
     }
 
     pub fn next(&mut self) {
-        self.0 += 1;
+        self.value = self.value.wrapping_add(1);
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "snake_case"))]
 pub enum ConnectionState {
+    /// Placeholder reserved with [Room::preregister_connection], that has not pinged yet
+    Pending,
+    /// Newly created, waiting for [RoomConfig::warm_up_pings] pings at the expected rate
+    Joining,
     Online,
+    /// Assessed as [QualityAssessment::RecommendDisconnect], and given [RoomConfig::quarantine_period] to recover
+    Quarantined,
     Disconnected,
 }
 
+/// Overall state of a [Room], see [Room::state]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "snake_case"))]
+pub enum RoomState {
+    /// Has an appointed leader
+    Active,
+    /// Has an elected leader that is not activated yet, see [RoomConfig::quorum_term_activation]
+    ProvisionalLeader,
+    /// Has connections, but no leader
+    Leaderless,
+    /// No pings have been received for a long time
+    Abandoned,
+    /// Waiting for the connections to acknowledge, see [Room::begin_close]
+    Closing,
+    /// Closed, all connections are removed
+    Closed,
+}
+
+impl RoomState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoomState::Active => "active",
+            RoomState::ProvisionalLeader => "provisional_leader",
+            RoomState::Leaderless => "leaderless",
+            RoomState::Abandoned => "abandoned",
+            RoomState::Closing => "closing",
+            RoomState::Closed => "closed",
+        }
+    }
+}
+
 /// A Room Connection
 #[derive(Debug)]
-pub struct Connection {
+pub struct Connection<K: KnowledgeOrd = Knowledge> {
     pub id: ConnectionIndex,
     quality: ConnectionQuality,
-    pub knowledge: Knowledge,
+    pub knowledge: K,
     pub state: ConnectionState,
     pub last_reported_term: Option<Term>,
     pub has_connection_host: ConnectionToLeader,
     pub debug_name: Option<String>,
+    /// Identity given when the connection was preregistered or created, see [Room::find_by_identity]
+    pub identity: Option<String>,
+    /// Set when the connection is created, see [Room::admit_with_eviction]
+    pub priority: ConnectionPriority,
+    pub protocol_version: Option<u16>,
+    last_sequence: Option<u16>,
+    dropped_pings: DroppedPingCounts,
+    auth_failures: u32,
+    suspicion_score: u32,
+    /// When [Connection::suspicion_score] was last raised or decayed
+    suspected_at: Option<Instant>,
+    is_lagging: bool,
+    needs_state_sync: bool,
+    warm_up_pings: u32,
+    is_idle: bool,
+    quarantined_at: Option<Instant>,
+    ping_intervals: IntervalHistogram,
+    pending_since: Option<Instant>,
+    group: Option<GroupId>,
+    /// Sorted and without duplicates, see [Room::create_connection_with_tags]
+    tags: Vec<String>,
+    /// Time of the latest down-voting ping, and how long the leader had been unreachable by then
+    lost_leader: Option<(Instant, Duration)>,
+    /// The leader of [Connection::last_reported_term], as reported by the connection
+    followed_leader: Option<ConnectionIndex>,
+    traffic: TrafficMeter,
+    clock_skew: Option<ClockSkew>,
+    round_trip: Option<RoundTripTrend>,
+    capability: Option<u8>,
+    down_vote_limiter: DownvoteLimiter,
+    /// The interval last sent with [OutgoingIntent::PingPacing]
+    paced_ping_interval: Option<Duration>,
+    quality_epoch: u32,
 }
 
-impl fmt::Display for Connection {
+impl<K: KnowledgeOrd> fmt::Display for Connection<K> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "[conn id:{} (name:{:?})  knowledge:{}, connectedToHost:{:?}, knownTerm:{:?}, quality:{}]", self.id, self.debug_name, self.knowledge, self.has_connection_host, self.last_reported_term, self.quality)
     }
 }
 
-impl Connection {
+impl<K: KnowledgeOrd> Connection<K> {
     fn new(
         connection_id: ConnectionIndex,
         time: Instant,
-        pings_per_second_threshold: f32,
+        config: &RoomConfig,
     ) -> Self {
         Connection {
             has_connection_host: ConnectionToLeader::Unknown,
             last_reported_term: None,
             id: connection_id,
-            quality: ConnectionQuality::new(pings_per_second_threshold, time),
-            knowledge: Knowledge(0),
+            quality: ConnectionQuality::from_config(config, time),
+            knowledge: K::default(),
             state: ConnectionState::Online,
             debug_name: None,
+            identity: None,
+            priority: ConnectionPriority::default(),
+            protocol_version: None,
+            last_sequence: None,
+            dropped_pings: DroppedPingCounts::default(),
+            auth_failures: 0,
+            suspicion_score: 0,
+            suspected_at: None,
+            is_lagging: false,
+            needs_state_sync: false,
+            warm_up_pings: 0,
+            is_idle: false,
+            quarantined_at: None,
+            ping_intervals: IntervalHistogram::new(),
+            pending_since: None,
+            group: None,
+            tags: Vec::new(),
+            lost_leader: None,
+            followed_leader: None,
+            traffic: TrafficMeter::new(time),
+            clock_skew: None,
+            round_trip: None,
+            capability: None,
+            down_vote_limiter: DownvoteLimiter::default(),
+            paced_ping_interval: None,
+            quality_epoch: 0,
         }
     }
 
-    fn on_ping(
-        &mut self,
-        term: Term,
-        has_connection_to_host: &ConnectionToLeader,
-        knowledge: Knowledge,
-        time: Instant,
-    ) {
-        self.last_reported_term = Some(term);
-        self.has_connection_host = *has_connection_to_host;
-        self.quality.on_ping(time);
-        self.knowledge = knowledge;
+    /// Rejects pings that are duplicates of, or older than, the latest accepted sequence number
+    fn check_sequence(&mut self, ping: &PingPayload<K>) -> Result<(), PingRejection> {
+        let (Some(sequence), Some(last_sequence)) = (ping.sequence, self.last_sequence) else {
+            return Ok(());
+        };
+
+        if sequence == last_sequence {
+            self.dropped_pings.duplicates += 1;
+            Err(PingRejection::Duplicate)
+        } else if !is_sequence_newer(sequence, last_sequence) {
+            self.dropped_pings.out_of_order += 1;
+            Err(PingRejection::OutOfOrder)
+        } else {
+            Ok(())
+        }
     }
 
-    fn update(&mut self, time: Instant) {
-        self.quality.update(time);
-        trace!("update {}", self);
+    /// Applies the ping, returns true if it changed the down-vote more often than [RoomConfig::max_down_vote_changes]
+    fn on_ping(&mut self, ping: &PingPayload<K>, time: Instant, config: &RoomConfig) -> bool {
+        if ping.sequence.is_some() {
+            self.last_sequence = ping.sequence;
+        }
+        self.protocol_version = Some(ping.protocol_version);
+        let is_down_vote_change = (self.has_connection_host == ConnectionToLeader::Disconnected)
+            != (ping.has_connection_to_leader == ConnectionToLeader::Disconnected);
+        let is_over_limit = is_down_vote_change
+            && config
+                .max_down_vote_changes
+                .is_some_and(|max| self.down_vote_limiter.on_change(max, config.down_vote_change_window, time));
+        self.lost_leader = match ping.has_connection_to_leader {
+            ConnectionToLeader::Disconnected => {
+                let measured = match self.lost_leader {
+                    Some((reported_at, duration)) if self.last_reported_term == Some(ping.term) => {
+                        duration + time.saturating_duration_since(reported_at)
+                    }
+                    _ => Duration::ZERO,
+                };
+                Some((time, ping.leader_unreachable_since.unwrap_or(measured)))
+            }
+            _ => None,
+        };
+        self.last_reported_term = Some(ping.term);
+        self.followed_leader = ping.followed_leader;
+        self.has_connection_host = ping.has_connection_to_leader;
+        self.quality.on_ping(time);
+        self.knowledge = ping.knowledge;
+        if let Some(sent_at) = ping.sent_at {
+            match &mut self.clock_skew {
+                Some(skew) => skew.sample(time, sent_at, config.clock_skew_smoothing),
+                None => self.clock_skew = Some(ClockSkew::new(time, sent_at)),
+            }
+        }
+        if ping.capability.is_some() {
+            self.capability = ping.capability;
+        }
+        is_over_limit
     }
 
     pub fn assessment(&self) -> QualityAssessment {
         self.quality.assessment
     }
+
+    /// When the latest ping was received, or when the connection was created if it has not pinged yet
+    pub fn last_ping_at(&self) -> Instant {
+        self.quality.last_ping_at
+    }
+
+    /// Time since [Connection::last_ping_at], zero if `now` is earlier
+    pub fn silent_for(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.quality.last_ping_at)
+    }
+
+    /// Time between the pings received from this connection
+    pub fn ping_intervals(&self) -> &IntervalHistogram {
+        &self.ping_intervals
+    }
+
+    /// Traffic reported by the transport, see [Room::record_traffic]
+    pub fn traffic(&self) -> &TrafficStats {
+        self.traffic.stats()
+    }
+
+    /// How the clock of the client relates to the room, `None` until it has pinged with [PingPayload::sent_at]
+    pub fn clock_skew(&self) -> Option<ClockSkew> {
+        self.clock_skew
+    }
+
+    /// Round trip times reported by the transport, `None` until [Room::record_round_trip] is called
+    pub fn round_trip(&self) -> Option<RoundTripTrend> {
+        self.round_trip
+    }
+
+    /// The latest [PingPayload::capability] the connection reported
+    pub fn capability(&self) -> Option<u8> {
+        self.capability
+    }
+
+    /// See [ConnectionQuality::stability]
+    pub fn stability(&self) -> f32 {
+        self.quality.stability()
+    }
+
+    pub fn dropped_pings(&self) -> DroppedPingCounts {
+        self.dropped_pings
+    }
+
+    /// Number of pings from this connection that failed authentication
+    pub fn auth_failures(&self) -> u32 {
+        self.auth_failures
+    }
+
+    /// Number of times the connection has reported implausible knowledge
+    pub fn suspicion_score(&self) -> u32 {
+        self.suspicion_score
+    }
+
+    pub fn is_suspicious(&self) -> bool {
+        self.suspicion_score > 0
+    }
+
+    /// True if the connection joined mid-session and has not yet received the full state, see [Room::pending_syncs]
+    pub fn needs_state_sync(&self) -> bool {
+        self.needs_state_sync
+    }
+
+    /// Pending, joining and quarantined connections do not vote on the leader and can not be elected
+    pub(crate) fn takes_part_in_election(&self) -> bool {
+        !matches!(
+            self.state,
+            ConnectionState::Pending | ConnectionState::Joining | ConnectionState::Quarantined
+        )
+    }
+
+    /// True if the latest ping down-voted the leader of `term` and is still fresh, see
+    /// [RoomConfig::down_vote_freshness]. With a [RoomConfig::sustained_leader_loss], the leader must also have
+    /// been unreachable for that long, and the down-vote must be no older than that.
+    fn is_down_voting(&self, term: Term, config: &RoomConfig, time: Instant) -> bool {
+        if self.has_connection_host != ConnectionToLeader::Disconnected
+            || self.last_reported_term != Some(term)
+            || self.down_vote_limiter.is_limited(time)
+        {
+            return false;
+        }
+        if config
+            .down_vote_freshness
+            .is_some_and(|intervals| !self.quality.is_within_intervals(intervals, time))
+        {
+            return false;
+        }
+        let Some(sustained) = config.sustained_leader_loss else {
+            return true;
+        };
+        self.lost_leader.is_some_and(|(reported_at, duration)| {
+            let age = time.saturating_duration_since(reported_at);
+            age <= sustained && duration + age >= sustained
+        })
+    }
+
+    /// True if the connection changed its down-vote more often than [RoomConfig::max_down_vote_changes], so
+    /// its down-votes do not count at `time`
+    pub fn is_down_vote_rate_limited(&self, time: Instant) -> bool {
+        self.down_vote_limiter.is_limited(time)
+    }
+
+    /// Number of down-vote changes over [RoomConfig::max_down_vote_changes]
+    pub fn down_vote_violations(&self) -> u32 {
+        self.down_vote_limiter.violations()
+    }
+
+    /// True if the player behind the connection has not given any input for [RoomConfig::idle_after]
+    pub fn is_idle(&self) -> bool {
+        self.is_idle
+    }
+
+    /// See [Room::set_group]
+    pub fn group(&self) -> Option<GroupId> {
+        self.group
+    }
+
+    /// Sorted, see [Room::create_connection_with_tags]
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.binary_search_by(|probe| probe.as_str().cmp(tag)).is_ok()
+    }
 }
 
 /// Configuration for a Room
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RoomConfig {
     pub allowed_to_remove_single_leader: bool,
     pub pings_per_second_threshold: f32,
     pub disconnect_bad_connections: bool,
     pub destroy_disconnected_connections: bool,
+    pub min_supported_version: u16,
+    pub max_auth_failures: Option<u32>,
+    pub knowledge_lead_tolerance: Option<u64>,
+    pub exclude_suspicious_from_election: bool,
+    /// One point of [Connection::suspicion_score] is forgiven for every `suspicion_decay` without new suspicion,
+    /// `None` keeps the score for as long as the connection is in the room
+    pub suspicion_decay: Option<Duration>,
+    pub knowledge_lag_threshold: Option<u64>,
+    /// Highest connection index value handed out, see [Room::create_connection]
+    pub max_connection_index: u32,
+    /// Consecutive pings at the expected rate needed before a new connection goes from
+    /// [ConnectionState::Joining] to [ConnectionState::Online]. Zero means connections start out online
+    pub warm_up_pings: u32,
+    /// Connections reporting a [PingPayload::last_input_age] longer than this are idle, `None` disables idle detection
+    pub idle_after: Option<Duration>,
+    /// Idle connections are only elected leader if there is no active connection to choose
+    pub deprioritize_idle_in_election: bool,
+    /// How long a bad connection is quarantined before it is disconnected, `None` disconnects it right away
+    pub quarantine_period: Option<Duration>,
+    /// Among the candidates with at most this much less knowledge than the most knowledgeable one,
+    /// the one with the highest [Connection::stability] is elected leader
+    pub stability_knowledge_tolerance: u64,
+    /// If set, the threshold is lowered to this fraction of the median ping rate in the room, so a slowdown
+    /// that hits every connection does not get them all disconnected
+    pub adaptive_threshold_fraction: Option<f32>,
+    /// No leader is appointed until this many connections have joined, then a single election is held
+    pub min_connections_for_election: usize,
+    /// Preregistered connections that have not pinged within this time are removed
+    pub pending_timeout: Duration,
+    /// How long pings are counted before a new rate is calculated and assessed
+    pub assessment_window: Duration,
+    /// Number of rates calculated for a new connection before it is assessed for the first time
+    pub min_assessment_samples: u32,
+    /// Weight of the latest rate when smoothing rates with an exponential moving average, `None` uses the latest rate as is
+    pub rate_smoothing: Option<f32>,
+    /// Holds back leader changes in [Room::update] on jittery networks
+    pub leader_stability: LeaderStability,
+    /// A new leader is not disconnected or replaced for bad quality for this long, so it has time to start
+    /// hosting. A majority down-voting it still replaces it. `None` disables the probation
+    pub leader_probation: Option<Duration>,
+    /// The leader is replaced right away if it has not pinged for this long, see [Room::lease_deadline]
+    pub lease_duration: Option<Duration>,
+    /// A new leader is provisional until a majority of the voters have pinged with its term, and traffic keeps
+    /// going through the previous leader until then, see [Room::routing_leader]
+    pub quorum_term_activation: bool,
+    /// The room is abandoned when no pings have been received for this long, see [Room::is_abandoned] and
+    /// [RoomEvent::RoomAbandoned]
+    pub abandoned_after: Duration,
+    /// Number of events kept for [Room::recent_events], zero disables the history
+    pub event_history: usize,
+    /// Events returned by [Room::drain_events]. The history of [Room::recent_events] keeps every event
+    pub event_filter: EventFilter,
+    /// A majority down-voting the leader for this long without it being replaced is reported with
+    /// [RoomEvent::RoomStuck], `None` disables the watchdog
+    pub election_watchdog: Option<Duration>,
+    /// When the watchdog reports the room as stuck, a new leader is forced, see [RoomEvent::RoomStuck]
+    pub force_reelection_when_stuck: bool,
+    /// A room that has lost its leader elects a new one in [Room::update] as soon as an online connection has
+    /// acceptable quality again, see [RoomEvent::LeaderRestored]
+    pub restore_leader_when_leaderless: bool,
+    /// A down-vote only counts towards replacing the leader once the connection has been unable to reach it for
+    /// this long, see [PingPayload::leader_unreachable_since], and only while the down-vote is no older than
+    /// this. `None` counts every down-vote right away
+    pub sustained_leader_loss: Option<Duration>,
+    /// A down-vote only counts while the last ping from the connection is no older than this many ping intervals
+    /// at [RoomConfig::pings_per_second_threshold], so a connection that went silent after down-voting does not
+    /// keep counting. `None` counts the down-vote until the connection pings again
+    pub down_vote_freshness: Option<f32>,
+    /// Scores from 1 to 100 that emit [RoomEvent::HealthDegraded] when [Room::health] drops below them, and
+    /// [RoomEvent::HealthRecovered] when it is back at or above them. Empty disables the health tracking
+    pub health_thresholds: Vec<u8>,
+    /// Online connections further than this behind the leader have not caught up, see [Room::knowledge_spread]
+    pub convergence_tolerance: u64,
+    /// After a leader change, a previous leader that is still online is kept as [Room::retiring_leader] for this
+    /// long, see [RoomEvent::RetiredLeaderReleased]. `None` releases it right away
+    pub leader_overlap: Option<Duration>,
+    /// Connections reporting a term ahead of the room, e.g. after a network partition healed, make the room follow
+    /// the highest term and the leader of it, see [RoomEvent::PartitionHealed]. Only followed when more than half
+    /// of the online connections report a term ahead
+    pub reconcile_partitions: bool,
+    /// Terms reported further than this ahead of the room are not followed by [RoomConfig::reconcile_partitions]
+    pub max_partition_term_lead: u16,
+    /// Candidates sending more bytes per second than this, see [Room::record_traffic], are only elected if no
+    /// candidate has headroom left. `None` does not consider traffic in elections
+    pub traffic_budget: Option<u64>,
+    /// Weight of the latest ping when smoothing [Connection::clock_skew] with an exponential moving average
+    pub clock_skew_smoothing: f32,
+    /// What [Room::create_connection_with_identity] does when the identity already has a connection
+    pub duplicate_identity: DuplicateIdentity,
+    /// Times a connection may start or stop down-voting within [RoomConfig::down_vote_change_window]. Its
+    /// down-votes do not count for a window after every change over the limit, so a client flipping its
+    /// [PingPayload::has_connection_to_leader] can not keep adding fresh down-votes. `None` for no limit
+    pub max_down_vote_changes: Option<u32>,
+    pub down_vote_change_window: Duration,
+    /// How long [Room::begin_close] waits for the connections to acknowledge before the room is closed anyway
+    pub close_drain_period: Duration,
+    /// The room is quiet when no pings have been received for this long, see [RoomEvent::RoomQuiet]. `None`
+    /// skips the stage
+    pub quiet_after: Option<Duration>,
+    /// The room starts closing with [CloseReason::Abandoned] when no pings have been received for this long, see
+    /// [Room::begin_close]. `None` leaves closing to the host
+    pub close_abandoned_after: Option<Duration>,
+    /// Clients are asked to ping at this multiple of their threshold, see [Room::recommended_ping_interval]
+    pub ping_headroom: f32,
+    /// Sends [OutgoingIntent::PingPacing] when the recommended ping interval of a connection changes
+    pub send_ping_pacing: bool,
+    /// Number of connections a typical device can host as leader, see [Room::burden_as_leader]
+    pub leader_burden_room_size: u32,
+    /// A leader with a higher [Room::leader_burden] is replaced by a candidate that is below it, see
+    /// [RoomEvent::LoadRebalance]. `None` never replaces the leader for its burden
+    pub max_leader_burden: Option<f32>,
+    /// Number of [PingPayload::knowledge_checksum] sent by the leader that are kept to check the ones echoed by
+    /// the other connections, zero turns the check off
+    pub attestation_history: usize,
+    /// New connections are refused with [RoomError::RoomFull] when the room has this many, pending ones
+    /// included. See [Room::admit_with_eviction]. `None` for no limit
+    pub max_connections: Option<usize>,
+    /// How long a vote opened by [Room::propose_kick] stays open
+    pub kick_vote_duration: Duration,
+    /// The target of [Room::propose_kick] is kicked when more than this fraction of the voters approve
+    pub kick_vote_quorum: f32,
+    /// A [RoomStats] sample is kept for [Room::stats_history] at the first update after every interval. `None`
+    /// turns sampling off
+    pub stats_interval: Option<Duration>,
+    /// Number of samples kept for [Room::stats_history]
+    pub stats_history: usize,
+    /// Number of [ElectionRecord] kept for [Room::election_records], zero keeps none. They are emitted with
+    /// [RoomEvent::ElectionRecorded] either way
+    pub election_record_history: usize,
+    /// Most connections the update after every [Room::on_ping] may go through. In a bigger room pings skip the
+    /// update, it runs in full on the next [Room::update], and [RoomEvent::Backpressure] is emitted. This keeps the
+    /// time spent on every ping bounded as long as the transport calls [Room::update] regularly. A room size
+    /// rather than a duration, as the room never reads a clock. `None` for no limit
+    pub update_budget: Option<usize>,
+    /// Prefer the candidate with the lowest estimated latency from the other connections over the one with the most
+    /// stable link of its own. Estimated from the region tags of the connections, see [REGION_TAG_PREFIX], and
+    /// [RoomConfig::region_latencies]
+    pub latency_preference: LatencyPreference,
+    /// Latency between two regions, in either direction. Two connections in the same region are assumed to have
+    /// no latency unless the region is listed with itself
+    pub region_latencies: Vec<(String, String, Duration)>,
 }
 
 impl Default for RoomConfig {
@@ -127,6 +693,63 @@ impl Default for RoomConfig {
             pings_per_second_threshold: 5.0,
             disconnect_bad_connections: true,
             destroy_disconnected_connections: false,
+            min_supported_version: 0,
+            max_auth_failures: None,
+            knowledge_lead_tolerance: None,
+            exclude_suspicious_from_election: false,
+            suspicion_decay: Some(Duration::from_secs(60)),
+            knowledge_lag_threshold: None,
+            max_connection_index: u32::MAX,
+            warm_up_pings: 0,
+            idle_after: None,
+            deprioritize_idle_in_election: false,
+            quarantine_period: None,
+            stability_knowledge_tolerance: 10,
+            adaptive_threshold_fraction: None,
+            min_connections_for_election: 1,
+            pending_timeout: Duration::from_secs(60),
+            assessment_window: DEFAULT_RATE_PERIOD,
+            min_assessment_samples: 1,
+            rate_smoothing: None,
+            leader_stability: LeaderStability::default(),
+            leader_probation: None,
+            lease_duration: None,
+            quorum_term_activation: false,
+            abandoned_after: ABANDONED_TIMEOUT,
+            event_history: 128,
+            event_filter: EventFilter::default(),
+            election_watchdog: None,
+            force_reelection_when_stuck: false,
+            restore_leader_when_leaderless: false,
+            sustained_leader_loss: None,
+            down_vote_freshness: None,
+            health_thresholds: Vec::new(),
+            convergence_tolerance: 0,
+            leader_overlap: None,
+            reconcile_partitions: false,
+            max_partition_term_lead: 8,
+            traffic_budget: None,
+            clock_skew_smoothing: 0.1,
+            duplicate_identity: DuplicateIdentity::Reject,
+            max_down_vote_changes: None,
+            down_vote_change_window: Duration::from_secs(10),
+            close_drain_period: Duration::from_secs(5),
+            quiet_after: None,
+            close_abandoned_after: None,
+            ping_headroom: 3.0,
+            send_ping_pacing: false,
+            leader_burden_room_size: 8,
+            max_leader_burden: None,
+            attestation_history: 64,
+            max_connections: None,
+            kick_vote_duration: Duration::from_secs(30),
+            kick_vote_quorum: 0.5,
+            stats_interval: None,
+            stats_history: 300,
+            election_record_history: 0,
+            update_budget: None,
+            latency_preference: LatencyPreference::Ignore,
+            region_latencies: Vec::new(),
         }
     }
 }
@@ -157,6 +780,304 @@ impl RoomConfig {
         self
     }
 
+    /// Pings reporting an older [PingPayload::protocol_version] are rejected
+    pub fn with_min_supported_version(mut self, min_supported_version: u16) -> Self {
+        self.min_supported_version = min_supported_version;
+        self
+    }
+
+    /// Kick a connection once this many of its pings have failed authentication
+    pub fn with_max_auth_failures(mut self, max_auth_failures: u32) -> Self {
+        self.max_auth_failures = Some(max_auth_failures);
+        self
+    }
+
+    /// Knowledge reported more than `tolerance` ahead of the leader's knowledge is flagged as suspicious
+    pub fn with_knowledge_lead_tolerance(mut self, tolerance: u64) -> Self {
+        self.knowledge_lead_tolerance = Some(tolerance);
+        self
+    }
+
+    /// Connections that have reported suspicious knowledge can not be elected leader
+    pub fn with_exclude_suspicious_from_election(mut self, should_exclude: bool) -> Self {
+        self.exclude_suspicious_from_election = should_exclude;
+        self
+    }
+
+    /// How long it takes for one point of suspicion to be forgiven, `None` to never forgive it
+    pub fn with_suspicion_decay(mut self, decay: Option<Duration>) -> Self {
+        self.suspicion_decay = decay;
+        self
+    }
+
+    /// Connections further behind the leader's knowledge than `threshold` are reported as lagging
+    pub fn with_knowledge_lag_threshold(mut self, threshold: u64) -> Self {
+        self.knowledge_lag_threshold = Some(threshold);
+        self
+    }
+
+    pub fn with_max_connection_index(mut self, max_connection_index: u32) -> Self {
+        self.max_connection_index = max_connection_index;
+        self
+    }
+
+    pub fn with_warm_up_pings(mut self, warm_up_pings: u32) -> Self {
+        self.warm_up_pings = warm_up_pings;
+        self
+    }
+
+    /// Connections that have not had any input for `idle_after` are reported as idle
+    pub fn with_idle_after(mut self, idle_after: Duration) -> Self {
+        self.idle_after = Some(idle_after);
+        self
+    }
+
+    /// Prefer active connections over idle ones when electing a leader
+    pub fn with_deprioritize_idle_in_election(mut self, should_deprioritize: bool) -> Self {
+        self.deprioritize_idle_in_election = should_deprioritize;
+        self
+    }
+
+    /// Bad connections get `period` to recover before they are disconnected, see [ConnectionState::Quarantined]
+    pub fn with_quarantine_period(mut self, period: Duration) -> Self {
+        self.quarantine_period = Some(period);
+        self
+    }
+
+    pub fn with_stability_knowledge_tolerance(mut self, tolerance: u64) -> Self {
+        self.stability_knowledge_tolerance = tolerance;
+        self
+    }
+
+    /// Scale the threshold with the median ping rate of the room, see [RoomConfig::adaptive_threshold_fraction]
+    pub fn with_adaptive_threshold_fraction(mut self, fraction: f32) -> Self {
+        self.adaptive_threshold_fraction = Some(fraction);
+        self
+    }
+
+    pub fn with_min_connections_for_election(mut self, min_connections: usize) -> Self {
+        self.min_connections_for_election = min_connections;
+        self
+    }
+
+    pub fn with_pending_timeout(mut self, timeout: Duration) -> Self {
+        self.pending_timeout = timeout;
+        self
+    }
+
+    pub fn with_assessment_window(mut self, window: Duration) -> Self {
+        self.assessment_window = window;
+        self
+    }
+
+    pub fn with_min_assessment_samples(mut self, min_samples: u32) -> Self {
+        self.min_assessment_samples = min_samples;
+        self
+    }
+
+    /// Smooth the measured ping rates, `alpha` between 0.0 and 1.0 is the weight of the latest rate
+    pub fn with_rate_smoothing(mut self, alpha: f32) -> Self {
+        self.rate_smoothing = Some(alpha);
+        self
+    }
+
+    pub fn with_leader_stability(mut self, stability: LeaderStability) -> Self {
+        self.leader_stability = stability;
+        self
+    }
+
+    pub fn with_leader_probation(mut self, probation: Duration) -> Self {
+        self.leader_probation = Some(probation);
+        self
+    }
+
+    pub fn with_lease_duration(mut self, duration: Duration) -> Self {
+        self.lease_duration = Some(duration);
+        self
+    }
+
+    pub fn with_quorum_term_activation(mut self, enabled: bool) -> Self {
+        self.quorum_term_activation = enabled;
+        self
+    }
+
+    pub fn with_abandoned_after(mut self, duration: Duration) -> Self {
+        self.abandoned_after = duration;
+        self
+    }
+
+    pub fn with_event_history(mut self, length: usize) -> Self {
+        self.event_history = length;
+        self
+    }
+
+    pub fn with_election_watchdog(mut self, timeout: Option<Duration>) -> Self {
+        self.election_watchdog = timeout;
+        self
+    }
+
+    pub fn with_force_reelection_when_stuck(mut self, should_force: bool) -> Self {
+        self.force_reelection_when_stuck = should_force;
+        self
+    }
+
+    pub fn with_restore_leader_when_leaderless(mut self, should_restore: bool) -> Self {
+        self.restore_leader_when_leaderless = should_restore;
+        self
+    }
+
+    pub fn with_sustained_leader_loss(mut self, duration: Option<Duration>) -> Self {
+        self.sustained_leader_loss = duration;
+        self
+    }
+
+    pub fn with_down_vote_freshness(mut self, intervals: Option<f32>) -> Self {
+        self.down_vote_freshness = intervals;
+        self
+    }
+
+    pub fn with_health_thresholds(mut self, thresholds: Vec<u8>) -> Self {
+        self.health_thresholds = thresholds;
+        self
+    }
+
+    pub fn with_convergence_tolerance(mut self, tolerance: u64) -> Self {
+        self.convergence_tolerance = tolerance;
+        self
+    }
+
+    pub fn with_event_filter(mut self, filter: EventFilter) -> Self {
+        self.event_filter = filter;
+        self
+    }
+
+    pub fn with_leader_overlap(mut self, overlap: Duration) -> Self {
+        self.leader_overlap = Some(overlap);
+        self
+    }
+
+    pub fn with_reconcile_partitions(mut self, should_reconcile: bool) -> Self {
+        self.reconcile_partitions = should_reconcile;
+        self
+    }
+
+    pub fn with_max_partition_term_lead(mut self, lead: u16) -> Self {
+        self.max_partition_term_lead = lead;
+        self
+    }
+
+    pub fn with_traffic_budget(mut self, bytes_per_second: u64) -> Self {
+        self.traffic_budget = Some(bytes_per_second);
+        self
+    }
+
+    pub fn with_clock_skew_smoothing(mut self, alpha: f32) -> Self {
+        self.clock_skew_smoothing = alpha;
+        self
+    }
+
+    pub fn with_duplicate_identity(mut self, duplicate_identity: DuplicateIdentity) -> Self {
+        self.duplicate_identity = duplicate_identity;
+        self
+    }
+
+    pub fn with_max_down_vote_changes(mut self, max_changes: Option<u32>) -> Self {
+        self.max_down_vote_changes = max_changes;
+        self
+    }
+
+    pub fn with_down_vote_change_window(mut self, window: Duration) -> Self {
+        self.down_vote_change_window = window;
+        self
+    }
+
+    pub fn with_close_drain_period(mut self, period: Duration) -> Self {
+        self.close_drain_period = period;
+        self
+    }
+
+    pub fn with_quiet_after(mut self, duration: Option<Duration>) -> Self {
+        self.quiet_after = duration;
+        self
+    }
+
+    pub fn with_close_abandoned_after(mut self, duration: Option<Duration>) -> Self {
+        self.close_abandoned_after = duration;
+        self
+    }
+
+    pub fn with_ping_headroom(mut self, headroom: f32) -> Self {
+        self.ping_headroom = headroom;
+        self
+    }
+
+    pub fn with_send_ping_pacing(mut self, should_send: bool) -> Self {
+        self.send_ping_pacing = should_send;
+        self
+    }
+
+    pub fn with_leader_burden_room_size(mut self, size: u32) -> Self {
+        self.leader_burden_room_size = size;
+        self
+    }
+
+    pub fn with_max_leader_burden(mut self, burden: Option<f32>) -> Self {
+        self.max_leader_burden = burden;
+        self
+    }
+
+    pub fn with_attestation_history(mut self, count: usize) -> Self {
+        self.attestation_history = count;
+        self
+    }
+
+    pub fn with_max_connections(mut self, max: Option<usize>) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    pub fn with_kick_vote_duration(mut self, duration: Duration) -> Self {
+        self.kick_vote_duration = duration;
+        self
+    }
+
+    pub fn with_kick_vote_quorum(mut self, quorum: f32) -> Self {
+        self.kick_vote_quorum = quorum;
+        self
+    }
+
+    pub fn with_stats_interval(mut self, interval: Option<Duration>) -> Self {
+        self.stats_interval = interval;
+        self
+    }
+
+    pub fn with_stats_history(mut self, length: usize) -> Self {
+        self.stats_history = length;
+        self
+    }
+
+    pub fn with_election_record_history(mut self, length: usize) -> Self {
+        self.election_record_history = length;
+        self
+    }
+
+    pub fn with_update_budget(mut self, budget: Option<usize>) -> Self {
+        self.update_budget = budget;
+        self
+    }
+
+    pub fn with_latency_preference(mut self, preference: LatencyPreference) -> Self {
+        self.latency_preference = preference;
+        self
+    }
+
+    /// Adds or replaces the latency between regions `a` and `b`, see [RoomConfig::region_latencies]
+    pub fn with_region_latency(mut self, a: &str, b: &str, latency: Duration) -> Self {
+        self.region_latencies.retain(|(from, to, _)| !((from == a && to == b) || (from == b && to == a)));
+        self.region_latencies.push((a.to_string(), b.to_string(), latency));
+        self
+    }
+
     pub fn recommended_for_debug() -> Self {
         Self::default().pings_per_second_threshold(4.0)
     }
@@ -165,8 +1086,63 @@ impl RoomConfig {
         Self::default().pings_per_second_threshold(10.0)
     }
 
-    pub fn build(self) -> Room {
-        Room::new_with_config(self)
+    /// For mobile and cellular clients, where pings are delayed and dropped in bursts and the app can be
+    /// backgrounded for a while. Rates are smoothed over longer windows, bad connections get a quarantine to
+    /// recover in, and leader changes are held back so a network hiccup does not cause a chain of elections.
+    pub fn recommended_for_mobile() -> Self {
+        Self::default()
+            .pings_per_second_threshold(3.0)
+            .with_assessment_window(Duration::from_secs(2))
+            .with_min_assessment_samples(2)
+            .with_rate_smoothing(0.3)
+            .with_adaptive_threshold_fraction(0.5)
+            .with_warm_up_pings(3)
+            .with_quarantine_period(Duration::from_secs(5))
+            .with_leader_stability(
+                LeaderStability::new()
+                    .with_min_term_duration(Duration::from_secs(10))
+                    .with_challenger_margin(0.2)
+                    .with_max_changes_per_minute(3)
+                    .with_reelection_backoff(Duration::from_secs(5)),
+            )
+            .with_leader_probation(Duration::from_secs(5))
+            .with_quorum_term_activation(true)
+            .with_abandoned_after(Duration::from_secs(10 * 60))
+    }
+
+    /// For LAN tournaments, where pings are fast and regular. A silent connection or leader is noticed
+    /// within about a second, and a new leader needs most of the room to know about it before it is used.
+    pub fn recommended_for_lan() -> Self {
+        Self::default()
+            .pings_per_second_threshold(10.0)
+            .with_warm_up_pings(5)
+            .with_leader_stability(
+                LeaderStability::new()
+                    .with_min_term_duration(Duration::from_secs(2))
+                    .with_max_changes_per_minute(6),
+            )
+            .with_leader_probation(Duration::from_secs(1))
+            .with_lease_duration(Duration::from_secs(1))
+            .with_quorum_term_activation(true)
+            .with_abandoned_after(Duration::from_secs(2 * 60))
+    }
+
+    /// Builds the room, after checking the config with [RoomConfig::validate]
+    pub fn build(self) -> Result<Room, ConfigError> {
+        self.validate()?;
+        Ok(Room::new_with_config(self))
+    }
+
+    /// Like [RoomConfig::build], but panics with the error message if the config is not valid
+    pub fn build_unchecked(self) -> Room {
+        self.build().unwrap_or_else(|error| panic!("invalid room config: {}", error))
+    }
+
+    /// Builds a room that elects leaders using a custom knowledge type, after checking the config with
+    /// [RoomConfig::validate]
+    pub fn build_with_knowledge<K: KnowledgeOrd>(self) -> Result<Room<K>, ConfigError> {
+        self.validate()?;
+        Ok(Room::from_config(self))
     }
 }
 
@@ -174,25 +1150,136 @@ const ABANDONED_TIMEOUT: Duration = Duration::from_secs(15 * 60);
 
 /// Contains the Room [Connection]s as well the appointed Leader.
 #[derive(Debug)]
-pub struct Room {
-    pub id: ConnectionIndex,
-    pub connections: HashMap<ConnectionIndex, Connection>,
-    pub leader_index: Option<ConnectionIndex>,
-    pub term: Term,
-    pub config: RoomConfig,
+pub struct Room<K: KnowledgeOrd = Knowledge> {
+    id: ConnectionIndex,
+    /// Tells the rooms of the process apart, see [ConnectionHandle]
+    instance: u64,
+    connections: ConnectionMap<Connection<K>>,
+    scan: ScanSummary,
+    leader_index: Option<ConnectionIndex>,
+    term: Term,
+    config: RoomConfig,
     pub latest_ping_timestamp: Option<Instant>,
+    events: Vec<RoomEvent>,
+    authenticator: Option<Box<dyn PingAuthenticator<K>>>,
+    leader_eligibility: Option<Box<dyn LeaderEligibility<K>>>,
+    state_syncs: Vec<StateSync>,
+    outgoing: Vec<Outgoing>,
+    notifications: Vec<Notification>,
+    metrics_sink: Option<Box<dyn MetricsSink>>,
+    observer: Option<Box<dyn RoomObserver>>,
+    #[cfg(feature = "testing")]
+    chaos: Option<Chaos<K>>,
+    abandonment_stage: AbandonmentStage,
+    /// The highest term the room has had after a mutation, see [InvariantViolation::TermDecreased]
+    highest_term: Term,
+    /// Where [Room::view] is published, shared with every [ViewReader]
+    published_view: Arc<RwLock<Arc<RoomView>>>,
+    /// Set whenever the room changes, until the view is published again
+    is_view_stale: AtomicBool,
+    recorder: Option<Recorder>,
+    paused_at: Option<Instant>,
+    latest_time: Option<Instant>,
+    generation: u32,
+    ping_intervals: IntervalHistogram,
+    last_election: Option<LastElection>,
+    representatives: HashMap<GroupId, ConnectionIndex>,
+    /// The connection of every identity, see [Room::find_by_identity]
+    identities: HashMap<String, ConnectionIndex>,
+    /// When the leader was changed within the last minute, oldest first
+    leader_changes: VecDeque<Instant>,
+    quality_reelection_blocked_until: Option<Instant>,
+    backoff_rng: u64,
+    probation_until: Option<Instant>,
+    /// The previous leader and when it is released, see [Room::retiring_leader]
+    retiring_leader: Option<(ConnectionIndex, Instant)>,
+    lease_expires_at: Option<Instant>,
+    unreachable_by_leader: Vec<ConnectionIndex>,
+    converged_term: Option<Term>,
+    provisional_previous_leader: Option<ConnectionIndex>,
+    event_history: VecDeque<TimedEvent>,
+    stats_samples: VecDeque<RoomStats>,
+    /// The record of the latest election, until its term converges
+    pending_election_record: Option<ElectionRecord>,
+    election_records: VecDeque<ElectionRecord>,
+    next_stats_at: Option<Instant>,
+    checkpoints: VecDeque<Checkpoint>,
+    kick_vote: Option<KickProposal>,
+    /// Id of the latest [KickProposal]
+    kick_proposals: u32,
+    /// An update after a ping was deferred by [RoomConfig::update_budget]
+    update_deferred: bool,
+    critical_section: Option<CriticalSection>,
+    stuck_election_since: Option<Instant>,
+    stuck_election_attempts: u32,
+    /// The score at the previous update, see [Room::health]
+    health_score: u8,
+    metrics: RoomMetrics,
+    #[cfg(feature = "snapshot")]
+    persistence: Option<Persistence<K>>,
+    closing: Option<Closing>,
 }
 
 
-impl Default for Room {
+impl<K: KnowledgeOrd> Default for Room<K> {
     fn default() -> Self {
         Self {
-            id: ConnectionIndex(0),
-            connections: HashMap::new(),
+            id: ConnectionIndex::new(0),
+            instance: handle::next_room_instance(),
+            connections: ConnectionMap::default(),
+            scan: ScanSummary::default(),
             leader_index: None,
             term: Term(0),
             config: Default::default(),
             latest_ping_timestamp: None,
+            events: Vec::new(),
+            authenticator: None,
+            leader_eligibility: None,
+            state_syncs: Vec::new(),
+            outgoing: Vec::new(),
+            notifications: Vec::new(),
+            metrics_sink: None,
+            observer: None,
+            #[cfg(feature = "testing")]
+            chaos: None,
+            abandonment_stage: AbandonmentStage::Active,
+            highest_term: Term(0),
+            published_view: Arc::new(RwLock::new(Arc::new(RoomView::empty()))),
+            is_view_stale: AtomicBool::new(true),
+            recorder: None,
+            paused_at: None,
+            latest_time: None,
+            generation: 0,
+            ping_intervals: IntervalHistogram::new(),
+            last_election: None,
+            representatives: HashMap::new(),
+            identities: HashMap::new(),
+            leader_changes: VecDeque::new(),
+            quality_reelection_blocked_until: None,
+            backoff_rng: 0,
+            probation_until: None,
+            retiring_leader: None,
+            lease_expires_at: None,
+            unreachable_by_leader: Vec::new(),
+            converged_term: None,
+            provisional_previous_leader: None,
+            event_history: VecDeque::new(),
+            stats_samples: VecDeque::new(),
+            pending_election_record: None,
+            election_records: VecDeque::new(),
+            next_stats_at: None,
+            checkpoints: VecDeque::new(),
+            kick_vote: None,
+            kick_proposals: 0,
+            update_deferred: false,
+            critical_section: None,
+            stuck_election_since: None,
+            stuck_election_attempts: 0,
+            health_score: 100,
+            metrics: RoomMetrics::default(),
+            #[cfg(feature = "snapshot")]
+            persistence: None,
+            closing: None,
         }
     }
 }
@@ -203,57 +1290,99 @@ impl Room {
     }
 
     pub fn new_with_config(config: RoomConfig) -> Self {
+        Self::from_config(config)
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Creates a room for any knowledge type, use [Room::new_with_config] for the default [Knowledge]
+    pub fn from_config(config: RoomConfig) -> Self {
         Self {
+            backoff_rng: config.leader_stability.backoff_seed,
             config,
             ..Default::default()
         }
     }
 
-    /// checks if most connections, that are on the same term, has lost connection to leader
-    fn has_most_lost_connection_to_leader(&self) -> bool {
-        self.connections
-            .iter()
-            .filter(|(_, connection)| {
-                connection.has_connection_host == ConnectionToLeader::Disconnected
-                    && connection.last_reported_term == Some(self.term)
-            })
+    /// checks if most connections, that are on the same term, has lost connection to leader.
+    /// Connections that are pending, joining or quarantined do not vote, see [RoomConfig::sustained_leader_loss].
+    fn has_most_lost_connection_to_leader(&self, time: Instant) -> bool {
+        if !self.scan.may_down_vote(self.term) {
+            return false;
+        }
+        let voters = self
+            .connections
+            .values()
+            .filter(|connection| connection.takes_part_in_election());
+        let voter_count = voters.clone().count();
+        voters
+            .filter(|connection| connection.is_down_voting(self.term, &self.config, time))
             .count()
-            > self.connections.len() / 2
-    }
-
-    fn connection_with_most_knowledge_and_acceptable_quality(
-        &self,
-        exclude_index: Option<ConnectionIndex>,
-    ) -> Option<ConnectionIndex> {
-        self.connections
-            .iter()
-            .filter(|(_, connection)| exclude_index.map_or(true, |ex_id| connection.id != ex_id))
-            .max_by_key(|(_, connection)| connection.knowledge)
-            .map(|(_, connection)| connection.id)
+            > voter_count / 2
     }
 
     fn switch_leader(&mut self, leader_index: Option<ConnectionIndex>) {
+        let previous = self.leader_index;
         self.leader_index = leader_index;
+        self.begin_term_activation(previous);
+        // What the previous leader could reach says nothing about the new one
+        self.unreachable_by_leader.clear();
         // We start a new term, since we have a new leader
         self.term.next();
-        debug!("elected a new leader {:?} for the term {}", self.leader_index, self.term)
+        debug!("elected a new leader {:?} for the term {}", self.leader_index, self.term);
+        #[cfg(feature = "tracing")]
+        tracing::info!(room = %self.id, term = %self.term, leader = ?self.leader_index, "leader changed");
+        self.push_event(RoomEvent::LeaderChanged {
+            term: self.term,
+            leader: self.leader_index,
+        });
+        self.notify_about(self.leader_index, NotificationReason::LeaderChanged { term: self.term });
+        if let Some(sink) = &self.metrics_sink {
+            sink.leader_changed();
+        }
+        self.record_leader_change();
+        self.begin_probation();
+        self.begin_retirement(previous);
+        self.renew_lease(self.latest_time);
+        self.announce_leader_to_all();
+        #[cfg(feature = "snapshot")]
+        self.checkpoint_leader_change();
+    }
+
+    fn switch_leader_to_best_knowledge_and_quality(&mut self, trigger: ElectionTrigger) {
+        let report = self.evaluate_election(self.leader_index);
+        self.switch_leader(report.winner);
+        self.remember_election(report, trigger);
     }
 
-    fn switch_leader_to_best_knowledge_and_quality(&mut self) {
-        let leader_index =
-            self.connection_with_most_knowledge_and_acceptable_quality(self.leader_index);
-        self.switch_leader(leader_index)
+    /// Elects among everyone once [RoomConfig::min_connections_for_election] have joined. Like the very first
+    /// connection, `newest` is appointed if nobody is eligible yet, unless it is vetoed by a [LeaderEligibility].
+    fn elect_first_leader(&mut self, newest: ConnectionIndex) {
+        let report = self.evaluate_election(None);
+        let fallback = Some(newest).filter(|newest| !self.is_vetoed(&self.connections[newest]));
+        self.switch_leader(report.winner.or(fallback));
+        self.remember_election(report, ElectionTrigger::FirstLeader);
     }
 
-    fn change_leader_if_down_voted(&mut self) -> bool {
+    /// Replaces the leader with the best candidate, unless [RoomConfig::leader_stability] holds the change back
+    fn replace_leader_if_allowed(&mut self, time: Instant, trigger: ElectionTrigger) -> bool {
+        let report = self.evaluate_election(self.leader_index);
+        if !self.is_leader_change_allowed(report.winner, time) {
+            return false;
+        }
+        self.switch_leader(report.winner);
+        self.remember_election(report, trigger);
+        true
+    }
+
+    fn change_leader_if_down_voted(&mut self, time: Instant) -> bool {
         if self.leader_index.is_none() {
             return false;
         }
 
-        if self.has_most_lost_connection_to_leader() {
+        if self.has_most_lost_connection_to_leader(time) {
             info!("most members have down-voted leader {}, so switching to a new one", self.leader_index.unwrap());
-            self.switch_leader_to_best_knowledge_and_quality();
-            return true;
+            return self.replace_leader_if_allowed(time, ElectionTrigger::DownVoted);
         }
 
         false
@@ -263,7 +1392,7 @@ impl Room {
         self.connections.len() > 1 || self.config.allowed_to_remove_single_leader
     }
 
-    fn switch_leader_if_non_responsive(&mut self) {
+    fn switch_leader_if_non_responsive(&mut self, time: Instant) {
         if self.leader_index.is_none() {
             return;
         }
@@ -271,44 +1400,131 @@ impl Room {
         let leader_connection = self.connections.get(&self.leader_index.unwrap()).unwrap();
         if leader_connection.assessment() == QualityAssessment::RecommendDisconnect
             && self.is_possible_to_switch_leader()
+            && !self.is_backing_off_reelection(time)
+            && self.leader_on_probation(time).is_none()
         {
             debug!("leader {} connection has bad quality, switching to a new leader", self.leader_index.unwrap());
-            self.switch_leader_to_best_knowledge_and_quality()
+            self.replace_leader_if_allowed(time, ElectionTrigger::BadQuality);
+        }
+    }
+
+    fn is_connection_value_used(&self, value: u32) -> bool {
+        self.connections.keys().any(|index| index.value() == value)
+    }
+
+    /// The value after `value`, wrapping around to 1 after [RoomConfig::max_connection_index]
+    fn next_connection_value(&self, value: u32) -> u32 {
+        if value >= self.config.max_connection_index {
+            1
+        } else {
+            value + 1
         }
     }
 
-    fn find_unique_connection_index(&self) -> ConnectionIndex {
-        let mut candidate = self.id;
+    fn find_unique_connection_value(&self) -> Result<u32, RoomError> {
+        let start = self.next_connection_value(self.id.value());
+        let mut candidate = start;
 
-        while self.connections.contains_key(&candidate) {
-            candidate.next();
-            if candidate == self.id {
-                panic!("No unique connection index available");
+        while self.is_connection_value_used(candidate) {
+            candidate = self.next_connection_value(candidate);
+            if candidate == start {
+                return Err(RoomError::NoConnectionIndexAvailable);
             }
         }
 
-        candidate
+        Ok(candidate)
     }
 
-    pub fn create_connection(&mut self, time: Instant) -> ConnectionIndex {
-        self.id.next();
-        let connection_id = self.find_unique_connection_index();
-        let connection = Connection::new(
-            connection_id,
-            time,
-            self.config.pings_per_second_threshold,
-        );
+    /// Adds a connection to the room, the first connection is appointed leader.
+    ///
+    /// Index values are handed out in increasing order, wrapping around after [RoomConfig::max_connection_index],
+    /// so a value is not reused until all other values have been handed out.
+    /// Returns [RoomError::NoConnectionIndexAvailable] if every value is in use.
+    pub fn create_connection(&mut self, time: Instant) -> Result<ConnectionIndex, RoomError> {
+        self.record(time, RecordedInput::CreateConnection);
+        let time = self.observe_time(time);
+        self.ensure_open()?;
+        self.ensure_capacity()?;
+        let value = self.find_unique_connection_value()?;
+        self.id = ConnectionIndex::new(value);
+        let connection_id = self.add_connection(value, Vec::new(), time);
+        self.publish_view();
+        Ok(connection_id)
+    }
+
+    /// Adds a connection using the index value chosen by the host, e.g. a player id assigned by matchmaking.
+    ///
+    /// The generation of `requested` is ignored, the room assigns a new one to the returned index.
+    /// Returns [RoomError::ConnectionIndexInUse] if a connection with the same value is already in the room.
+    pub fn create_connection_with_id(
+        &mut self,
+        requested: ConnectionIndex,
+        time: Instant,
+    ) -> Result<ConnectionIndex, RoomError> {
+        self.record(time, RecordedInput::CreateConnectionWithId { value: requested.value() });
+        let time = self.observe_time(time);
+        self.ensure_open()?;
+        self.ensure_capacity()?;
+        if let Some(current) = self.connections.keys().find(|index| index.value() == requested.value()) {
+            return Err(RoomError::ConnectionIndexInUse(*current));
+        }
+        let connection_id = self.add_connection(requested.value(), Vec::new(), time);
+        self.publish_view();
+        Ok(connection_id)
+    }
+
+    pub(crate) fn add_connection(&mut self, value: u32, tags: Vec<String>, time: Instant) -> ConnectionIndex {
+        let mut connection = self.new_connection(value, time);
+        connection.tags = tags;
+        let connection_id = connection.id;
 
         info!("create connection {}", connection);
 
-        if self.leader_index.is_none() {
-            info!("this was first connection {}, so this will be leader:{}", &connection, self.id);
-            self.switch_leader(Some(self.id));
+        self.insert_connection(connection);
+        self.admit_connection(connection_id);
+
+        self.assert_invariants();
+        connection_id
+    }
+
+    /// A connection with the next generation of `value`, not yet in the room
+    pub(crate) fn new_connection(&mut self, value: u32, time: Instant) -> Connection<K> {
+        self.generation = self.generation.wrapping_add(1);
+        let connection_id = ConnectionIndex::with_generation(value, self.generation);
+        let mut connection = Connection::new(
+            connection_id,
+            time,
+            &self.config,
+        );
+        if self.config.warm_up_pings > 0 {
+            connection.state = ConnectionState::Joining;
         }
+        connection
+    }
 
-        self.connections.insert(self.id, connection);
-
-        self.id
+    /// Lets a connection that is in the room take part: the first connection is appointed leader (or the first
+    /// election is held, see [RoomConfig::min_connections_for_election]), later ones are told who the leader is
+    /// and get the state synced.
+    pub(crate) fn admit_connection(&mut self, connection_id: ConnectionIndex) {
+        let is_late_joiner = self.leader_index.is_some();
+        let min_connections = self.config.min_connections_for_election;
+        if is_late_joiner {
+            self.announce_leader_to(connection_id);
+            self.begin_state_sync(connection_id);
+        } else if min_connections <= 1 {
+            if self.is_vetoed(&self.connections[&connection_id]) {
+                info!("first connection {} is not allowed to lead, waiting for another", connection_id);
+                self.announce_leader_to(connection_id);
+            } else {
+                info!("this was first connection {}, so this will be leader", connection_id);
+                self.switch_leader(Some(connection_id));
+            }
+        } else if self.admitted_connection_count() >= min_connections {
+            info!("{} connections have joined, electing the first leader", self.admitted_connection_count());
+            self.elect_first_leader(connection_id);
+        } else {
+            self.announce_leader_to(connection_id);
+        }
     }
 
     /// Determines if a given connection is aware of the current term.
@@ -331,7 +1547,7 @@ impl Room {
     /// use std::time::Instant;
     /// use conclave_room_session::Room;
     /// let mut room = Room::new();
-    /// let some_connection_index = room.create_connection(Instant::now());
+    /// let some_connection_index = room.create_connection(Instant::now()).unwrap();
     /// let is_aware = room.connection_knows_about_current_term(some_connection_index);
     /// if is_aware {
     ///     println!("The connection is aware of the current term.");
@@ -353,80 +1569,467 @@ impl Room {
     }
 
     pub fn update(&mut self, time: Instant) {
-        trace!("update connections {} time:{:?}", self.connections.len(), time);
-        for connection in self.connections.values_mut() {
-            connection.update(time);
+        #[cfg(feature = "testing")]
+        self.deliver_delayed_pings(time);
+        self.record(time, RecordedInput::Update);
+        let time = self.observe_time(time);
+        self.update_connections(time);
+        self.publish_view();
+    }
+
+    fn update_connections(&mut self, time: Instant) {
+        if self.paused_at.is_some() {
+            trace!("room is paused, skipping update");
+            return;
         }
+        self.close_if_drained(time);
+        if self.is_closed() {
+            return;
+        }
+        self.update_deferred = false;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("update", room = %self.id, term = %self.term).entered();
+        trace!("update connections {} time:{:?}", self.connections.len(), time);
+        self.assess_connections(time);
+        #[cfg(feature = "testing")]
+        self.apply_forced_assessments();
+        self.update_ping_pacing();
+        self.expire_pending(time);
 
         if self.config.disconnect_bad_connections {
-            let mut connection_index_vector = Vec::<ConnectionIndex>::new();
-            for connection in self.connections.values_mut() {
-                if connection.assessment() == QualityAssessment::RecommendDisconnect {
+            self.update_quarantine(time);
+            self.disconnect_bad_connections(time);
+        }
+
+        let leader_before = self.leader_index;
+        if !self.is_in_critical_section(time) {
+            self.reconcile_partitions();
+            let leader_was_changed = self.expire_lease(time) || self.change_leader_if_down_voted(time);
+            if !leader_was_changed {
+                self.switch_leader_if_non_responsive(time);
+            }
+            if self.leader_index == leader_before {
+                self.rebalance_leader_load(time);
+            }
+            self.watch_election(leader_before, time);
+        }
+        self.restore_leader_if_leaderless();
+        if self.config.disconnect_bad_connections && self.leader_index != leader_before {
+            // The replaced leader was kept connected for as long as it led
+            self.disconnect_bad_connections(time);
+        }
+
+        self.reassign_state_sync_donors();
+        self.update_knowledge_lag();
+        self.update_representatives();
+        self.update_probation(time);
+        self.update_retirement(time);
+        self.update_term_convergence();
+        self.update_election_record();
+        self.update_term_activation();
+        self.update_health();
+        self.resolve_kick_vote(time);
+        self.sample_stats_if_due(time);
+
+        if let Some(sink) = &self.metrics_sink {
+            sink.connection_count(self.connections.len());
+            sink.connections_by_tag(&self.connections_by_tag());
+            sink.room_state(self.state(time));
+        }
+        self.escalate_abandonment(time);
+        #[cfg(feature = "snapshot")]
+        self.checkpoint_if_due(time);
+
+        self.assert_invariants();
+    }
+
+    /// Disconnects the assessed connections that are recommended to disconnect. Unless disconnected connections
+    /// are destroyed, the leader is replaced first, see [Room::switch_leader_if_non_responsive], and if it can not
+    /// be, it stays connected.
+    fn disconnect_bad_connections(&mut self, time: Instant) {
+        let quarantine_period = self.config.quarantine_period;
+        let kept_leader = self.leader_index.filter(|_| !self.config.destroy_disconnected_connections);
+        let mut connection_index_vector = Vec::<ConnectionIndex>::new();
+        let mut disconnected = Vec::<ConnectionIndex>::new();
+        // Only the assessed connections can be recommended to disconnect
+        for connection_index in &self.scan.assessed {
+            let Some(connection) = self.connections.get_mut(connection_index) else {
+                continue;
+            };
+            if connection.assessment() == QualityAssessment::RecommendDisconnect
+                && connection.state != ConnectionState::Pending
+                && kept_leader != Some(connection.id)
+                && !connection.is_serving_quarantine(quarantine_period, time)
+            {
+                if connection.state != ConnectionState::Disconnected {
                     connection.state = ConnectionState::Disconnected;
                     debug!("disconnecting {}", connection);
-                    if self.config.destroy_disconnected_connections {
-                        connection_index_vector.push(connection.id);
-                    }
+                    disconnected.push(connection.id);
+                }
+                if self.config.destroy_disconnected_connections {
+                    connection_index_vector.push(connection.id);
                 }
             }
+        }
+        disconnected.sort_by_key(|index| index.value());
+        for connection_index in disconnected {
+            self.on_disconnected(connection_index, DisconnectReason::PoorQuality);
+        }
 
-            if self.config.destroy_disconnected_connections {
-                for connection_index in connection_index_vector {
-                    debug!("destroying {}", connection_index);
-                    self.destroy_connection(connection_index);
-                }
+        if self.config.destroy_disconnected_connections {
+            // The leader goes first, so the room never has a disconnected leader after a removal
+            let leader_index = self.leader_index;
+            connection_index_vector.sort_by_key(|index| (Some(*index) != leader_index, index.value()));
+            connection_index_vector.dedup();
+            for connection_index in connection_index_vector {
+                debug!("destroying {}", connection_index);
+                self.notify_about(Some(connection_index), NotificationReason::Destroyed);
+                self.remove_connection(connection_index);
             }
         }
+    }
 
-        let leader_was_changed = self.change_leader_if_down_voted();
-        if leader_was_changed {
-            return;
+    pub fn state(&self, now: Instant) -> RoomState {
+        if self.is_closed() {
+            RoomState::Closed
+        } else if self.is_closing() {
+            RoomState::Closing
+        } else if self.is_abandoned(now) {
+            RoomState::Abandoned
+        } else if self.leader_index.is_none() {
+            RoomState::Leaderless
+        } else if self.is_leader_provisional() {
+            RoomState::ProvisionalLeader
+        } else {
+            RoomState::Active
         }
+    }
+
+    /// Time between consecutive pings from the same connection, for all connections in the room
+    pub fn ping_intervals(&self) -> &IntervalHistogram {
+        &self.ping_intervals
+    }
+
+    /// Counters for the whole room since it was created
+    pub fn metrics(&self) -> RoomMetrics {
+        self.metrics
+    }
 
-        self.switch_leader_if_non_responsive();
+    /// Measurements are reported to the sink as they happen
+    pub fn set_metrics_sink(&mut self, sink: Box<dyn MetricsSink>) {
+        self.metrics_sink = Some(sink);
     }
 
-    /// True if the room has not received a ping from anyone in `ABANDONED_TIMEOUT` amount of time
+    /// True if the room has not received a ping from anyone in [RoomConfig::abandoned_after] amount of time
     pub fn is_abandoned(&self, now: Instant) -> bool {
         let Some(prev) = self.latest_ping_timestamp else {
             // This room has never received a single ping
             return true;
         };
 
-        now - prev > ABANDONED_TIMEOUT
+        now.saturating_duration_since(prev) > self.config.abandoned_after
     }
 
     /// Receiving a ping command from a connection
-    pub fn on_ping(
+    ///
+    /// Pings from connections running a protocol version older than [RoomConfig::min_supported_version]
+    /// are refused: the connection is set to [ConnectionState::Disconnected] and a [RoomEvent::ProtocolMismatch] is emitted.
+    /// If a [PingAuthenticator] is set, pings that fail verification are dropped and counted, and the connection is kicked
+    /// when it reaches [RoomConfig::max_auth_failures].
+    /// Pings carrying a [PingPayload::sequence] that is not newer than the last accepted one are ignored, so duplicated
+    /// or reordered datagrams can not overwrite newer information or inflate the measured ping rate.
+    pub fn on_ping(&mut self, connection_index: ConnectionIndex, ping: &PingPayload<K>, time: Instant) -> PingOutcome {
+        #[cfg(feature = "testing")]
+        if let Some(outcome) = self.inject_ping_fault(connection_index, ping, time) {
+            return outcome;
+        }
+        let outcome = self.receive_ping(connection_index, ping, time);
+        self.publish_view();
+        outcome
+    }
+
+    fn receive_ping(&mut self, connection_index: ConnectionIndex, ping: &PingPayload<K>, time: Instant) -> PingOutcome {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("on_ping", room = %self.id, connection = %connection_index, term = %self.term).entered();
+        self.record_ping(connection_index, ping, time);
+        let time = self.observe_time(time);
+        if let Err(error) = self.validate_connection(connection_index) {
+            info!("ignoring {} from {}", ping, error);
+            return self.reject_ping(PingRejection::InvalidConnection(error));
+        }
+        if let Some(authenticator) = &self.authenticator {
+            if !authenticator.verify(connection_index, ping) {
+                self.on_auth_failure(connection_index);
+                return self.reject_ping(PingRejection::AuthenticationFailed);
+            }
+        }
+
+        let min_supported_version = self.config.min_supported_version;
+        let connection = self.connections.get_mut(&connection_index).unwrap();
+        if ping.protocol_version < min_supported_version {
+            info!("refusing {}, protocol version {} is older than {}", connection, ping.protocol_version, min_supported_version);
+            let was_disconnected = connection.state == ConnectionState::Disconnected;
+            connection.state = ConnectionState::Disconnected;
+            self.push_event(RoomEvent::ProtocolMismatch {
+                connection: connection_index,
+                version: ping.protocol_version,
+                min_supported_version,
+            });
+            if !was_disconnected {
+                self.on_disconnected(connection_index, DisconnectReason::ProtocolMismatch);
+            }
+            if self.leader_index == Some(connection_index) {
+                self.switch_leader_to_best_knowledge_and_quality(ElectionTrigger::BadQuality);
+            }
+            return self.reject_ping(PingRejection::ProtocolMismatch);
+        }
+
+        if let Err(rejection) = connection.check_sequence(ping) {
+            trace!("ignoring {} from {}: {:?}", ping, connection, rejection);
+            return self.reject_ping(rejection);
+        }
+
+        if connection.state == ConnectionState::Pending {
+            self.activate_pending(connection_index, time);
+        }
+        let connection = self.connections.get_mut(&connection_index).unwrap();
+        if connection.last_reported_term.is_some() {
+            let interval = time.saturating_duration_since(connection.quality.last_ping_at);
+            connection.ping_intervals.record(interval);
+            self.ping_intervals.record(interval);
+            if let Some(sink) = &self.metrics_sink {
+                sink.ping_interval(interval);
+            }
+        }
+
+        let is_within_rate = connection.last_reported_term.is_none() || connection.quality.is_within_rate(time);
+
+        self.latest_ping_timestamp = Some(time);
+        self.abandonment_stage = AbandonmentStage::Active;
+        self.decay_suspicion(connection_index, time);
+        self.check_reported_knowledge(connection_index, ping.knowledge, time);
+        self.attest_knowledge(connection_index, ping, time);
+        self.cast_kick_ballot(connection_index, ping.kick_vote);
+        self.scan.observe_ping(ping);
+        if self.connections.get_mut(&connection_index).unwrap().on_ping(ping, time, &self.config) {
+            debug!("{} changes its down-vote too often, not counting it", connection_index);
+            self.metrics.down_vote_violations = self.metrics.down_vote_violations.saturating_add(1);
+            if let Some(sink) = &self.metrics_sink {
+                sink.down_vote_rate_limited();
+            }
+        }
+        self.advance_warm_up(connection_index, is_within_rate);
+        self.update_idle(connection_index, ping.last_input_age);
+        self.update_leader_reachability(connection_index, &ping.unreachable);
+        if self.leader_index == Some(connection_index) {
+            self.renew_lease(Some(time));
+        }
+        if !self.is_too_large_to_update_after_ping() {
+            self.update_connections(time);
+        }
+
+        PingOutcome::Accepted
+    }
+
+    fn reject_ping(&mut self, rejection: PingRejection) -> PingOutcome {
+        self.metrics.rejected_pings.count(rejection);
+        if let Some(sink) = &self.metrics_sink {
+            sink.ping_rejected(rejection);
+        }
+        PingOutcome::Rejected(rejection)
+    }
+
+    fn on_disconnected(&mut self, connection_index: ConnectionIndex, reason: DisconnectReason) {
+        #[cfg(feature = "tracing")]
+        tracing::info!(room = %self.id, connection = %connection_index, term = %self.term, reason = reason.as_str(), "disconnected");
+        self.push_event(RoomEvent::Disconnected {
+            connection: connection_index,
+            reason,
+        });
+        self.notify_about(Some(connection_index), NotificationReason::Disconnected(reason));
+        if let Some(sink) = &self.metrics_sink {
+            sink.disconnected(reason);
+        }
+    }
+
+    fn on_auth_failure(&mut self, connection_index: ConnectionIndex) {
+        let connection = self.connections.get_mut(&connection_index).unwrap();
+        connection.auth_failures += 1;
+        let failures = connection.auth_failures;
+        info!("ping from {} failed authentication ({} failures)", connection, failures);
+        self.push_event(RoomEvent::AuthFailure {
+            connection: connection_index,
+            failures,
+        });
+
+        if self.config.max_auth_failures.is_some_and(|max_failures| failures >= max_failures) {
+            info!("kicking {} after {} authentication failures", connection_index, failures);
+            self.kick(connection_index, KickReason::AuthenticationFailed);
+        }
+    }
+
+    /// Tells the connection that it was kicked, removes it and emits [RoomEvent::Kicked]
+    pub(crate) fn kick(&mut self, connection_index: ConnectionIndex, reason: KickReason) {
+        self.push_outgoing(connection_index, OutgoingIntent::YouWereKicked { reason });
+        self.notify_about(Some(connection_index), NotificationReason::Kicked(reason));
+        self.remove_connection(connection_index);
+        self.push_event(RoomEvent::Kicked {
+            connection: connection_index,
+            reason,
+        });
+    }
+
+    /// Every ping is verified by the authenticator before it is applied
+    pub fn set_ping_authenticator(&mut self, authenticator: Box<dyn PingAuthenticator<K>>) {
+        self.authenticator = Some(authenticator);
+    }
+
+    /// Takes all events that have been collected since the last call
+    pub fn drain_events(&mut self) -> Vec<RoomEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Thin shim for the previous `on_ping` signature, forwards to [Room::on_ping]
+    #[deprecated(note = "construct a `PingPayload` and call `on_ping`")]
+    pub fn on_ping_legacy(
         &mut self,
         connection_index: ConnectionIndex,
         term: Term,
         has_connection_to_host: &ConnectionToLeader,
-        knowledge: Knowledge,
+        knowledge: K,
         time: Instant,
     ) {
-        self.latest_ping_timestamp = Some(time);
-        let connection = self.connections.get_mut(&connection_index).unwrap();
-        connection.on_ping(term, has_connection_to_host, knowledge, time);
-        self.update(time);
+        let ping = PingPayload::new()
+            .with_term(term)
+            .with_connection_to_leader(*has_connection_to_host)
+            .with_knowledge(knowledge);
+        self.on_ping(connection_index, &ping, time);
+    }
+
+    /// Checks that the index refers to a connection in the room, and not to a destroyed one
+    pub fn validate_connection(&self, connection_index: ConnectionIndex) -> Result<(), RoomError> {
+        if self.connections.contains_key(&connection_index) {
+            return Ok(());
+        }
+        match self.connections.keys().find(|index| index.value() == connection_index.value()) {
+            Some(current) => Err(RoomError::StaleHandle {
+                handle: connection_index,
+                current: *current,
+            }),
+            None => Err(RoomError::UnknownConnection(connection_index)),
+        }
+    }
+
+    /// Identifies the room in logs. It is the value of the most recently allocated connection index, without a
+    /// generation, and new connections are given the next free value after it
+    pub fn id(&self) -> ConnectionIndex {
+        self.id
+    }
+
+    /// The connection appointed leader for the current term, `None` if no connection could be elected
+    pub fn leader(&self) -> Option<ConnectionIndex> {
+        self.leader_index
+    }
+
+    /// Increased every time the leader changes
+    pub fn term(&self) -> Term {
+        self.term
+    }
+
+    /// The config the room was built with, or given to [Room::update_config] last
+    pub fn config(&self) -> &RoomConfig {
+        &self.config
+    }
+
+    /// Number of connections in the room, including pending and disconnected ones
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// True if `connection_index` is a connection in the room, a stale handle is not
+    pub fn contains(&self, connection_index: ConnectionIndex) -> bool {
+        self.connections.contains_key(&connection_index)
+    }
+
+    /// Makes the connection leader in a new term, regardless of how it would place in an election
+    pub fn appoint_leader(&mut self, connection_index: ConnectionIndex) -> Result<(), RoomError> {
+        self.record_untimed(RecordedInput::AppointLeader {
+            connection: connection_index.value(),
+            generation: connection_index.generation(),
+        });
+        self.validate_connection(connection_index)?;
+        if self.leader_index != Some(connection_index) {
+            info!("appointing {} as leader of room {}", connection_index, self.id);
+            self.switch_leader(Some(connection_index));
+        }
+        self.assert_invariants();
+        self.publish_view();
+        Ok(())
     }
 
-    pub fn get_mut(&mut self, connection_index: ConnectionIndex) -> &mut Connection {
+    /// # Panics
+    ///
+    /// If the connection is not in the room, see [Room::try_get_mut]
+    pub fn get_mut(&mut self, connection_index: ConnectionIndex) -> &mut Connection<K> {
+        self.invalidate_view();
         self.connections.get_mut(&connection_index).unwrap()
     }
 
-    pub fn get(&self, connection_index: ConnectionIndex) -> &Connection {
+    /// # Panics
+    ///
+    /// If the connection is not in the room, see [Room::try_get]
+    pub fn get(&self, connection_index: ConnectionIndex) -> &Connection<K> {
         self.connections.get(&connection_index).unwrap()
     }
 
-    pub fn destroy_connection(&mut self, connection_index: ConnectionIndex) {
+    pub fn try_get(&self, connection_index: ConnectionIndex) -> Result<&Connection<K>, RoomError> {
+        self.validate_connection(connection_index)?;
+        Ok(self.get(connection_index))
+    }
+
+    pub fn try_get_mut(&mut self, connection_index: ConnectionIndex) -> Result<&mut Connection<K>, RoomError> {
+        self.validate_connection(connection_index)?;
+        Ok(self.get_mut(connection_index))
+    }
+
+    pub fn destroy_connection(&mut self, connection_index: ConnectionIndex) -> Result<(), RoomError> {
+        self.record_untimed(RecordedInput::Destroy {
+            connection: connection_index.value(),
+            generation: connection_index.generation(),
+        });
+        self.validate_connection(connection_index)?;
+        self.remove_connection(connection_index);
+        self.publish_view();
+        Ok(())
+    }
+
+    /// Removes the connection, electing a new leader if it was the leader, and returns it
+    fn remove_connection(&mut self, connection_index: ConnectionIndex) -> Option<Connection<K>> {
+        let removed = self.connections.remove(&connection_index);
+        if let Some(identity) = removed.as_ref().and_then(|connection| connection.identity.as_deref()) {
+            // A connection that replaced this one may have taken the identity over already
+            if self.identities.get(identity) == Some(&connection_index) {
+                self.identities.remove(identity);
+            }
+        }
+        self.unreachable_by_leader.retain(|index| *index != connection_index);
+        self.cancel_state_sync(connection_index);
+        self.withdraw_kick_vote(connection_index);
         if let Some(leader_index) = self.leader_index {
             if leader_index == connection_index {
                 // If it was the leader, we must select a new leader
-                self.switch_leader_to_best_knowledge_and_quality();
+                self.switch_leader_to_best_knowledge_and_quality(ElectionTrigger::LeaderLeft);
             }
         }
-        self.connections.remove(&connection_index);
+        self.reassign_state_sync_donors();
+        self.update_representatives();
+        self.assert_invariants();
+        removed
     }
 
     pub fn set_debug_name(&mut self, connection_index: ConnectionIndex, name: &str) {
@@ -435,21 +2038,28 @@ impl Room {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use std::time::{Duration, Instant};
 
     use log::info;
     use test_log::test;
 
-    use conclave_types::{ConnectionToLeader, Knowledge, Term};
+    use core::cmp::Ordering;
+    use core::fmt;
 
-    use crate::{QualityAssessment, Room, RoomConfig};
+    use conclave_types::{ConnectionToLeader, Knowledge, KnowledgeOrd, Term};
+
+    use crate::{
+        ConnectionIndex, ConnectionState, DisconnectReason, KickReason, LeaderStability, PingAuthenticator, PingOutcome, PingPayload, PingRejection,
+        QualityAssessment, Room, RoomConfig, RoomError, RoomEvent,
+    };
 
     #[test]
     fn check_ping() {
         let mut room = Room::new();
         let now = Instant::now();
-        let connection_id = room.create_connection(now);
+        let connection_id = room.create_connection(now).unwrap();
         assert_eq!(connection_id.value(), 1);
         let knowledge: Knowledge = Knowledge(42);
         let term: Term = Term(1);
@@ -457,18 +2067,20 @@ mod tests {
         {
             room.on_ping(
                 connection_id,
-                term,
-                &ConnectionToLeader::Connected,
-                knowledge,
+                &PingPayload::new()
+                    .with_term(term)
+                    .with_connection_to_leader(ConnectionToLeader::Connected)
+                    .with_knowledge(knowledge),
                 now,
             );
 
             let time_in_future = now + Duration::new(10, 0);
             room.on_ping(
                 connection_id,
-                term,
-                &ConnectionToLeader::Connected,
-                knowledge,
+                &PingPayload::new()
+                    .with_term(term)
+                    .with_connection_to_leader(ConnectionToLeader::Connected)
+                    .with_knowledge(knowledge),
                 time_in_future,
             );
             assert_eq!(
@@ -478,16 +2090,221 @@ mod tests {
         }
     }
 
+    #[test]
+    #[allow(deprecated)]
+    fn legacy_ping_shim() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let connection_id = room.create_connection(now).unwrap();
+        room.on_ping_legacy(
+            connection_id,
+            room.term,
+            &ConnectionToLeader::Connected,
+            Knowledge(7),
+            now,
+        );
+
+        let connection = room.get(connection_id);
+        assert_eq!(connection.knowledge, Knowledge(7));
+        assert_eq!(connection.has_connection_host, ConnectionToLeader::Connected);
+        assert_eq!(connection.last_reported_term, Some(room.term));
+    }
+
+    #[test]
+    fn refuse_unsupported_protocol_version() {
+        let mut room = RoomConfig::new().with_min_supported_version(2).build().unwrap();
+        let now = Instant::now();
+        let connection_id = room.create_connection(now).unwrap();
+        room.drain_events();
+
+        let outcome = room.on_ping(
+            connection_id,
+            &PingPayload::new()
+                .with_term(room.term)
+                .with_knowledge(Knowledge(42))
+                .with_protocol_version(1),
+            now,
+        );
+
+        assert_eq!(outcome, PingOutcome::Rejected(PingRejection::ProtocolMismatch));
+        assert_eq!(room.get(connection_id).state, ConnectionState::Disconnected);
+        assert_eq!(room.get(connection_id).knowledge, Knowledge(0));
+        assert!(room.latest_ping_timestamp.is_none());
+        assert_eq!(
+            room.drain_events(),
+            vec![
+                RoomEvent::ProtocolMismatch {
+                    connection: connection_id,
+                    version: 1,
+                    min_supported_version: 2,
+                },
+                RoomEvent::Disconnected {
+                    connection: connection_id,
+                    reason: DisconnectReason::ProtocolMismatch,
+                },
+                RoomEvent::LeaderChanged {
+                    term: Term(2),
+                    leader: None,
+                },
+                RoomEvent::ElectionRanked {
+                    term: Term(2),
+                    ranking: vec![],
+                },
+            ]
+        );
+        assert!(room.drain_events().is_empty());
+
+        let outcome = room.on_ping(connection_id, &PingPayload::new().with_protocol_version(2), now);
+        assert!(outcome.is_accepted());
+        assert_eq!(room.get(connection_id).protocol_version, Some(2));
+    }
+
+    #[test]
+    fn ignore_duplicate_and_reordered_pings() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let connection_id = room.create_connection(now).unwrap();
+        let term = room.term;
+
+        let ping = |sequence, has_connection_to_leader| {
+            PingPayload::new()
+                .with_term(term)
+                .with_connection_to_leader(has_connection_to_leader)
+                .with_sequence(sequence)
+        };
+
+        assert!(room.on_ping(connection_id, &ping(10, ConnectionToLeader::Connected), now).is_accepted());
+        assert_eq!(
+            room.on_ping(connection_id, &ping(10, ConnectionToLeader::Connected), now),
+            PingOutcome::Rejected(PingRejection::Duplicate)
+        );
+        assert_eq!(
+            room.on_ping(connection_id, &ping(9, ConnectionToLeader::Unknown), now),
+            PingOutcome::Rejected(PingRejection::OutOfOrder)
+        );
+
+        let connection = room.get(connection_id);
+        assert_eq!(connection.has_connection_host, ConnectionToLeader::Connected);
+        assert_eq!(connection.dropped_pings().duplicates, 1);
+        assert_eq!(connection.dropped_pings().out_of_order, 1);
+
+        assert!(room.on_ping(connection_id, &ping(11, ConnectionToLeader::Unknown), now).is_accepted());
+        assert_eq!(room.get(connection_id).has_connection_host, ConnectionToLeader::Unknown);
+    }
+
+    #[test]
+    fn room_can_be_moved_to_another_thread() {
+        // Fails to compile if a field, e.g. a boxed policy or sink, is not Send or Sync
+        fn assert_send_and_sync<T: Send + Sync>() {}
+        assert_send_and_sync::<Room>();
+    }
+
+    #[derive(Debug)]
+    struct ExpectSignature(Vec<u8>);
+
+    impl PingAuthenticator for ExpectSignature {
+        fn verify(&self, _: ConnectionIndex, ping: &PingPayload) -> bool {
+            ping.signature.as_ref() == Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn kick_after_failed_authentication() {
+        let mut room = RoomConfig::new().with_max_auth_failures(2).build().unwrap();
+        room.set_ping_authenticator(Box::new(ExpectSignature(vec![0xca, 0xfe])));
+        let now = Instant::now();
+        let connection_id = room.create_connection(now).unwrap();
+
+        let signed = PingPayload::new().with_knowledge(Knowledge(1)).with_signature(vec![0xca, 0xfe]);
+        assert!(room.on_ping(connection_id, &signed, now).is_accepted());
+        room.drain_events();
+
+        let spoofed = PingPayload::new().with_knowledge(Knowledge(u64::MAX));
+        assert_eq!(
+            room.on_ping(connection_id, &spoofed, now),
+            PingOutcome::Rejected(PingRejection::AuthenticationFailed)
+        );
+        assert_eq!(room.get(connection_id).knowledge, Knowledge(1));
+        assert_eq!(room.get(connection_id).auth_failures(), 1);
+
+        room.on_ping(connection_id, &spoofed.with_signature(vec![0xde, 0xad]), now);
+        assert!(room.connections.is_empty());
+        assert_eq!(
+            room.drain_events(),
+            vec![
+                RoomEvent::AuthFailure {
+                    connection: connection_id,
+                    failures: 1
+                },
+                RoomEvent::AuthFailure {
+                    connection: connection_id,
+                    failures: 2
+                },
+                RoomEvent::LeaderChanged {
+                    term: Term(2),
+                    leader: None
+                },
+                RoomEvent::ElectionRanked {
+                    term: Term(2),
+                    ranking: vec![]
+                },
+                RoomEvent::Kicked {
+                    connection: connection_id,
+                    reason: KickReason::AuthenticationFailed
+                },
+            ]
+        );
+    }
+
+    /// Knowledge where the checksum only tells if the state is the same, not which one is ahead
+    #[derive(Default, Debug, Clone, Copy, PartialEq)]
+    struct TickAndChecksum {
+        tick: u32,
+        checksum: u32,
+    }
+
+    impl fmt::Display for TickAndChecksum {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "tick:{} checksum:{:x}", self.tick, self.checksum)
+        }
+    }
+
+    impl KnowledgeOrd for TickAndChecksum {
+        fn cmp_knowledge(&self, other: &Self) -> Ordering {
+            self.tick.cmp(&other.tick)
+        }
+
+        fn progress(&self) -> u64 {
+            self.tick as u64
+        }
+    }
+
+    #[test]
+    fn elect_using_custom_knowledge() {
+        let mut room = RoomConfig::new().build_with_knowledge::<TickAndChecksum>().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let behind = room.create_connection(now).unwrap();
+        let ahead = room.create_connection(now).unwrap();
+
+        let knowledge = |tick, checksum| PingPayload::new().with_knowledge(TickAndChecksum { tick, checksum });
+        room.on_ping(behind, &knowledge(10, 0xffffffff), now);
+        room.on_ping(ahead, &knowledge(11, 0x1), now);
+
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader_index, Some(ahead));
+    }
+
     #[test]
     fn remove_connection() {
         let mut room = Room::new();
         let now = Instant::now();
-        let connection_id = room.create_connection(now);
+        let connection_id = room.create_connection(now).unwrap();
         assert_eq!(room.connections.len(), 1);
         assert_eq!(connection_id.value(), 1);
         assert_eq!(room.leader_index, Some(connection_id));
 
-        room.destroy_connection(connection_id);
+        room.destroy_connection(connection_id).unwrap();
         assert_eq!(room.connections.len(), 0);
         assert_eq!(room.leader_index, None);
     }
@@ -496,28 +2313,30 @@ mod tests {
     fn change_leader() {
         let mut room = Room::new();
         let now = Instant::now();
-        let connection_id = room.create_connection(now);
+        let connection_id = room.create_connection(now).unwrap();
         let term = room.term;
         assert_eq!(connection_id.value(), 1);
         assert_eq!(room.leader_index.unwrap().value(), 1);
 
-        let supporter_connection_id = room.create_connection(now);
+        let supporter_connection_id = room.create_connection(now).unwrap();
 
         assert_eq!(supporter_connection_id.value(), 2);
         assert_eq!(room.leader_index.unwrap().value(), 1);
 
-        let time_in_future = now + Duration::new(10, 0);
-
         let has_connection_to_host = ConnectionToLeader::Connected;
         let knowledge: Knowledge = Knowledge(42);
 
-        room.on_ping(
-            supporter_connection_id,
-            term,
-            &has_connection_to_host,
-            knowledge,
-            time_in_future,
-        );
+        // A connection that is recommended to disconnect can not be elected, so the supporter keeps pinging
+        for millis in (0..=10_000).step_by(100) {
+            room.on_ping(
+                supporter_connection_id,
+                &PingPayload::new()
+                    .with_term(term)
+                    .with_connection_to_leader(has_connection_to_host)
+                    .with_knowledge(knowledge),
+                now + Duration::from_millis(millis),
+            );
+        }
 
         // Only the supporter connection has reported, so the leader_connection should be disconnected
         assert_eq!(room.leader_index.unwrap().value(), 2);
@@ -527,7 +2346,7 @@ mod tests {
     fn retain_leader_if_single_leader_times_out() {
         let mut room = Room::new();
         let now = Instant::now();
-        let single_leader_connection_id = room.create_connection(now);
+        let single_leader_connection_id = room.create_connection(now).unwrap();
         let term = room.term;
         assert_eq!(single_leader_connection_id.value(), 1);
         assert_eq!(room.leader_index.unwrap().value(), 1);
@@ -539,9 +2358,10 @@ mod tests {
 
         room.on_ping(
             single_leader_connection_id,
-            term,
-            &has_connection_to_host,
-            knowledge,
+            &PingPayload::new()
+                .with_term(term)
+                .with_connection_to_leader(has_connection_to_host)
+                .with_knowledge(knowledge),
             time_in_future,
         );
 
@@ -554,9 +2374,9 @@ mod tests {
         let mut room = RoomConfig::new()
             .allow_remove_single_leader()
             .pings_per_second_threshold(0.9)
-            .build();
+            .build().unwrap();
         let now = Instant::now();
-        let single_leader_connection_id = room.create_connection(now);
+        let single_leader_connection_id = room.create_connection(now).unwrap();
         let term = room.term;
         assert_eq!(single_leader_connection_id.value(), 1);
         assert_eq!(room.leader_index.unwrap().value(), 1);
@@ -570,9 +2390,10 @@ mod tests {
             time += Duration::new(1, 0);
             room.on_ping(
                 single_leader_connection_id,
-                term,
-                &has_connection_to_host,
-                knowledge,
+                &PingPayload::new()
+                    .with_term(term)
+                    .with_connection_to_leader(has_connection_to_host)
+                    .with_knowledge(knowledge),
                 time,
             );
         }
@@ -583,9 +2404,10 @@ mod tests {
             time += Duration::new(2, 0);
             room.on_ping(
                 single_leader_connection_id,
-                term,
-                &has_connection_to_host,
-                knowledge,
+                &PingPayload::new()
+                    .with_term(term)
+                    .with_connection_to_leader(has_connection_to_host)
+                    .with_knowledge(knowledge),
                 time,
             );
         }
@@ -596,9 +2418,9 @@ mod tests {
 
     #[test]
     fn kick_leader_if_single_leader_times_out() {
-        let mut room = RoomConfig::new().allow_remove_single_leader().build();
+        let mut room = RoomConfig::new().allow_remove_single_leader().build().unwrap();
         let now = Instant::now();
-        let single_leader_connection_id = room.create_connection(now);
+        let single_leader_connection_id = room.create_connection(now).unwrap();
         let term = room.term;
         assert_eq!(single_leader_connection_id.value(), 1);
         assert_eq!(room.leader_index.unwrap().value(), 1);
@@ -610,9 +2432,10 @@ mod tests {
 
         room.on_ping(
             single_leader_connection_id,
-            term,
-            &has_connection_to_host,
-            knowledge,
+            &PingPayload::new()
+                .with_term(term)
+                .with_connection_to_leader(has_connection_to_host)
+                .with_knowledge(knowledge),
             time_in_future,
         );
 
@@ -620,24 +2443,74 @@ mod tests {
         assert!(room.leader_index.is_none());
     }
 
+    #[test]
+    fn defer_election_until_room_has_filled_up() {
+        let mut room = RoomConfig::new().with_min_connections_for_election(3).build().unwrap();
+        let now = Instant::now();
+        let first = room.create_connection(now).unwrap();
+        let second = room.create_connection(now).unwrap();
+        room.on_ping(second, &PingPayload::new().with_knowledge(Knowledge(10)), now);
+        assert_eq!(room.leader_index, None);
+        assert_eq!(room.term, Term(0));
+
+        let third = room.create_connection(now).unwrap();
+        assert_eq!(room.leader_index, Some(second));
+        assert_eq!(room.term, Term(1));
+        let leader_changes = room
+            .drain_events()
+            .into_iter()
+            .filter(|event| matches!(event, RoomEvent::LeaderChanged { .. }))
+            .count();
+        assert_eq!(leader_changes, 1);
+
+        room.destroy_connection(third).unwrap();
+        room.destroy_connection(second).unwrap();
+        assert_eq!(room.leader_index, Some(first));
+    }
+
     #[test]
     fn change_leader_when_destroying_leader_connection() {
         let mut room = Room::new();
         let now = Instant::now();
         assert_eq!(room.term.value(), 0);
-        let connection_id = room.create_connection(now);
+        let connection_id = room.create_connection(now).unwrap();
         assert_eq!(connection_id.value(), 1);
         assert_eq!(room.leader_index.unwrap().value(), 1);
-        room.destroy_connection(connection_id);
+        room.destroy_connection(connection_id).unwrap();
         assert_eq!(room.term.value(), 2);
         assert!(room.leader_index.is_none())
     }
 
+    #[test]
+    fn prefer_stable_leader_over_flapping() {
+        let mut room = RoomConfig::new().with_disconnect_bad_connections(false).build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let stable = room.create_connection(now).unwrap();
+        let flapping = room.create_connection(now).unwrap();
+
+        for millis in (100..=3000).step_by(100) {
+            let time = now + Duration::from_millis(millis);
+            room.on_ping(leader, &PingPayload::new(), time);
+            room.on_ping(stable, &PingPayload::new().with_knowledge(Knowledge(10)), time);
+            // Pings for 600 milliseconds, then goes quiet for 600 milliseconds
+            if (millis - 1) / 600 % 2 == 0 {
+                room.on_ping(flapping, &PingPayload::new().with_knowledge(Knowledge(15)), time);
+            }
+            room.update(time);
+        }
+        assert_eq!(room.get(stable).stability(), 1.0);
+        assert!(room.get(flapping).stability() < 0.5);
+
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader_index, Some(stable));
+    }
+
     #[test]
     fn knows_about_current_term() {
         let mut room = Room::new();
         let now = Instant::now();
-        let connection_id = room.create_connection(now);
+        let connection_id = room.create_connection(now).unwrap();
 
         assert_eq!(room.connection_knows_about_current_term(connection_id), false);
         let wrong_term = Term(0);
@@ -645,9 +2518,10 @@ mod tests {
         let knowledge: Knowledge = Knowledge(42);
         room.on_ping(
             connection_id,
-            wrong_term,
-            &has_connection_to_host,
-            knowledge,
+            &PingPayload::new()
+                .with_term(wrong_term)
+                .with_connection_to_leader(has_connection_to_host)
+                .with_knowledge(knowledge),
             now,
         );
 
@@ -658,9 +2532,10 @@ mod tests {
         let time_in_future = now + Duration::new(40, 0);
         room.on_ping(
             connection_id,
-            room.term,
-            &has_connection_to_host,
-            knowledge,
+            &PingPayload::new()
+                .with_term(room.term)
+                .with_connection_to_leader(has_connection_to_host)
+                .with_knowledge(knowledge),
             time_in_future,
         );
 
@@ -672,7 +2547,7 @@ mod tests {
     fn check_set_debug_name() {
         let mut room = Room::new();
         let now = Instant::now();
-        let connection_id = room.create_connection(now);
+        let connection_id = room.create_connection(now).unwrap();
         room.set_debug_name(connection_id, "Hello");
         info!("connection: {}", room.get(connection_id))
     }
@@ -682,9 +2557,9 @@ mod tests {
         let mut room = RoomConfig::new()
             .with_destroy_disconnected_connections(true)
             .with_disconnect_bad_connections(true)
-            .build();
+            .build().unwrap();
         let now = Instant::now();
-        let connection_id = room.create_connection(now);
+        let connection_id = room.create_connection(now).unwrap();
 
         assert_eq!(room.connection_knows_about_current_term(connection_id), false);
         let wrong_term = Term(0);
@@ -692,9 +2567,10 @@ mod tests {
         let knowledge: Knowledge = Knowledge(42);
         room.on_ping(
             connection_id,
-            wrong_term,
-            &has_connection_to_host,
-            knowledge,
+            &PingPayload::new()
+                .with_term(wrong_term)
+                .with_connection_to_leader(has_connection_to_host)
+                .with_knowledge(knowledge),
             now,
         );
 
@@ -706,9 +2582,10 @@ mod tests {
         assert_eq!(room.connections.len(), 1);
         room.on_ping(
             connection_id,
-            room.term,
-            &has_connection_to_host,
-            knowledge,
+            &PingPayload::new()
+                .with_term(room.term)
+                .with_connection_to_leader(has_connection_to_host)
+                .with_knowledge(knowledge),
             time_in_future,
         );
         assert_eq!(room.connections.len(), 1);
@@ -725,4 +2602,185 @@ mod tests {
         let fifteen_minutes_later = time_in_future_with_no_ping + Duration::new(15 * 60, 0);
         assert_eq!(room.is_abandoned(fifteen_minutes_later), true);
     }
+
+    #[test]
+    fn recycle_connection_indices() {
+        let mut room = RoomConfig::new().with_max_connection_index(3).build().unwrap();
+        let now = Instant::now();
+        let first = room.create_connection(now).unwrap();
+        let second = room.create_connection(now).unwrap();
+        room.destroy_connection(second).unwrap();
+        let third = room.create_connection(now).unwrap();
+        assert_eq!(third.value(), 3);
+
+        let reused = room.create_connection(now).unwrap();
+        assert_eq!(reused.value(), second.value());
+        assert_ne!(reused, second);
+        assert_eq!(room.create_connection(now), Err(RoomError::NoConnectionIndexAvailable));
+
+        room.destroy_connection(first).unwrap();
+        assert_eq!(room.create_connection(now).unwrap().value(), first.value());
+    }
+
+    #[test]
+    fn create_connection_with_requested_id() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let player = room.create_connection_with_id(ConnectionIndex::new(4711), now).unwrap();
+        assert_eq!(player.value(), 4711);
+        assert_eq!(room.leader_index, Some(player));
+
+        assert_eq!(
+            room.create_connection_with_id(ConnectionIndex::new(4711), now),
+            Err(RoomError::ConnectionIndexInUse(player))
+        );
+        assert_eq!(room.create_connection(now).unwrap().value(), 1);
+    }
+
+    #[test]
+    fn reject_stale_handles() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let stale = room.create_connection(now).unwrap();
+        room.destroy_connection(stale).unwrap();
+        room.id = ConnectionIndex::new(0);
+        let current = room.create_connection(now).unwrap();
+        assert_eq!(stale.value(), current.value());
+        assert_ne!(stale, current);
+
+        let expected = RoomError::StaleHandle {
+            handle: stale,
+            current,
+        };
+        assert_eq!(
+            room.on_ping(stale, &PingPayload::new(), now),
+            PingOutcome::Rejected(PingRejection::InvalidConnection(expected))
+        );
+        assert_eq!(room.destroy_connection(stale), Err(expected));
+        assert!(room.try_get(current).is_ok());
+        assert_eq!(
+            room.try_get(ConnectionIndex::new(7)).unwrap_err(),
+            RoomError::UnknownConnection(ConnectionIndex::new(7))
+        );
+    }
+
+    #[test]
+    fn require_sustained_leader_loss_before_replacing_leader() {
+        let mut room = RoomConfig::new()
+            .with_sustained_leader_loss(Some(Duration::from_secs(1)))
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let followers = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+        let term = room.term;
+        let connected = PingPayload::new().with_term(term).with_connection_to_leader(ConnectionToLeader::Connected);
+        let down_vote = PingPayload::new().with_term(term).with_connection_to_leader(ConnectionToLeader::Disconnected);
+
+        // A momentary coincidence of down-votes, every third tick
+        for millis in (100..=1500).step_by(100) {
+            let time = now + Duration::from_millis(millis);
+            room.on_ping(leader, &connected, time);
+            for follower in followers {
+                let ping = if millis % 300 == 0 { &down_vote } else { &connected };
+                room.on_ping(follower, ping, time);
+            }
+            room.update(time);
+        }
+        assert_eq!(room.leader_index, Some(leader));
+
+        // A loss the followers report as already sustained counts right away
+        let time = now + Duration::from_millis(1600);
+        let reported = down_vote.clone().with_leader_unreachable_since(Duration::from_secs(2));
+        room.on_ping(leader, &connected, time);
+        for follower in followers {
+            room.on_ping(follower, &reported, time);
+        }
+        room.update(time);
+        assert_ne!(room.leader_index, Some(leader));
+        assert_ne!(room.term, term);
+    }
+
+    #[test]
+    fn count_loss_measured_by_room_once_sustained() {
+        let mut room = RoomConfig::new()
+            .with_sustained_leader_loss(Some(Duration::from_secs(1)))
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let followers = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+        let term = room.term;
+        let down_vote = PingPayload::new().with_term(term).with_connection_to_leader(ConnectionToLeader::Disconnected);
+
+        let mut replaced_at = None;
+        for millis in (100..=2000).step_by(100) {
+            let time = now + Duration::from_millis(millis);
+            room.on_ping(leader, &PingPayload::new().with_term(term), time);
+            for follower in followers {
+                room.on_ping(follower, &down_vote, time);
+            }
+            room.update(time);
+            if room.leader_index != Some(leader) {
+                replaced_at = Some(millis);
+                break;
+            }
+        }
+        // The first down-vote was at 100 milliseconds
+        assert_eq!(replaced_at, Some(1100));
+    }
+
+    #[test]
+    fn ignore_down_votes_from_connections_that_went_silent() {
+        let run = |freshness: Option<f32>| {
+            let stability = LeaderStability::new().with_min_term_duration(Duration::from_secs(1));
+            let mut room = RoomConfig::new()
+                .with_leader_stability(stability)
+                .with_down_vote_freshness(freshness)
+                .build()
+                .unwrap();
+            let now = Instant::now();
+            let leader = room.create_connection(now).unwrap();
+            let followers = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+            let term = room.term;
+            let down_vote =
+                PingPayload::new().with_term(term).with_connection_to_leader(ConnectionToLeader::Disconnected);
+            for follower in followers {
+                room.on_ping(follower, &down_vote, now + Duration::from_millis(100));
+            }
+            for millis in (200..=2000).step_by(100) {
+                let time = now + Duration::from_millis(millis);
+                room.on_ping(leader, &PingPayload::new().with_term(term), time);
+                room.update(time);
+            }
+            room.leader_index == Some(leader)
+        };
+
+        assert!(run(Some(2.0)));
+        assert!(!run(None));
+    }
+
+    #[test]
+    fn read_room_through_accessors_and_appoint_leader() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        assert!(room.is_empty());
+        let first = room.create_connection(now).unwrap();
+        let second = room.create_connection(now).unwrap();
+        assert_eq!(room.len(), 2);
+        assert_eq!(room.id().value(), second.value());
+        assert_eq!(room.leader(), Some(first));
+        assert_eq!(room.term(), Term(1));
+        assert!(room.contains(second));
+
+        room.appoint_leader(second).unwrap();
+        assert_eq!(room.leader(), Some(second));
+        assert_eq!(room.term(), Term(2));
+        room.appoint_leader(second).unwrap();
+        assert_eq!(room.term(), Term(2));
+
+        room.destroy_connection(first).unwrap();
+        assert!(!room.contains(first));
+        assert_eq!(room.appoint_leader(first), Err(RoomError::UnknownConnection(first)));
+    }
 }
@@ -2,13 +2,20 @@
  *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
  *  Licensed under the MIT License. See LICENSE in the project root for license information.
  *--------------------------------------------------------------------------------------------------------*/
-use std::time::Instant;
+use core::fmt;
+use std::time::Duration;
+
+use crate::{DisconnectReason, Instant, PingRejection, RoomError, RoomState};
+
+/// Rates are only calculated when strictly more than this has passed, unless configured with [RateMetrics::with_period]
+pub const DEFAULT_RATE_PERIOD: Duration = Duration::from_millis(500);
 
 /// Evaluating how many times something occurs every second.
 #[derive(Debug)]
 pub struct RateMetrics {
     count: u32,
     last_calculated_at: Instant,
+    period: Duration,
 }
 
 impl RateMetrics {
@@ -16,15 +23,47 @@ impl RateMetrics {
         Self {
             count: 0,
             last_calculated_at: time,
+            period: DEFAULT_RATE_PERIOD,
         }
     }
 
+    pub fn with_period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// Changes the period, the rate currently being counted is calculated when the new period has passed
+    pub fn set_period(&mut self, period: Duration) {
+        self.period = period;
+    }
+
     pub fn increment(&mut self) {
         self.count += 1;
     }
 
     pub fn has_enough_time_passed(&self, time: Instant) -> bool {
-        (time - self.last_calculated_at).as_millis() > 500
+        time - self.last_calculated_at > self.period
+    }
+
+    /// The earliest time when [RateMetrics::has_enough_time_passed] returns true
+    pub fn next_calculation_at(&self) -> Instant {
+        self.last_calculated_at + self.period + Duration::from_millis(1)
+    }
+
+    /// End of the current period, [RateMetrics::has_enough_time_passed] is true for any time after it
+    pub(crate) fn period_ends_at(&self) -> Instant {
+        self.last_calculated_at + self.period
+    }
+
+    /// Drops the count so far and starts a new period at `time`
+    pub(crate) fn restart(&mut self, time: Instant) {
+        self.count = 0;
+        self.last_calculated_at = time;
+    }
+
+    /// Moves the start of the current period forward, so `duration` is not part of the rate
+    pub(crate) fn shift(&mut self, duration: Duration) {
+        self.last_calculated_at += duration;
     }
 
     pub(crate) fn calculate_rate(&mut self, time: Instant) -> f32 {
@@ -44,3 +83,324 @@ impl RateMetrics {
         rate
     }
 }
+
+/// Width of every bucket in an [IntervalHistogram]
+pub const INTERVAL_BUCKET_WIDTH: Duration = Duration::from_millis(10);
+
+/// Intervals from zero up to this are counted in buckets, longer intervals share a single overflow bucket
+pub const INTERVAL_HISTOGRAM_RANGE: Duration = Duration::from_secs(2);
+
+const INTERVAL_BUCKET_COUNT: usize = (INTERVAL_HISTOGRAM_RANGE.as_millis() / INTERVAL_BUCKET_WIDTH.as_millis()) as usize;
+
+/// Histogram of the time between pings, with a fixed number of buckets so it never grows
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntervalHistogram {
+    buckets: [u32; INTERVAL_BUCKET_COUNT],
+    overflow: u32,
+    count: u32,
+    max: Duration,
+}
+
+impl Default for IntervalHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; INTERVAL_BUCKET_COUNT],
+            overflow: 0,
+            count: 0,
+            max: Duration::ZERO,
+        }
+    }
+}
+
+impl IntervalHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, interval: Duration) {
+        // Each bucket counts the intervals that are longer than the end of the previous bucket and at most its own end
+        let bucket = interval.as_nanos().div_ceil(INTERVAL_BUCKET_WIDTH.as_nanos()).saturating_sub(1) as usize;
+        match self.buckets.get_mut(bucket) {
+            Some(count) => *count = count.saturating_add(1),
+            None => self.overflow = self.overflow.saturating_add(1),
+        }
+        self.count = self.count.saturating_add(1);
+        self.max = self.max.max(interval);
+    }
+
+    /// Number of recorded intervals
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// The interval that `percentile` percent of the recorded intervals are shorter than or equal to,
+    /// rounded up to the end of its bucket. `None` if nothing has been recorded.
+    pub fn percentile(&self, percentile: f32) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((percentile / 100.0 * self.count as f32).ceil() as u32).clamp(1, self.count);
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some((INTERVAL_BUCKET_WIDTH * (bucket as u32 + 1)).min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    pub fn p50(&self) -> Option<Duration> {
+        self.percentile(50.0)
+    }
+
+    pub fn p95(&self) -> Option<Duration> {
+        self.percentile(95.0)
+    }
+
+    pub fn p99(&self) -> Option<Duration> {
+        self.percentile(99.0)
+    }
+}
+
+/// Counts pings that were received but never applied
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DroppedPingCounts {
+    pub duplicates: u32,
+    pub out_of_order: u32,
+}
+
+/// Counts the pings [crate::Room::on_ping] rejected, per [PingRejection]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RejectedPingCounts {
+    pub unknown_connection: u32,
+    pub stale_handle: u32,
+    pub authentication_failed: u32,
+    pub protocol_mismatch: u32,
+    pub duplicates: u32,
+    pub out_of_order: u32,
+}
+
+impl RejectedPingCounts {
+    pub(crate) fn count(&mut self, rejection: PingRejection) {
+        let counter = match rejection {
+            PingRejection::InvalidConnection(RoomError::StaleHandle { .. }) => &mut self.stale_handle,
+            PingRejection::InvalidConnection(_) => &mut self.unknown_connection,
+            PingRejection::AuthenticationFailed => &mut self.authentication_failed,
+            PingRejection::ProtocolMismatch => &mut self.protocol_mismatch,
+            PingRejection::Duplicate => &mut self.duplicates,
+            PingRejection::OutOfOrder => &mut self.out_of_order,
+        };
+        *counter = counter.saturating_add(1);
+    }
+
+    pub fn total(&self) -> u32 {
+        self.unknown_connection
+            + self.stale_handle
+            + self.authentication_failed
+            + self.protocol_mismatch
+            + self.duplicates
+            + self.out_of_order
+    }
+}
+
+/// Counters for the whole room since it was created, see [crate::Room::metrics]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RoomMetrics {
+    pub rejected_pings: RejectedPingCounts,
+    /// Bytes reported with [crate::Room::record_traffic] for all connections
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Down-vote changes over [crate::RoomConfig::max_down_vote_changes], for all connections
+    pub down_vote_violations: u32,
+    /// Every time the leader changed, the first appointment included
+    pub leader_changes: u32,
+    /// Updates after a ping that were deferred by [crate::RoomConfig::update_budget]
+    pub deferred_updates: u32,
+}
+
+/// Traffic the transport reported for a connection, see [crate::Room::record_traffic]. Every report with
+/// incoming or outgoing bytes counts as one packet in that direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrafficStats {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub packets_in: u64,
+    pub packets_out: u64,
+    /// Measured over the latest period longer than [DEFAULT_RATE_PERIOD]
+    pub bytes_in_per_second: f32,
+    pub bytes_out_per_second: f32,
+    /// When bytes were last received from the connection, `None` if nothing has been received
+    pub last_received_at: Option<Instant>,
+}
+
+/// Adds up the reported traffic of a connection and measures its rate
+#[derive(Debug, Clone)]
+pub(crate) struct TrafficMeter {
+    stats: TrafficStats,
+    period_started_at: Instant,
+    period_bytes_in: u64,
+    period_bytes_out: u64,
+}
+
+impl TrafficMeter {
+    pub(crate) fn new(time: Instant) -> Self {
+        Self {
+            stats: TrafficStats {
+                bytes_in: 0,
+                bytes_out: 0,
+                packets_in: 0,
+                packets_out: 0,
+                bytes_in_per_second: 0.0,
+                bytes_out_per_second: 0.0,
+                last_received_at: None,
+            },
+            period_started_at: time,
+            period_bytes_in: 0,
+            period_bytes_out: 0,
+        }
+    }
+
+    pub(crate) fn stats(&self) -> &TrafficStats {
+        &self.stats
+    }
+
+    pub(crate) fn record(&mut self, bytes_in: u64, bytes_out: u64, time: Instant) {
+        let stats = &mut self.stats;
+        if bytes_in > 0 {
+            stats.bytes_in = stats.bytes_in.saturating_add(bytes_in);
+            stats.packets_in += 1;
+            stats.last_received_at = Some(time);
+        }
+        if bytes_out > 0 {
+            stats.bytes_out = stats.bytes_out.saturating_add(bytes_out);
+            stats.packets_out += 1;
+        }
+        self.period_bytes_in = self.period_bytes_in.saturating_add(bytes_in);
+        self.period_bytes_out = self.period_bytes_out.saturating_add(bytes_out);
+
+        let elapsed = time.saturating_duration_since(self.period_started_at);
+        if elapsed > DEFAULT_RATE_PERIOD {
+            let seconds = elapsed.as_secs_f32();
+            stats.bytes_in_per_second = self.period_bytes_in as f32 / seconds;
+            stats.bytes_out_per_second = self.period_bytes_out as f32 / seconds;
+            self.period_started_at = time;
+            self.period_bytes_in = 0;
+            self.period_bytes_out = 0;
+        }
+    }
+
+    /// Moves the times forward, so `duration` is not part of the rate
+    pub(crate) fn shift(&mut self, duration: Duration) {
+        self.period_started_at += duration;
+        if let Some(received_at) = &mut self.stats.last_received_at {
+            *received_at += duration;
+        }
+    }
+}
+
+/// Receives measurements from a [crate::Room] as they happen, e.g. to forward them to a metrics backend.
+///
+/// All methods default to doing nothing, so a sink only implements what it is interested in. Sinks can be shared
+/// by rooms on different threads, so the methods take `&self`.
+pub trait MetricsSink: fmt::Debug + Send + Sync {
+    fn leader_changed(&self) {}
+
+    /// Called after every update with the number of connections in the room
+    fn connection_count(&self, _count: usize) {}
+
+    /// Called after every update with [crate::Room::connections_by_tag]
+    fn connections_by_tag(&self, _counts: &[(&str, usize)]) {}
+
+    /// Time between two consecutive pings from the same connection
+    fn ping_interval(&self, _interval: Duration) {}
+
+    /// Called after every update with the current state of the room
+    fn room_state(&self, _state: RoomState) {}
+
+    fn disconnected(&self, _reason: DisconnectReason) {}
+
+    /// Called for every ping that [crate::Room::on_ping] did not apply
+    fn ping_rejected(&self, _rejection: PingRejection) {}
+
+    /// Called for every down-vote change over [crate::RoomConfig::max_down_vote_changes]
+    fn down_vote_rate_limited(&self) {}
+
+    /// Called for every update after a ping that was deferred by [crate::RoomConfig::update_budget], with the number
+    /// of connections in the room
+    fn update_deferred(&self, _connections: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::metrics::{IntervalHistogram, RejectedPingCounts};
+    use crate::{ConnectionIndex, PingPayload, Room, RoomConfig};
+
+    #[test]
+    fn interval_percentiles() {
+        let mut histogram = IntervalHistogram::new();
+        assert_eq!(histogram.p50(), None);
+
+        for _ in 0..94 {
+            histogram.record(Duration::from_millis(95));
+        }
+        for _ in 0..5 {
+            histogram.record(Duration::from_millis(333));
+        }
+        histogram.record(Duration::from_secs(5));
+
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.p50(), Some(Duration::from_millis(100)));
+        assert_eq!(histogram.p95(), Some(Duration::from_millis(340)));
+        assert_eq!(histogram.p99(), Some(Duration::from_millis(340)));
+        assert_eq!(histogram.percentile(100.0), Some(Duration::from_secs(5)));
+    }
+    #[test]
+    fn record_ping_intervals() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let first = room.create_connection(now).unwrap();
+        let second = room.create_connection(now).unwrap();
+        for millis in [0, 100, 200, 300] {
+            room.on_ping(first, &PingPayload::new(), now + Duration::from_millis(millis));
+        }
+        room.on_ping(second, &PingPayload::new(), now + Duration::from_millis(300));
+        room.on_ping(second, &PingPayload::new(), now + Duration::from_millis(700));
+
+        assert_eq!(room.get(first).ping_intervals().count(), 3);
+        assert_eq!(room.get(second).ping_intervals().p50(), Some(Duration::from_millis(400)));
+        assert_eq!(room.ping_intervals().count(), 4);
+        assert_eq!(room.ping_intervals().p50(), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn count_rejected_pings() {
+        let mut room = RoomConfig::new().with_min_supported_version(1).build().unwrap();
+        let now = Instant::now();
+        let connection = room.create_connection(now).unwrap();
+        room.on_ping(connection, &PingPayload::new().with_sequence(2), now);
+        room.on_ping(connection, &PingPayload::new().with_sequence(2), now);
+        room.on_ping(connection, &PingPayload::new().with_sequence(1), now);
+        room.on_ping(ConnectionIndex::new(42), &PingPayload::new(), now);
+        room.on_ping(connection, &PingPayload::new().with_protocol_version(0), now);
+
+        let rejected = room.metrics().rejected_pings;
+        assert_eq!(
+            rejected,
+            RejectedPingCounts {
+                unknown_connection: 1,
+                protocol_mismatch: 1,
+                duplicates: 1,
+                out_of_order: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(rejected.total(), 4);
+    }
+}
@@ -0,0 +1,97 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::{KnowledgeOrd, Term};
+
+use crate::{ConnectionIndex, ConnectionState, DisconnectReason, KickReason, Room};
+
+/// Why a connection is notified, see [Notification]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotificationReason {
+    /// The room set the connection to [ConnectionState::Disconnected]
+    Disconnected(DisconnectReason),
+    /// The room destroyed the connection, see [crate::RoomConfig::destroy_disconnected_connections]
+    Destroyed,
+    Kicked(KickReason),
+    /// A new term started with the connection as leader, or without a leader
+    LeaderChanged { term: Term },
+}
+
+/// Tells `recipient` that something happened to the connection `about`, see [Room::take_pending_notifications]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub recipient: ConnectionIndex,
+    /// `None` only for a [NotificationReason::LeaderChanged] to a room without a leader
+    pub about: Option<ConnectionIndex>,
+    pub reason: NotificationReason,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Takes the notifications for disconnects, kicks and leader changes since the last call. They are in the
+    /// order the room decided them, and each decision notifies every connection that has pinged, including the
+    /// one it is about, ordered by connection index.
+    pub fn take_pending_notifications(&mut self) -> Vec<Notification> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    /// Must be called while the connection `about` is still in the room, so it is notified as well
+    pub(crate) fn notify_about(&mut self, about: Option<ConnectionIndex>, reason: NotificationReason) {
+        let mut recipients: Vec<ConnectionIndex> = self
+            .connections
+            .values()
+            .filter(|connection| connection.state != ConnectionState::Pending)
+            .map(|connection| connection.id)
+            .collect();
+        recipients.sort_by_key(|index| index.value());
+        self.notifications.extend(recipients.into_iter().map(|recipient| Notification {
+            recipient,
+            about,
+            reason,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::Term;
+
+    use crate::{DisconnectReason, Notification, NotificationReason, PingPayload, RoomConfig};
+
+    #[test]
+    fn notify_everyone_about_destroyed_leader() {
+        let mut room = RoomConfig::new().with_destroy_disconnected_connections(true).build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let followers = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+        room.take_pending_notifications();
+
+        for millis in (100..=2000).step_by(100) {
+            let time = now + Duration::from_millis(millis);
+            for follower in followers {
+                room.on_ping(follower, &PingPayload::new().with_term(room.term), time);
+            }
+            room.update(time);
+        }
+        assert_ne!(room.leader_index, Some(leader));
+
+        let notify = |recipients: &[_], about, reason| {
+            recipients
+                .iter()
+                .map(move |recipient| Notification {
+                    recipient: *recipient,
+                    about,
+                    reason,
+                })
+                .collect::<Vec<_>>()
+        };
+        let everyone = [leader, followers[0], followers[1]];
+        let mut expected = notify(&everyone, Some(leader), NotificationReason::Disconnected(DisconnectReason::PoorQuality));
+        expected.extend(notify(&everyone, Some(leader), NotificationReason::Destroyed));
+        expected.extend(notify(&followers, room.leader_index, NotificationReason::LeaderChanged { term: Term(2) }));
+        assert_eq!(room.take_pending_notifications(), expected);
+        assert!(room.take_pending_notifications().is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::fmt;
+
+use conclave_types::{KnowledgeOrd, Term};
+
+use crate::{ConnectionIndex, DisconnectReason, Room, RoomEvent};
+
+/// Receives callbacks from a [Room] as things happen, an alternative to [Room::drain_events].
+///
+/// The callbacks are made synchronously from [Room::update], [Room::on_ping] and the other calls that change the
+/// room. The event queue is still filled, so both can be used at the same time. All methods default to doing
+/// nothing, so an observer only implements what it is interested in.
+pub trait RoomObserver: fmt::Debug + Send + Sync {
+    /// Called for every event, before the more specific method for it. Not limited by
+    /// [crate::RoomConfig::event_filter]
+    fn on_event(&mut self, _event: &RoomEvent) {}
+
+    fn on_leader_changed(&mut self, _term: Term, _leader: Option<ConnectionIndex>) {}
+
+    fn on_connection_disconnected(&mut self, _connection: ConnectionIndex, _reason: DisconnectReason) {}
+
+    /// The room has received pings before, but none within [crate::RoomConfig::abandoned_after]. Called with
+    /// [RoomEvent::RoomAbandoned], so once and again only after the room has received a ping in between
+    fn on_room_abandoned(&mut self) {}
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Replaces the observer, see [RoomObserver]
+    pub fn set_observer(&mut self, observer: Box<dyn RoomObserver>) {
+        self.observer = Some(observer);
+    }
+
+    pub(crate) fn notify_observer(&mut self, event: &RoomEvent) {
+        let Some(observer) = &mut self.observer else {
+            return;
+        };
+        observer.on_event(event);
+        match *event {
+            RoomEvent::LeaderChanged { term, leader } => observer.on_leader_changed(term, leader),
+            RoomEvent::Disconnected { connection, reason } => observer.on_connection_disconnected(connection, reason),
+            RoomEvent::RoomAbandoned { .. } => observer.on_room_abandoned(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use conclave_types::Term;
+
+    use crate::{ConnectionIndex, DisconnectReason, PingPayload, RoomConfig, RoomObserver};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Call {
+        LeaderChanged(Term, Option<ConnectionIndex>),
+        Disconnected(ConnectionIndex, DisconnectReason),
+        Abandoned,
+    }
+
+    #[derive(Debug)]
+    struct Recorder {
+        calls: Arc<Mutex<Vec<Call>>>,
+    }
+
+    impl RoomObserver for Recorder {
+        fn on_leader_changed(&mut self, term: Term, leader: Option<ConnectionIndex>) {
+            self.calls.lock().unwrap().push(Call::LeaderChanged(term, leader));
+        }
+
+        fn on_connection_disconnected(&mut self, connection: ConnectionIndex, reason: DisconnectReason) {
+            self.calls.lock().unwrap().push(Call::Disconnected(connection, reason));
+        }
+
+        fn on_room_abandoned(&mut self) {
+            self.calls.lock().unwrap().push(Call::Abandoned);
+        }
+    }
+
+    #[test]
+    fn call_observer_alongside_events() {
+        let mut room = RoomConfig::new()
+            .allow_remove_single_leader()
+            .with_abandoned_after(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        room.set_observer(Box::new(Recorder { calls: calls.clone() }));
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        room.update(now);
+        assert_eq!(*calls.lock().unwrap(), vec![Call::LeaderChanged(Term(1), Some(leader))]);
+
+        room.on_ping(leader, &PingPayload::new(), now);
+        for seconds in [2, 6, 7] {
+            room.update(now + Duration::from_secs(seconds));
+        }
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                Call::LeaderChanged(Term(1), Some(leader)),
+                Call::LeaderChanged(Term(2), None),
+                Call::Disconnected(leader, DisconnectReason::PoorQuality),
+                Call::Abandoned,
+            ]
+        );
+        assert!(!room.drain_events().is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! Big-endian encoding shared by the binary formats in [crate::wire] and [crate::snapshot]
+
+use std::io::{Error, ErrorKind, Result};
+
+use conclave_types::ConnectionToLeader;
+
+use crate::ConnectionIndex;
+
+pub(crate) fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub(crate) fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub(crate) fn write_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub(crate) fn write_connection_index(out: &mut Vec<u8>, index: ConnectionIndex) {
+    write_u32(out, index.value());
+    write_u32(out, index.generation());
+}
+
+pub(crate) fn write_optional_connection_index(out: &mut Vec<u8>, index: Option<ConnectionIndex>) {
+    match index {
+        Some(index) => {
+            out.push(1);
+            write_connection_index(out, index);
+        }
+        None => out.push(0),
+    }
+}
+
+pub(crate) struct OctetReader<'a> {
+    octets: &'a [u8],
+}
+
+impl<'a> OctetReader<'a> {
+    pub(crate) fn new(octets: &'a [u8]) -> Self {
+        Self { octets }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.octets.is_empty()
+    }
+
+    pub(crate) fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.read_octets(N)?.try_into().unwrap())
+    }
+
+    pub(crate) fn read_octets(&mut self, length: usize) -> Result<&'a [u8]> {
+        if self.octets.len() < length {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "octets are truncated"));
+        }
+        let (head, rest) = self.octets.split_at(length);
+        self.octets = rest;
+        Ok(head)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take()?))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take()?))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take()?))
+    }
+
+    pub(crate) fn read_connection_to_leader(&mut self) -> Result<ConnectionToLeader> {
+        let value = self.read_u8()?;
+        ConnectionToLeader::from_u8(value)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("illegal connection to leader {}", value)))
+    }
+
+    pub(crate) fn read_presence(&mut self) -> Result<bool> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(Error::new(ErrorKind::InvalidData, format!("illegal presence octet {}", other))),
+        }
+    }
+
+    pub(crate) fn read_connection_index(&mut self) -> Result<ConnectionIndex> {
+        let value = self.read_u32()?;
+        Ok(ConnectionIndex::with_generation(value, self.read_u32()?))
+    }
+
+    pub(crate) fn read_optional_connection_index(&mut self) -> Result<Option<ConnectionIndex>> {
+        Ok(if self.read_presence()? {
+            Some(self.read_connection_index()?)
+        } else {
+            None
+        })
+    }
+}
@@ -0,0 +1,152 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::{ConnectionToLeader, Knowledge, Term};
+
+use crate::{ConnectionIndex, Instant, PingPayload, Room};
+
+/// A single input to a [Room], compact enough to be generated by a fuzzer.
+///
+/// Connections are referred to by `slot`, which picks one of the existing connections (sorted by index),
+/// wrapping around, so that every operation hits a live connection. Operations on an empty room are ignored.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum RoomOp {
+    CreateConnection,
+    Ping {
+        slot: u8,
+        term: u16,
+        /// 0 is unknown, 1 is connected and anything else is disconnected
+        connection_to_leader: u8,
+        knowledge: u64,
+        protocol_version: u16,
+        sequence: Option<u16>,
+    },
+    Destroy {
+        slot: u8,
+    },
+    AdvanceTime {
+        milliseconds: u16,
+    },
+}
+
+impl Room {
+    fn connection_in_slot(&self, slot: u8) -> Option<ConnectionIndex> {
+        if self.connections.is_empty() {
+            return None;
+        }
+        let mut indices: Vec<ConnectionIndex> = self.connections.keys().copied().collect();
+        indices.sort_by_key(|index| index.value());
+        Some(indices[slot as usize % indices.len()])
+    }
+
+    /// Applies the operation to the room. `now` is moved forward by [RoomOp::AdvanceTime].
+    ///
+    /// ```
+    /// use std::time::Instant;
+    /// use conclave_room_session::{Room, RoomOp};
+    ///
+    /// let mut room = Room::new();
+    /// let mut now = Instant::now();
+    /// for op in [RoomOp::CreateConnection, RoomOp::AdvanceTime { milliseconds: 100 }, RoomOp::Destroy { slot: 3 }] {
+    ///     room.apply(&op, &mut now);
+    ///     room.check_invariants().unwrap();
+    /// }
+    /// ```
+    pub fn apply(&mut self, op: &RoomOp, now: &mut Instant) {
+        match *op {
+            RoomOp::CreateConnection => {
+                // Running out of connection indices is a valid outcome of an operation sequence
+                let _ = self.create_connection(*now);
+            }
+            RoomOp::Ping {
+                slot,
+                term,
+                connection_to_leader,
+                knowledge,
+                protocol_version,
+                sequence,
+            } => {
+                let Some(connection_index) = self.connection_in_slot(slot) else {
+                    return;
+                };
+                let connection_to_leader = match connection_to_leader {
+                    0 => ConnectionToLeader::Unknown,
+                    1 => ConnectionToLeader::Connected,
+                    _ => ConnectionToLeader::Disconnected,
+                };
+                let mut ping = PingPayload::new()
+                    .with_term(Term(term))
+                    .with_connection_to_leader(connection_to_leader)
+                    .with_knowledge(Knowledge(knowledge))
+                    .with_protocol_version(protocol_version);
+                ping.sequence = sequence;
+                self.on_ping(connection_index, &ping, *now);
+            }
+            RoomOp::Destroy { slot } => {
+                if let Some(connection_index) = self.connection_in_slot(slot) {
+                    self.destroy_connection(connection_index).expect("slots only refer to existing connections");
+                }
+            }
+            RoomOp::AdvanceTime { milliseconds } => {
+                *now += Duration::from_millis(milliseconds as u64);
+                self.update(*now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::ops::RoomOp;
+    use crate::{Room, RoomConfig};
+
+    /// Stand-in for a fuzzer, so arbitrary operation sequences are exercised by the regular tests
+    fn generate_ops(mut seed: u64, count: usize) -> Vec<RoomOp> {
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        (0..count)
+            .map(|_| match next() % 10 {
+                0 => RoomOp::CreateConnection,
+                1 => RoomOp::Destroy { slot: next() as u8 },
+                2..=4 => RoomOp::AdvanceTime {
+                    milliseconds: (next() % 1500) as u16,
+                },
+                _ => RoomOp::Ping {
+                    slot: next() as u8,
+                    term: (next() % 8) as u16,
+                    connection_to_leader: (next() % 3) as u8,
+                    knowledge: next() % 4 * (u64::MAX / 3),
+                    protocol_version: (next() % 3) as u16,
+                    sequence: if next() % 2 == 0 { Some(next() as u16) } else { None },
+                },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn survive_arbitrary_operations() {
+        for seed in 1..20 {
+            let mut room = Room::new_with_config(
+                RoomConfig::new()
+                    .with_min_supported_version(1)
+                    .with_knowledge_lag_threshold(10)
+                    .with_knowledge_lead_tolerance(100),
+            );
+            let mut now = Instant::now();
+            for op in generate_ops(seed, 500) {
+                room.apply(&op, &mut now);
+                room.check_invariants().unwrap();
+            }
+        }
+    }
+}
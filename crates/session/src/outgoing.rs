@@ -0,0 +1,134 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::{KnowledgeOrd, Term};
+
+use crate::{CloseReason, ConnectionIndex, ConnectionState, KickReason, Room};
+
+/// What the transport layer should tell a connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutgoingIntent {
+    AnnounceLeader {
+        term: Term,
+        leader: Option<ConnectionIndex>,
+    },
+    /// The receiver joined mid-session and should ask `donor` for the full state
+    RequestStateFrom { donor: ConnectionIndex },
+    /// The receiver should send its full state to `receiver`
+    SendStateTo { receiver: ConnectionIndex },
+    YouWereKicked { reason: KickReason },
+    /// The room is closing, acknowledge with [Room::acknowledge_close]
+    RoomClosing { reason: CloseReason },
+    /// The receiver should ping this often, see [Room::recommended_ping_interval]
+    PingPacing { interval: Duration },
+}
+
+/// An [OutgoingIntent] addressed to a single connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outgoing {
+    pub connection: ConnectionIndex,
+    pub intent: OutgoingIntent,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Takes everything the transport layer should send, in the order it was decided
+    pub fn drain_outgoing(&mut self) -> Vec<Outgoing> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    pub(crate) fn push_outgoing(&mut self, connection: ConnectionIndex, intent: OutgoingIntent) {
+        self.outgoing.push(Outgoing { connection, intent });
+    }
+
+    pub(crate) fn announce_leader_to(&mut self, connection: ConnectionIndex) {
+        let intent = OutgoingIntent::AnnounceLeader {
+            term: self.term,
+            leader: self.leader_index,
+        };
+        self.push_outgoing(connection, intent);
+    }
+
+    /// Announces the current leader to every connection that has pinged, ordered by connection index
+    pub(crate) fn announce_leader_to_all(&mut self) {
+        let mut indices: Vec<ConnectionIndex> = self
+            .connections
+            .values()
+            .filter(|connection| connection.state != ConnectionState::Pending)
+            .map(|connection| connection.id)
+            .collect();
+        indices.sort_by_key(|index| index.value());
+        for index in indices {
+            self.announce_leader_to(index);
+        }
+    }
+
+    pub(crate) fn request_state_transfer(&mut self, receiver: ConnectionIndex, donor: Option<ConnectionIndex>) {
+        if let Some(donor) = donor {
+            self.push_outgoing(receiver, OutgoingIntent::RequestStateFrom { donor });
+            self.push_outgoing(donor, OutgoingIntent::SendStateTo { receiver });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::Term;
+
+    use crate::outgoing::{Outgoing, OutgoingIntent};
+    use crate::Room;
+
+    #[test]
+    fn announce_and_request_state() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let late_joiner = room.create_connection(now).unwrap();
+
+        assert_eq!(
+            room.drain_outgoing(),
+            vec![
+                Outgoing {
+                    connection: leader,
+                    intent: OutgoingIntent::AnnounceLeader {
+                        term: Term(1),
+                        leader: Some(leader)
+                    }
+                },
+                Outgoing {
+                    connection: late_joiner,
+                    intent: OutgoingIntent::AnnounceLeader {
+                        term: Term(1),
+                        leader: Some(leader)
+                    }
+                },
+                Outgoing {
+                    connection: late_joiner,
+                    intent: OutgoingIntent::RequestStateFrom { donor: leader }
+                },
+                Outgoing {
+                    connection: leader,
+                    intent: OutgoingIntent::SendStateTo { receiver: late_joiner }
+                },
+            ]
+        );
+
+        room.destroy_connection(leader).unwrap();
+        let outgoing = room.drain_outgoing();
+        assert_eq!(
+            outgoing[0],
+            Outgoing {
+                connection: late_joiner,
+                intent: OutgoingIntent::AnnounceLeader {
+                    term: Term(2),
+                    leader: Some(late_joiner)
+                }
+            }
+        );
+        assert!(outgoing.iter().all(|outgoing| outgoing.connection != leader));
+    }
+}
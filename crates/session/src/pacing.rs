@@ -0,0 +1,116 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+
+use crate::outgoing::OutgoingIntent;
+use crate::{Connection, ConnectionIndex, Room, RoomError};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// How often the connection should ping, so clients can be paced by the room instead of hard-coding a rate.
+    ///
+    /// The rate is [crate::RoomConfig::ping_headroom] times the threshold of the connection (lowered by
+    /// [crate::RoomConfig::adaptive_threshold_fraction]), which slows down healthy clients that ping far more often
+    /// than needed. A connection whose latest measured rate is less than twice the threshold, so it is one hiccup
+    /// away from being disconnected, is asked for twice that rate. Rounded to whole milliseconds.
+    pub fn recommended_ping_interval(&self, connection_index: ConnectionIndex) -> Result<Duration, RoomError> {
+        let connection = self.try_get(connection_index)?;
+        Ok(self.ping_interval_for(connection, self.adaptive_threshold_ceiling()))
+    }
+
+    fn ping_interval_for(&self, connection: &Connection<K>, ceiling: Option<f32>) -> Duration {
+        let threshold = connection.quality.effective_threshold(ceiling);
+        let is_marginal = connection
+            .quality
+            .measured_pings_per_second()
+            .is_some_and(|rate| rate <= threshold * 2.0);
+        let headroom = if is_marginal {
+            self.config.ping_headroom * 2.0
+        } else {
+            self.config.ping_headroom
+        };
+        Duration::from_millis((1000.0 / (threshold * headroom)).round() as u64)
+    }
+
+    /// Sends [OutgoingIntent::PingPacing] to the connections that were just assessed and got another
+    /// recommended interval than they were last told, ordered by connection index
+    pub(crate) fn update_ping_pacing(&mut self) {
+        if !self.config.send_ping_pacing || self.scan.assessed.is_empty() {
+            return;
+        }
+        let ceiling = self.adaptive_threshold_ceiling();
+        let mut changed = Vec::<(ConnectionIndex, Duration)>::new();
+        for connection_index in &self.scan.assessed {
+            let Some(connection) = self.connections.get(connection_index) else {
+                continue;
+            };
+            let interval = self.ping_interval_for(connection, ceiling);
+            if connection.paced_ping_interval != Some(interval) {
+                changed.push((*connection_index, interval));
+            }
+        }
+        changed.sort_by_key(|(index, _)| index.value());
+
+        for (connection_index, interval) in changed {
+            self.connections.get_mut(&connection_index).unwrap().paced_ping_interval = Some(interval);
+            self.push_outgoing(connection_index, OutgoingIntent::PingPacing { interval });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::outgoing::{Outgoing, OutgoingIntent};
+    use crate::{PingPayload, Room, RoomConfig};
+
+    /// Pings every `interval` milliseconds for a second, starting after `from`
+    fn ping(room: &mut Room, connection: crate::ConnectionIndex, now: Instant, from: u64, interval: u64) {
+        for millis in (from + interval..=from + 1000).step_by(interval as usize) {
+            let time = now + Duration::from_millis(millis);
+            room.on_ping(connection, &PingPayload::new(), time);
+            room.update(time);
+        }
+    }
+
+    #[test]
+    fn slow_down_healthy_and_speed_up_marginal() {
+        let mut room = RoomConfig::new()
+            .pings_per_second_threshold(4.0)
+            .with_ping_headroom(2.5)
+            .with_send_ping_pacing(true)
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let connection = room.create_connection(now).unwrap();
+        assert_eq!(room.recommended_ping_interval(connection), Ok(Duration::from_millis(100)));
+        room.drain_outgoing();
+
+        ping(&mut room, connection, now, 0, 50);
+        assert_eq!(
+            room.drain_outgoing(),
+            vec![Outgoing {
+                connection,
+                intent: OutgoingIntent::PingPacing {
+                    interval: Duration::from_millis(100)
+                },
+            }]
+        );
+
+        ping(&mut room, connection, now, 1000, 50);
+        assert!(room.drain_outgoing().is_empty());
+
+        ping(&mut room, connection, now, 2000, 200);
+        assert_eq!(room.recommended_ping_interval(connection), Ok(Duration::from_millis(50)));
+        assert!(room.drain_outgoing().contains(&Outgoing {
+            connection,
+            intent: OutgoingIntent::PingPacing {
+                interval: Duration::from_millis(50)
+            },
+        }));
+    }
+}
@@ -0,0 +1,167 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::{KnowledgeOrd, Term};
+use log::info;
+
+use crate::{Connection, ConnectionIndex, ConnectionState, ElectionTrigger, Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Online connections that report a term ahead of the room, e.g. because they elected a leader of their own
+    /// while the network was partitioned. Sorted by connection index.
+    pub fn connections_ahead_of_term(&self) -> Vec<ConnectionIndex> {
+        let mut ahead: Vec<ConnectionIndex> = self
+            .online_reports()
+            .filter(|connection| reported_term(connection) > self.term.value())
+            .map(|connection| connection.id)
+            .collect();
+        ahead.sort_by_key(|index| index.value());
+        ahead
+    }
+
+    /// Online connections that have reported a term
+    fn online_reports(&self) -> impl Iterator<Item = &Connection<K>> {
+        self.connections
+            .values()
+            .filter(|connection| connection.state == ConnectionState::Online && connection.last_reported_term.is_some())
+    }
+
+    /// Picks the winning lineage when connections report a term ahead of the room: the highest term wins, and
+    /// among the leaders followed in it, the one with the lowest index value that is online in the room. If
+    /// none of them is, the best candidate is elected. The room then starts the term after the winning one, so
+    /// every connection, on both sides of the partition, has to converge on it.
+    ///
+    /// A single client can not take over the room by reporting a made up term: only terms at most
+    /// [crate::RoomConfig::max_partition_term_lead] ahead count, and more than half of the online connections
+    /// that have reported a term must report one of them. A term that has no term after it is never followed.
+    pub(crate) fn reconcile_partitions(&mut self) {
+        if !self.config.reconcile_partitions || !self.scan.may_be_ahead_of(self.term) {
+            return;
+        }
+        let current = self.term.value();
+        let max_lead = self.config.max_partition_term_lead;
+        let is_followable = |term: u16| term > current && term - current <= max_lead && term.checked_add(1).is_some();
+        let reporting = self.online_reports().count();
+        let ahead = self.online_reports().filter(|connection| is_followable(reported_term(connection))).count();
+        if ahead * 2 <= reporting {
+            return;
+        }
+        let Some(winning_term) = self.online_reports().map(reported_term).filter(|term| is_followable(*term)).max()
+        else {
+            return;
+        };
+
+        let followed_leader = self
+            .online_reports()
+            .filter(|connection| reported_term(connection) == winning_term)
+            .filter_map(|connection| connection.followed_leader)
+            .filter(|leader| {
+                self.connections
+                    .get(leader)
+                    .is_some_and(|connection| connection.state == ConnectionState::Online)
+            })
+            .min_by_key(|leader| leader.value());
+
+        let mut conflicting_terms: Vec<u16> = self.online_reports().map(reported_term).collect();
+        conflicting_terms.push(self.term.value());
+        conflicting_terms.sort_unstable();
+        conflicting_terms.dedup();
+        let mut stragglers: Vec<ConnectionIndex> = self
+            .online_reports()
+            .filter(|connection| reported_term(connection) != winning_term)
+            .map(|connection| connection.id)
+            .collect();
+        stragglers.sort_by_key(|index| index.value());
+
+        info!(
+            "terms {:?} conflict in room {}, following term {} with leader {:?}",
+            conflicting_terms, self.id, winning_term, followed_leader
+        );
+        self.term = Term(winning_term);
+        match followed_leader {
+            Some(leader) => self.switch_leader(Some(leader)),
+            None => self.switch_leader_to_best_knowledge_and_quality(ElectionTrigger::PartitionHealed),
+        }
+        self.push_event(RoomEvent::PartitionHealed {
+            term: self.term,
+            leader: self.leader_index,
+            winning_term: Term(winning_term),
+            conflicting_terms: conflicting_terms.into_iter().map(Term).collect(),
+            stragglers,
+        });
+    }
+}
+
+fn reported_term<K: KnowledgeOrd>(connection: &Connection<K>) -> u16 {
+    connection.last_reported_term.map_or(0, |term| term.value())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::Term;
+
+    use crate::{PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn follow_highest_term_after_partition() {
+        let mut room = RoomConfig::new().build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let other_side = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+        let straggler = room.create_connection(now).unwrap();
+        assert_eq!(room.term(), Term(1));
+
+        let ping = |term, leader| PingPayload::new().with_term(Term(term)).with_followed_leader(leader);
+        room.on_ping(leader, &ping(1, leader), now);
+        room.on_ping(straggler, &ping(3, leader), now);
+        room.on_ping(other_side[0], &ping(4, other_side[1]), now);
+        room.on_ping(other_side[1], &ping(4, other_side[0]), now);
+        assert_eq!(room.connections_ahead_of_term(), vec![other_side[0], other_side[1], straggler]);
+        assert_eq!(room.leader(), Some(leader));
+
+        room.drain_events();
+        room.config.reconcile_partitions = true;
+        room.update(now);
+        assert_eq!(room.leader(), Some(other_side[0]));
+        assert_eq!(room.term(), Term(5));
+        assert!(room.connections_ahead_of_term().is_empty());
+        assert!(room.drain_events().contains(&RoomEvent::PartitionHealed {
+            term: Term(5),
+            leader: Some(other_side[0]),
+            winning_term: Term(4),
+            conflicting_terms: vec![Term(1), Term(3), Term(4)],
+            stragglers: vec![leader, straggler],
+        }));
+    }
+
+    #[test]
+    fn ignore_implausible_terms() {
+        let mut room = RoomConfig::new().with_reconcile_partitions(true).build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let others = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+
+        let ping = |term| PingPayload::new().with_term(Term(term)).with_followed_leader(others[0]);
+        room.on_ping(leader, &ping(1), now);
+        room.on_ping(others[0], &ping(1), now);
+        room.on_ping(others[1], &ping(u16::MAX), now);
+        room.update(now);
+        assert_eq!(room.term(), Term(1));
+        assert_eq!(room.leader(), Some(leader));
+
+        // Ahead by more than the allowed lead, even when most of the room reports it
+        room.on_ping(others[0], &ping(u16::MAX), now);
+        room.update(now);
+        assert_eq!(room.term(), Term(1));
+
+        // A single connection is not enough to move the room
+        room.on_ping(others[0], &ping(1), now);
+        room.on_ping(others[1], &ping(3), now);
+        room.update(now);
+        assert_eq!(room.term(), Term(1));
+        assert_eq!(room.leader(), Some(leader));
+    }
+}
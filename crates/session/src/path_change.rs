@@ -0,0 +1,83 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{Connection, ConnectionIndex, Instant, IntervalHistogram, RecordedInput, Room, RoomError};
+
+impl<K: KnowledgeOrd> Connection<K> {
+    /// Forgets the quality history of the connection, e.g. after it moved from Wi-Fi to a mobile network, so it
+    /// is assessed on the network it is on now. The ping rate, assessment, flapping, ping intervals and round trip
+    /// times start over from `now`, and [Connection::quality_epoch] is increased.
+    pub fn reset_quality(&mut self, now: Instant) {
+        self.quality.reset(now);
+        self.ping_intervals = IntervalHistogram::new();
+        self.round_trip = None;
+        self.quality_epoch = self.quality_epoch.wrapping_add(1);
+    }
+
+    /// Number of times the quality history has been reset, zero for a new connection
+    pub fn quality_epoch(&self) -> u32 {
+        self.quality_epoch
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Called by the transport when the network path to the connection has changed, e.g. a new address or
+    /// interface. Starts a new quality epoch with [Connection::reset_quality]
+    pub fn report_path_change(&mut self, connection_index: ConnectionIndex, time: Instant) -> Result<(), RoomError> {
+        self.record(
+            time,
+            RecordedInput::PathChange {
+                connection: connection_index.value(),
+                generation: connection_index.generation(),
+            },
+        );
+        let time = self.observe_time(time);
+        self.validate_connection(connection_index)?;
+        let connection = self.connections.get_mut(&connection_index).unwrap();
+        connection.reset_quality(time);
+        info!("path to {} changed, starting quality epoch {}", connection_index, connection.quality_epoch);
+        self.publish_view();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{PingPayload, QualityAssessment, Room};
+
+    #[test]
+    fn assess_only_pings_after_path_change() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let connection = room.create_connection(now).unwrap();
+        for millis in (0..=1100).step_by(50) {
+            room.on_ping(connection, &PingPayload::new(), now + Duration::from_millis(millis));
+        }
+        room.record_round_trip(connection, Duration::from_millis(20), now + Duration::from_millis(1100)).unwrap();
+        assert_eq!(room.get(connection).assessment(), QualityAssessment::Good);
+        for millis in (1150..=1300).step_by(50) {
+            room.on_ping(connection, &PingPayload::new(), now + Duration::from_millis(millis));
+        }
+
+        let changed_at = now + Duration::from_millis(1300);
+        room.report_path_change(connection, changed_at).unwrap();
+        let reset = room.get(connection);
+        assert_eq!(reset.quality_epoch(), 1);
+        assert_eq!(reset.assessment(), QualityAssessment::NeedMoreInformation);
+        assert_eq!(reset.ping_intervals().count(), 0);
+        assert!(reset.round_trip().is_none());
+
+        room.on_ping(connection, &PingPayload::new(), changed_at + Duration::from_millis(600));
+        assert_eq!(room.get(connection).assessment(), QualityAssessment::RecommendDisconnect);
+        assert_eq!(room.get(connection).ping_intervals().count(), 1);
+
+        room.get_mut(connection).reset_quality(changed_at + Duration::from_millis(600));
+        assert_eq!(room.get(connection).quality_epoch(), 2);
+    }
+}
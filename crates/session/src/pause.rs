@@ -0,0 +1,129 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{Instant, Room};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Freezes the room, e.g. before the host application is suspended.
+    ///
+    /// [Room::update] does nothing until [Room::resume] is called. Pausing an already paused room
+    /// keeps the original pause time.
+    pub fn pause(&mut self, now: Instant) {
+        if self.paused_at.is_none() {
+            info!("pausing room {}", self.id);
+            self.paused_at = Some(now);
+        }
+    }
+
+    /// Unfreezes the room and moves all quality timestamps forward by the time spent paused, so
+    /// connections are not assessed as timed out because of the pause itself.
+    ///
+    /// Returns how long the room was paused.
+    pub fn resume(&mut self, now: Instant) -> Duration {
+        let Some(paused_at) = self.paused_at.take() else {
+            return Duration::ZERO;
+        };
+        let paused_duration = now.saturating_duration_since(paused_at);
+        info!("resuming room {} after {:?}", self.id, paused_duration);
+
+        for connection in self.connections.values_mut() {
+            connection.quality.shift(paused_duration);
+            if let Some(quarantined_at) = &mut connection.quarantined_at {
+                *quarantined_at += paused_duration;
+            }
+            if let Some(pending_since) = &mut connection.pending_since {
+                *pending_since += paused_duration;
+            }
+            if let Some(suspected_at) = &mut connection.suspected_at {
+                *suspected_at += paused_duration;
+            }
+            if let Some((reported_at, _)) = &mut connection.lost_leader {
+                *reported_at += paused_duration;
+            }
+            connection.traffic.shift(paused_duration);
+            connection.down_vote_limiter.shift(paused_duration);
+        }
+        for changed_at in &mut self.leader_changes {
+            *changed_at += paused_duration;
+        }
+        if let Some(blocked_until) = &mut self.quality_reelection_blocked_until {
+            *blocked_until += paused_duration;
+        }
+        if let Some(probation_until) = &mut self.probation_until {
+            *probation_until += paused_duration;
+        }
+        if let Some((_, retired_at)) = &mut self.retiring_leader {
+            *retired_at += paused_duration;
+        }
+        if let Some(lease_expires_at) = &mut self.lease_expires_at {
+            *lease_expires_at += paused_duration;
+        }
+        if let Some(stuck_since) = &mut self.stuck_election_since {
+            *stuck_since += paused_duration;
+        }
+        if let Some(latest_ping) = &mut self.latest_ping_timestamp {
+            *latest_ping += paused_duration;
+        }
+        self.shift_close_deadline(paused_duration);
+        self.shift_kick_vote(paused_duration);
+        self.shift_stats_sampling(paused_duration);
+        self.shift_critical_section(paused_duration);
+        #[cfg(feature = "testing")]
+        if let Some(chaos) = &mut self.chaos {
+            chaos.shift(paused_duration);
+        }
+
+        paused_duration
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::ConnectionToLeader;
+
+    use crate::{ConnectionState, PingPayload, QualityAssessment, Room};
+
+    #[test]
+    fn keep_connections_after_pause() {
+        let mut room = Room::new();
+        let mut now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        let ping = PingPayload::new().with_connection_to_leader(ConnectionToLeader::Connected);
+        for _ in 0..10 {
+            now += Duration::from_millis(50);
+            room.on_ping(leader, &ping, now);
+            room.on_ping(follower, &ping, now);
+        }
+
+        room.pause(now);
+        assert!(room.is_paused());
+        assert_eq!(room.time_until_next_action(now), None);
+        now += Duration::from_secs(30);
+        room.update(now);
+        assert_eq!(room.resume(now), Duration::from_secs(30));
+
+        for _ in 0..12 {
+            now += Duration::from_millis(50);
+            room.on_ping(leader, &ping, now);
+            room.on_ping(follower, &ping, now);
+        }
+
+        assert_eq!(room.get(follower).state, ConnectionState::Online);
+        assert_ne!(room.get(follower).assessment(), QualityAssessment::RecommendDisconnect);
+        assert_eq!(room.leader_index, Some(leader));
+        assert!(!room.is_abandoned(now));
+    }
+}
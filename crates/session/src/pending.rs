@@ -0,0 +1,146 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+use crate::recorder::RecordedInput;
+use crate::{Connection, ConnectionIndex, ConnectionQuality, ConnectionState, Instant, Room, RoomError, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Reserves a connection index for a participant that is known to be on the way, e.g. from a matchmaking
+    /// result. The placeholder is [ConnectionState::Pending]: it does not vote, can not be elected and is not
+    /// disconnected for not pinging. It becomes a regular connection on its first ping, and is removed if that
+    /// has not happened within [crate::RoomConfig::pending_timeout].
+    ///
+    /// Returns [RoomError::IdentityInUse] if a connection was already preregistered with the same identity.
+    pub fn preregister_connection(&mut self, identity: &str, time: Instant) -> Result<ConnectionIndex, RoomError> {
+        self.record(
+            time,
+            RecordedInput::Preregister {
+                identity: identity.to_string(),
+            },
+        );
+        let time = self.observe_time(time);
+        self.ensure_open()?;
+        self.ensure_capacity()?;
+        if let Some(existing) = self.find_by_identity(identity) {
+            return Err(RoomError::IdentityInUse(existing));
+        }
+        let value = self.find_unique_connection_value()?;
+        self.id = ConnectionIndex::new(value);
+        self.generation = self.generation.wrapping_add(1);
+        let connection_id = ConnectionIndex::with_generation(value, self.generation);
+        let mut connection = Connection::new(connection_id, time, &self.config);
+        connection.state = ConnectionState::Pending;
+        connection.identity = Some(identity.to_string());
+        connection.pending_since = Some(time);
+        info!("preregistered {} for '{}'", connection, identity);
+        self.insert_connection(connection);
+        self.assert_invariants();
+        self.publish_view();
+        Ok(connection_id)
+    }
+    /// The connection that was preregistered or created with `identity`, see
+    /// [Room::create_connection_with_identity]
+    pub fn find_by_identity(&self, identity: &str) -> Option<ConnectionIndex> {
+        self.identities.get(identity).copied()
+    }
+    /// Number of connections that are not [ConnectionState::Pending]
+    pub(crate) fn admitted_connection_count(&self) -> usize {
+        self.connections
+            .values()
+            .filter(|connection| connection.state != ConnectionState::Pending)
+            .count()
+    }
+    /// Turns a pending placeholder into a regular connection on its first ping, quality is measured from now
+    pub(crate) fn activate_pending(&mut self, connection_index: ConnectionIndex, time: Instant) {
+        let warm_up_pings = self.config.warm_up_pings;
+        let quality = ConnectionQuality::from_config(&self.config, time);
+        self.scan.invalidate_measurements();
+        let connection = self.connections.get_mut(&connection_index).unwrap();
+        connection.state = if warm_up_pings > 0 { ConnectionState::Joining } else { ConnectionState::Online };
+        connection.quality = quality;
+        connection.pending_since = None;
+        info!("preregistered {} pinged and is activated", connection);
+        self.push_event(RoomEvent::PendingActivated {
+            connection: connection_index,
+        });
+        self.admit_connection(connection_index);
+    }
+    /// Removes the placeholders that have not pinged within the pending timeout
+    pub(crate) fn expire_pending(&mut self, time: Instant) {
+        if !self.scan.may_have_pending {
+            return;
+        }
+        let timeout = self.config.pending_timeout;
+        let mut pending_count = 0;
+        let mut expired = Vec::<ConnectionIndex>::new();
+        for connection in self.connections.values() {
+            let Some(pending_since) = connection.pending_since else {
+                continue;
+            };
+            if time.saturating_duration_since(pending_since) >= timeout {
+                expired.push(connection.id);
+            } else {
+                pending_count += 1;
+            }
+        }
+        self.scan.may_have_pending = pending_count > 0;
+        expired.sort_by_key(|index| index.value());
+
+        for connection_index in expired {
+            info!("preregistered {} never pinged, removing it", connection_index);
+            self.remove_connection(connection_index);
+            self.push_event(RoomEvent::PendingExpired {
+                connection: connection_index,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{ConnectionState, PingPayload, Room, RoomConfig, RoomError, RoomEvent};
+
+    #[test]
+    fn activate_preregistered_connection_on_first_ping() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let seeded = room.preregister_connection("alice", now).unwrap();
+        assert_eq!(room.preregister_connection("alice", now), Err(RoomError::IdentityInUse(seeded)));
+        assert_eq!(room.find_by_identity("alice"), Some(seeded));
+        assert_eq!(room.get(seeded).state, ConnectionState::Pending);
+        assert_eq!(room.leader_index, None);
+
+        // Not pinging does not disconnect a placeholder, and a pending connection can not be elected
+        let other = room.create_connection(now).unwrap();
+        for millis in (100..=2000).step_by(100) {
+            room.on_ping(other, &PingPayload::new(), now + Duration::from_millis(millis));
+        }
+        assert_eq!(room.get(seeded).state, ConnectionState::Pending);
+        assert_eq!(room.leader_index, Some(other));
+        assert_eq!(room.would_elect(Some(other), now), None);
+        room.drain_events();
+        room.on_ping(seeded, &PingPayload::new(), now + Duration::from_millis(2100));
+        assert_eq!(room.get(seeded).state, ConnectionState::Online);
+        assert!(room.drain_events().contains(&RoomEvent::PendingActivated { connection: seeded }));
+        assert_eq!(room.would_elect(Some(other), now), Some(seeded));
+    }
+
+    #[test]
+    fn expire_placeholder_that_never_pings() {
+        let mut room = RoomConfig::new().with_pending_timeout(Duration::from_secs(10)).build().unwrap();
+        let now = Instant::now();
+        let seeded = room.preregister_connection("bob", now).unwrap();
+        room.update(now + Duration::from_secs(9));
+        assert!(room.try_get(seeded).is_ok());
+
+        room.update(now + Duration::from_secs(10));
+        assert!(room.try_get(seeded).is_err());
+        assert_eq!(room.find_by_identity("bob"), None);
+        assert_eq!(room.drain_events(), vec![RoomEvent::PendingExpired { connection: seeded }]);
+    }
+}
@@ -0,0 +1,269 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Result;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+use log::{debug, info};
+
+use crate::{Instant, Room, RoomConfig};
+
+/// Keeps the latest snapshot of each room, written with [Room::to_snapshot_bytes], e.g. on disk or in a
+/// key-value store, so the rooms can be recovered with [Room::restore_all] after the process restarts.
+///
+/// Rooms share the store, so the methods take `&self` and implementations synchronize internally.
+pub trait RoomStore: fmt::Debug + Send + Sync {
+    /// Replaces the snapshot of the room
+    fn save_snapshot(&self, room: u64, snapshot: &[u8]);
+
+    /// Every saved snapshot, in any order
+    fn load_all(&self) -> Vec<(u64, Vec<u8>)>;
+
+    /// Called when the room is closed, see [Room::close]
+    fn delete(&self, room: u64);
+}
+
+/// A [RoomStore] that only lives as long as the process, useful for tests and as a reference implementation
+#[derive(Debug, Default)]
+pub struct InMemoryRoomStore {
+    snapshots: Mutex<BTreeMap<u64, Vec<u8>>>,
+}
+
+impl InMemoryRoomStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl RoomStore for InMemoryRoomStore {
+    fn save_snapshot(&self, room: u64, snapshot: &[u8]) {
+        self.snapshots.lock().unwrap().insert(room, snapshot.to_vec());
+    }
+
+    fn load_all(&self) -> Vec<(u64, Vec<u8>)> {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(room, snapshot)| (*room, snapshot.clone()))
+            .collect()
+    }
+
+    fn delete(&self, room: u64) {
+        self.snapshots.lock().unwrap().remove(&room);
+    }
+}
+
+/// When a room saves a snapshot to its [RoomStore]. A snapshot is always saved when the store is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoints {
+    /// Save whenever a new leader is appointed
+    pub on_leader_change: bool,
+    /// Save on the first [Room::update] or [Room::on_ping] after this long since the previous snapshot
+    pub interval: Option<Duration>,
+}
+
+impl Default for Checkpoints {
+    fn default() -> Self {
+        Self {
+            on_leader_change: true,
+            interval: Some(Duration::from_secs(5)),
+        }
+    }
+}
+
+impl Checkpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_on_leader_change(mut self, on_leader_change: bool) -> Self {
+        self.on_leader_change = on_leader_change;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Option<Duration>) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+/// The store a room saves itself to, see [Room::set_store]
+#[derive(Debug)]
+pub(crate) struct Persistence<K: KnowledgeOrd> {
+    key: u64,
+    store: Arc<dyn RoomStore>,
+    checkpoints: Checkpoints,
+    saved_at: Option<Instant>,
+    /// Snapshots only exist for the default knowledge, this is [Room::to_snapshot_bytes]
+    encode: fn(&Room<K>) -> Vec<u8>,
+}
+
+impl Room {
+    /// Saves the room to `store` under `key` now, and from then on at the `checkpoints`, until [Room::close]
+    pub fn set_store(&mut self, key: u64, store: Arc<dyn RoomStore>, checkpoints: Checkpoints) {
+        self.persistence = Some(Persistence {
+            key,
+            store,
+            checkpoints,
+            saved_at: None,
+            encode: Room::to_snapshot_bytes,
+        });
+        self.save_checkpoint();
+    }
+
+    /// Restores every room in `store` with `config`, and sets the store on them again. Rooms are ordered by key.
+    pub fn restore_all(
+        store: &Arc<dyn RoomStore>,
+        config: &RoomConfig,
+        checkpoints: Checkpoints,
+        time: Instant,
+    ) -> Result<Vec<(u64, Room)>> {
+        let mut snapshots = store.load_all();
+        snapshots.sort_by_key(|(key, _)| *key);
+
+        let mut rooms = Vec::with_capacity(snapshots.len());
+        for (key, snapshot) in snapshots {
+            let mut room = Room::from_snapshot_bytes(&snapshot, config.clone(), time)?;
+            info!("restored room {} with {} connections", key, room.connections.len());
+            room.set_store(key, store.clone(), checkpoints);
+            rooms.push((key, room));
+        }
+        Ok(rooms)
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Deletes the snapshot of the room from its store, so it is not restored again. Does nothing without a store.
+    pub fn close(&mut self) {
+        if let Some(persistence) = self.persistence.take() {
+            info!("closing room {}", persistence.key);
+            persistence.store.delete(persistence.key);
+        }
+    }
+
+    fn save_checkpoint(&mut self) {
+        let Some(persistence) = &self.persistence else {
+            return;
+        };
+        debug!("saving snapshot of room {}", persistence.key);
+        let snapshot = (persistence.encode)(self);
+        persistence.store.save_snapshot(persistence.key, &snapshot);
+        let latest_time = self.latest_time;
+        if let Some(persistence) = &mut self.persistence {
+            persistence.saved_at = latest_time;
+        }
+    }
+
+    pub(crate) fn checkpoint_leader_change(&mut self) {
+        if self
+            .persistence
+            .as_ref()
+            .is_some_and(|persistence| persistence.checkpoints.on_leader_change)
+        {
+            self.save_checkpoint();
+        }
+    }
+
+    pub(crate) fn checkpoint_if_due(&mut self, time: Instant) {
+        let Some(persistence) = &self.persistence else {
+            return;
+        };
+        let Some(interval) = persistence.checkpoints.interval else {
+            return;
+        };
+        let is_due = match persistence.saved_at {
+            Some(saved_at) => time.saturating_duration_since(saved_at) >= interval,
+            None => true,
+        };
+        if is_due {
+            self.save_checkpoint();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{Knowledge, Term};
+
+    use crate::{Checkpoints, InMemoryRoomStore, PingPayload, Room, RoomConfig, RoomStore};
+
+    #[test]
+    fn recover_all_rooms_after_restart() {
+        let memory = Arc::new(InMemoryRoomStore::new());
+        let store: Arc<dyn RoomStore> = memory.clone();
+        let checkpoints = Checkpoints::new().with_interval(Some(Duration::from_secs(1)));
+        let now = Instant::now();
+
+        let mut rooms: Vec<Room> = (0..3).map(|_| Room::new()).collect();
+        for (key, room) in rooms.iter_mut().enumerate() {
+            room.set_store(key as u64 + 10, store.clone(), checkpoints);
+            room.create_connection(now).unwrap();
+            room.create_connection(now).unwrap();
+        }
+        assert_eq!(memory.len(), 3);
+
+        for millis in (100..=2000).step_by(100) {
+            let time = now + Duration::from_millis(millis);
+            for room in &mut rooms {
+                let connections: Vec<_> = room.connections.keys().copied().collect();
+                for connection in connections {
+                    let ping = PingPayload::new().with_term(room.term).with_knowledge(Knowledge(millis));
+                    room.on_ping(connection, &ping, time);
+                }
+                room.update(time);
+            }
+        }
+        rooms[2].close();
+        assert_eq!(memory.len(), 2);
+
+        // The process goes down, everything in memory except the store is lost
+        let before: Vec<_> = rooms[..2].iter().map(|room| (room.leader(), room.connections().len())).collect();
+        drop(rooms);
+
+        let later = now + Duration::from_secs(60);
+        let restored = Room::restore_all(&store, &RoomConfig::default(), checkpoints, later).unwrap();
+        let keys: Vec<u64> = restored.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, vec![10, 11]);
+        for ((_, room), (leader, connection_count)) in restored.iter().zip(before) {
+            assert_eq!(room.term, Term(1));
+            assert_eq!(room.leader(), leader);
+            assert_eq!(room.connections().len(), connection_count);
+            // Saved by the last interval checkpoint, on the first ping at 2 seconds
+            let knowledge = room.connections().iter().map(|connection| connection.knowledge).max();
+            assert_eq!(knowledge, Some(Knowledge(2000)));
+        }
+    }
+
+    #[test]
+    fn save_on_leader_change() {
+        let memory = Arc::new(InMemoryRoomStore::new());
+        let mut room = Room::new();
+        let now = Instant::now();
+        let first = room.create_connection(now).unwrap();
+        room.create_connection(now).unwrap();
+        room.set_store(1, memory.clone(), Checkpoints::new().with_interval(None));
+
+        room.destroy_connection(first).unwrap();
+        let (_, snapshot) = memory.load_all().remove(0);
+        let restored = Room::from_snapshot_bytes(&snapshot, RoomConfig::default(), now).unwrap();
+        assert_eq!(restored.term, room.term);
+        assert_eq!(restored.leader_index, room.leader_index);
+    }
+}
@@ -0,0 +1,216 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::fmt;
+use std::time::Duration;
+
+use conclave_types::{ConnectionToLeader, Knowledge, KnowledgeOrd, Term};
+
+use crate::{ConnectionIndex, KickBallot, RoomError};
+
+/// The room protocol version implemented by this crate, reported by clients in every ping
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Everything a connection reports to the room in a single ping.
+///
+/// The struct is `non_exhaustive` so new fields can be added without breaking callers,
+/// construct it with [PingPayload::new] and the `with_` builder methods.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct PingPayload<K: KnowledgeOrd = Knowledge> {
+    pub term: Term,
+    pub has_connection_to_leader: ConnectionToLeader,
+    pub knowledge: K,
+    pub protocol_version: u16,
+    /// Optional sequence number, incremented (and wrapping) for every ping sent by a connection
+    pub sequence: Option<u16>,
+    /// Signature or HMAC for the payload, checked by a [crate::PingAuthenticator] if one is set on the room
+    pub signature: Option<Vec<u8>>,
+    /// Time since the player last gave any input, `None` if the client does not track activity
+    pub last_input_age: Option<Duration>,
+    /// Sent by the leader: the followers it can not reach
+    pub unreachable: Vec<ConnectionIndex>,
+    /// Time since the connection lost its link to the leader, sent along with [ConnectionToLeader::Disconnected].
+    /// `None` if the client does not track it, the room then measures it from the first down-voting ping
+    pub leader_unreachable_since: Option<Duration>,
+    /// The leader the connection follows in [PingPayload::term], used to reconcile terms after a partition,
+    /// see [crate::RoomConfig::reconcile_partitions]
+    pub followed_leader: Option<ConnectionIndex>,
+    /// Time on the clock of the client when the ping was sent, counted from any origin that the client keeps for
+    /// the whole session, see [crate::Connection::clock_skew]
+    pub sent_at: Option<Duration>,
+    /// How much load the client can take as leader, 100 for a typical device, more for a stronger one. See
+    /// [crate::Room::burden_as_leader]
+    pub capability: Option<u8>,
+    /// Checksum or hash of the state at [PingPayload::knowledge]. The leader attests its knowledge with it, and
+    /// the other connections echo the one they received for the knowledge they claim
+    pub knowledge_checksum: Option<u64>,
+    /// Vote on the open kick proposal, see [crate::Room::propose_kick]
+    pub kick_vote: Option<KickBallot>,
+}
+
+impl<K: KnowledgeOrd> Default for PingPayload<K> {
+    fn default() -> Self {
+        Self {
+            term: Term(0),
+            has_connection_to_leader: ConnectionToLeader::Unknown,
+            knowledge: K::default(),
+            protocol_version: PROTOCOL_VERSION,
+            sequence: None,
+            signature: None,
+            last_input_age: None,
+            unreachable: Vec::new(),
+            leader_unreachable_since: None,
+            followed_leader: None,
+            sent_at: None,
+            capability: None,
+            knowledge_checksum: None,
+            kick_vote: None,
+        }
+    }
+}
+
+impl<K: KnowledgeOrd> fmt::Display for PingPayload<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[ping {} {} connectedToLeader:{:?} version:{}]", self.term, self.knowledge, self.has_connection_to_leader, self.protocol_version)
+    }
+}
+
+/// Ping payload builder
+impl<K: KnowledgeOrd> PingPayload<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_term(mut self, term: Term) -> Self {
+        self.term = term;
+        self
+    }
+
+    pub fn with_connection_to_leader(mut self, has_connection_to_leader: ConnectionToLeader) -> Self {
+        self.has_connection_to_leader = has_connection_to_leader;
+        self
+    }
+
+    pub fn with_knowledge(mut self, knowledge: K) -> Self {
+        self.knowledge = knowledge;
+        self
+    }
+
+    pub fn with_protocol_version(mut self, protocol_version: u16) -> Self {
+        self.protocol_version = protocol_version;
+        self
+    }
+
+    pub fn with_sequence(mut self, sequence: u16) -> Self {
+        self.sequence = Some(sequence);
+        self
+    }
+
+    pub fn with_signature(mut self, signature: Vec<u8>) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    pub fn with_last_input_age(mut self, last_input_age: Duration) -> Self {
+        self.last_input_age = Some(last_input_age);
+        self
+    }
+
+    pub fn with_unreachable(mut self, unreachable: Vec<ConnectionIndex>) -> Self {
+        self.unreachable = unreachable;
+        self
+    }
+
+    pub fn with_leader_unreachable_since(mut self, leader_unreachable_since: Duration) -> Self {
+        self.leader_unreachable_since = Some(leader_unreachable_since);
+        self
+    }
+
+    pub fn with_followed_leader(mut self, leader: ConnectionIndex) -> Self {
+        self.followed_leader = Some(leader);
+        self
+    }
+
+    pub fn with_sent_at(mut self, sent_at: Duration) -> Self {
+        self.sent_at = Some(sent_at);
+        self
+    }
+
+    pub fn with_capability(mut self, capability: u8) -> Self {
+        self.capability = Some(capability);
+        self
+    }
+
+    pub fn with_knowledge_checksum(mut self, checksum: u64) -> Self {
+        self.knowledge_checksum = Some(checksum);
+        self
+    }
+
+    pub fn with_kick_vote(mut self, ballot: KickBallot) -> Self {
+        self.kick_vote = Some(ballot);
+        self
+    }
+}
+
+/// True if `sequence` comes after `previous`, taking wrap-around into account
+pub(crate) fn is_sequence_newer(sequence: u16, previous: u16) -> bool {
+    sequence != previous && sequence.wrapping_sub(previous) < 0x8000
+}
+
+/// Why a ping was not applied to the room
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PingRejection {
+    ProtocolMismatch,
+    Duplicate,
+    OutOfOrder,
+    AuthenticationFailed,
+    /// The connection index was stale or unknown
+    InvalidConnection(RoomError),
+}
+
+impl PingRejection {
+    /// Short name for logs and metric labels, an invalid connection is either a stale handle or unknown
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PingRejection::ProtocolMismatch => "protocol_mismatch",
+            PingRejection::Duplicate => "duplicate",
+            PingRejection::OutOfOrder => "out_of_order",
+            PingRejection::AuthenticationFailed => "authentication_failed",
+            PingRejection::InvalidConnection(RoomError::StaleHandle { .. }) => "stale_handle",
+            PingRejection::InvalidConnection(_) => "unknown_connection",
+        }
+    }
+}
+
+/// Result of handing a ping to [crate::Room::on_ping]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PingOutcome {
+    Accepted,
+    Rejected(PingRejection),
+    /// Dropped or delayed by the chaos controller of the `testing` feature, a delayed ping is received later.
+    /// Never returned without that feature
+    Injected,
+}
+
+impl PingOutcome {
+    pub fn is_accepted(&self) -> bool {
+        *self == PingOutcome::Accepted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ping::is_sequence_newer;
+
+    #[test]
+    fn sequence_wrap_around() {
+        assert!(is_sequence_newer(2, 1));
+        assert!(!is_sequence_newer(1, 2));
+        assert!(!is_sequence_newer(5, 5));
+        assert!(is_sequence_newer(0, u16::MAX));
+        assert!(is_sequence_newer(3, u16::MAX - 3));
+        assert!(!is_sequence_newer(u16::MAX, 0));
+    }
+}
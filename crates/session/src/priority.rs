@@ -0,0 +1,147 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::recorder::RecordedInput;
+use crate::{ConnectionIndex, ConnectionState, Instant, KickReason, Room, RoomError, RoomEvent};
+
+/// Who gets to stay when the room is full, see [Room::admit_with_eviction]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ConnectionPriority {
+    Spectator,
+    #[default]
+    Participant,
+    /// E.g. a party member, or a player rejoining after being disconnected
+    Privileged,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Like [Room::create_connection_with_identity], but if the room is at [crate::RoomConfig::max_connections]
+    /// a connection with a lower priority than `priority` is kicked to make room for it.
+    ///
+    /// The lowest priority goes first, among those the ones that are still pending, then the ones with the least
+    /// knowledge. The leader is never evicted. The evicted connection gets [crate::OutgoingIntent::YouWereKicked] and
+    /// [RoomEvent::Kicked] with [KickReason::Evicted], followed by [RoomEvent::AdmittedByEviction] for the new one.
+    /// Returns [RoomError::RoomFull] if no connection has a lower priority.
+    pub fn admit_with_eviction(
+        &mut self,
+        identity: &str,
+        priority: ConnectionPriority,
+        time: Instant,
+    ) -> Result<ConnectionIndex, RoomError> {
+        self.record(
+            time,
+            RecordedInput::AdmitWithEviction {
+                identity: identity.to_string(),
+                priority,
+            },
+        );
+        let time = self.observe_time(time);
+        self.ensure_open()?;
+        let evicted = if self.find_by_identity(identity).is_none() && self.is_full() {
+            let victim = self.eviction_candidate(priority).ok_or(RoomError::RoomFull)?;
+            info!("evicting {} to make room", victim);
+            self.kick(victim, KickReason::Evicted);
+            Some(victim)
+        } else {
+            None
+        };
+        let connection = self.create_identified(identity, priority, time)?;
+        if let Some(evicted) = evicted {
+            self.push_event(RoomEvent::AdmittedByEviction { connection, evicted });
+        }
+        self.publish_view();
+        Ok(connection)
+    }
+
+    fn is_full(&self) -> bool {
+        self.config
+            .max_connections
+            .is_some_and(|max| self.connections.len() >= max)
+    }
+
+    /// Returns [RoomError::RoomFull] if the room is at [crate::RoomConfig::max_connections]
+    pub(crate) fn ensure_capacity(&self) -> Result<(), RoomError> {
+        if self.is_full() {
+            return Err(RoomError::RoomFull);
+        }
+        Ok(())
+    }
+
+    fn eviction_candidate(&self, priority: ConnectionPriority) -> Option<ConnectionIndex> {
+        self.connections
+            .values()
+            .filter(|connection| connection.priority < priority && Some(connection.id) != self.leader_index)
+            .min_by_key(|connection| {
+                (
+                    connection.priority,
+                    connection.state != ConnectionState::Pending,
+                    connection.knowledge.progress(),
+                    connection.id.value(),
+                )
+            })
+            .map(|connection| connection.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::Knowledge;
+
+    use crate::outgoing::{Outgoing, OutgoingIntent};
+    use crate::{ConnectionPriority, KickReason, PingPayload, RoomConfig, RoomError, RoomEvent};
+
+    #[test]
+    fn evict_lowest_priority_when_full() {
+        let mut room = RoomConfig::new().with_max_connections(Some(3)).build().unwrap();
+        let now = Instant::now();
+        let leader = room.admit_with_eviction("host", ConnectionPriority::Spectator, now).unwrap();
+        let watching = room.admit_with_eviction("watching", ConnectionPriority::Spectator, now).unwrap();
+        let playing = room.create_connection_with_identity("playing", now).unwrap();
+        room.on_ping(watching, &PingPayload::new().with_knowledge(Knowledge(5)), now);
+        assert_eq!(room.create_connection(now), Err(RoomError::RoomFull));
+        room.drain_outgoing();
+        room.drain_events();
+
+        let friend = room.admit_with_eviction("friend", ConnectionPriority::Privileged, now).unwrap();
+
+        assert!(room.try_get(watching).is_err());
+        assert_eq!(room.get(friend).priority, ConnectionPriority::Privileged);
+        assert_eq!(room.leader(), Some(leader));
+        assert!(room.drain_outgoing().contains(&Outgoing {
+            connection: watching,
+            intent: OutgoingIntent::YouWereKicked {
+                reason: KickReason::Evicted
+            },
+        }));
+        let events = room.drain_events();
+        assert_eq!(
+            events[0],
+            RoomEvent::Kicked {
+                connection: watching,
+                reason: KickReason::Evicted
+            }
+        );
+        assert!(events.contains(&RoomEvent::AdmittedByEviction {
+            connection: friend,
+            evicted: watching
+        }));
+
+        // Only the leader and connections with the same priority are left
+        assert_eq!(
+            room.admit_with_eviction("another", ConnectionPriority::Participant, now),
+            Err(RoomError::RoomFull)
+        );
+        assert!(room.try_get(playing).is_ok());
+    }
+}
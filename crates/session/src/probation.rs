@@ -0,0 +1,84 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+use crate::{ConnectionIndex, Instant, Room, RoomEvent};
+impl<K: KnowledgeOrd> Room<K> {
+    /// Starts the [crate::RoomConfig::leader_probation] for a leader that was just appointed
+    pub(crate) fn begin_probation(&mut self) {
+        self.probation_until = match (self.config.leader_probation, self.leader_index, self.latest_time) {
+            (Some(probation), Some(_), Some(time)) => Some(time + probation),
+            _ => None,
+        };
+    }
+
+    /// The leader, if it is still on probation and can not be disconnected or replaced for bad quality
+    pub(crate) fn leader_on_probation(&self, time: Instant) -> Option<ConnectionIndex> {
+        self.leader_index
+            .filter(|_| self.probation_until.is_some_and(|until| time < until))
+    }
+
+    /// Confirms the leader once it has made it through the probation
+    pub(crate) fn update_probation(&mut self, time: Instant) {
+        let Some(until) = self.probation_until else {
+            return;
+        };
+        if time < until {
+            return;
+        }
+        self.probation_until = None;
+        if let Some(leader) = self.leader_index {
+            info!("leader {} made it through probation", leader);
+            self.push_event(RoomEvent::LeaderConfirmed { term: self.term, leader });
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use conclave_types::ConnectionToLeader;
+    use crate::{ConnectionState, PingPayload, RoomConfig, RoomEvent};
+    #[test]
+    fn keep_slow_leader_during_probation() {
+        let mut room = RoomConfig::new().with_leader_probation(Duration::from_secs(2)).build().unwrap();
+        let now = Instant::now();
+        let at = |millis| now + Duration::from_millis(millis);
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        let ping = PingPayload::new().with_connection_to_leader(ConnectionToLeader::Connected);
+
+        // The leader has not started pinging yet
+        for millis in (100..=1900).step_by(100) {
+            room.on_ping(follower, &ping, at(millis));
+            room.update(at(millis));
+        }
+        assert_eq!(room.leader_index, Some(leader));
+        assert_eq!(room.get(leader).state, ConnectionState::Online);
+
+        room.drain_events();
+        for millis in (2000..=2600).step_by(100) {
+            room.on_ping(leader, &ping, at(millis));
+            room.on_ping(follower, &ping, at(millis));
+            room.update(at(millis));
+        }
+        assert_eq!(room.leader_index, Some(leader));
+        assert!(room.drain_events().contains(&RoomEvent::LeaderConfirmed { term: room.term, leader }));
+    }
+
+    #[test]
+    fn down_vote_during_probation() {
+        let mut room = RoomConfig::new().with_leader_probation(Duration::from_secs(10)).build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let followers = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+        let ping = PingPayload::new()
+            .with_term(room.term)
+            .with_connection_to_leader(ConnectionToLeader::Disconnected);
+        for follower in followers {
+            room.on_ping(follower, &ping, now + Duration::from_millis(100));
+        }
+        assert_ne!(room.leader_index, Some(leader));
+    }
+}
@@ -0,0 +1,233 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! Exports room measurements to a [prometheus::Registry].
+//!
+//! Register the metrics once with [PrometheusMetrics::register] and hand every room its own sink
+//! from [PrometheusMetrics::for_room]:
+//!
+//! ```ignore
+//! let metrics = PrometheusMetrics::register(prometheus::default_registry())?;
+//! room.set_metrics_sink(Box::new(metrics.for_room("lobby")));
+//! ```
+use std::sync::Mutex;
+use std::time::Duration;
+
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry};
+
+use crate::{DisconnectReason, MetricsSink, PingRejection, RoomState};
+
+/// Buckets for the ping interval histogram, in seconds
+const PING_INTERVAL_BUCKETS: [f64; 9] = [0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Metric families shared by all rooms, labeled per room
+#[derive(Debug, Clone)]
+pub struct PrometheusMetrics {
+    leader_changes: IntCounterVec,
+    active_connections: IntGaugeVec,
+    tagged_connections: IntGaugeVec,
+    ping_intervals: HistogramVec,
+    rooms_by_state: IntGaugeVec,
+    disconnects: IntCounterVec,
+    rejected_pings: IntCounterVec,
+    down_vote_violations: IntCounterVec,
+    deferred_updates: IntCounterVec,
+}
+
+impl PrometheusMetrics {
+    pub fn register(registry: &Registry) -> prometheus::Result<Self> {
+        let leader_changes = IntCounterVec::new(
+            Opts::new("conclave_room_leader_changes_total", "Number of times a new leader was elected"),
+            &["room"],
+        )?;
+        let active_connections =
+            IntGaugeVec::new(Opts::new("conclave_room_connections", "Number of connections in the room"), &["room"])?;
+        let tagged_connections = IntGaugeVec::new(
+            Opts::new("conclave_room_tagged_connections", "Number of connections in the room with the tag"),
+            &["room", "tag"],
+        )?;
+        let ping_intervals = HistogramVec::new(
+            HistogramOpts::new("conclave_room_ping_interval_seconds", "Time between pings from the same connection")
+                .buckets(PING_INTERVAL_BUCKETS.to_vec()),
+            &["room"],
+        )?;
+        let rooms_by_state =
+            IntGaugeVec::new(Opts::new("conclave_rooms_by_state", "Number of rooms in each state"), &["state"])?;
+        let disconnects = IntCounterVec::new(
+            Opts::new("conclave_room_disconnects_total", "Number of connections disconnected by the room"),
+            &["room", "reason"],
+        )?;
+
+        let rejected_pings = IntCounterVec::new(
+            Opts::new("conclave_room_rejected_pings_total", "Number of pings the room received but did not apply"),
+            &["room", "reason"],
+        )?;
+        let down_vote_violations = IntCounterVec::new(
+            Opts::new(
+                "conclave_room_down_vote_violations_total",
+                "Number of down-vote changes over the limit, which kept the down-votes from counting",
+            ),
+            &["room"],
+        )?;
+        let deferred_updates = IntCounterVec::new(
+            Opts::new(
+                "conclave_room_deferred_updates_total",
+                "Number of updates after a ping that were over the update budget and deferred",
+            ),
+            &["room"],
+        )?;
+
+        registry.register(Box::new(leader_changes.clone()))?;
+        registry.register(Box::new(active_connections.clone()))?;
+        registry.register(Box::new(tagged_connections.clone()))?;
+        registry.register(Box::new(ping_intervals.clone()))?;
+        registry.register(Box::new(rooms_by_state.clone()))?;
+        registry.register(Box::new(disconnects.clone()))?;
+        registry.register(Box::new(rejected_pings.clone()))?;
+        registry.register(Box::new(down_vote_violations.clone()))?;
+        registry.register(Box::new(deferred_updates.clone()))?;
+
+        Ok(Self {
+            leader_changes,
+            active_connections,
+            tagged_connections,
+            ping_intervals,
+            rooms_by_state,
+            disconnects,
+            rejected_pings,
+            down_vote_violations,
+            deferred_updates,
+        })
+    }
+
+    /// Creates a sink for a single room, all measurements are labeled with `room`
+    pub fn for_room(&self, room: &str) -> PrometheusRoomMetrics {
+        PrometheusRoomMetrics {
+            metrics: self.clone(),
+            room: room.to_string(),
+            state: Mutex::new(None),
+            tags: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+/// [MetricsSink] for one room. Removes the room from the per-state gauge when dropped.
+#[derive(Debug)]
+pub struct PrometheusRoomMetrics {
+    metrics: PrometheusMetrics,
+    room: String,
+    state: Mutex<Option<RoomState>>,
+    /// Tags reported by the latest update, to remove the ones no connection has anymore
+    tags: Mutex<Vec<String>>,
+}
+
+impl MetricsSink for PrometheusRoomMetrics {
+    fn leader_changed(&self) {
+        self.metrics.leader_changes.with_label_values(&[&self.room]).inc();
+    }
+
+    fn connection_count(&self, count: usize) {
+        self.metrics.active_connections.with_label_values(&[&self.room]).set(count as i64);
+    }
+
+    fn connections_by_tag(&self, counts: &[(&str, usize)]) {
+        let mut tags = self.tags.lock().unwrap();
+        for tag in tags.iter() {
+            if !counts.iter().any(|(counted, _)| counted == tag) {
+                let _ = self.metrics.tagged_connections.remove_label_values(&[&self.room, tag]);
+            }
+        }
+        tags.clear();
+        for (tag, count) in counts {
+            self.metrics.tagged_connections.with_label_values(&[&self.room, tag]).set(*count as i64);
+            tags.push(tag.to_string());
+        }
+    }
+
+    fn ping_interval(&self, interval: Duration) {
+        self.metrics.ping_intervals.with_label_values(&[&self.room]).observe(interval.as_secs_f64());
+    }
+
+    fn room_state(&self, state: RoomState) {
+        let previous = self.state.lock().unwrap().replace(state);
+        if previous == Some(state) {
+            return;
+        }
+        if let Some(previous) = previous {
+            self.metrics.rooms_by_state.with_label_values(&[previous.as_str()]).dec();
+        }
+        self.metrics.rooms_by_state.with_label_values(&[state.as_str()]).inc();
+    }
+
+    fn disconnected(&self, reason: DisconnectReason) {
+        self.metrics.disconnects.with_label_values(&[&self.room, reason.as_str()]).inc();
+    }
+
+    fn ping_rejected(&self, rejection: PingRejection) {
+        self.metrics.rejected_pings.with_label_values(&[&self.room, rejection.as_str()]).inc();
+    }
+
+    fn down_vote_rate_limited(&self) {
+        self.metrics.down_vote_violations.with_label_values(&[&self.room]).inc();
+    }
+
+    fn update_deferred(&self, _connections: usize) {
+        self.metrics.deferred_updates.with_label_values(&[&self.room]).inc();
+    }
+}
+
+impl Drop for PrometheusRoomMetrics {
+    fn drop(&mut self) {
+        if let Some(state) = *self.state.get_mut().unwrap() {
+            self.metrics.rooms_by_state.with_label_values(&[state.as_str()]).dec();
+        }
+        let _ = self.metrics.leader_changes.remove_label_values(&[&self.room]);
+        let _ = self.metrics.active_connections.remove_label_values(&[&self.room]);
+        for tag in self.tags.get_mut().unwrap().iter() {
+            let _ = self.metrics.tagged_connections.remove_label_values(&[&self.room, tag]);
+        }
+        let _ = self.metrics.ping_intervals.remove_label_values(&[&self.room]);
+        let _ = self.metrics.down_vote_violations.remove_label_values(&[&self.room]);
+        let _ = self.metrics.deferred_updates.remove_label_values(&[&self.room]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{ConnectionToLeader, Knowledge, Term};
+    use prometheus::Registry;
+
+    use crate::prometheus_exporter::PrometheusMetrics;
+    use crate::{ConnectionIndex, PingPayload, Room};
+
+    #[test]
+    fn export_room_metrics() {
+        let registry = Registry::new();
+        let metrics = PrometheusMetrics::register(&registry).unwrap();
+
+        let mut room = Room::new();
+        room.set_metrics_sink(Box::new(metrics.for_room("lobby")));
+        let now = Instant::now();
+        let connection_id = room.create_connection_with_tags(&["region:eu"], now).unwrap();
+        let ping = PingPayload::new()
+            .with_term(Term(0))
+            .with_connection_to_leader(ConnectionToLeader::Connected)
+            .with_knowledge(Knowledge(0));
+        room.on_ping(connection_id, &ping, now);
+        room.on_ping(connection_id, &ping, now + Duration::from_millis(100));
+        room.on_ping(ConnectionIndex::new(7), &ping, now + Duration::from_millis(100));
+
+        assert_eq!(metrics.leader_changes.with_label_values(&["lobby"]).get(), 1);
+        assert_eq!(metrics.active_connections.with_label_values(&["lobby"]).get(), 1);
+        assert_eq!(metrics.tagged_connections.with_label_values(&["lobby", "region:eu"]).get(), 1);
+        assert_eq!(metrics.ping_intervals.with_label_values(&["lobby"]).get_sample_count(), 1);
+        assert_eq!(metrics.rooms_by_state.with_label_values(&["active"]).get(), 1);
+        assert_eq!(metrics.rejected_pings.with_label_values(&["lobby", "unknown_connection"]).get(), 1);
+
+        drop(room);
+        assert_eq!(metrics.rooms_by_state.with_label_values(&["active"]).get(), 0);
+    }
+}
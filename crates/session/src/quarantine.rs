@@ -0,0 +1,147 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{Connection, ConnectionIndex, ConnectionState, Instant, QualityAssessment, Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Connection<K> {
+    /// True if the connection is quarantined and the quarantine period is not over yet
+    pub(crate) fn is_serving_quarantine(&self, quarantine_period: Option<Duration>, time: Instant) -> bool {
+        match (self.state, self.quarantined_at, quarantine_period) {
+            (ConnectionState::Quarantined, Some(quarantined_at), Some(period)) => time < quarantined_at + period,
+            _ => false,
+        }
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Quarantines online connections that are assessed as bad or that the leader can not reach, and lets
+    /// quarantined connections that are assessed as acceptable and reachable again go back online.
+    /// Does nothing without a [crate::RoomConfig::quarantine_period].
+    pub(crate) fn update_quarantine(&mut self, time: Instant) {
+        if self.config.quarantine_period.is_none() {
+            return;
+        }
+
+        let leader_on_probation = self.leader_on_probation(time);
+        let mut changed = Vec::<(ConnectionIndex, bool)>::new();
+        for connection in self.connections.values_mut() {
+            let is_unreachable = self.unreachable_by_leader.contains(&connection.id);
+            match (connection.state, connection.assessment()) {
+                (ConnectionState::Online, QualityAssessment::RecommendDisconnect)
+                    if leader_on_probation != Some(connection.id) =>
+                {
+                    info!("quarantining {}", connection);
+                    connection.state = ConnectionState::Quarantined;
+                    connection.quarantined_at = Some(time);
+                    changed.push((connection.id, true));
+                }
+                (ConnectionState::Online, _) if is_unreachable => {
+                    info!("quarantining {}, the leader can not reach it", connection);
+                    info!("quarantining {}", connection);
+                    connection.state = ConnectionState::Quarantined;
+                    connection.quarantined_at = Some(time);
+                    changed.push((connection.id, true));
+                }
+                (ConnectionState::Quarantined, QualityAssessment::Acceptable | QualityAssessment::Good)
+                    if !is_unreachable =>
+                {
+                    info!("{} recovered and is released from quarantine", connection);
+                    connection.state = ConnectionState::Online;
+                    connection.quarantined_at = None;
+                    changed.push((connection.id, false));
+                }
+                _ => {}
+            }
+        }
+
+        changed.sort_by_key(|(index, _)| index.value());
+        for (connection_index, is_quarantined) in changed {
+            self.push_event(if is_quarantined {
+                RoomEvent::Quarantined {
+                    connection: connection_index,
+                }
+            } else {
+                RoomEvent::Rehabilitated {
+                    connection: connection_index,
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::ConnectionToLeader;
+
+    use crate::{ConnectionIndex, ConnectionState, PingPayload, Room, RoomConfig, RoomEvent};
+
+    /// Pings and updates every 100 milliseconds in `(from, to]`, `healthy` always pings while `flaky` only
+    /// pings if `flaky_pings` is set
+    fn run(
+        room: &mut Room,
+        healthy: ConnectionIndex,
+        flaky: ConnectionIndex,
+        flaky_pings: bool,
+        at: impl Fn(u64) -> Instant,
+        from: u64,
+        to: u64,
+    ) {
+        let ping = PingPayload::new().with_connection_to_leader(ConnectionToLeader::Connected);
+        for millis in (from + 100..=to).step_by(100) {
+            room.on_ping(healthy, &ping, at(millis));
+            if flaky_pings {
+                room.on_ping(flaky, &ping, at(millis));
+            }
+            room.update(at(millis));
+        }
+    }
+
+    #[test]
+    fn rehabilitate_then_disconnect_after_quarantine() {
+        let mut room = RoomConfig::new().with_quarantine_period(Duration::from_secs(2)).build().unwrap();
+        let now = Instant::now();
+        let at = |millis| now + Duration::from_millis(millis);
+        let healthy = room.create_connection(now).unwrap();
+        let flaky = room.create_connection(now).unwrap();
+
+        run(&mut room, healthy, flaky, true, at, 0, 1200);
+        room.drain_events();
+        run(&mut room, healthy, flaky, false, at, 1200, 1800);
+        assert_eq!(room.get(flaky).state, ConnectionState::Quarantined);
+        assert_eq!(room.drain_events(), vec![RoomEvent::Quarantined { connection: flaky }]);
+
+        // A burst of pings within the quarantine brings it back
+        run(&mut room, healthy, flaky, true, at, 1800, 2400);
+        assert_eq!(room.get(flaky).state, ConnectionState::Online);
+        assert_eq!(room.drain_events(), vec![RoomEvent::Rehabilitated { connection: flaky }]);
+
+        run(&mut room, healthy, flaky, false, at, 2400, 4800);
+        assert_eq!(room.get(flaky).state, ConnectionState::Quarantined);
+
+        run(&mut room, healthy, flaky, false, at, 4800, 5400);
+        assert_eq!(room.get(flaky).state, ConnectionState::Disconnected);
+    }
+
+    #[test]
+    fn quarantined_connection_can_not_be_elected() {
+        let mut room = RoomConfig::new().with_quarantine_period(Duration::from_secs(2)).build().unwrap();
+        let now = Instant::now();
+        let at = |millis| now + Duration::from_millis(millis);
+        let leader = room.create_connection(now).unwrap();
+        let flaky = room.create_connection(now).unwrap();
+
+        run(&mut room, leader, flaky, false, at, 0, 600);
+        assert_eq!(room.get(flaky).state, ConnectionState::Quarantined);
+
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader_index, None);
+    }
+}
@@ -0,0 +1,80 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+
+use crate::{Connection, ConnectionState, QualityAssessment, Room};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Every connection matching `filter`, sorted by connection index
+    fn connections_where(&self, filter: impl Fn(&Connection<K>) -> bool) -> Vec<&Connection<K>> {
+        let mut connections: Vec<&Connection<K>> =
+            self.connections.values().filter(|connection| filter(connection)).collect();
+        connections.sort_by_key(|connection| connection.id.value());
+        connections
+    }
+
+    /// Every connection in the room, sorted by connection index
+    pub fn connections(&self) -> Vec<&Connection<K>> {
+        self.connections_where(|_| true)
+    }
+
+    /// Connections in [ConnectionState::Online], sorted by connection index
+    pub fn online_connections(&self) -> Vec<&Connection<K>> {
+        self.connections_where(|connection| connection.state == ConnectionState::Online)
+    }
+
+    /// Connections with the latest `assessment`, sorted by connection index
+    pub fn connections_by_assessment(&self, assessment: QualityAssessment) -> Vec<&Connection<K>> {
+        self.connections_where(|connection| connection.assessment() == assessment)
+    }
+
+    /// Every connection, most knowledge first. Connections with equal knowledge are sorted by connection index.
+    pub fn connections_sorted_by_knowledge(&self) -> Vec<&Connection<K>> {
+        let mut connections = self.connections();
+        connections.sort_by(|a, b| b.knowledge.cmp_knowledge(&a.knowledge));
+        connections
+    }
+
+    /// Connections created with `tag`, see [Room::create_connection_with_tags]. Sorted by connection index
+    pub fn connections_with_tag(&self, tag: &str) -> Vec<&Connection<K>> {
+        self.connections_where(|connection| connection.has_tag(tag))
+    }
+
+    /// Every connection except the leader, sorted by connection index
+    pub fn non_leader_connections(&self) -> Vec<&Connection<K>> {
+        self.connections_where(|connection| Some(connection.id) != self.leader_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::Knowledge;
+
+    use crate::{Connection, ConnectionIndex, PingPayload, QualityAssessment, Room};
+
+    #[test]
+    fn query_connections() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let ahead = room.create_connection(now).unwrap();
+        let behind = room.create_connection(now).unwrap();
+        room.preregister_connection("late", now).unwrap();
+        room.on_ping(ahead, &PingPayload::new().with_knowledge(Knowledge(20)), now);
+        room.on_ping(behind, &PingPayload::new().with_knowledge(Knowledge(10)), now);
+
+        let ids = |connections: Vec<&Connection>| -> Vec<ConnectionIndex> {
+            connections.iter().map(|connection| connection.id).collect()
+        };
+        let pending = room.find_by_identity("late").unwrap();
+        assert_eq!(ids(room.online_connections()), vec![leader, ahead, behind]);
+        assert_eq!(ids(room.non_leader_connections()), vec![ahead, behind, pending]);
+        assert_eq!(ids(room.connections_sorted_by_knowledge()), vec![ahead, behind, leader, pending]);
+        assert_eq!(room.connections_by_assessment(QualityAssessment::NeedMoreInformation).len(), 4);
+        assert!(room.connections_by_assessment(QualityAssessment::Good).is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{ConnectionIndex, Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// The followers that the leader reported, in its latest ping, that it can not reach. Sorted by connection index
+    pub fn unreachable_by_leader(&self) -> &[ConnectionIndex] {
+        &self.unreachable_by_leader
+    }
+
+    /// True if the leader reported that it can not reach the connection
+    pub fn is_unreachable_by_leader(&self, connection_index: ConnectionIndex) -> bool {
+        self.unreachable_by_leader.contains(&connection_index)
+    }
+
+    /// Takes the [crate::PingPayload::unreachable] from a ping sent by the leader. Connections that are not in the
+    /// room, and the leader itself, are ignored.
+    pub(crate) fn update_leader_reachability(&mut self, sender: ConnectionIndex, unreachable: &[ConnectionIndex]) {
+        if self.leader_index != Some(sender) {
+            return;
+        }
+
+        let mut reported: Vec<ConnectionIndex> = unreachable
+            .iter()
+            .copied()
+            .filter(|index| *index != sender && self.connections.contains_key(index))
+            .collect();
+        reported.sort_by_key(|index| index.value());
+        reported.dedup();
+
+        for connection in &reported {
+            if !self.unreachable_by_leader.contains(connection) {
+                info!("leader {} can not reach {}", sender, connection);
+                self.push_event(RoomEvent::UnreachableByLeader { connection: *connection });
+            }
+        }
+        let previous = std::mem::replace(&mut self.unreachable_by_leader, reported);
+        for connection in previous {
+            if !self.unreachable_by_leader.contains(&connection) && self.connections.contains_key(&connection) {
+                info!("leader {} can reach {} again", sender, connection);
+                self.push_event(RoomEvent::ReachableByLeader { connection });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{ConnectionState, PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn quarantine_follower_leader_can_not_reach() {
+        let mut room = RoomConfig::new().with_quarantine_period(Duration::from_secs(5)).build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+
+        // Only the leader can report what it can not reach
+        room.on_ping(follower, &PingPayload::new().with_unreachable(vec![leader]), now);
+        assert!(room.unreachable_by_leader().is_empty());
+
+        room.drain_events();
+        room.on_ping(leader, &PingPayload::new().with_unreachable(vec![follower]), now);
+        assert!(room.is_unreachable_by_leader(follower));
+        assert_eq!(room.get(follower).state, ConnectionState::Quarantined);
+        assert_eq!(
+            room.drain_events(),
+            vec![
+                RoomEvent::UnreachableByLeader { connection: follower },
+                RoomEvent::Quarantined { connection: follower },
+            ]
+        );
+        assert!(room.debug_dump(now).connections[1].unreachable_by_leader);
+
+        let time = now + Duration::from_millis(100);
+        room.on_ping(follower, &PingPayload::new(), time);
+        room.on_ping(leader, &PingPayload::new(), time);
+        assert!(room.unreachable_by_leader().is_empty());
+        assert_eq!(room.drain_events(), vec![RoomEvent::ReachableByLeader { connection: follower }]);
+    }
+}
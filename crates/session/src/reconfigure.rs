@@ -0,0 +1,85 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::recorder::RecordedInput;
+use crate::{ConfigError, Room, RoomConfig};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Replaces the config of a live room, e.g. to loosen the quality threshold during a known network incident.
+    /// The config is checked with [RoomConfig::validate] first, and left unchanged if it is not valid.
+    ///
+    /// Most settings take effect immediately, including the quality threshold, assessment window, minimum samples
+    /// and rate smoothing, which are applied to the existing connections without losing the pings counted so far.
+    /// Some settings only apply from the next time they are used:
+    ///
+    /// * [RoomConfig::leader_probation], [RoomConfig::lease_duration] and [crate::LeaderStability::reelection_backoff]
+    ///   from the next leader change (the lease also from the next ping by the leader)
+    /// * [RoomConfig::min_connections_for_election] only before the first leader has been appointed
+    /// * [RoomConfig::max_connection_index] for connections created from now on
+    /// * [crate::LeaderStability::backoff_seed] only when a room is created
+    ///
+    /// A shorter [RoomConfig::event_history], [RoomConfig::stats_history] or [RoomConfig::election_record_history]
+    /// drops the oldest entries right away.
+    pub fn update_config(&mut self, config: RoomConfig) -> Result<(), ConfigError> {
+        self.record_untimed(RecordedInput::UpdateConfig { config: Box::new(config.clone()) });
+        config.validate()?;
+
+        info!("updating config of room {}", self.id);
+        for connection in self.connections.values_mut() {
+            connection.quality.reconfigure(&config);
+        }
+        self.scan.invalidate_measurements();
+        self.config = config;
+        self.trim_event_history();
+        self.trim_stats_history();
+        self.trim_election_records();
+        self.publish_view();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{ConnectionIndex, ConnectionState, PingPayload, Room, RoomConfig};
+
+    /// The follower pings three times a second, the leader ten times
+    fn run(room: &mut Room, leader: ConnectionIndex, follower: ConnectionIndex, now: Instant, from: u64, to: u64) {
+        for millis in (from + 100..=to).step_by(100) {
+            let time = now + Duration::from_millis(millis);
+            room.on_ping(leader, &PingPayload::new(), time);
+            if millis % 300 == 0 {
+                room.on_ping(follower, &PingPayload::new(), time);
+            }
+            room.update(time);
+        }
+    }
+
+    #[test]
+    fn loosen_threshold_of_live_room() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+
+        let loosened = RoomConfig::new()
+            .pings_per_second_threshold(1.0)
+            .with_assessment_window(Duration::from_secs(1));
+        assert!(room.update_config(loosened.clone().pings_per_second_threshold(-1.0)).is_err());
+        assert_eq!(room.config, RoomConfig::default());
+
+        room.update_config(loosened.clone()).unwrap();
+        assert_eq!(room.config, loosened);
+        run(&mut room, leader, follower, now, 0, 3000);
+        assert_eq!(room.get(follower).state, ConnectionState::Online);
+
+        room.update_config(RoomConfig::new()).unwrap();
+        run(&mut room, leader, follower, now, 3000, 4000);
+        assert_eq!(room.get(follower).state, ConnectionState::Disconnected);
+    }
+}
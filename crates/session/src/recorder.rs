@@ -0,0 +1,423 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::{ConnectionToLeader, Knowledge, KnowledgeOrd, Term};
+
+use crate::{CloseReason, ConnectionIndex, ConnectionPriority, GroupId, KickBallot, Instant, PingPayload, Room, RoomConfig};
+
+/// An input given to a [Room] from the outside, as captured by the recorder
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecordedInput {
+    CreateConnection,
+    CreateConnectionWithId {
+        value: u32,
+    },
+    Preregister {
+        identity: String,
+    },
+    CreateConnectionWithIdentity {
+        identity: String,
+    },
+    AdmitWithEviction {
+        identity: String,
+        priority: ConnectionPriority,
+    },
+    CreateConnectionWithTags {
+        tags: Vec<String>,
+    },
+    Ping {
+        connection: u32,
+        generation: u32,
+        term: u16,
+        /// `None` if the connection did not know if it could reach the leader
+        has_connection_to_leader: Option<bool>,
+        /// The [KnowledgeOrd::progress] of the reported knowledge
+        knowledge: u64,
+        protocol_version: u16,
+        sequence: Option<u16>,
+        signature: Option<Vec<u8>>,
+        last_input_age: Option<Duration>,
+        /// Value and generation of each connection index in [PingPayload::unreachable]
+        unreachable: Vec<(u32, u32)>,
+        leader_unreachable_since: Option<Duration>,
+        /// Value and generation of [PingPayload::followed_leader]
+        followed_leader: Option<(u32, u32)>,
+        sent_at: Option<Duration>,
+        capability: Option<u8>,
+        knowledge_checksum: Option<u64>,
+        /// Proposal id and approval of [PingPayload::kick_vote]
+        kick_vote: Option<(u32, bool)>,
+    },
+    Destroy {
+        connection: u32,
+        generation: u32,
+    },
+    SetGroup {
+        connection: u32,
+        generation: u32,
+        group: Option<u32>,
+    },
+    AppointLeader {
+        connection: u32,
+        generation: u32,
+    },
+    Traffic {
+        connection: u32,
+        generation: u32,
+        bytes_in: u64,
+        bytes_out: u64,
+    },
+    RoundTrip {
+        connection: u32,
+        generation: u32,
+        round_trip: Duration,
+    },
+    PathChange {
+        connection: u32,
+        generation: u32,
+    },
+    UpdateConfig {
+        config: Box<RoomConfig>,
+    },
+    /// The connection was moved to another room, which is not part of the recording
+    TransferOut {
+        connection: u32,
+        generation: u32,
+    },
+    BeginClose {
+        reason: CloseReason,
+    },
+    AcknowledgeClose {
+        connection: u32,
+        generation: u32,
+    },
+    ProposeKick {
+        target: u32,
+        target_generation: u32,
+        proposer: u32,
+        proposer_generation: u32,
+    },
+    EnterCriticalSection {
+        tag: String,
+        max_duration: Duration,
+    },
+    ExitCriticalSection,
+    Update,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordedEntry {
+    /// Time since the recording started
+    pub at: Duration,
+    pub input: RecordedInput,
+}
+
+/// Every input given to a room while recording, see [Room::start_recording] and [Room::replay]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoomLog {
+    pub config: RoomConfig,
+    pub entries: Vec<RecordedEntry>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    started_at: Instant,
+    log: RoomLog,
+}
+
+impl Recorder {
+    fn record(&mut self, time: Instant, input: RecordedInput) {
+        self.log.entries.push(RecordedEntry {
+            at: time.saturating_duration_since(self.started_at),
+            input,
+        });
+    }
+
+    /// For inputs that do not depend on time, they are placed at the time of the previous entry
+    fn record_untimed(&mut self, input: RecordedInput) {
+        let at = self.log.entries.last().map_or(Duration::ZERO, |entry| entry.at);
+        self.log.entries.push(RecordedEntry { at, input });
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Starts capturing all inputs to the room.
+    ///
+    /// The log can only be replayed into an identical state if the recording is started on a newly
+    /// created room, before the first connection is added.
+    pub fn start_recording(&mut self, now: Instant) {
+        self.recorder = Some(Recorder {
+            started_at: now,
+            log: RoomLog {
+                config: self.config.clone(),
+                entries: Vec::new(),
+            },
+        });
+    }
+
+    /// The inputs recorded so far, `None` if the room is not recording
+    pub fn recording(&self) -> Option<&RoomLog> {
+        self.recorder.as_ref().map(|recorder| &recorder.log)
+    }
+
+    /// Stops recording and returns the log
+    pub fn take_recording(&mut self) -> Option<RoomLog> {
+        self.recorder.take().map(|recorder| recorder.log)
+    }
+
+    /// Called with every input given to the room, so it also drops the [Room::view] the input may change
+    pub(crate) fn record(&mut self, time: Instant, input: RecordedInput) {
+        self.invalidate_view();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(time, input);
+        }
+    }
+
+    pub(crate) fn record_untimed(&mut self, input: RecordedInput) {
+        self.invalidate_view();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record_untimed(input);
+        }
+    }
+
+    /// The input for a ping, only built when the room is recording
+    pub(crate) fn record_ping(&mut self, connection_index: ConnectionIndex, ping: &PingPayload<K>, time: Instant) {
+        self.invalidate_view();
+        if self.recorder.is_none() {
+            return;
+        }
+        let input = RecordedInput::Ping {
+            connection: connection_index.value(),
+            generation: connection_index.generation(),
+            term: ping.term.value(),
+            has_connection_to_leader: match ping.has_connection_to_leader {
+                ConnectionToLeader::Unknown => None,
+                ConnectionToLeader::Connected => Some(true),
+                ConnectionToLeader::Disconnected => Some(false),
+            },
+            knowledge: ping.knowledge.progress(),
+            protocol_version: ping.protocol_version,
+            sequence: ping.sequence,
+            signature: ping.signature.clone(),
+            last_input_age: ping.last_input_age,
+            unreachable: ping.unreachable.iter().map(|index| (index.value(), index.generation())).collect(),
+            leader_unreachable_since: ping.leader_unreachable_since,
+            followed_leader: ping.followed_leader.map(|index| (index.value(), index.generation())),
+            sent_at: ping.sent_at,
+            capability: ping.capability,
+            knowledge_checksum: ping.knowledge_checksum,
+            kick_vote: ping.kick_vote.map(|ballot| (ballot.proposal, ballot.approve)),
+        };
+        self.record(time, input);
+    }
+}
+
+impl Room {
+    /// Creates a room from the log's config and feeds it all recorded inputs, with the recording starting at `start`.
+    ///
+    /// Ping authenticators are not part of the log, so pings are replayed without verification.
+    pub fn replay(log: &RoomLog, start: Instant) -> Room {
+        let mut room = Room::new_with_config(log.config.clone());
+        for entry in &log.entries {
+            let time = start + entry.at;
+            match &entry.input {
+                RecordedInput::CreateConnection => {
+                    let _ = room.create_connection(time);
+                }
+                RecordedInput::CreateConnectionWithId { value } => {
+                    let _ = room.create_connection_with_id(ConnectionIndex::new(*value), time);
+                }
+                RecordedInput::Preregister { identity } => {
+                    let _ = room.preregister_connection(identity, time);
+                }
+                RecordedInput::CreateConnectionWithIdentity { identity } => {
+                    let _ = room.create_connection_with_identity(identity, time);
+                }
+                RecordedInput::AdmitWithEviction { identity, priority } => {
+                    let _ = room.admit_with_eviction(identity, *priority, time);
+                }
+                RecordedInput::CreateConnectionWithTags { tags } => {
+                    let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+                    let _ = room.create_connection_with_tags(&tags, time);
+                }
+                RecordedInput::Ping {
+                    connection,
+                    generation,
+                    term,
+                    has_connection_to_leader,
+                    knowledge,
+                    protocol_version,
+                    sequence,
+                    signature,
+                    last_input_age,
+                    unreachable,
+                    leader_unreachable_since,
+                    followed_leader,
+                    sent_at,
+                    capability,
+                    knowledge_checksum,
+                    kick_vote,
+                } => {
+                    let has_connection_to_leader = match has_connection_to_leader {
+                        None => ConnectionToLeader::Unknown,
+                        Some(true) => ConnectionToLeader::Connected,
+                        Some(false) => ConnectionToLeader::Disconnected,
+                    };
+                    let mut ping = PingPayload::new()
+                        .with_term(Term(*term))
+                        .with_connection_to_leader(has_connection_to_leader)
+                        .with_knowledge(Knowledge(*knowledge))
+                        .with_protocol_version(*protocol_version);
+                    ping.sequence = *sequence;
+                    ping.signature = signature.clone();
+                    ping.last_input_age = *last_input_age;
+                    ping.unreachable = unreachable
+                        .iter()
+                        .map(|(value, generation)| ConnectionIndex::with_generation(*value, *generation))
+                        .collect();
+                    ping.leader_unreachable_since = *leader_unreachable_since;
+                    ping.followed_leader = followed_leader
+                        .map(|(value, generation)| ConnectionIndex::with_generation(value, generation));
+                    ping.sent_at = *sent_at;
+                    ping.capability = *capability;
+                    ping.knowledge_checksum = *knowledge_checksum;
+                    ping.kick_vote = kick_vote.map(|(proposal, approve)| KickBallot { proposal, approve });
+                    room.on_ping(ConnectionIndex::with_generation(*connection, *generation), &ping, time);
+                }
+                RecordedInput::Destroy { connection, generation } => {
+                    // Failed operations are recorded as well, and fail the same way when replayed
+                    let _ = room.destroy_connection(ConnectionIndex::with_generation(*connection, *generation));
+                }
+                RecordedInput::SetGroup {
+                    connection,
+                    generation,
+                    group,
+                } => {
+                    let _ = room.set_group(ConnectionIndex::with_generation(*connection, *generation), group.map(GroupId));
+                }
+                RecordedInput::AppointLeader { connection, generation } => {
+                    let _ = room.appoint_leader(ConnectionIndex::with_generation(*connection, *generation));
+                }
+                RecordedInput::Traffic {
+                    connection,
+                    generation,
+                    bytes_in,
+                    bytes_out,
+                } => {
+                    let connection = ConnectionIndex::with_generation(*connection, *generation);
+                    let _ = room.record_traffic(connection, *bytes_in, *bytes_out, time);
+                }
+                RecordedInput::RoundTrip {
+                    connection,
+                    generation,
+                    round_trip,
+                } => {
+                    let connection = ConnectionIndex::with_generation(*connection, *generation);
+                    let _ = room.record_round_trip(connection, *round_trip, time);
+                }
+                RecordedInput::PathChange { connection, generation } => {
+                    let connection = ConnectionIndex::with_generation(*connection, *generation);
+                    let _ = room.report_path_change(connection, time);
+                }
+                RecordedInput::UpdateConfig { config } => {
+                    let _ = room.update_config(config.as_ref().clone());
+                }
+                RecordedInput::TransferOut { connection, generation } => {
+                    let _ = room.transfer_connection(
+                        ConnectionIndex::with_generation(*connection, *generation),
+                        &mut Room::new(),
+                        time,
+                    );
+                }
+                RecordedInput::BeginClose { reason } => room.begin_close(*reason, time),
+                RecordedInput::AcknowledgeClose { connection, generation } => {
+                    let _ = room.acknowledge_close(ConnectionIndex::with_generation(*connection, *generation));
+                }
+                RecordedInput::ProposeKick {
+                    target,
+                    target_generation,
+                    proposer,
+                    proposer_generation,
+                } => {
+                    let _ = room.propose_kick(
+                        ConnectionIndex::with_generation(*target, *target_generation),
+                        ConnectionIndex::with_generation(*proposer, *proposer_generation),
+                        time,
+                    );
+                }
+                RecordedInput::EnterCriticalSection { tag, max_duration } => {
+                    room.enter_critical_section(tag, *max_duration, time);
+                }
+                RecordedInput::ExitCriticalSection => {
+                    room.exit_critical_section();
+                }
+                RecordedInput::Update => room.update(time),
+            }
+        }
+        room
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{ConnectionToLeader, Knowledge, Term};
+
+    use crate::recorder::RecordedInput;
+    use crate::{PingPayload, Room};
+
+    fn record_session(start: Instant) -> Room {
+        let mut room = Room::new();
+        room.start_recording(start);
+        let leader = room.create_connection(start).unwrap();
+        let follower = room.create_connection(start).unwrap();
+        for tick in 1..40u64 {
+            let now = start + Duration::from_millis(tick * 50);
+            room.on_ping(follower, &PingPayload::new().with_term(room.term).with_knowledge(Knowledge(tick)), now);
+            if tick < 20 {
+                room.on_ping(leader, &PingPayload::new().with_term(room.term).with_knowledge(Knowledge(tick)), now);
+            } else {
+                let ping = PingPayload::new()
+                    .with_term(room.term)
+                    .with_connection_to_leader(ConnectionToLeader::Disconnected)
+                    .with_knowledge(Knowledge(tick));
+                room.on_ping(follower, &ping, now);
+            }
+        }
+        room.update(start + Duration::from_secs(3));
+        room
+    }
+
+    #[test]
+    fn replay_recording() {
+        let start = Instant::now();
+        let mut room = record_session(start);
+        let log = room.take_recording().unwrap();
+        assert_eq!(log.entries.len(), 2 + 39 * 2 + 1);
+        assert_eq!(log.entries[0].input, RecordedInput::CreateConnection);
+        assert_eq!(log.entries.last().unwrap().at, Duration::from_secs(3));
+
+        let replayed = Room::replay(&log, start);
+
+        assert_eq!(replayed.debug_dump(start + Duration::from_secs(3)), room.debug_dump(start + Duration::from_secs(3)));
+        assert_ne!(room.term, Term(1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_recording() {
+        let start = Instant::now();
+        let log = record_session(start).take_recording().unwrap();
+
+        let json = serde_json::to_string(&log).unwrap();
+
+        assert_eq!(serde_json::from_str::<crate::RoomLog>(&json).unwrap(), log);
+    }
+}
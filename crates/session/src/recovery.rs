@@ -0,0 +1,100 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::{KnowledgeOrd, Term};
+use log::info;
+
+use crate::{ConnectionState, ElectionTrigger, QualityAssessment, Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Elects a leader again if the room has lost its leader, e.g. when the only candidates were quarantined as
+    /// the leader left. Only online connections with acceptable or good quality can win, and nothing happens
+    /// before the first leader has been appointed, so [crate::RoomConfig::min_connections_for_election] still
+    /// holds. See [crate::RoomConfig::restore_leader_when_leaderless].
+    pub(crate) fn restore_leader_if_leaderless(&mut self) {
+        if !self.config.restore_leader_when_leaderless || self.leader_index.is_some() || self.term == Term(0) {
+            return;
+        }
+
+        let mut report = self.evaluate_election(None);
+        let mut ranked: Vec<_> = report
+            .candidates
+            .iter()
+            .filter(|candidate| {
+                matches!(candidate.assessment, QualityAssessment::Acceptable | QualityAssessment::Good)
+                    && self.connections[&candidate.connection].state == ConnectionState::Online
+            })
+            .filter_map(|candidate| candidate.rank.map(|rank| (rank, candidate.connection)))
+            .collect();
+        ranked.sort_by_key(|(rank, _)| *rank);
+        let Some(&(_, winner)) = ranked.first() else {
+            return;
+        };
+
+        info!("room {} has been without a leader, restoring it with {}", self.id, winner);
+        report.winner = Some(winner);
+        self.switch_leader(Some(winner));
+        self.remember_election(report, ElectionTrigger::Restored);
+        self.push_event(RoomEvent::LeaderRestored {
+            term: self.term,
+            leader: winner,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::ConnectionToLeader;
+
+    use crate::{ConnectionIndex, ConnectionState, PingPayload, Room, RoomConfig, RoomEvent};
+
+    /// The leader leaves while the only other connection is quarantined, which then starts pinging again
+    fn lose_leader_and_recover(config: RoomConfig) -> (Room, ConnectionIndex) {
+        let mut room = config.with_quarantine_period(Duration::from_secs(2)).build().unwrap();
+        let now = Instant::now();
+        let at = |millis| now + Duration::from_millis(millis);
+        let leader = room.create_connection(now).unwrap();
+        let flaky = room.create_connection(now).unwrap();
+        let ping = PingPayload::new().with_connection_to_leader(ConnectionToLeader::Connected);
+
+        for millis in (100..=600).step_by(100) {
+            room.on_ping(leader, &ping, at(millis));
+            room.update(at(millis));
+        }
+        assert_eq!(room.get(flaky).state, ConnectionState::Quarantined);
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader_index, None);
+        room.drain_events();
+
+        for millis in (700..=1300).step_by(100) {
+            room.on_ping(flaky, &ping, at(millis));
+            room.update(at(millis));
+        }
+        assert_eq!(room.get(flaky).state, ConnectionState::Online);
+        (room, flaky)
+    }
+
+    #[test]
+    fn restore_leader_when_connection_recovers() {
+        let (mut room, flaky) = lose_leader_and_recover(RoomConfig::new().with_restore_leader_when_leaderless(true));
+        assert_eq!(room.leader_index, Some(flaky));
+        let events = room.drain_events();
+        assert!(events.contains(&RoomEvent::Rehabilitated { connection: flaky }));
+        assert_eq!(
+            events.last(),
+            Some(&RoomEvent::LeaderRestored {
+                term: room.term,
+                leader: flaky,
+            })
+        );
+    }
+
+    #[test]
+    fn stay_leaderless_when_not_restoring() {
+        let (room, _) = lose_leader_and_recover(RoomConfig::new());
+        assert_eq!(room.leader_index, None);
+    }
+}
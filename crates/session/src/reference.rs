@@ -0,0 +1,268 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! A reference model of the election rules, to check the [Room] against.
+//!
+//! The model keeps nothing but the history of inputs, and recomputes the leader, the term and the knowledge of
+//! every connection from the start of that history each time it is asked. It covers the rules that do not depend
+//! on time passing: the first connection leads, a destroyed leader is replaced, and a majority of down-votes in
+//! the current term replaces the leader. The best candidate is the one with the most knowledge, ties going to the
+//! lowest connection index. All inputs must be given at the same instant, with [ReferenceModel::config].
+//!
+//! Available in the crate's own tests and, for other crates, behind the `testing` feature.
+//!
+//! ```
+//! use std::time::Instant;
+//! use conclave_room_session::reference::{ModelOp, ModelState, ReferenceModel};
+//!
+//! let mut model = ReferenceModel::new();
+//! let mut room = model.config().build().unwrap();
+//! let now = Instant::now();
+//! for op in [ModelOp::CreateConnection, ModelOp::CreateConnection, ModelOp::Destroy { slot: 0 }] {
+//!     model.apply(&mut room, &op, now);
+//!     assert_eq!(ModelState::observe(&room), model.state());
+//! }
+//! ```
+use conclave_types::{ConnectionToLeader, Knowledge, KnowledgeOrd, Term};
+
+use crate::{ConnectionIndex, Instant, PingPayload, Room, RoomConfig};
+
+/// An input to both the model and the room. Connections are referred to by `slot`, like in [crate::RoomOp].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelOp {
+    CreateConnection,
+    Ping {
+        slot: u8,
+        knowledge: u64,
+        /// Reports the term before the current one, as a connection that has not heard about the new leader yet
+        is_stale: bool,
+        connection_to_leader: ConnectionToLeader,
+    },
+    Destroy {
+        slot: u8,
+    },
+}
+
+/// An input as resolved against the connections that existed when it was given
+#[derive(Debug, Clone, PartialEq)]
+enum Input {
+    Created(ConnectionIndex),
+    Pinged {
+        connection: ConnectionIndex,
+        term: Term,
+        knowledge: u64,
+        connection_to_leader: ConnectionToLeader,
+    },
+    Destroyed(ConnectionIndex),
+}
+
+/// What the election rules decide, compared between the model and the room
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelState {
+    pub term: Term,
+    pub leader: Option<ConnectionIndex>,
+    /// Every connection and its knowledge, sorted by connection index
+    pub knowledge: Vec<(ConnectionIndex, u64)>,
+}
+
+impl ModelState {
+    pub fn observe(room: &Room) -> Self {
+        Self {
+            term: room.term(),
+            leader: room.leader(),
+            knowledge: room
+                .connections()
+                .iter()
+                .map(|connection| (connection.id, connection.knowledge.progress()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ModelConnection {
+    index: ConnectionIndex,
+    knowledge: u64,
+    latest_report: Option<(Term, ConnectionToLeader)>,
+}
+
+/// Recomputes the outcome of the election rules from the full history, see the [module documentation](self)
+#[derive(Debug, Default, Clone)]
+pub struct ReferenceModel {
+    history: Vec<Input>,
+}
+
+impl ReferenceModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The room config the model describes. Only the settings that keep a frozen clock from mattering differ from
+    /// the default.
+    pub fn config(&self) -> RoomConfig {
+        RoomConfig::new()
+            .with_disconnect_bad_connections(false)
+    }
+
+    /// Gives the operation to the room at `now` and records it in the history
+    pub fn apply(&mut self, room: &mut Room, op: &ModelOp, now: Instant) {
+        let state = self.state();
+        let in_slot = |slot: u8| {
+            (!state.knowledge.is_empty()).then(|| state.knowledge[slot as usize % state.knowledge.len()].0)
+        };
+        match *op {
+            ModelOp::CreateConnection => {
+                let connection = room.create_connection(now).expect("the model never runs out of connection indices");
+                self.history.push(Input::Created(connection));
+            }
+            ModelOp::Ping {
+                slot,
+                knowledge,
+                is_stale,
+                connection_to_leader,
+            } => {
+                let Some(connection) = in_slot(slot) else {
+                    return;
+                };
+                let term = if is_stale { Term(state.term.value().saturating_sub(1)) } else { state.term };
+                let ping = PingPayload::new()
+                    .with_term(term)
+                    .with_knowledge(Knowledge(knowledge))
+                    .with_connection_to_leader(connection_to_leader);
+                room.on_ping(connection, &ping, now);
+                self.history.push(Input::Pinged {
+                    connection,
+                    term,
+                    knowledge,
+                    connection_to_leader,
+                });
+            }
+            ModelOp::Destroy { slot } => {
+                let Some(connection) = in_slot(slot) else {
+                    return;
+                };
+                let _ = room.destroy_connection(connection);
+                self.history.push(Input::Destroyed(connection));
+            }
+        }
+    }
+
+    /// Replays the whole history through the election rules
+    pub fn state(&self) -> ModelState {
+        let mut term = Term(0);
+        let mut leader = None;
+        let mut connections: Vec<ModelConnection> = Vec::new();
+        let elect = |term: &mut Term, leader: &mut Option<ConnectionIndex>, winner| {
+            *leader = winner;
+            *term = Term(term.value() + 1);
+        };
+
+        for input in &self.history {
+            match *input {
+                Input::Created(index) => {
+                    connections.push(ModelConnection {
+                        index,
+                        knowledge: 0,
+                        latest_report: None,
+                    });
+                    if leader.is_none() {
+                        elect(&mut term, &mut leader, Some(index));
+                    }
+                }
+                Input::Pinged {
+                    connection: index,
+                    term: reported_term,
+                    knowledge,
+                    connection_to_leader,
+                } => {
+                    let connection = connections.iter_mut().find(|connection| connection.index == index).unwrap();
+                    connection.knowledge = knowledge;
+                    connection.latest_report = Some((reported_term, connection_to_leader));
+
+                    let down_votes = connections
+                        .iter()
+                        .filter(|connection| connection.latest_report == Some((term, ConnectionToLeader::Disconnected)))
+                        .count();
+                    if leader.is_some() && down_votes > connections.len() / 2 {
+                        let winner = best_candidate(&connections, leader);
+                        elect(&mut term, &mut leader, winner);
+                    }
+                }
+                Input::Destroyed(index) => {
+                    connections.retain(|connection| connection.index != index);
+                    if leader == Some(index) {
+                        let winner = best_candidate(&connections, None);
+                        elect(&mut term, &mut leader, winner);
+                    }
+                }
+            }
+        }
+
+        connections.sort_by_key(|connection| connection.index.value());
+        ModelState {
+            term,
+            leader,
+            knowledge: connections.iter().map(|connection| (connection.index, connection.knowledge)).collect(),
+        }
+    }
+}
+
+/// The connection with the most knowledge, the lowest index if several have the same
+fn best_candidate(connections: &[ModelConnection], excluded: Option<ConnectionIndex>) -> Option<ConnectionIndex> {
+    let mut best: Option<&ModelConnection> = None;
+    for connection in connections.iter().filter(|connection| Some(connection.index) != excluded) {
+        let is_better = best.is_none_or(|best| {
+            connection.knowledge > best.knowledge
+                || (connection.knowledge == best.knowledge && connection.index.value() < best.index.value())
+        });
+        if is_better {
+            best = Some(connection);
+        }
+    }
+    best.map(|connection| connection.index)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::ConnectionToLeader;
+    use proptest::prelude::*;
+
+    use crate::reference::{ModelOp, ModelState, ReferenceModel};
+
+    fn model_op() -> impl Strategy<Value = ModelOp> {
+        let connection_to_leader = prop_oneof![
+            Just(ConnectionToLeader::Unknown),
+            Just(ConnectionToLeader::Connected),
+            Just(ConnectionToLeader::Disconnected),
+        ];
+        prop_oneof![
+            1 => Just(ModelOp::CreateConnection),
+            1 => any::<u8>().prop_map(|slot| ModelOp::Destroy { slot }),
+            // Few knowledge values, so candidates often tie
+            5 => (any::<u8>(), 0..8u64, any::<bool>(), connection_to_leader).prop_map(
+                |(slot, knowledge, is_stale, connection_to_leader)| ModelOp::Ping {
+                    slot,
+                    knowledge,
+                    is_stale,
+                    connection_to_leader,
+                }
+            ),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn room_agrees_with_model(ops in prop::collection::vec(model_op(), 1..150)) {
+            let mut model = ReferenceModel::new();
+            let mut room = model.config().build().unwrap();
+            let now = Instant::now();
+            for op in &ops {
+                model.apply(&mut room, op, now);
+                prop_assert_eq!(ModelState::observe(&room), model.state(), "after {:?}", op);
+            }
+        }
+    }
+}
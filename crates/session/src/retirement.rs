@@ -0,0 +1,108 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{ConnectionIndex, ConnectionState, Instant, Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// The previous leader while it is kept around after a re-election, see [crate::RoomConfig::leader_overlap].
+    /// Clients can drain in-flight state from it while they connect to [Room::leader].
+    pub fn retiring_leader(&self) -> Option<ConnectionIndex> {
+        self.retiring_leader.map(|(connection, _)| connection)
+    }
+
+    /// Called when the leader has changed from `previous`. A retirement that is still going on is released first.
+    pub(crate) fn begin_retirement(&mut self, previous: Option<ConnectionIndex>) {
+        self.release_retiring_leader();
+        let (Some(overlap), Some(previous), Some(time)) = (self.config.leader_overlap, previous, self.latest_time)
+        else {
+            return;
+        };
+        let is_online = self
+            .connections
+            .get(&previous)
+            .is_some_and(|connection| connection.state == ConnectionState::Online);
+        if is_online && Some(previous) != self.leader_index {
+            self.retiring_leader = Some((previous, time + overlap));
+        }
+    }
+
+    /// Releases the retiring leader when the overlap is over, or as soon as it is no longer online
+    pub(crate) fn update_retirement(&mut self, time: Instant) {
+        let Some((connection, until)) = self.retiring_leader else {
+            return;
+        };
+        let is_online = self
+            .connections
+            .get(&connection)
+            .is_some_and(|connection| connection.state == ConnectionState::Online);
+        if time >= until || !is_online {
+            self.release_retiring_leader();
+        }
+    }
+
+    fn release_retiring_leader(&mut self) {
+        if let Some((connection, _)) = self.retiring_leader.take() {
+            info!("released retiring leader {} in term {}", connection, self.term);
+            self.push_event(RoomEvent::RetiredLeaderReleased {
+                term: self.term,
+                connection,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::ConnectionToLeader;
+
+    use crate::{PingPayload, Room, RoomConfig, RoomEvent};
+
+    #[test]
+    fn keep_old_leader_during_overlap() {
+        let mut room = RoomConfig::new().with_leader_overlap(Duration::from_millis(500)).build().unwrap();
+        let now = Instant::now();
+        let old_leader = room.create_connection(now).unwrap();
+        let followers = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+        assert_eq!(room.retiring_leader(), None);
+
+        let tick = |room: &mut Room, millis| {
+            let time = now + Duration::from_millis(millis);
+            let connection_to_leader = if room.leader() == Some(old_leader) {
+                ConnectionToLeader::Disconnected
+            } else {
+                ConnectionToLeader::Connected
+            };
+            let ping = PingPayload::new()
+                .with_term(room.term())
+                .with_connection_to_leader(connection_to_leader);
+            room.on_ping(old_leader, &ping.clone().with_connection_to_leader(ConnectionToLeader::Connected), time);
+            for follower in followers {
+                room.on_ping(follower, &ping, time);
+            }
+            room.update(time);
+        };
+        tick(&mut room, 100);
+        let term = room.term();
+        assert_ne!(room.leader(), Some(old_leader));
+        assert_eq!(room.retiring_leader(), Some(old_leader));
+
+        room.drain_events();
+        for millis in (200..=500).step_by(100) {
+            tick(&mut room, millis);
+        }
+        assert_eq!(room.retiring_leader(), Some(old_leader));
+        tick(&mut room, 600);
+        assert_eq!(room.retiring_leader(), None);
+        assert_eq!(room.term(), term);
+        assert!(room.drain_events().contains(&RoomEvent::RetiredLeaderReleased {
+            term,
+            connection: old_leader,
+        }));
+    }
+}
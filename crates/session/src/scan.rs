@@ -0,0 +1,151 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::{ConnectionToLeader, KnowledgeOrd, Term};
+use log::trace;
+
+use crate::{Connection, ConnectionIndex, Instant, PingPayload, QualityAssessment, Room};
+
+/// What the room knows about its connections without looking at every one of them. The room is updated on
+/// every ping, and most updates can skip the passes over all connections that would not find anything.
+///
+/// Everything here is a bound or a "maybe": it must stay true when connections change, but may be more
+/// pessimistic than the connections are.
+#[derive(Debug, Default)]
+pub(crate) struct ScanSummary {
+    /// No connection has a rate measurement due up to and including this time, `None` if unknown
+    measurement_due_after: Option<Instant>,
+    /// Connections that may have another assessment than [QualityAssessment::NeedMoreInformation]
+    pub(crate) assessed: Vec<ConnectionIndex>,
+    /// Highest term any connection has reported
+    highest_reported_term: Option<Term>,
+    /// Highest term reported together with [ConnectionToLeader::Disconnected]
+    highest_down_voted_term: Option<Term>,
+    pub(crate) may_have_pending: bool,
+    pub(crate) may_have_groups: bool,
+}
+
+fn is_above(term: Option<Term>, other: Term) -> bool {
+    term.is_some_and(|term| term.value() > other.value())
+}
+
+impl ScanSummary {
+    pub(crate) fn observe_ping<K: KnowledgeOrd>(&mut self, ping: &PingPayload<K>) {
+        self.observe_report(Some(ping.term), ping.has_connection_to_leader);
+    }
+
+    fn observe_report(&mut self, term: Option<Term>, has_connection_to_leader: ConnectionToLeader) {
+        let Some(term) = term else {
+            return;
+        };
+        if !is_above(self.highest_reported_term, term) {
+            self.highest_reported_term = Some(term);
+        }
+        if has_connection_to_leader == ConnectionToLeader::Disconnected && !is_above(self.highest_down_voted_term, term) {
+            self.highest_down_voted_term = Some(term);
+        }
+    }
+
+    /// The quality of a connection was replaced or reconfigured, so every connection has to be measured
+    pub(crate) fn invalidate_measurements(&mut self) {
+        self.measurement_due_after = None;
+    }
+
+    /// False if no connection can report a term ahead of `term`
+    pub(crate) fn may_be_ahead_of(&self, term: Term) -> bool {
+        is_above(self.highest_reported_term, term)
+    }
+
+    /// False if no connection can be down-voting the leader of `term`
+    pub(crate) fn may_down_vote(&self, term: Term) -> bool {
+        self.highest_down_voted_term.is_some_and(|down_voted| down_voted.value() >= term.value())
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Adds a connection that was created, preregistered, restored or transferred to the room
+    pub(crate) fn insert_connection(&mut self, connection: Connection<K>) {
+        // Transfers add connections to a room without recording an input there
+        self.invalidate_view();
+        let summary = &mut self.scan;
+        summary.observe_report(connection.last_reported_term, connection.has_connection_host);
+        summary.invalidate_measurements();
+        summary.may_have_pending |= connection.pending_since.is_some();
+        summary.may_have_groups |= connection.group.is_some();
+        if connection.assessment() != QualityAssessment::NeedMoreInformation {
+            summary.assessed.push(connection.id);
+        }
+        if let Some(identity) = &connection.identity {
+            self.identities.insert(identity.clone(), connection.id);
+        }
+        self.connections.insert(connection.id, connection);
+    }
+
+    /// Calculates the ping rate of the connections that have a measurement due and assesses them, and resets
+    /// the assessment of the others.
+    ///
+    /// Updates where no measurement is due only reset the connections assessed before. The assessed connections
+    /// are kept in a buffer that is reused, so updates do not allocate once the room has settled.
+    pub(crate) fn assess_connections(&mut self, time: Instant) {
+        let summary = &mut self.scan;
+        if summary.measurement_due_after.is_some_and(|due_after| time <= due_after) {
+            for connection_index in summary.assessed.drain(..) {
+                if let Some(connection) = self.connections.get_mut(&connection_index) {
+                    connection.quality.measure(time);
+                }
+            }
+            return;
+        }
+
+        summary.assessed.clear();
+        let mut due_after: Option<Instant> = None;
+        for connection in self.connections.values_mut() {
+            if connection.quality.measure(time) {
+                summary.assessed.push(connection.id);
+            }
+            let period_ends_at = connection.quality.pings_per_second.period_ends_at();
+            if due_after.is_none_or(|due_after| period_ends_at < due_after) {
+                due_after = Some(period_ends_at);
+            }
+        }
+        summary.measurement_due_after = due_after;
+
+        let ceiling = self.adaptive_threshold_ceiling();
+        for connection_index in &self.scan.assessed {
+            let connection = self.connections.get_mut(connection_index).unwrap();
+            connection.quality.assess(ceiling, time);
+            trace!("update {}", connection);
+        }
+    }
+
+    /// Keeps track of an assessment that was set outside of [Room::assess_connections]
+    #[cfg(feature = "testing")]
+    pub(crate) fn observe_assessment(&mut self, connection_index: ConnectionIndex) {
+        self.scan.assessed.push(connection_index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{PingPayload, QualityAssessment, Room};
+
+    #[test]
+    fn reset_assessment_when_update_skips_measurements() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let connection = room.create_connection(now).unwrap();
+        let other = room.create_connection(now + Duration::from_millis(200)).unwrap();
+        for millis in (50..=550).step_by(50) {
+            room.on_ping(connection, &PingPayload::new(), now + Duration::from_millis(millis));
+        }
+        assert_eq!(room.get(connection).assessment(), QualityAssessment::Good);
+        assert_eq!(room.get(other).assessment(), QualityAssessment::NeedMoreInformation);
+
+        room.update(now + Duration::from_millis(560));
+
+        assert_eq!(room.get(connection).assessment(), QualityAssessment::NeedMoreInformation);
+    }
+}
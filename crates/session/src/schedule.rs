@@ -0,0 +1,114 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+use log::warn;
+
+use crate::{Instant, Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// How long the host can wait before calling [Room::update] again without missing a decision.
+    ///
+    /// Returns `Duration::ZERO` if a deadline has already passed and `None` if nothing will happen
+    /// until the next ping or connection arrives, or while the room is paused.
+    pub fn time_until_next_action(&self, now: Instant) -> Option<Duration> {
+        self.next_action_at()
+            .map(|deadline| deadline.saturating_duration_since(now))
+    }
+
+    /// The latest time given to the room by [Room::create_connection], [Room::on_ping] or [Room::update]
+    pub fn latest_time(&self) -> Option<Instant> {
+        self.latest_time
+    }
+
+    /// Returns `time`, or the latest observed time if `time` is earlier than that, so the room never goes
+    /// back in time when the host mixes clocks.
+    pub(crate) fn observe_time(&mut self, time: Instant) -> Instant {
+        match self.latest_time {
+            Some(latest_time) if time < latest_time => {
+                let by = latest_time - time;
+                warn!("time went backwards by {:?}, using the latest time instead", by);
+                self.push_event(RoomEvent::TimeWentBackwards { by });
+                latest_time
+            }
+            _ => {
+                self.latest_time = Some(time);
+                time
+            }
+        }
+    }
+
+    fn next_action_at(&self) -> Option<Instant> {
+        if self.paused_at.is_some() {
+            return None;
+        }
+
+        let quality_deadlines = self
+            .connections
+            .values()
+            .map(|connection| connection.quality.next_assessment_at());
+
+        quality_deadlines
+            .chain(self.next_escalation_at())
+            .chain(self.close_deadline())
+            .chain(self.kick_vote.as_ref().and_then(|proposal| proposal.ends_at))
+            .chain(self.next_stats_sample_at())
+            .chain(self.critical_section.as_ref().and_then(|section| section.expires_at))
+            .chain(self.latest_time.filter(|_| self.update_deferred))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{PingPayload, Room, RoomEvent};
+
+    #[test]
+    fn next_action_is_quality_window_or_abandonment() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        assert_eq!(room.time_until_next_action(now), None);
+
+        let connection = room.create_connection(now).unwrap();
+        assert_eq!(room.time_until_next_action(now), Some(Duration::from_millis(501)));
+        assert_eq!(room.time_until_next_action(now + Duration::from_secs(1)), Some(Duration::ZERO));
+
+        room.on_ping(connection, &PingPayload::new(), now);
+        room.destroy_connection(connection).unwrap();
+        let next = room.time_until_next_action(now).unwrap();
+        assert!(next > Duration::from_secs(15 * 60));
+        assert!(!room.is_abandoned(now + next - Duration::from_millis(1)));
+        assert!(room.is_abandoned(now + next));
+    }
+
+    #[test]
+    fn clamp_time_going_backwards() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let connection = room.create_connection(now + Duration::from_secs(2)).unwrap();
+        room.drain_events();
+
+        room.on_ping(connection, &PingPayload::new(), now);
+        room.update(now + Duration::from_secs(1));
+
+        assert_eq!(room.latest_time(), Some(now + Duration::from_secs(2)));
+        assert_eq!(room.latest_ping_timestamp, Some(now + Duration::from_secs(2)));
+        assert_eq!(
+            room.drain_events(),
+            vec![
+                RoomEvent::TimeWentBackwards {
+                    by: Duration::from_secs(2)
+                },
+                RoomEvent::TimeWentBackwards {
+                    by: Duration::from_secs(1)
+                },
+            ]
+        );
+        assert!(!room.is_abandoned(now));
+    }
+}
@@ -0,0 +1,51 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+
+use crate::{ConnectionIndex, Instant, Room};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Connections that have not pinged for longer than `threshold`, see [crate::Connection::silent_for].
+    /// Sorted by connection index.
+    pub fn silent_connections(&self, now: Instant, threshold: Duration) -> Vec<ConnectionIndex> {
+        let mut silent: Vec<ConnectionIndex> = self
+            .connections
+            .values()
+            .filter(|connection| connection.silent_for(now) > threshold)
+            .map(|connection| connection.id)
+            .collect();
+        silent.sort_by_key(|index| index.value());
+        silent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{PingPayload, Room};
+
+    #[test]
+    fn find_silent_connections() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let chatty = room.create_connection(now).unwrap();
+        let quiet = room.create_connection(now).unwrap();
+        let later = now + Duration::from_millis(1500);
+        room.on_ping(chatty, &PingPayload::new(), later);
+
+        assert_eq!(room.get(chatty).last_ping_at(), later);
+        assert_eq!(room.get(quiet).last_ping_at(), now);
+        assert_eq!(room.get(quiet).silent_for(later), Duration::from_millis(1500));
+        assert_eq!(room.get(quiet).silent_for(now - Duration::from_secs(1)), Duration::ZERO);
+
+        let at = later + Duration::from_millis(500);
+        assert_eq!(room.silent_connections(at, Duration::from_secs(1)), vec![quiet]);
+        assert_eq!(room.silent_connections(at, Duration::from_millis(400)), vec![chatty, quiet]);
+        assert!(room.silent_connections(at, Duration::from_secs(2)).is_empty());
+    }
+}
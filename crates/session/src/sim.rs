@@ -0,0 +1,416 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! Deterministic simulation of a [Room] with scripted clients and a virtual clock.
+//!
+//! Available in the crate's own tests and, for other crates, behind the `sim` feature.
+//!
+//! ```
+//! use std::time::Duration;
+//! use conclave_room_session::sim::{ScriptedClient, Simulation};
+//!
+//! let mut sim = Simulation::new(42);
+//! let first = sim.add_client(ScriptedClient::new(10.0)).unwrap();
+//! let second = sim.add_client(ScriptedClient::new(10.0).with_loss(0.1)).unwrap();
+//! sim.run_for(Duration::from_secs(2));
+//! sim.client_mut(first).stop();
+//! sim.run_for(Duration::from_secs(2));
+//! assert_eq!(sim.leader_at(Duration::from_secs(1)), Some(first));
+//! assert_eq!(sim.room.leader(), Some(second));
+//! ```
+//!
+//! Network partitions are declared up front with [Simulation::partition], and the clients report them in their pings.
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::Duration;
+
+use conclave_types::{ConnectionToLeader, Knowledge, Term};
+
+use crate::{ConnectionIndex, Instant, PingPayload, Room, RoomConfig, RoomError, RoomEvent};
+
+/// How far the clock advances between two room updates, unless [Simulation::with_step] is used
+const DEFAULT_STEP: Duration = Duration::from_millis(10);
+
+/// Clock that only moves when told to
+#[derive(Debug, Clone, Copy)]
+pub struct VirtualClock {
+    origin: Instant,
+    elapsed: Duration,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self {
+            origin: crate::time::origin(),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn now(&self) -> Instant {
+        self.origin + self.elapsed
+    }
+
+    /// Time passed since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn advance(&mut self, duration: Duration) {
+        self.elapsed += duration;
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Small xorshift generator, so simulations are reproducible from a seed without extra dependencies
+#[derive(Debug, Clone)]
+struct SimRng(u64);
+
+impl SimRng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform in `[0, 1)`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// A client that pings the room at a fixed rate, optionally losing pings and jittering the interval
+#[derive(Debug, Clone)]
+pub struct ScriptedClient {
+    pub pings_per_second: f32,
+    /// Probability, between 0 and 1, that a ping never reaches the room
+    pub loss: f64,
+    /// Each interval is moved randomly by up to this much in either direction
+    pub jitter: Duration,
+    pub knowledge_per_ping: u64,
+    pub connection_to_leader: ConnectionToLeader,
+    knowledge: Knowledge,
+    next_ping_at: Duration,
+    is_stopped: bool,
+}
+
+impl ScriptedClient {
+    pub fn new(pings_per_second: f32) -> Self {
+        Self {
+            pings_per_second,
+            loss: 0.0,
+            jitter: Duration::ZERO,
+            knowledge_per_ping: 1,
+            connection_to_leader: ConnectionToLeader::Connected,
+            knowledge: Knowledge(0),
+            next_ping_at: Duration::ZERO,
+            is_stopped: false,
+        }
+    }
+
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_knowledge_per_ping(mut self, knowledge_per_ping: u64) -> Self {
+        self.knowledge_per_ping = knowledge_per_ping;
+        self
+    }
+
+    pub fn with_connection_to_leader(mut self, connection_to_leader: ConnectionToLeader) -> Self {
+        self.connection_to_leader = connection_to_leader;
+        self
+    }
+
+    /// Stops pinging, as if the client crashed or lost its network
+    pub fn stop(&mut self) {
+        self.is_stopped = true;
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.is_stopped
+    }
+
+    fn interval(&self, rng: &mut SimRng) -> Duration {
+        let interval = 1.0 / self.pings_per_second as f64;
+        let jitter = self.jitter.as_secs_f64() * (rng.next_f64() * 2.0 - 1.0);
+        Duration::from_secs_f64((interval + jitter).max(0.001))
+    }
+}
+
+/// Splits the room in two for a time range: the `connections` and everyone else can not reach each other
+#[derive(Debug, Clone, PartialEq)]
+struct Partition {
+    connections: Vec<ConnectionIndex>,
+    /// Simulated time since the start of the simulation
+    during: Range<Duration>,
+}
+
+/// A leader change observed during the simulation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LeaderChange {
+    /// Simulated time since the start of the simulation
+    pub at: Duration,
+    pub term: Term,
+    pub leader: Option<ConnectionIndex>,
+}
+
+/// Drives a [Room] with [ScriptedClient]s on a [VirtualClock] and records the leader timeline
+#[derive(Debug)]
+pub struct Simulation {
+    pub room: Room,
+    pub clock: VirtualClock,
+    clients: HashMap<ConnectionIndex, ScriptedClient>,
+    timeline: Vec<LeaderChange>,
+    events: Vec<RoomEvent>,
+    partitions: Vec<Partition>,
+    step: Duration,
+    rng: SimRng,
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Self {
+        Self::new_with_config(seed, RoomConfig::default())
+    }
+
+    pub fn new_with_config(seed: u64, config: RoomConfig) -> Self {
+        Self {
+            room: Room::new_with_config(config),
+            clock: VirtualClock::new(),
+            clients: HashMap::new(),
+            timeline: Vec::new(),
+            events: Vec::new(),
+            partitions: Vec::new(),
+            step: DEFAULT_STEP,
+            rng: SimRng::new(seed),
+        }
+    }
+
+    /// Sets how far the clock advances between room updates
+    pub fn with_step(mut self, step: Duration) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Creates a connection in the room for the client, it starts pinging right away
+    pub fn add_client(&mut self, mut client: ScriptedClient) -> Result<ConnectionIndex, RoomError> {
+        let connection_index = self.room.create_connection(self.clock.now())?;
+        client.next_ping_at = self.clock.elapsed();
+        self.clients.insert(connection_index, client);
+        self.collect_events();
+        Ok(connection_index)
+    }
+
+    pub fn client_mut(&mut self, connection_index: ConnectionIndex) -> &mut ScriptedClient {
+        self.clients.get_mut(&connection_index).unwrap()
+    }
+
+    /// Removes the client and its connection from the room
+    pub fn remove_client(&mut self, connection_index: ConnectionIndex) -> Result<(), RoomError> {
+        self.clients.remove(&connection_index);
+        let result = self.room.destroy_connection(connection_index);
+        self.collect_events();
+        result
+    }
+
+    /// Cuts `connections` off from the rest of the room `during` the simulated time range.
+    ///
+    /// While the leader is on the other side, the clients ping with [ConnectionToLeader::Disconnected] and
+    /// [PingPayload::leader_unreachable_since] counted from the start of the partition, and the leader reports them in
+    /// [PingPayload::unreachable]. Clients on the same side as the leader ping as their [ScriptedClient] is set up.
+    pub fn partition(&mut self, connections: impl IntoIterator<Item = ConnectionIndex>, during: Range<Duration>) {
+        self.partitions.push(Partition {
+            connections: connections.into_iter().collect(),
+            during,
+        });
+    }
+
+    /// When the earliest partition that separates the connections at `elapsed` started
+    fn separated_since(&self, a: ConnectionIndex, b: ConnectionIndex, elapsed: Duration) -> Option<Duration> {
+        self.partitions
+            .iter()
+            .filter(|partition| {
+                partition.during.contains(&elapsed)
+                    && partition.connections.contains(&a) != partition.connections.contains(&b)
+            })
+            .map(|partition| partition.during.start)
+            .min()
+    }
+
+    pub fn run_for(&mut self, duration: Duration) {
+        let end = self.clock.elapsed() + duration;
+        while self.clock.elapsed() < end {
+            self.clock.advance(self.step.min(end - self.clock.elapsed()));
+            self.tick();
+        }
+    }
+
+    fn tick(&mut self) {
+        let elapsed = self.clock.elapsed();
+        let now = self.clock.now();
+
+        let mut due: Vec<ConnectionIndex> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| !client.is_stopped && client.next_ping_at <= elapsed)
+            .map(|(connection_index, _)| *connection_index)
+            .collect();
+        due.sort_by_key(|connection_index| connection_index.value());
+
+        for connection_index in due {
+            let leader = self.room.leader_index;
+            let cut_off_since = leader.and_then(|leader| self.separated_since(connection_index, leader, elapsed));
+            let mut unreachable: Vec<ConnectionIndex> = if leader == Some(connection_index) {
+                self.clients
+                    .keys()
+                    .copied()
+                    .filter(|other| self.separated_since(connection_index, *other, elapsed).is_some())
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            unreachable.sort_by_key(|connection_index| connection_index.value());
+
+            let client = self.clients.get_mut(&connection_index).unwrap();
+            client.next_ping_at = elapsed + client.interval(&mut self.rng);
+            if self.rng.next_f64() < client.loss {
+                continue;
+            }
+            client.knowledge.0 += client.knowledge_per_ping;
+            let mut ping = PingPayload::new()
+                .with_term(self.room.term)
+                .with_connection_to_leader(client.connection_to_leader)
+                .with_knowledge(client.knowledge)
+                .with_unreachable(unreachable);
+            if let Some(since) = cut_off_since {
+                ping = ping
+                    .with_connection_to_leader(ConnectionToLeader::Disconnected)
+                    .with_leader_unreachable_since(elapsed - since);
+            }
+            if self.room.connections.contains_key(&connection_index) {
+                self.room.on_ping(connection_index, &ping, now);
+            }
+        }
+
+        self.room.update(now);
+        self.collect_events();
+    }
+
+    fn collect_events(&mut self) {
+        for event in self.room.drain_events() {
+            if let RoomEvent::LeaderChanged { term, leader } = event {
+                self.timeline.push(LeaderChange {
+                    at: self.clock.elapsed(),
+                    term,
+                    leader,
+                });
+            }
+            self.events.push(event);
+        }
+    }
+
+    /// All leader changes, in the order they happened
+    pub fn leader_timeline(&self) -> &[LeaderChange] {
+        &self.timeline
+    }
+
+    /// All room events, the simulation drains them from the room after every step
+    pub fn events(&self) -> &[RoomEvent] {
+        &self.events
+    }
+
+    /// The leader at the simulated time `at`
+    pub fn leader_at(&self, at: Duration) -> Option<ConnectionIndex> {
+        self.timeline.iter().take_while(|change| change.at <= at).last().and_then(|change| change.leader)
+    }
+
+    /// Panics unless the leader never changed after the simulated time `at`
+    pub fn assert_stable_since(&self, at: Duration) {
+        let changes: Vec<&LeaderChange> = self.timeline.iter().filter(|change| change.at > at).collect();
+        assert!(changes.is_empty(), "expected a stable leader after {:?}, but got {:?}", at, changes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use conclave_types::ConnectionToLeader;
+
+    use crate::sim::{ScriptedClient, Simulation};
+
+    #[test]
+    fn keep_leader_with_lossy_followers() {
+        let mut sim = Simulation::new(7);
+        let leader = sim.add_client(ScriptedClient::new(20.0)).unwrap();
+        sim.add_client(ScriptedClient::new(20.0).with_loss(0.2).with_jitter(Duration::from_millis(10))).unwrap();
+        sim.add_client(ScriptedClient::new(20.0).with_loss(0.2).with_jitter(Duration::from_millis(10))).unwrap();
+
+        sim.run_for(Duration::from_secs(5));
+
+        assert_eq!(sim.room.leader_index, Some(leader));
+        assert_eq!(sim.leader_timeline().len(), 1);
+        sim.assert_stable_since(Duration::ZERO);
+    }
+
+    #[test]
+    fn replace_leader_that_stops_pinging() {
+        let mut sim = Simulation::new(1);
+        let leader = sim.add_client(ScriptedClient::new(20.0)).unwrap();
+        let follower = sim.add_client(ScriptedClient::new(20.0).with_knowledge_per_ping(2)).unwrap();
+
+        sim.run_for(Duration::from_secs(2));
+        sim.client_mut(leader).stop();
+        sim.client_mut(follower).connection_to_leader = ConnectionToLeader::Disconnected;
+        sim.run_for(Duration::from_secs(2));
+
+        assert_eq!(sim.leader_at(Duration::from_secs(1)), Some(leader));
+        assert_eq!(sim.room.leader_index, Some(follower));
+        sim.assert_stable_since(Duration::from_secs(3));
+    }
+
+    #[test]
+    fn follow_majority_side_of_partition() {
+        let mut sim = Simulation::new(3);
+        let leader = sim.add_client(ScriptedClient::new(20.0)).unwrap();
+        let isolated = sim.add_client(ScriptedClient::new(20.0)).unwrap();
+        // The majority side keeps making progress, so it is preferred in the elections
+        let majority = [
+            sim.add_client(ScriptedClient::new(20.0).with_knowledge_per_ping(2)).unwrap(),
+            sim.add_client(ScriptedClient::new(20.0).with_knowledge_per_ping(2)).unwrap(),
+            sim.add_client(ScriptedClient::new(20.0).with_knowledge_per_ping(2)).unwrap(),
+        ];
+        sim.partition([isolated], Duration::from_secs(1)..Duration::from_secs(2));
+        sim.partition(majority, Duration::from_secs(3)..Duration::from_secs(5));
+
+        // A minority that can not see the leader does not replace it
+        sim.run_for(Duration::from_secs(3));
+        assert_eq!(sim.room.leader_index, Some(leader));
+        assert!(sim.room.unreachable_by_leader().is_empty());
+
+        sim.run_for(Duration::from_secs(1));
+        let new_leader = sim.room.leader_index.unwrap();
+        assert!(majority.contains(&new_leader));
+
+        // Once the new leader is on the majority side, the old leader is the one that is cut off
+        sim.run_for(Duration::from_secs(2));
+        assert_eq!(sim.room.leader_index, Some(new_leader));
+        sim.assert_stable_since(Duration::from_secs(4));
+    }
+}
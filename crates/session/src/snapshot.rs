@@ -0,0 +1,387 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! Versioned binary snapshot of a room, for restoring it after a crash
+//!
+//! A snapshot starts with the magic octets `CRSS` followed by the schema version: u16. All integers are
+//! big-endian and optional values are a presence octet followed by the value, like in [crate::wire].
+//!
+//! | part       | layout                                                                                     |
+//! |------------|--------------------------------------------------------------------------------------------|
+//! | room       | id: connection index, generation: u32, term: u16, leader: optional connection index,      |
+//! |            | connection count: u32, connections...                                                      |
+//! | connection | id: connection index, knowledge: u64, state: u8, last_reported_term: optional u16,         |
+//! |            | connection_to_leader: u8, protocol_version: optional u16, last_sequence: optional u16,     |
+//! |            | auth_failures: u32, suspicion_score: u32, warm_up_pings: u32, group: optional u32,         |
+//! |            | debug_name: optional string, identity: optional string, tag count: u16, tags: strings (v2) |
+//! |            | priority: u8 (v3)                                                                          |
+//!
+//! Strings are a length: u16 followed by that many UTF-8 octets.
+//!
+//! Only what can not be measured again is kept. Quality history and everything timed (quarantine, probation,
+//! lease, election backoff) starts over from the time the room is restored. The config is not part of the
+//! snapshot, it is given when restoring.
+//!
+//! # Migration
+//!
+//! A change to the layout increases [SNAPSHOT_VERSION]. New fields are appended to the end of the room or
+//! connection part, and read only if the snapshot version is at least the version that added them, otherwise
+//! they get their default. A reader accepts every version from [MIN_SNAPSHOT_VERSION] up to its own
+//! [SNAPSHOT_VERSION], so snapshots written by the previous release can always be loaded.
+
+use std::io::{Error, ErrorKind, Result};
+
+use conclave_types::{Knowledge, Term};
+
+use crate::octets::{
+    write_connection_index, write_optional_connection_index, write_u16, write_u32, write_u64, OctetReader,
+};
+use crate::{Connection, ConnectionPriority, ConnectionState, GroupId, Instant, Room, RoomConfig};
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"CRSS";
+/// The version written by [Room::to_snapshot_bytes]
+pub const SNAPSHOT_VERSION: u16 = 3;
+/// The oldest version [Room::from_snapshot_bytes] can migrate from
+pub const MIN_SNAPSHOT_VERSION: u16 = 1;
+
+fn state_to_u8(state: ConnectionState) -> u8 {
+    match state {
+        ConnectionState::Pending => 0,
+        ConnectionState::Joining => 1,
+        ConnectionState::Online => 2,
+        ConnectionState::Quarantined => 3,
+        ConnectionState::Disconnected => 4,
+    }
+}
+
+fn state_from_u8(value: u8) -> Result<ConnectionState> {
+    Ok(match value {
+        0 => ConnectionState::Pending,
+        1 => ConnectionState::Joining,
+        2 => ConnectionState::Online,
+        3 => ConnectionState::Quarantined,
+        4 => ConnectionState::Disconnected,
+        _ => return Err(Error::new(ErrorKind::InvalidData, format!("illegal connection state {}", value))),
+    })
+}
+
+fn priority_to_u8(priority: ConnectionPriority) -> u8 {
+    match priority {
+        ConnectionPriority::Spectator => 0,
+        ConnectionPriority::Participant => 1,
+        ConnectionPriority::Privileged => 2,
+    }
+}
+
+fn priority_from_u8(value: u8) -> Result<ConnectionPriority> {
+    Ok(match value {
+        0 => ConnectionPriority::Spectator,
+        1 => ConnectionPriority::Participant,
+        2 => ConnectionPriority::Privileged,
+        _ => return Err(Error::new(ErrorKind::InvalidData, format!("illegal connection priority {}", value))),
+    })
+}
+
+fn write_optional_u16(out: &mut Vec<u8>, value: Option<u16>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            write_u16(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    let mut length = value.len().min(u16::MAX as usize);
+    while !value.is_char_boundary(length) {
+        length -= 1;
+    }
+    let octets = &value.as_bytes()[..length];
+    write_u16(out, octets.len() as u16);
+    out.extend_from_slice(octets);
+}
+
+fn write_optional_string(out: &mut Vec<u8>, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            write_string(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+fn read_optional_u16(reader: &mut OctetReader) -> Result<Option<u16>> {
+    Ok(if reader.read_presence()? {
+        Some(reader.read_u16()?)
+    } else {
+        None
+    })
+}
+
+fn read_string(reader: &mut OctetReader) -> Result<String> {
+    let length = reader.read_u16()? as usize;
+    let octets = reader.read_octets(length)?;
+    String::from_utf8(octets.to_vec()).map_err(|_| Error::new(ErrorKind::InvalidData, "string is not utf-8"))
+}
+
+fn read_optional_string(reader: &mut OctetReader) -> Result<Option<String>> {
+    if !reader.read_presence()? {
+        return Ok(None);
+    }
+    read_string(reader).map(Some)
+}
+
+impl Room {
+    /// Encodes the room as a snapshot of the current [SNAPSHOT_VERSION]. Connections are ordered by index, so
+    /// two snapshots of the same state are identical.
+    pub fn to_snapshot_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&SNAPSHOT_MAGIC);
+        write_u16(&mut out, SNAPSHOT_VERSION);
+
+        write_connection_index(&mut out, self.id);
+        write_u32(&mut out, self.generation);
+        write_u16(&mut out, self.term.value());
+        write_optional_connection_index(&mut out, self.leader_index);
+
+        let mut connections: Vec<&Connection> = self.connections.values().collect();
+        connections.sort_by_key(|connection| connection.id.value());
+        write_u32(&mut out, connections.len() as u32);
+        for connection in connections {
+            write_connection_index(&mut out, connection.id);
+            write_u64(&mut out, connection.knowledge.value());
+            out.push(state_to_u8(connection.state));
+            write_optional_u16(&mut out, connection.last_reported_term.map(|term| term.value()));
+            out.push(connection.has_connection_host.to_u8());
+            write_optional_u16(&mut out, connection.protocol_version);
+            write_optional_u16(&mut out, connection.last_sequence);
+            write_u32(&mut out, connection.auth_failures);
+            write_u32(&mut out, connection.suspicion_score);
+            write_u32(&mut out, connection.warm_up_pings);
+            match connection.group {
+                Some(group) => {
+                    out.push(1);
+                    write_u32(&mut out, group.0);
+                }
+                None => out.push(0),
+            }
+            write_optional_string(&mut out, connection.debug_name.as_deref());
+            write_optional_string(&mut out, connection.identity.as_deref());
+            write_u16(&mut out, connection.tags.len().min(u16::MAX as usize) as u16);
+            for tag in connection.tags.iter().take(u16::MAX as usize) {
+                write_string(&mut out, tag);
+            }
+            out.push(priority_to_u8(connection.priority));
+        }
+        out
+    }
+
+    /// Restores a room from a snapshot written by this or an earlier version, back to [MIN_SNAPSHOT_VERSION].
+    /// Quality measurement and all timers start over at `time`.
+    pub fn from_snapshot_bytes(octets: &[u8], config: RoomConfig, time: Instant) -> Result<Room> {
+        let mut reader = OctetReader::new(octets);
+
+        if reader.take::<4>()? != SNAPSHOT_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a room snapshot"));
+        }
+        let version = reader.read_u16()?;
+        if !(MIN_SNAPSHOT_VERSION..=SNAPSHOT_VERSION).contains(&version) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "unsupported snapshot version {} (supported {} to {})",
+                    version, MIN_SNAPSHOT_VERSION, SNAPSHOT_VERSION
+                ),
+            ));
+        }
+
+        let mut room = Room::new_with_config(config);
+        room.id = reader.read_connection_index()?;
+        room.generation = reader.read_u32()?;
+        room.term = Term(reader.read_u16()?);
+        room.leader_index = reader.read_optional_connection_index()?;
+
+        let count = reader.read_u32()?;
+        for _ in 0..count {
+            let mut connection = Connection::new(reader.read_connection_index()?, time, &room.config);
+            connection.knowledge = Knowledge(reader.read_u64()?);
+            connection.state = state_from_u8(reader.read_u8()?)?;
+            connection.last_reported_term = read_optional_u16(&mut reader)?.map(Term);
+            connection.has_connection_host = reader.read_connection_to_leader()?;
+            connection.protocol_version = read_optional_u16(&mut reader)?;
+            connection.last_sequence = read_optional_u16(&mut reader)?;
+            connection.auth_failures = reader.read_u32()?;
+            connection.suspicion_score = reader.read_u32()?;
+            connection.warm_up_pings = reader.read_u32()?;
+            if reader.read_presence()? {
+                connection.group = Some(GroupId(reader.read_u32()?));
+            }
+            connection.debug_name = read_optional_string(&mut reader)?;
+            connection.identity = read_optional_string(&mut reader)?;
+            if version >= 2 {
+                for _ in 0..reader.read_u16()? {
+                    connection.tags.push(read_string(&mut reader)?);
+                }
+                connection.tags.sort();
+                connection.tags.dedup();
+            }
+            if version >= 3 {
+                connection.priority = priority_from_u8(reader.read_u8()?)?;
+            }
+            match connection.state {
+                ConnectionState::Pending => connection.pending_since = Some(time),
+                ConnectionState::Quarantined => connection.quarantined_at = Some(time),
+                _ => {}
+            }
+
+            if room.is_connection_value_used(connection.id.value()) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("connection {} appears twice", connection.id),
+                ));
+            }
+            if let Some(identity) = &connection.identity {
+                if room.find_by_identity(identity).is_some() {
+                    return Err(Error::new(ErrorKind::InvalidData, format!("identity '{}' appears twice", identity)));
+                }
+            }
+            room.insert_connection(connection);
+        }
+
+        if !reader.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "trailing octets after snapshot"));
+        }
+        if let Some(leader) = room.leader_index {
+            if !room.connections.contains_key(&leader) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("leader {} is not a connection in the snapshot", leader),
+                ));
+            }
+        }
+
+        room.latest_time = Some(time);
+        room.renew_lease(Some(time));
+        room.update_representatives();
+        room.assert_invariants();
+        Ok(room)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{Knowledge, Term};
+
+    use crate::snapshot::SNAPSHOT_VERSION;
+    use crate::{ConnectionPriority, ConnectionState, GroupId, PingPayload, Room, RoomConfig};
+
+    fn room_with_history(now: Instant) -> Room {
+        let mut room = Room::new();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection_with_tags(&["region:eu"], now).unwrap();
+        room.preregister_connection("player-7", now).unwrap();
+        room.set_debug_name(follower, "follower");
+        room.set_group(follower, Some(GroupId(3))).unwrap();
+        room.on_ping(
+            follower,
+            &PingPayload::new()
+                .with_term(room.term)
+                .with_knowledge(Knowledge(42))
+                .with_sequence(9),
+            now + Duration::from_millis(100),
+        );
+        room.destroy_connection(leader).unwrap();
+        room
+    }
+
+    #[test]
+    fn restore_room_from_snapshot() {
+        let now = Instant::now();
+        let room = room_with_history(now);
+        let octets = room.to_snapshot_bytes();
+        assert_eq!(&octets[..6], b"CRSS\x00\x03");
+
+        let later = now + Duration::from_secs(30);
+        let restored = Room::from_snapshot_bytes(&octets, RoomConfig::default(), later).unwrap();
+        assert_eq!(restored.to_snapshot_bytes(), octets);
+        assert_eq!(restored.term, room.term);
+        assert_eq!(restored.leader_index, room.leader_index);
+        let members = |room: &Room| -> Vec<_> {
+            room.connections()
+                .iter()
+                .map(|connection| (connection.id, connection.knowledge, connection.has_connection_host))
+                .collect()
+        };
+        assert_eq!(members(&restored), members(&room));
+
+        let follower = restored.leader_index.unwrap();
+        assert_eq!(restored.get(follower).debug_name.as_deref(), Some("follower"));
+        assert_eq!(restored.get(follower).group(), Some(GroupId(3)));
+        assert_eq!(restored.get(follower).tags(), ["region:eu"]);
+        assert_eq!(restored.representative(GroupId(3)), Some(follower));
+        let pending = restored.find_by_identity("player-7").unwrap();
+        assert_eq!(restored.get(pending).state, ConnectionState::Pending);
+
+        // Duplicate sequence numbers are still rejected after the restore
+        let mut restored = restored;
+        let ping = PingPayload::new().with_term(Term(restored.term.value())).with_sequence(9);
+        assert!(!restored.on_ping(follower, &ping, later).is_accepted());
+        // New connections never reuse a handle from before the snapshot
+        let created = restored.create_connection(later).unwrap();
+        assert!(room.connections.keys().all(|index| *index != created));
+    }
+
+    #[test]
+    fn migrate_snapshot_without_tags() {
+        let now = Instant::now();
+        let mut room = Room::new();
+        let connection = room.create_connection(now).unwrap();
+        let mut octets = room.to_snapshot_bytes();
+        // Version 1 ends the connection part after the identity, before the tag count and priority
+        octets.truncate(octets.len() - 3);
+        octets[4..6].copy_from_slice(&1u16.to_be_bytes());
+
+        let restored = Room::from_snapshot_bytes(&octets, RoomConfig::default(), now).unwrap();
+        assert_eq!(restored.leader_index, Some(connection));
+        assert!(restored.get(connection).tags().is_empty());
+        assert_eq!(restored.get(connection).priority, ConnectionPriority::Participant);
+    }
+
+    #[test]
+    fn keep_priority_and_migrate_snapshot_without_it() {
+        let now = Instant::now();
+        let mut room = Room::new();
+        let host = room.admit_with_eviction("host", ConnectionPriority::Privileged, now).unwrap();
+        let octets = room.to_snapshot_bytes();
+        let restored = Room::from_snapshot_bytes(&octets, RoomConfig::default(), now).unwrap();
+        assert_eq!(restored.get(host).priority, ConnectionPriority::Privileged);
+
+        // Version 2 ends the connection part after the tags
+        let mut octets = octets;
+        octets.truncate(octets.len() - 1);
+        octets[4..6].copy_from_slice(&2u16.to_be_bytes());
+        let restored = Room::from_snapshot_bytes(&octets, RoomConfig::default(), now).unwrap();
+        assert_eq!(restored.get(host).priority, ConnectionPriority::Participant);
+    }
+
+    #[test]
+    fn reject_unsupported_and_damaged_snapshots() {
+        let now = Instant::now();
+        let mut octets = room_with_history(now).to_snapshot_bytes();
+        let restore = |octets: &[u8]| Room::from_snapshot_bytes(octets, RoomConfig::default(), now).unwrap_err().kind();
+
+        assert_eq!(restore(&octets[..octets.len() - 1]), ErrorKind::UnexpectedEof);
+
+        octets[4..6].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_be_bytes());
+        assert_eq!(restore(&octets), ErrorKind::InvalidData);
+
+        octets[0] = b'X';
+        assert_eq!(restore(&octets), ErrorKind::InvalidData);
+    }
+}
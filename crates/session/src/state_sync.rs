@@ -0,0 +1,269 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::{debug, info};
+
+use crate::{ConnectionIndex, ConnectionState, Room, RoomEvent};
+
+/// A connection that joined mid-session and needs the full game state from a donor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateSync {
+    pub receiver: ConnectionIndex,
+    /// The connection that should send the state, `None` if there currently is no one to send it
+    pub donor: Option<ConnectionIndex>,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// State transfers that have not been completed yet, in the order the receivers joined
+    pub fn pending_syncs(&self) -> &[StateSync] {
+        &self.state_syncs
+    }
+
+    /// Marks the state transfer to `receiver` as done. Returns false if no sync was pending for it.
+    pub fn complete_state_sync(&mut self, receiver: ConnectionIndex) -> bool {
+        let Some(position) = self.state_syncs.iter().position(|sync| sync.receiver == receiver) else {
+            return false;
+        };
+        self.state_syncs.remove(position);
+        if let Some(connection) = self.connections.get_mut(&receiver) {
+            connection.needs_state_sync = false;
+        }
+        debug!("state sync to {} completed", receiver);
+        true
+    }
+
+    pub(crate) fn begin_state_sync(&mut self, receiver: ConnectionIndex) {
+        let donor = self.choose_state_sync_donor(receiver);
+        self.connections.get_mut(&receiver).unwrap().needs_state_sync = true;
+        self.state_syncs.push(StateSync { receiver, donor });
+        info!("{} needs state sync from {:?}", receiver, donor);
+        self.push_event(RoomEvent::StateSyncAssigned { receiver, donor });
+        self.request_state_transfer(receiver, donor);
+    }
+
+    /// Prefers the leader, otherwise the online connection with the most knowledge, and the lowest index value
+    /// among those with the same knowledge
+    fn choose_state_sync_donor(&self, receiver: ConnectionIndex) -> Option<ConnectionIndex> {
+        let is_possible_donor = |index: &ConnectionIndex| {
+            *index != receiver
+                && self
+                    .connections
+                    .get(index)
+                    .is_some_and(|connection| connection.state == ConnectionState::Online && !connection.needs_state_sync)
+        };
+
+        self.leader_index.filter(is_possible_donor).or_else(|| {
+            self.connections
+                .values()
+                .filter(|connection| is_possible_donor(&connection.id))
+                .max_by(|a, b| {
+                    a.knowledge
+                        .cmp_knowledge(&b.knowledge)
+                        .then_with(|| b.id.value().cmp(&a.id.value()))
+                })
+                .map(|connection| connection.id)
+        })
+    }
+
+    /// Drops the sync for a receiver that has left the room
+    pub(crate) fn cancel_state_sync(&mut self, receiver: ConnectionIndex) {
+        self.state_syncs.retain(|sync| sync.receiver != receiver);
+    }
+
+    /// Finds a new donor for syncs whose donor has disconnected. Called on every update, most syncs share the
+    /// same donor, so it is only looked up when it differs from the previous one.
+    pub(crate) fn reassign_state_sync_donors(&mut self) {
+        let mut checked: Option<(Option<ConnectionIndex>, bool)> = None;
+        for position in 0..self.state_syncs.len() {
+            let sync = self.state_syncs[position];
+            let donor_is_online = match checked {
+                Some((donor, is_online)) if donor == sync.donor => is_online,
+                _ => sync.donor.is_some_and(|donor| {
+                    self.connections
+                        .get(&donor)
+                        .is_some_and(|connection| connection.state == ConnectionState::Online)
+                }),
+            };
+            checked = Some((sync.donor, donor_is_online));
+            if donor_is_online {
+                continue;
+            }
+
+            let donor = self.choose_state_sync_donor(sync.receiver);
+            if donor == sync.donor {
+                continue;
+            }
+            info!("donor {:?} for {} is gone, reassigning to {:?}", sync.donor, sync.receiver, donor);
+            self.state_syncs[position].donor = donor;
+            self.push_event(RoomEvent::StateSyncAssigned {
+                receiver: sync.receiver,
+                donor,
+            });
+            self.request_state_transfer(sync.receiver, donor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use conclave_types::{Knowledge, Term};
+
+    use crate::state_sync::StateSync;
+    use crate::{Connection, ConnectionIndex, LeaderEligibility, PingPayload, Room, RoomEvent, RoomOp, PROTOCOL_VERSION};
+
+    /// Only lets one connection lead
+    #[derive(Debug)]
+    struct Only(ConnectionIndex);
+
+    impl LeaderEligibility for Only {
+        fn is_eligible(&self, connection: &Connection) -> bool {
+            connection.id == self.0
+        }
+    }
+
+    #[test]
+    fn late_joiner_is_synced_by_leader() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        assert!(room.pending_syncs().is_empty());
+
+        let late_joiner = room.create_connection(now).unwrap();
+        assert!(room.get(late_joiner).needs_state_sync());
+        assert_eq!(
+            room.pending_syncs(),
+            &[StateSync {
+                receiver: late_joiner,
+                donor: Some(leader)
+            }]
+        );
+
+        assert!(room.complete_state_sync(late_joiner));
+        assert!(!room.complete_state_sync(late_joiner));
+        assert!(!room.get(late_joiner).needs_state_sync());
+        assert!(room.pending_syncs().is_empty());
+    }
+
+    #[test]
+    fn reassign_when_donor_leaves() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        room.drain_events();
+        let follower = room.create_connection(now).unwrap();
+        room.complete_state_sync(follower);
+        room.on_ping(follower, &PingPayload::new().with_knowledge(Knowledge(10)), now);
+
+        let late_joiner = room.create_connection(now).unwrap();
+        room.destroy_connection(leader).unwrap();
+
+        assert_eq!(room.leader_index, Some(follower));
+        assert_eq!(room.pending_syncs()[0].donor, Some(follower));
+        assert_eq!(
+            room.drain_events(),
+            vec![
+                RoomEvent::StateSyncAssigned {
+                    receiver: follower,
+                    donor: Some(leader)
+                },
+                RoomEvent::StateSyncAssigned {
+                    receiver: late_joiner,
+                    donor: Some(leader)
+                },
+                RoomEvent::LeaderChanged {
+                    term: Term(2),
+                    leader: Some(follower)
+                },
+                RoomEvent::ElectionRanked {
+                    term: Term(2),
+                    ranking: vec![follower, late_joiner]
+                },
+                RoomEvent::StateSyncAssigned {
+                    receiver: late_joiner,
+                    donor: Some(follower)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn donor_with_lowest_index_wins_tie() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        room.set_leader_eligibility(Box::new(Only(leader)));
+        let mut donors = Vec::new();
+        for _ in 0..16 {
+            let donor = room.create_connection(now).unwrap();
+            room.complete_state_sync(donor);
+            donors.push(donor);
+        }
+        let late_joiner = room.create_connection(now).unwrap();
+
+        room.destroy_connection(leader).unwrap();
+
+        assert_eq!(room.leader(), None);
+        let lowest = donors.iter().copied().min_by_key(|donor| donor.value());
+        assert_eq!(room.pending_syncs(), &[StateSync { receiver: late_joiner, donor: lowest }]);
+    }
+
+    #[test]
+    fn same_operations_give_same_donors() {
+        let ping = |slot, knowledge| RoomOp::Ping {
+            slot,
+            term: 1,
+            connection_to_leader: 1,
+            knowledge,
+            protocol_version: PROTOCOL_VERSION,
+            sequence: None,
+        };
+        // Equally knowledgeable connections, so only tie-breaks decide who leads and who syncs whom
+        let mut synced = vec![RoomOp::CreateConnection; 8];
+        synced.extend((0..8).map(|slot| ping(slot, 10)));
+        let churn = [
+            RoomOp::CreateConnection,
+            RoomOp::CreateConnection,
+            RoomOp::Destroy { slot: 0 },
+            RoomOp::AdvanceTime { milliseconds: 100 },
+            RoomOp::Destroy { slot: 3 },
+            ping(2, 20),
+            RoomOp::CreateConnection,
+            RoomOp::Destroy { slot: 0 },
+            RoomOp::AdvanceTime { milliseconds: 100 },
+        ];
+
+        let start = Instant::now();
+        let run = || {
+            let mut room = Room::new();
+            let mut now = start;
+            for op in &synced {
+                room.apply(op, &mut now);
+            }
+            for receiver in room.pending_syncs().iter().map(|sync| sync.receiver).collect::<Vec<_>>() {
+                room.complete_state_sync(receiver);
+            }
+            for op in &churn {
+                room.apply(op, &mut now);
+            }
+            (room.drain_events(), room.pending_syncs().to_vec())
+        };
+        let (events, syncs) = run();
+        let mut donors: Vec<u32> = events
+            .iter()
+            .filter_map(|event| match event {
+                RoomEvent::StateSyncAssigned { donor: Some(donor), .. } => Some(donor.value()),
+                _ => None,
+            })
+            .collect();
+        donors.dedup();
+        assert!(donors.len() > 1, "the donor is reassigned when it leaves");
+        assert!(syncs.iter().any(|sync| sync.donor.is_some()));
+        for _ in 0..8 {
+            assert_eq!(run(), (events.clone(), syncs.clone()));
+        }
+    }
+}
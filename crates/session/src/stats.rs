@@ -0,0 +1,113 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::{KnowledgeOrd, Term};
+
+use crate::{ConnectionIndex, Instant, Room, RoomState};
+
+/// The room at a point in time, see [Room::stats] and [Room::stats_history]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoomStats {
+    pub at: Instant,
+    pub state: RoomState,
+    /// All connections, pending ones included
+    pub connection_count: usize,
+    pub term: Term,
+    pub leader: Option<ConnectionIndex>,
+    /// Leader changes since the room was created, the difference between two samples is the changes between them
+    pub leader_changes: u32,
+    /// See [crate::RoomHealth::score]
+    pub health_score: u8,
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    pub fn stats(&self, now: Instant) -> RoomStats {
+        RoomStats {
+            at: now,
+            state: self.state(now),
+            connection_count: self.connections.len(),
+            term: self.term,
+            leader: self.leader_index,
+            leader_changes: self.metrics.leader_changes,
+            health_score: self.health().score,
+        }
+    }
+
+    /// The last [crate::RoomConfig::stats_history] samples taken every [crate::RoomConfig::stats_interval], oldest
+    /// first
+    pub fn stats_history(&self) -> impl DoubleEndedIterator<Item = &RoomStats> {
+        self.stats_samples.iter()
+    }
+
+    /// Takes a sample at the first update, and then at the first update after every interval
+    pub(crate) fn sample_stats_if_due(&mut self, time: Instant) {
+        let Some(interval) = self.config.stats_interval else {
+            return;
+        };
+        if self.next_stats_at.is_some_and(|next| time < next) {
+            return;
+        }
+        self.next_stats_at = Some(time + interval);
+        self.stats_samples.push_back(self.stats(time));
+        self.trim_stats_history();
+    }
+
+    pub(crate) fn trim_stats_history(&mut self) {
+        while self.stats_samples.len() > self.config.stats_history {
+            self.stats_samples.pop_front();
+        }
+    }
+
+    /// When the next sample is due, `None` if it is taken at the next update or sampling is off
+    pub(crate) fn next_stats_sample_at(&self) -> Option<Instant> {
+        self.config.stats_interval.and(self.next_stats_at)
+    }
+
+    /// Moves the next sample forward, so `duration` does not count towards the interval
+    pub(crate) fn shift_stats_sampling(&mut self, duration: Duration) {
+        if let Some(next) = &mut self.next_stats_at {
+            *next += duration;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{PingPayload, RoomConfig, RoomState};
+
+    #[test]
+    fn sample_at_interval_into_bounded_history() {
+        let mut room = RoomConfig::new()
+            .with_stats_interval(Some(Duration::from_secs(1)))
+            .with_stats_history(3)
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let first = room.create_connection(now).unwrap();
+        let second = room.create_connection(now).unwrap();
+        room.update(now);
+        room.update(now + Duration::from_millis(500));
+        assert_eq!(room.stats_history().count(), 1);
+
+        room.destroy_connection(first).unwrap();
+        for seconds in 1..=4 {
+            let time = now + Duration::from_secs(seconds);
+            room.on_ping(second, &PingPayload::new(), time);
+            room.update(time);
+        }
+
+        let samples: Vec<_> = room.stats_history().collect();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].at, now + Duration::from_secs(2));
+        assert_eq!(samples[2].at, now + Duration::from_secs(4));
+        assert_eq!(samples[2].connection_count, 1);
+        assert_eq!(samples[2].leader, Some(second));
+        assert_eq!(samples[2].state, RoomState::Active);
+        assert_eq!(samples[2].leader_changes, 2);
+    }
+}
@@ -0,0 +1,91 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+
+use crate::recorder::RecordedInput;
+use crate::{ConnectionIndex, Instant, Room, RoomError};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Adds a connection like [Room::create_connection], labeled with `tags` that describe the participant, e.g.
+    /// `"platform:ios"` or `"region:eu"`. The tags can not change while the connection is in the room.
+    ///
+    /// Find the connections with [Room::connections_with_tag] and count them with [Room::connections_by_tag]. The
+    /// events in [Room::recent_events] about a connection carry its tags, and the [crate::MetricsSink] receives
+    /// the counts after every update.
+    pub fn create_connection_with_tags(&mut self, tags: &[&str], time: Instant) -> Result<ConnectionIndex, RoomError> {
+        self.record(
+            time,
+            RecordedInput::CreateConnectionWithTags {
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            },
+        );
+        let time = self.observe_time(time);
+        self.ensure_open()?;
+        self.ensure_capacity()?;
+        let value = self.find_unique_connection_value()?;
+        self.id = ConnectionIndex::new(value);
+        let mut tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+        tags.sort();
+        tags.dedup();
+        let connection_id = self.add_connection(value, tags, time);
+        self.publish_view();
+        Ok(connection_id)
+    }
+
+    /// Number of connections with each tag, sorted by tag. Tags that no connection has are left out
+    pub fn connections_by_tag(&self) -> Vec<(&str, usize)> {
+        let mut tags: Vec<&str> = self
+            .connections
+            .values()
+            .flat_map(|connection| connection.tags.iter().map(String::as_str))
+            .collect();
+        tags.sort_unstable();
+        let mut counts = Vec::<(&str, usize)>::new();
+        for tag in tags {
+            match counts.last_mut() {
+                Some((last, count)) if *last == tag => *count += 1,
+                _ => counts.push((tag, 1)),
+            }
+        }
+        counts
+    }
+
+    /// The tags of the connection, empty if it is not in the room
+    pub(crate) fn tags_of(&self, connection_index: Option<ConnectionIndex>) -> Vec<String> {
+        connection_index
+            .and_then(|index| self.connections.get(&index))
+            .map_or_else(Vec::new, |connection| connection.tags.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::{RoomConfig, RoomEvent};
+
+    #[test]
+    fn query_and_count_tagged_connections() {
+        let mut room = RoomConfig::new().with_event_history(8).build().unwrap();
+        let now = Instant::now();
+        let first = room.create_connection_with_tags(&["region:eu", "platform:ios", "region:eu"], now).unwrap();
+        let second = room.create_connection_with_tags(&["region:eu"], now).unwrap();
+        let untagged = room.create_connection(now).unwrap();
+
+        assert_eq!(room.get(first).tags(), ["platform:ios", "region:eu"]);
+        assert!(room.get(second).has_tag("region:eu"));
+        assert!(room.get(untagged).tags().is_empty());
+        let in_eu: Vec<_> = room.connections_with_tag("region:eu").iter().map(|connection| connection.id).collect();
+        assert_eq!(in_eu, vec![first, second]);
+        assert_eq!(room.connections_by_tag(), vec![("platform:ios", 1), ("region:eu", 2)]);
+
+        let leader_elected = room
+            .recent_events()
+            .find(|timed| matches!(timed.event, RoomEvent::LeaderChanged { .. }))
+            .unwrap();
+        assert_eq!(leader_elected.event.connection(), Some(first));
+        assert_eq!(leader_elected.tags, ["platform:ios", "region:eu"]);
+    }
+}
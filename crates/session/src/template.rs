@@ -0,0 +1,136 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use core::fmt;
+
+use crate::{ConfigError, LeaderEligibility, PingAuthenticator, Room, RoomConfig};
+
+/// Creates the policy for every new room, policies are not shared between rooms
+type PolicyFactory<T> = Box<dyn Fn() -> Box<T>>;
+
+/// A validated [RoomConfig], and the policies that go with it, for many rooms, e.g. every room created by
+/// matchmaking. The rooms are created with the config of the template and only the differences they need, so
+/// they can not drift apart.
+///
+/// What is set up for a single room, like a [crate::MetricsSink] labeled with the room or the key of a room
+/// store, is set on the created room.
+pub struct RoomTemplate {
+    config: RoomConfig,
+    leader_eligibility: Option<PolicyFactory<dyn LeaderEligibility>>,
+    ping_authenticator: Option<PolicyFactory<dyn PingAuthenticator>>,
+}
+
+impl fmt::Debug for RoomTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RoomTemplate")
+            .field("config", &self.config)
+            .field("has_leader_eligibility", &self.leader_eligibility.is_some())
+            .field("has_ping_authenticator", &self.ping_authenticator.is_some())
+            .finish()
+    }
+}
+
+impl RoomTemplate {
+    /// Fails like [RoomConfig::build] if `config` is not valid
+    pub fn new(config: RoomConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            leader_eligibility: None,
+            ping_authenticator: None,
+        })
+    }
+
+    pub fn config(&self) -> &RoomConfig {
+        &self.config
+    }
+
+    /// Every room gets a [LeaderEligibility] created by `factory`
+    pub fn with_leader_eligibility(mut self, factory: impl Fn() -> Box<dyn LeaderEligibility> + 'static) -> Self {
+        self.leader_eligibility = Some(Box::new(factory));
+        self
+    }
+
+    /// Every room gets a [PingAuthenticator] created by `factory`
+    pub fn with_ping_authenticator(mut self, factory: impl Fn() -> Box<dyn PingAuthenticator> + 'static) -> Self {
+        self.ping_authenticator = Some(Box::new(factory));
+        self
+    }
+
+    pub fn create_room(&self) -> Room {
+        self.with_policies(Room::new_with_config(self.config.clone()))
+    }
+
+    /// A room with the config of the template changed by `overrides`, e.g. the capacity and how long an abandoned
+    /// room lives:
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use conclave_room_session::{RoomConfig, RoomTemplate};
+    /// let template = RoomTemplate::new(RoomConfig::new()).unwrap();
+    /// let room = template
+    ///     .create_room_with(|config| {
+    ///         config.with_max_connections(Some(4)).with_close_abandoned_after(Some(Duration::from_secs(600)))
+    ///     })
+    ///     .unwrap();
+    /// ```
+    ///
+    /// Fails if the changed config is not valid.
+    pub fn create_room_with(&self, overrides: impl FnOnce(RoomConfig) -> RoomConfig) -> Result<Room, ConfigError> {
+        let room = overrides(self.config.clone()).build()?;
+        Ok(self.with_policies(room))
+    }
+
+    fn with_policies(&self, mut room: Room) -> Room {
+        if let Some(factory) = &self.leader_eligibility {
+            room.set_leader_eligibility(factory());
+        }
+        if let Some(factory) = &self.ping_authenticator {
+            room.set_ping_authenticator(factory());
+        }
+        room
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::{ConfigError, Connection, LeaderEligibility, RoomConfig, RoomError, RoomTemplate};
+
+    #[derive(Debug)]
+    struct NoDebugNames;
+
+    impl LeaderEligibility for NoDebugNames {
+        fn is_eligible(&self, connection: &Connection) -> bool {
+            connection.debug_name.is_none()
+        }
+    }
+
+    #[test]
+    fn create_rooms_from_template_with_overrides() {
+        assert_eq!(
+            RoomTemplate::new(RoomConfig::new().with_max_connections(Some(0))).unwrap_err(),
+            ConfigError::Zero {
+                field: "max_connections"
+            }
+        );
+        let template = RoomTemplate::new(RoomConfig::new().with_max_connections(Some(8)))
+            .unwrap()
+            .with_leader_eligibility(|| Box::new(NoDebugNames));
+        let now = Instant::now();
+
+        let mut room = template.create_room();
+        assert_eq!(room.config, *template.config());
+        let vetoed = room.create_connection(now).unwrap();
+        room.set_debug_name(vetoed, "bot");
+        let other = room.create_connection(now).unwrap();
+        assert_eq!(room.would_elect(None, now), Some(other));
+
+        let mut small = template.create_room_with(|config| config.with_max_connections(Some(1))).unwrap();
+        small.create_connection(now).unwrap();
+        assert_eq!(small.create_connection(now), Err(RoomError::RoomFull));
+        assert!(template.create_room_with(|config| config.with_max_connections(Some(0))).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! The point in time type taken by every [crate::Room] method.
+//!
+//! The room never reads a clock itself, all time is provided by the caller. On most targets [Instant] is
+//! [std::time::Instant]. On `wasm32-unknown-unknown`, where [std::time::Instant::now] panics, it is a timestamp
+//! that the caller creates from its own clock, e.g. `Date.now()` or `performance.now()` in JavaScript, with
+//! [Instant::from_millis].
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use std::time::Instant;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub use self::caller_provided::Instant;
+
+/// The time a room driven only by millisecond timestamps, like the C interface, counts from
+#[cfg(any(feature = "ffi", feature = "sim", test))]
+pub(crate) fn origin() -> Instant {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    return Instant::now();
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    return Instant::from_millis(0);
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod caller_provided {
+    use std::fmt;
+    use std::ops::{Add, AddAssign, Sub, SubAssign};
+    use std::time::Duration;
+
+    /// Time since an origin chosen by the caller, with the same methods as [std::time::Instant] except `now()`
+    #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Instant(Duration);
+
+    impl Instant {
+        pub fn from_millis(millis: u64) -> Self {
+            Self(Duration::from_millis(millis))
+        }
+
+        pub fn from_duration(since_origin: Duration) -> Self {
+            Self(since_origin)
+        }
+
+        pub fn since_origin(&self) -> Duration {
+            self.0
+        }
+
+        /// Zero if `earlier` is later than `self`, like [std::time::Instant::duration_since]
+        pub fn duration_since(&self, earlier: Instant) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+
+        pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+            self.0.checked_sub(earlier.0)
+        }
+
+        pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+            self.0.saturating_sub(earlier.0)
+        }
+
+        pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+            self.0.checked_add(duration).map(Self)
+        }
+
+        pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+            self.0.checked_sub(duration).map(Self)
+        }
+    }
+
+    impl fmt::Debug for Instant {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Instant({:?})", self.0)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, duration: Duration) -> Instant {
+            self.checked_add(duration)
+                .expect("overflow when adding duration to instant")
+        }
+    }
+
+    impl AddAssign<Duration> for Instant {
+        fn add_assign(&mut self, duration: Duration) {
+            *self = *self + duration;
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Instant;
+
+        fn sub(self, duration: Duration) -> Instant {
+            self.checked_sub(duration)
+                .expect("overflow when subtracting duration from instant")
+        }
+    }
+
+    impl SubAssign<Duration> for Instant {
+        fn sub_assign(&mut self, duration: Duration) {
+            *self = *self - duration;
+        }
+    }
+
+    impl Sub<Instant> for Instant {
+        type Output = Duration;
+
+        fn sub(self, earlier: Instant) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+    }
+}
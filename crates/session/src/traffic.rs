@@ -0,0 +1,110 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use conclave_types::KnowledgeOrd;
+
+use crate::{ConnectionIndex, Instant, RecordedInput, Room, RoomError};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Called by the transport with the bytes it received from and sent to the connection since the previous
+    /// call, see [crate::Connection::traffic]. Pings are expected to be counted as well.
+    pub fn record_traffic(
+        &mut self,
+        connection_index: ConnectionIndex,
+        bytes_in: u64,
+        bytes_out: u64,
+        time: Instant,
+    ) -> Result<(), RoomError> {
+        self.record(
+            time,
+            RecordedInput::Traffic {
+                connection: connection_index.value(),
+                generation: connection_index.generation(),
+                bytes_in,
+                bytes_out,
+            },
+        );
+        let time = self.observe_time(time);
+        self.validate_connection(connection_index)?;
+        self.connections
+            .get_mut(&connection_index)
+            .unwrap()
+            .traffic
+            .record(bytes_in, bytes_out, time);
+        self.metrics.bytes_in = self.metrics.bytes_in.saturating_add(bytes_in);
+        self.metrics.bytes_out = self.metrics.bytes_out.saturating_add(bytes_out);
+        self.publish_view();
+        Ok(())
+    }
+
+    /// Connections that have pinged within `threshold`, but that the transport has not received anything else from for longer
+    /// than `threshold`, e.g. a client that stopped sending game data. Only connections that have had traffic
+    /// recorded are considered. Sorted by connection index.
+    pub fn connections_without_traffic(&self, now: Instant, threshold: Duration) -> Vec<ConnectionIndex> {
+        let mut silent: Vec<ConnectionIndex> = self
+            .connections
+            .values()
+            .filter(|connection| {
+                connection.silent_for(now) <= threshold
+                    && connection
+                        .traffic()
+                        .last_received_at
+                        .is_some_and(|received_at| now.saturating_duration_since(received_at) > threshold)
+            })
+            .map(|connection| connection.id)
+            .collect();
+        silent.sort_by_key(|index| index.value());
+        silent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{ConnectionIndex, PingPayload, Room, RoomConfig, RoomError};
+
+    #[test]
+    fn account_traffic() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let connection = room.create_connection(now).unwrap();
+        room.record_traffic(connection, 100, 0, now).unwrap();
+        room.record_traffic(connection, 300, 1200, now + Duration::from_secs(1)).unwrap();
+
+        let traffic = room.get(connection).traffic();
+        assert_eq!((traffic.bytes_in, traffic.bytes_out), (400, 1200));
+        assert_eq!((traffic.packets_in, traffic.packets_out), (2, 1));
+        assert_eq!(traffic.bytes_in_per_second, 400.0);
+        assert_eq!(traffic.bytes_out_per_second, 1200.0);
+        assert_eq!(room.metrics().bytes_out, 1200);
+        assert_eq!(
+            room.record_traffic(ConnectionIndex::new(9), 1, 1, now),
+            Err(RoomError::UnknownConnection(ConnectionIndex::new(9)))
+        );
+
+        let later = now + Duration::from_secs(4);
+        room.on_ping(connection, &PingPayload::new(), later);
+        assert_eq!(room.connections_without_traffic(later, Duration::from_secs(2)), vec![connection]);
+        assert!(room.connections_without_traffic(later, Duration::from_secs(3)).is_empty());
+    }
+
+    #[test]
+    fn prefer_candidates_with_headroom() {
+        let mut room = RoomConfig::new().with_traffic_budget(1000).build().unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let busy = room.create_connection(now).unwrap();
+        let spare = room.create_connection(now).unwrap();
+        room.record_traffic(busy, 0, 5000, now + Duration::from_secs(1)).unwrap();
+        room.record_traffic(spare, 0, 500, now + Duration::from_secs(1)).unwrap();
+
+        let report = room.election_report(now).dry_run;
+        assert_eq!(report.winner, Some(spare));
+        assert!(!report.candidates[1].has_traffic_headroom);
+        assert_eq!(room.would_elect(Some(leader), now), Some(spare));
+    }
+}
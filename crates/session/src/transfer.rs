@@ -0,0 +1,107 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+use crate::recorder::RecordedInput;
+use crate::{ConnectionIndex, ConnectionState, Instant, Room, RoomError, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Moves a connection to the room `to`, e.g. to follow a party or when two rooms are merged.
+    ///
+    /// Knowledge, quality history and metadata move with the connection, while what only makes sense in this room
+    /// (the reported term, ping sequence, authentication failures, state sync and group) starts over. If the
+    /// connection was the leader here, a new leader is elected. In `to` the connection gets a new index and joins
+    /// like any other connection, or becomes leader if the room has none. Returns the new index.
+    ///
+    /// Only this room records the transfer, the receiving room does not, so a replay of it will differ.
+    pub fn transfer_connection(
+        &mut self,
+        connection_index: ConnectionIndex,
+        to: &mut Room<K>,
+        time: Instant,
+    ) -> Result<ConnectionIndex, RoomError> {
+        self.record(
+            time,
+            RecordedInput::TransferOut {
+                connection: connection_index.value(),
+                generation: connection_index.generation(),
+            },
+        );
+        let time = self.observe_time(time);
+        self.validate_connection(connection_index)?;
+        to.ensure_open()?;
+        to.ensure_capacity()?;
+        if let Some(identity) = &self.get(connection_index).identity {
+            if let Some(existing) = to.find_by_identity(identity) {
+                return Err(RoomError::IdentityInUse(existing));
+            }
+        }
+        let value = to.find_unique_connection_value()?;
+        let mut connection = self.remove_connection(connection_index).unwrap();
+        info!("transferring {} to another room", connection);
+        self.push_event(RoomEvent::TransferredOut {
+            connection: connection_index,
+        });
+        to.observe_time(time);
+        to.id = ConnectionIndex::new(value);
+        to.generation = to.generation.wrapping_add(1);
+        let new_index = ConnectionIndex::with_generation(value, to.generation);
+        connection.id = new_index;
+        connection.last_reported_term = None;
+        connection.last_sequence = None;
+        connection.auth_failures = 0;
+        connection.needs_state_sync = false;
+        connection.group = None;
+        let is_pending = connection.state == ConnectionState::Pending;
+        to.insert_connection(connection);
+        to.push_event(RoomEvent::TransferredIn {
+            connection: new_index,
+            previous: connection_index,
+        });
+        if !is_pending {
+            to.admit_connection(new_index);
+        }
+        to.assert_invariants();
+        self.publish_view();
+        to.publish_view();
+        Ok(new_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::Knowledge;
+
+    use crate::{PingPayload, Room, RoomEvent};
+
+    #[test]
+    fn transfer_leader_to_other_room() {
+        let now = Instant::now();
+        let mut lobby = Room::new();
+        let leader = lobby.create_connection(now).unwrap();
+        let follower = lobby.create_connection(now).unwrap();
+        lobby.on_ping(leader, &PingPayload::new().with_knowledge(Knowledge(42)), now);
+        lobby.set_debug_name(leader, "host");
+        let mut game = Room::new();
+        let existing = game.create_connection(now).unwrap();
+        lobby.drain_events();
+        let moved = lobby.transfer_connection(leader, &mut game, now + Duration::from_millis(10)).unwrap();
+        assert!(lobby.try_get(leader).is_err());
+        assert_eq!(lobby.leader_index, Some(follower));
+        assert!(lobby.drain_events().contains(&RoomEvent::TransferredOut { connection: leader }));
+
+        assert_ne!(moved, existing);
+        assert_eq!(game.get(moved).knowledge, Knowledge(42));
+        assert_eq!(game.get(moved).debug_name.as_deref(), Some("host"));
+        assert_eq!(game.get(moved).last_reported_term, None);
+        assert_eq!(game.leader_index, Some(existing));
+        assert!(game.drain_events().contains(&RoomEvent::TransferredIn {
+            connection: moved,
+            previous: leader,
+        }));
+    }
+}
@@ -0,0 +1,159 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::time::Duration;
+
+use crate::{ConfigError, RoomConfig};
+
+fn check_positive(field: &'static str, value: f32) -> Result<(), ConfigError> {
+    if value.is_finite() && value > 0.0 {
+        Ok(())
+    } else {
+        Err(ConfigError::NotPositive { field, value })
+    }
+}
+
+fn check_range(field: &'static str, value: f32, min: f32, max: f32) -> Result<(), ConfigError> {
+    if value.is_finite() && value >= min && value <= max {
+        Ok(())
+    } else {
+        Err(ConfigError::OutOfRange { field, value, min, max })
+    }
+}
+
+/// Fractions and weights, where zero would turn the feature into nonsense
+fn check_fraction(field: &'static str, value: f32) -> Result<(), ConfigError> {
+    check_positive(field, value)?;
+    check_range(field, value, 0.0, 1.0)
+}
+
+fn check_nonzero(field: &'static str, is_zero: bool) -> Result<(), ConfigError> {
+    if is_zero {
+        Err(ConfigError::Zero { field })
+    } else {
+        Ok(())
+    }
+}
+
+fn check_duration(field: &'static str, duration: Option<Duration>) -> Result<(), ConfigError> {
+    check_nonzero(field, duration.is_some_and(|duration| duration.is_zero()))
+}
+
+impl RoomConfig {
+    /// Checks that every numeric setting is within a range that makes sense, returns the first one that is not
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        check_positive("pings_per_second_threshold", self.pings_per_second_threshold)?;
+        check_nonzero("max_auth_failures", self.max_auth_failures == Some(0))?;
+        check_nonzero("max_connection_index", self.max_connection_index == 0)?;
+        check_duration("idle_after", self.idle_after)?;
+        if let Some(fraction) = self.adaptive_threshold_fraction {
+            check_fraction("adaptive_threshold_fraction", fraction)?;
+        }
+        check_nonzero("min_connections_for_election", self.min_connections_for_election == 0)?;
+        check_duration("pending_timeout", Some(self.pending_timeout))?;
+        check_duration("assessment_window", Some(self.assessment_window))?;
+        check_nonzero("min_assessment_samples", self.min_assessment_samples == 0)?;
+        if let Some(alpha) = self.rate_smoothing {
+            check_fraction("rate_smoothing", alpha)?;
+        }
+
+        let stability = &self.leader_stability;
+        check_range("leader_stability.challenger_margin", stability.challenger_margin, 0.0, 1.0)?;
+        check_nonzero("leader_stability.max_changes_per_minute", stability.max_changes_per_minute == Some(0))?;
+        check_duration("leader_stability.reelection_backoff", stability.reelection_backoff)?;
+
+        check_duration("suspicion_decay", self.suspicion_decay)?;
+        check_duration("leader_probation", self.leader_probation)?;
+        check_duration("lease_duration", self.lease_duration)?;
+        check_duration("abandoned_after", Some(self.abandoned_after))?;
+        check_duration("election_watchdog", self.election_watchdog)?;
+        check_duration("sustained_leader_loss", self.sustained_leader_loss)?;
+        if let Some(intervals) = self.down_vote_freshness {
+            check_positive("down_vote_freshness", intervals)?;
+        }
+        check_duration("leader_overlap", self.leader_overlap)?;
+        check_nonzero("max_partition_term_lead", self.max_partition_term_lead == 0)?;
+        check_nonzero("traffic_budget", self.traffic_budget == Some(0))?;
+        check_fraction("clock_skew_smoothing", self.clock_skew_smoothing)?;
+        check_nonzero("max_down_vote_changes", self.max_down_vote_changes == Some(0))?;
+        check_duration("down_vote_change_window", Some(self.down_vote_change_window))?;
+        check_duration("quiet_after", self.quiet_after)?;
+        check_duration("close_abandoned_after", self.close_abandoned_after)?;
+        check_positive("ping_headroom", self.ping_headroom)?;
+        check_nonzero("leader_burden_room_size", self.leader_burden_room_size == 0)?;
+        check_nonzero("max_connections", self.max_connections == Some(0))?;
+        check_duration("kick_vote_duration", Some(self.kick_vote_duration))?;
+        check_fraction("kick_vote_quorum", self.kick_vote_quorum)?;
+        check_duration("stats_interval", self.stats_interval)?;
+        check_nonzero("stats_history", self.stats_history == 0)?;
+        check_nonzero("update_budget", self.update_budget == Some(0))?;
+        if let Some(burden) = self.max_leader_burden {
+            check_positive("max_leader_burden", burden)?;
+        }
+        for threshold in &self.health_thresholds {
+            check_range("health_thresholds", *threshold as f32, 1.0, 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{ConfigError, LeaderStability, RoomConfig};
+
+    #[test]
+    fn refuse_nonsense_config() {
+        assert!(RoomConfig::new().build().is_ok());
+
+        assert_eq!(
+            RoomConfig::new().pings_per_second_threshold(0.0).build().unwrap_err(),
+            ConfigError::NotPositive {
+                field: "pings_per_second_threshold",
+                value: 0.0,
+            }
+        );
+        assert!(RoomConfig::new().pings_per_second_threshold(f32::NAN).validate().is_err());
+        assert_eq!(
+            RoomConfig::new().with_rate_smoothing(1.5).validate(),
+            Err(ConfigError::OutOfRange {
+                field: "rate_smoothing",
+                value: 1.5,
+                min: 0.0,
+                max: 1.0,
+            })
+        );
+        assert_eq!(
+            RoomConfig::new().with_assessment_window(Duration::ZERO).validate(),
+            Err(ConfigError::Zero { field: "assessment_window" })
+        );
+        assert_eq!(
+            RoomConfig::new()
+                .with_leader_stability(LeaderStability::new().with_max_changes_per_minute(0))
+                .validate()
+                .unwrap_err()
+                .to_string(),
+            "leader_stability.max_changes_per_minute must be above zero"
+        );
+    }
+
+    #[test]
+    fn presets_are_valid() {
+        for preset in [
+            RoomConfig::recommended_for_debug(),
+            RoomConfig::recommended_for_release(),
+            RoomConfig::recommended_for_mobile(),
+            RoomConfig::recommended_for_lan(),
+        ] {
+            assert_eq!(preset.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid room config: pings_per_second_threshold must be a finite number above zero")]
+    fn build_unchecked_panics_on_invalid_config() {
+        RoomConfig::new().pings_per_second_threshold(-1.0).build_unchecked();
+    }
+}
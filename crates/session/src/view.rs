@@ -0,0 +1,186 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+
+use conclave_types::{KnowledgeOrd, Term};
+
+use crate::{ConnectionIndex, ConnectionState, Instant, QualityAssessment, Room};
+
+/// A single connection in a [RoomView]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberView {
+    pub index: ConnectionIndex,
+    pub identity: Option<String>,
+    pub state: ConnectionState,
+    pub assessment: QualityAssessment,
+    /// The [KnowledgeOrd::progress] of the knowledge
+    pub knowledge: u64,
+}
+
+/// An immutable copy of the room, see [Room::view]. It can be shared with other threads and read without
+/// holding on to the room.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomView {
+    /// The latest time the room had been given when the view was taken
+    pub at: Option<Instant>,
+    pub term: Term,
+    pub leader: Option<ConnectionIndex>,
+    /// Every connection, sorted by connection index
+    pub members: Vec<MemberView>,
+}
+
+impl RoomView {
+    pub fn member(&self, index: ConnectionIndex) -> Option<&MemberView> {
+        self.members
+            .binary_search_by_key(&index.value(), |member| member.index.value())
+            .ok()
+            .map(|position| &self.members[position])
+            .filter(|member| member.index == index)
+    }
+
+    pub fn leader_member(&self) -> Option<&MemberView> {
+        self.member(self.leader?)
+    }
+
+    pub(crate) fn empty() -> Self {
+        Self {
+            at: None,
+            term: Term(0),
+            leader: None,
+            members: Vec::new(),
+        }
+    }
+}
+
+/// Reads the latest [RoomView] a room has published, without access to the room, see [Room::view_reader]
+#[derive(Debug, Clone)]
+pub struct ViewReader {
+    published: Arc<RwLock<Arc<RoomView>>>,
+}
+
+impl ViewReader {
+    /// The view published at the end of the latest call that changed the room
+    pub fn latest(&self) -> Arc<RoomView> {
+        self.published.read().unwrap().clone()
+    }
+}
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// The room as it is now, for readers that should not contend with [Room::on_ping] and [Room::update].
+    ///
+    /// The view is taken once after the room has changed and shared until the next change, so calling it for
+    /// every query is cheap. Hand the [Arc] to the readers, they keep seeing the same view until they ask for a new
+    /// one, or hand them a [ViewReader] to get the view published at the end of every call that changes the room.
+    pub fn view(&self) -> Arc<RoomView> {
+        // Also catches a connection changed through Room::get_mut, as the room can not see when that is done
+        self.refresh_view();
+        self.published_view.read().unwrap().clone()
+    }
+
+    /// Gives other threads the views published by the room, see [Room::view]
+    pub fn view_reader(&self) -> ViewReader {
+        self.refresh_view();
+        ViewReader {
+            published: self.published_view.clone(),
+        }
+    }
+
+    fn take_view(&self) -> RoomView {
+        RoomView {
+            at: self.latest_time,
+            term: self.term,
+            leader: self.leader_index,
+            members: self
+                .connections()
+                .into_iter()
+                .map(|connection| MemberView {
+                    index: connection.id,
+                    identity: connection.identity.clone(),
+                    state: connection.state,
+                    assessment: connection.assessment(),
+                    knowledge: connection.knowledge.progress(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The room has changed, so the view is taken anew when it is published
+    pub(crate) fn invalidate_view(&self) {
+        self.is_view_stale.store(true, Ordering::Release);
+    }
+
+    /// Replaces the published view if the room has changed since it was taken. Called at the end of every call
+    /// that changes the room, but only takes the view there if a [ViewReader] is waiting for it, so a room that
+    /// nobody reads does not allocate a view for every ping
+    pub(crate) fn publish_view(&self) {
+        if Arc::strong_count(&self.published_view) > 1 {
+            self.refresh_view();
+        }
+    }
+
+    fn refresh_view(&self) {
+        if self.is_view_stale.swap(false, Ordering::AcqRel) {
+            *self.published_view.write().unwrap() = Arc::new(self.take_view());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Instant;
+
+    use conclave_types::Knowledge;
+
+    use crate::{PingPayload, Room};
+
+    #[test]
+    fn share_view_until_room_changes() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+
+        let view = room.view();
+        assert!(Arc::ptr_eq(&view, &room.view()));
+        assert_eq!(view.leader_member().unwrap().index, leader);
+        let reader = {
+            let view = view.clone();
+            thread::spawn(move || view.members.len())
+        };
+        assert_eq!(reader.join().unwrap(), 2);
+
+        room.on_ping(follower, &PingPayload::new().with_knowledge(Knowledge(8)), now);
+
+        let changed = room.view();
+        assert!(!Arc::ptr_eq(&view, &changed));
+        assert_eq!(view.member(follower).unwrap().knowledge, 0);
+        assert_eq!(changed.member(follower).unwrap().knowledge, 8);
+
+        room.destroy_connection(follower).unwrap();
+        assert!(room.view().member(follower).is_none());
+    }
+
+    #[test]
+    fn publish_view_to_readers() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        let reader = room.view_reader();
+        assert!(reader.latest().members.is_empty());
+
+        let leader = room.create_connection(now).unwrap();
+        let reading = {
+            let reader = reader.clone();
+            thread::spawn(move || reader.latest().leader)
+        };
+        assert_eq!(reading.join().unwrap(), Some(leader));
+
+        room.on_ping(leader, &PingPayload::new().with_knowledge(Knowledge(3)), now);
+        assert_eq!(reader.latest().member(leader).unwrap().knowledge, 3);
+        assert!(Arc::ptr_eq(&reader.latest(), &room.view()));
+    }
+}
@@ -0,0 +1,69 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::info;
+
+use crate::{ConnectionIndex, ConnectionState, Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// Counts a ping from a joining connection, a ping that arrives too late starts the count over
+    pub(crate) fn advance_warm_up(&mut self, connection_index: ConnectionIndex, is_within_rate: bool) {
+        let required = self.config.warm_up_pings;
+        let connection = self.connections.get_mut(&connection_index).unwrap();
+        if connection.state != ConnectionState::Joining {
+            return;
+        }
+
+        connection.warm_up_pings = if is_within_rate { connection.warm_up_pings + 1 } else { 1 };
+        if connection.warm_up_pings >= required {
+            info!("{} has warmed up and is now online", connection);
+            connection.state = ConnectionState::Online;
+            self.push_event(RoomEvent::WarmedUp {
+                connection: connection_index,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{ConnectionToLeader, Knowledge};
+
+    use crate::{ConnectionState, PingPayload, RoomConfig, RoomEvent};
+
+    #[test]
+    fn joining_connection_can_not_be_elected() {
+        let mut room = RoomConfig::new().pings_per_second_threshold(10.0).with_warm_up_pings(3).build().unwrap();
+        let now = Instant::now();
+        let at = |millis| now + Duration::from_millis(millis);
+        let leader = room.create_connection(now).unwrap();
+        let follower = room.create_connection(now).unwrap();
+        let newcomer = room.create_connection(now).unwrap();
+        let ping = PingPayload::new().with_connection_to_leader(ConnectionToLeader::Connected);
+        for millis in [50, 100, 150] {
+            room.on_ping(leader, &ping, at(millis));
+            room.on_ping(follower, &ping, at(millis));
+        }
+        assert_eq!(room.get(follower).state, ConnectionState::Online);
+
+        // A late ping restarts the warm up
+        let well_informed = ping.clone().with_knowledge(Knowledge(100));
+        room.on_ping(newcomer, &well_informed, at(150));
+        room.on_ping(newcomer, &well_informed, at(200));
+        room.on_ping(newcomer, &well_informed, at(350));
+        assert_eq!(room.get(newcomer).state, ConnectionState::Joining);
+
+        room.destroy_connection(leader).unwrap();
+        assert_eq!(room.leader_index, Some(follower));
+
+        room.drain_events();
+        room.on_ping(newcomer, &well_informed, at(400));
+        room.on_ping(newcomer, &well_informed, at(450));
+        assert_eq!(room.get(newcomer).state, ConnectionState::Online);
+        assert!(room.drain_events().contains(&RoomEvent::WarmedUp { connection: newcomer }));
+    }
+}
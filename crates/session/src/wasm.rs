@@ -0,0 +1,64 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! Memory management for using the [crate::ffi] functions from JavaScript, e.g. in a lobby server on
+//! Cloudflare Workers. See `examples/wasm/lobby.mjs`, which drives a room from JS timers.
+//!
+//! Build it with `cargo rustc -p conclave-room-session --release --target wasm32-unknown-unknown --features wasm
+//! --crate-type cdylib`. On that target [crate::Instant] is created from the `now_ms` timestamps passed to the
+//! functions, the room never reads a clock.
+//!
+//! JavaScript can only pass numbers, so the structs the functions read and write, including the
+//! [crate::ffi::ConclaveConnection] that is passed by value in C, are placed in buffers from [conclave_alloc].
+//! `u64` parameters are `BigInt` on the JavaScript side.
+
+use std::alloc::{alloc_zeroed, dealloc, Layout};
+
+/// Alignment of every buffer, enough for all the structs in [crate::ffi]
+const ALIGN: usize = 8;
+
+/// Allocates `size` zeroed bytes in the linear memory, null if `size` is zero or the allocation failed.
+/// Release with [conclave_free] and the same `size`.
+#[no_mangle]
+pub extern "C" fn conclave_alloc(size: usize) -> *mut u8 {
+    match Layout::from_size_align(size, ALIGN) {
+        Ok(layout) if size > 0 => unsafe { alloc_zeroed(layout) },
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `buffer` must be null or returned by [conclave_alloc] with the same `size`, and not freed before
+#[no_mangle]
+pub unsafe extern "C" fn conclave_free(buffer: *mut u8, size: usize) {
+    if buffer.is_null() {
+        return;
+    }
+    if let Ok(layout) = Layout::from_size_align(size, ALIGN) {
+        dealloc(buffer, layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::{room_create, room_create_connection, room_destroy, ConclaveConnection, CONCLAVE_OK};
+
+    #[test]
+    fn connection_in_allocated_buffer() {
+        unsafe {
+            let size = size_of::<ConclaveConnection>();
+            let buffer = conclave_alloc(size);
+            assert!(!buffer.is_null());
+            assert!(conclave_alloc(0).is_null());
+
+            let room = room_create();
+            assert_eq!(room_create_connection(room, 0, buffer.cast()), CONCLAVE_OK);
+            assert_eq!((*buffer.cast::<ConclaveConnection>()).value, 1);
+            room_destroy(room);
+            conclave_free(buffer, size);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+use conclave_types::KnowledgeOrd;
+use log::warn;
+
+use crate::{ConnectionIndex, ConnectionState, ElectionTrigger, Instant, Room, RoomEvent};
+
+impl<K: KnowledgeOrd> Room<K> {
+    /// How many updates in a row a majority has down-voted the leader without it being replaced, zero if the
+    /// election is not stuck
+    pub fn stuck_election_attempts(&self) -> u32 {
+        self.stuck_election_attempts
+    }
+
+    /// Called after every update with the leader from before the update. Notices when a majority keeps
+    /// down-voting a leader that is not replaced, and reports it with [RoomEvent::RoomStuck] once it has gone on
+    /// for [crate::RoomConfig::election_watchdog].
+    pub(crate) fn watch_election(&mut self, leader_before: Option<ConnectionIndex>, time: Instant) {
+        let Some(timeout) = self.config.election_watchdog else {
+            return;
+        };
+        let is_ineffective = leader_before.is_some()
+            && self.leader_index == leader_before
+            && self.has_most_lost_connection_to_leader(time);
+        if !is_ineffective {
+            self.stuck_election_since = None;
+            self.stuck_election_attempts = 0;
+            return;
+        }
+
+        self.stuck_election_attempts += 1;
+        let since = *self.stuck_election_since.get_or_insert(time);
+        if time.saturating_duration_since(since) < timeout || self.stuck_election_attempts < 2 {
+            return;
+        }
+
+        warn!(
+            "room {} is stuck, leader {:?} has been down-voted {} times without being replaced",
+            self.id, self.leader_index, self.stuck_election_attempts
+        );
+        self.push_event(RoomEvent::RoomStuck {
+            term: self.term,
+            leader: self.leader_index,
+            attempts: self.stuck_election_attempts,
+        });
+        // Report again only if it goes on for another full period
+        self.stuck_election_since = Some(time);
+
+        if self.config.force_reelection_when_stuck {
+            self.force_reelection();
+        }
+    }
+
+    /// Elects the best candidate other than the leader, bypassing [crate::RoomConfig::leader_stability] and
+    /// probation. If no candidate is eligible, the connection with the most knowledge that is not pending,
+    /// disconnected or vetoed by a [crate::LeaderEligibility] is chosen, regardless of why it was excluded.
+    fn force_reelection(&mut self) {
+        let report = self.evaluate_election(self.leader_index);
+        let winner = report.winner.or_else(|| {
+            let mut connections: Vec<_> = self
+                .connections
+                .values()
+                .filter(|connection| {
+                    Some(connection.id) != self.leader_index
+                        && !matches!(connection.state, ConnectionState::Pending | ConnectionState::Disconnected)
+                        && !self.is_vetoed(connection)
+                })
+                .collect();
+            connections.sort_by_key(|connection| connection.id.value());
+            connections
+                .into_iter()
+                .min_by(|a, b| b.knowledge.cmp_knowledge(&a.knowledge))
+                .map(|connection| connection.id)
+        });
+        let Some(winner) = winner else {
+            warn!("no connection to force as leader of room {}", self.id);
+            return;
+        };
+
+        warn!("forcing {} as leader of room {}", winner, self.id);
+        self.switch_leader(Some(winner));
+        self.remember_election(report, ElectionTrigger::Watchdog);
+        self.stuck_election_since = None;
+        self.stuck_election_attempts = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use conclave_types::ConnectionToLeader;
+
+    use crate::{LeaderStability, PingPayload, Room, RoomConfig, RoomEvent};
+
+    #[test]
+    fn report_and_break_stuck_election() {
+        // A minimum term far longer than the test keeps the down-voted leader in place
+        let stability = LeaderStability::new().with_min_term_duration(Duration::from_secs(3600));
+        let mut room = RoomConfig::new()
+            .with_leader_stability(stability)
+            .with_election_watchdog(Some(Duration::from_secs(1)))
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        let leader = room.create_connection(now).unwrap();
+        let followers = [room.create_connection(now).unwrap(), room.create_connection(now).unwrap()];
+        let term = room.term;
+        room.drain_events();
+
+        let tick = |room: &mut Room, millis: u64| {
+            let time = now + Duration::from_millis(millis);
+            room.on_ping(leader, &PingPayload::new().with_term(term), time);
+            for follower in followers {
+                let down_vote = PingPayload::new()
+                    .with_term(term)
+                    .with_connection_to_leader(ConnectionToLeader::Disconnected);
+                room.on_ping(follower, &down_vote, time);
+            }
+            room.update(time);
+        };
+
+        let mut stuck = Vec::new();
+        for millis in (100..=2500).step_by(100) {
+            tick(&mut room, millis);
+            stuck.extend(room.drain_events().into_iter().filter(|event| matches!(event, RoomEvent::RoomStuck { .. })));
+        }
+        assert_eq!(room.leader_index, Some(leader));
+        assert_eq!(stuck.len(), 2);
+        assert!(room.stuck_election_attempts() > 0);
+
+        room.update_config(room.config.clone().with_force_reelection_when_stuck(true)).unwrap();
+        for millis in (2600..=3600).step_by(100) {
+            tick(&mut room, millis);
+            if room.leader_index != Some(leader) {
+                break;
+            }
+        }
+        assert!(followers.contains(&room.leader_index.unwrap()));
+        assert_eq!(room.stuck_election_attempts(), 0);
+    }
+}
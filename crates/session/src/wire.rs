@@ -0,0 +1,454 @@
+/*----------------------------------------------------------------------------------------------------------
+ *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
+ *  Licensed under the MIT License. See LICENSE in the project root for license information.
+ *--------------------------------------------------------------------------------------------------------*/
+//! Compact binary wire format for the room messages
+//!
+//! Every message starts with a two octet header: the wire version followed by the message type.
+//! All integers are big-endian, so implementations in other languages can agree on the layout.
+//!
+//! | message              | payload                                                              |
+//! |----------------------|----------------------------------------------------------------------|
+//! | Ping                 | term: u16, knowledge: u64, connection_to_leader: u8, protocol: u16,  |
+//! |                      | sequence: optional u16, signature: optional (length: u8, octets),    |
+//! |                      | last_input_age: optional u32 milliseconds,                           |
+//! |                      | unreachable count: u8, connection indices...,                        |
+//! |                      | leader_unreachable_since: optional u32 milliseconds,                 |
+//! |                      | followed_leader: optional connection index,                          |
+//! |                      | sent_at: optional u64 milliseconds,                                  |
+//! |                      | capability: optional u8,                                             |
+//! |                      | knowledge_checksum: optional u64,                                    |
+//! |                      | kick_vote: optional (proposal: u32, approve: u8)                     |
+//! | LeaderAnnouncement   | term: u16, leader: optional connection index                         |
+//! | RoomStateDigest      | term: u16, leader: optional connection index, count: u16, members... |
+//!
+//! Optional values are a presence octet (0 or 1) followed by the value when present.
+//! A connection index is its value: u32 followed by its generation: u32.
+//! Each digest member is connection index, knowledge: u64, connection_to_leader: u8.
+
+use std::io::{Error, ErrorKind, Result};
+use std::time::Duration;
+
+use conclave_types::{ConnectionToLeader, Knowledge, Term};
+
+use crate::octets::{
+    write_connection_index, write_optional_connection_index, write_u16, write_u32, write_u64, OctetReader,
+};
+use crate::{ConnectionIndex, KickBallot, PingPayload, Room};
+
+pub const WIRE_VERSION: u8 = 11;
+
+pub const PING_MESSAGE_TYPE_ID: u8 = 0x01;
+pub const LEADER_ANNOUNCEMENT_MESSAGE_TYPE_ID: u8 = 0x02;
+pub const ROOM_STATE_DIGEST_MESSAGE_TYPE_ID: u8 = 0x03;
+
+/// Sent to all connections when a leader has been appointed for a term
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderAnnouncement {
+    pub term: Term,
+    pub leader: Option<ConnectionIndex>,
+}
+
+/// What a single connection has reported, as part of a [RoomStateDigest]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigestMember {
+    pub connection: ConnectionIndex,
+    pub knowledge: Knowledge,
+    pub has_connection_to_leader: ConnectionToLeader,
+}
+
+/// Condensed view of the room, enough for a client or relay to verify that it agrees with the room
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomStateDigest {
+    pub term: Term,
+    pub leader: Option<ConnectionIndex>,
+    pub members: Vec<DigestMember>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WireMessage {
+    Ping(PingPayload),
+    LeaderAnnouncement(LeaderAnnouncement),
+    RoomStateDigest(RoomStateDigest),
+}
+
+impl WireMessage {
+    fn message_type_id(&self) -> u8 {
+        match self {
+            WireMessage::Ping(_) => PING_MESSAGE_TYPE_ID,
+            WireMessage::LeaderAnnouncement(_) => LEADER_ANNOUNCEMENT_MESSAGE_TYPE_ID,
+            WireMessage::RoomStateDigest(_) => ROOM_STATE_DIGEST_MESSAGE_TYPE_ID,
+        }
+    }
+
+    /// Fails if a list is longer than its length prefix can hold, the message would not decode
+    fn check_lengths(&self) -> Result<()> {
+        match self {
+            WireMessage::Ping(ping) => {
+                if let Some(signature) = &ping.signature {
+                    check_length("signature", signature.len(), u8::MAX as usize)?;
+                }
+                check_length("unreachable", ping.unreachable.len(), u8::MAX as usize)
+            }
+            WireMessage::LeaderAnnouncement(_) => Ok(()),
+            WireMessage::RoomStateDigest(digest) => check_length("members", digest.members.len(), u16::MAX as usize),
+        }
+    }
+
+    /// Appends the header and the message to `out`. Fails, without appending anything, if a list in the message
+    /// is too long for the wire format
+    pub fn encode(&self, out: &mut Vec<u8>) -> Result<()> {
+        self.check_lengths()?;
+        out.push(WIRE_VERSION);
+        out.push(self.message_type_id());
+
+        match self {
+            WireMessage::Ping(ping) => {
+                write_u16(out, ping.term.value());
+                write_u64(out, ping.knowledge.value());
+                out.push(ping.has_connection_to_leader.to_u8());
+                write_u16(out, ping.protocol_version);
+                match ping.sequence {
+                    Some(sequence) => {
+                        out.push(1);
+                        write_u16(out, sequence);
+                    }
+                    None => out.push(0),
+                }
+                match &ping.signature {
+                    Some(signature) => {
+                        out.push(1);
+                        out.push(signature.len() as u8);
+                        out.extend_from_slice(signature);
+                    }
+                    None => out.push(0),
+                }
+                match ping.last_input_age {
+                    Some(last_input_age) => {
+                        out.push(1);
+                        write_u32(out, last_input_age.as_millis().min(u32::MAX as u128) as u32);
+                    }
+                    None => out.push(0),
+                }
+                out.push(ping.unreachable.len() as u8);
+                for index in &ping.unreachable {
+                    write_connection_index(out, *index);
+                }
+                match ping.leader_unreachable_since {
+                    Some(leader_unreachable_since) => {
+                        out.push(1);
+                        write_u32(out, leader_unreachable_since.as_millis().min(u32::MAX as u128) as u32);
+                    }
+                    None => out.push(0),
+                }
+                write_optional_connection_index(out, ping.followed_leader);
+                match ping.sent_at {
+                    Some(sent_at) => {
+                        out.push(1);
+                        write_u64(out, sent_at.as_millis().min(u64::MAX as u128) as u64);
+                    }
+                    None => out.push(0),
+                }
+                match ping.capability {
+                    Some(capability) => {
+                        out.push(1);
+                        out.push(capability);
+                    }
+                    None => out.push(0),
+                }
+                match ping.knowledge_checksum {
+                    Some(checksum) => {
+                        out.push(1);
+                        write_u64(out, checksum);
+                    }
+                    None => out.push(0),
+                }
+                match ping.kick_vote {
+                    Some(ballot) => {
+                        out.push(1);
+                        write_u32(out, ballot.proposal);
+                        out.push(ballot.approve as u8);
+                    }
+                    None => out.push(0),
+                }
+            }
+            WireMessage::LeaderAnnouncement(announcement) => {
+                write_u16(out, announcement.term.value());
+                write_optional_connection_index(out, announcement.leader);
+            }
+            WireMessage::RoomStateDigest(digest) => {
+                write_u16(out, digest.term.value());
+                write_optional_connection_index(out, digest.leader);
+                write_u16(out, digest.members.len() as u16);
+                for member in &digest.members {
+                    write_connection_index(out, member.connection);
+                    write_u64(out, member.knowledge.value());
+                    out.push(member.has_connection_to_leader.to_u8());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_octets(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.encode(&mut out)?;
+        Ok(out)
+    }
+
+    /// Decodes a complete message, including the header. Trailing octets are treated as an error.
+    pub fn decode(octets: &[u8]) -> Result<Self> {
+        let mut reader = OctetReader::new(octets);
+
+        let version = reader.read_u8()?;
+        if version != WIRE_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported wire version {} (expected {})", version, WIRE_VERSION),
+            ));
+        }
+
+        let message_type_id = reader.read_u8()?;
+        let message = match message_type_id {
+            PING_MESSAGE_TYPE_ID => {
+                let term = Term(reader.read_u16()?);
+                let knowledge = Knowledge(reader.read_u64()?);
+                let has_connection_to_leader = reader.read_connection_to_leader()?;
+                let protocol_version = reader.read_u16()?;
+                let mut ping = PingPayload::new()
+                    .with_term(term)
+                    .with_knowledge(knowledge)
+                    .with_connection_to_leader(has_connection_to_leader)
+                    .with_protocol_version(protocol_version);
+                if reader.read_presence()? {
+                    ping = ping.with_sequence(reader.read_u16()?);
+                }
+                if reader.read_presence()? {
+                    let length = reader.read_u8()? as usize;
+                    ping = ping.with_signature(reader.read_octets(length)?.to_vec());
+                }
+                if reader.read_presence()? {
+                    ping = ping.with_last_input_age(Duration::from_millis(reader.read_u32()? as u64));
+                }
+                let count = reader.read_u8()? as usize;
+                let mut unreachable = Vec::with_capacity(count);
+                for _ in 0..count {
+                    unreachable.push(reader.read_connection_index()?);
+                }
+                ping = ping.with_unreachable(unreachable);
+                if reader.read_presence()? {
+                    ping = ping.with_leader_unreachable_since(Duration::from_millis(reader.read_u32()? as u64));
+                }
+                ping.followed_leader = reader.read_optional_connection_index()?;
+                if reader.read_presence()? {
+                    ping = ping.with_sent_at(Duration::from_millis(reader.read_u64()?));
+                }
+                if reader.read_presence()? {
+                    ping = ping.with_capability(reader.read_u8()?);
+                }
+                if reader.read_presence()? {
+                    ping = ping.with_knowledge_checksum(reader.read_u64()?);
+                }
+                if reader.read_presence()? {
+                    ping = ping.with_kick_vote(KickBallot {
+                        proposal: reader.read_u32()?,
+                        approve: reader.read_u8()? != 0,
+                    });
+                }
+                WireMessage::Ping(ping)
+            }
+            LEADER_ANNOUNCEMENT_MESSAGE_TYPE_ID => WireMessage::LeaderAnnouncement(LeaderAnnouncement {
+                term: Term(reader.read_u16()?),
+                leader: reader.read_optional_connection_index()?,
+            }),
+            ROOM_STATE_DIGEST_MESSAGE_TYPE_ID => {
+                let term = Term(reader.read_u16()?);
+                let leader = reader.read_optional_connection_index()?;
+                let count = reader.read_u16()? as usize;
+                let mut members = Vec::with_capacity(count);
+                for _ in 0..count {
+                    members.push(DigestMember {
+                        connection: reader.read_connection_index()?,
+                        knowledge: Knowledge(reader.read_u64()?),
+                        has_connection_to_leader: reader.read_connection_to_leader()?,
+                    });
+                }
+                WireMessage::RoomStateDigest(RoomStateDigest { term, leader, members })
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown message type 0x{:x}", message_type_id),
+                ))
+            }
+        };
+
+        if !reader.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "trailing octets after message"));
+        }
+
+        Ok(message)
+    }
+}
+
+fn check_length(field: &str, length: usize, max: usize) -> Result<()> {
+    if length > max {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} has {} entries, at most {} fit in a message", field, length, max),
+        ));
+    }
+    Ok(())
+}
+
+impl Room {
+    pub fn leader_announcement(&self) -> LeaderAnnouncement {
+        LeaderAnnouncement {
+            term: self.term,
+            leader: self.leader_index,
+        }
+    }
+
+    /// Members are ordered by connection index, so two digests of the same state encode identically
+    pub fn state_digest(&self) -> RoomStateDigest {
+        let mut members: Vec<DigestMember> = self
+            .connections
+            .values()
+            .map(|connection| DigestMember {
+                connection: connection.id,
+                knowledge: connection.knowledge,
+                has_connection_to_leader: connection.has_connection_host,
+            })
+            .collect();
+        members.sort_by_key(|member| member.connection.value());
+
+        RoomStateDigest {
+            term: self.term,
+            leader: self.leader_index,
+            members,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::time::{Duration, Instant};
+
+    use conclave_types::{ConnectionToLeader, Knowledge, Term};
+
+    use crate::wire::{WireMessage, PING_MESSAGE_TYPE_ID, WIRE_VERSION};
+    use crate::{ConnectionIndex, KickBallot, PingPayload, Room};
+
+    fn round_trip(message: WireMessage) {
+        let octets = message.to_octets().unwrap();
+        assert_eq!(octets[0], WIRE_VERSION);
+        assert_eq!(WireMessage::decode(&octets).unwrap(), message);
+    }
+
+    #[test]
+    fn ping_round_trip() {
+        let ping = PingPayload::new()
+            .with_term(Term(32))
+            .with_knowledge(Knowledge(444441))
+            .with_connection_to_leader(ConnectionToLeader::Disconnected);
+        round_trip(WireMessage::Ping(ping.clone()));
+        round_trip(WireMessage::Ping(ping.clone().with_sequence(u16::MAX).with_signature(vec![1, 2, 3])));
+        round_trip(WireMessage::Ping(ping.clone().with_last_input_age(Duration::from_millis(90_500))));
+        round_trip(WireMessage::Ping(
+            ping.clone().with_unreachable(vec![ConnectionIndex::with_generation(3, 7), ConnectionIndex::new(9)]),
+        ));
+        round_trip(WireMessage::Ping(ping.clone().with_leader_unreachable_since(Duration::from_millis(1_250))));
+        round_trip(WireMessage::Ping(ping.clone().with_followed_leader(ConnectionIndex::with_generation(4, 2))));
+        round_trip(WireMessage::Ping(ping.clone().with_sent_at(Duration::from_millis(86_400_123))));
+        round_trip(WireMessage::Ping(ping.clone().with_capability(250)));
+        round_trip(WireMessage::Ping(ping.clone().with_knowledge_checksum(0x0123_4567_89AB_CDEF)));
+        round_trip(WireMessage::Ping(ping.with_kick_vote(KickBallot {
+            proposal: 70_000,
+            approve: true,
+        })));
+    }
+
+    #[test]
+    fn ping_layout() {
+        let ping = PingPayload::new()
+            .with_term(Term(0x20))
+            .with_knowledge(Knowledge(0xF5E60E32E9E47F08))
+            .with_connection_to_leader(ConnectionToLeader::Connected)
+            .with_protocol_version(3);
+        let octets = WireMessage::Ping(ping).to_octets().unwrap();
+
+        assert_eq!(
+            octets,
+            vec![
+                WIRE_VERSION,
+                PING_MESSAGE_TYPE_ID,
+                0x00,
+                0x20,
+                0xF5,
+                0xE6,
+                0x0E,
+                0x32,
+                0xE9,
+                0xE4,
+                0x7F,
+                0x08,
+                0x01,
+                0x00,
+                0x03,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn room_messages_round_trip() {
+        let mut room = Room::new();
+        let now = Instant::now();
+        room.create_connection(now).unwrap();
+        room.create_connection(now).unwrap();
+
+        round_trip(WireMessage::LeaderAnnouncement(room.leader_announcement()));
+        round_trip(WireMessage::RoomStateDigest(room.state_digest()));
+
+        room.leader_index = None;
+        round_trip(WireMessage::LeaderAnnouncement(room.leader_announcement()));
+    }
+
+    #[test]
+    fn reject_unknown_version_and_truncated() {
+        let mut octets = WireMessage::Ping(PingPayload::new()).to_octets().unwrap();
+        assert_eq!(
+            WireMessage::decode(&octets[..octets.len() - 1]).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        octets[0] = WIRE_VERSION + 1;
+        assert_eq!(WireMessage::decode(&octets).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reject_lists_too_long_to_encode() {
+        let mut out = Vec::new();
+        let signature = PingPayload::new().with_signature(vec![0; 256]);
+        assert_eq!(WireMessage::Ping(signature).encode(&mut out).unwrap_err().kind(), ErrorKind::InvalidInput);
+        let unreachable = PingPayload::new().with_unreachable((1..=256).map(ConnectionIndex::new).collect());
+        assert_eq!(WireMessage::Ping(unreachable).encode(&mut out).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(out.is_empty());
+
+        round_trip(WireMessage::Ping(
+            PingPayload::new()
+                .with_signature(vec![7; 255])
+                .with_unreachable((1..=255).map(ConnectionIndex::new).collect()),
+        ));
+    }
+}
@@ -2,6 +2,7 @@
  *  Copyright (c) Peter Bjorklund. All rights reserved. https://github.com/conclave-rust/room-session
  *  Licensed under the MIT License. See LICENSE in the project root for license information.
  *--------------------------------------------------------------------------------------------------------*/
+use core::cmp::Ordering;
 use core::fmt;
 
 pub type GuiseUserSessionId = u64;
@@ -52,6 +53,28 @@ impl Knowledge {
     }
 }
 
+/// Knowledge that can be compared when electing a leader.
+///
+/// Implement this for domain specific knowledge, e.g. a tick together with a checksum, where the
+/// connection with the "most" knowledge is not simply the largest number.
+pub trait KnowledgeOrd: Copy + Default + PartialEq + fmt::Debug + fmt::Display {
+    /// Ordering used to find the connection with the most knowledge
+    fn cmp_knowledge(&self, other: &Self) -> Ordering;
+
+    /// Monotonically increasing progress, typically the tick ID, used to measure how far apart two knowledges are
+    fn progress(&self) -> u64;
+}
+
+impl KnowledgeOrd for Knowledge {
+    fn cmp_knowledge(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+
+    fn progress(&self) -> u64 {
+        self.0
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ConnectionToLeader {
     Unknown,
@@ -92,11 +115,19 @@ mod term_tests {
 
 #[cfg(test)]
 mod knowledge_tests {
-    use crate::Knowledge;
+    use core::cmp::Ordering;
+
+    use crate::{Knowledge, KnowledgeOrd};
 
     #[test]
     fn new_knowledge() {
         let knowledge = Knowledge::new(100);
         assert_eq!(knowledge.value(), 100);
     }
+
+    #[test]
+    fn knowledge_ord() {
+        assert_eq!(Knowledge(3).cmp_knowledge(&Knowledge(4)), Ordering::Less);
+        assert_eq!(Knowledge(3).progress(), 3);
+    }
 }